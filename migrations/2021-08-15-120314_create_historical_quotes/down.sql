@@ -0,0 +1 @@
+DROP TABLE historical_quotes
\ No newline at end of file
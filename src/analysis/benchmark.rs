@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+
+use log::trace;
+
+use crate::core::GenericResult;
+use crate::currency::{Cash, CashAssets};
+use crate::currency::converter::CurrencyConverter;
+use crate::exchanges::Exchange;
+use crate::formatting;
+use crate::quotes::{Quotes, QuoteQuery};
+use crate::time::Date;
+use crate::types::Decimal;
+
+use super::deposit_emulator::Transaction;
+use super::portfolio_analysis::InstrumentPerformanceAnalysis;
+use super::returns;
+
+/// Emulates investing of the portfolio's deposits into the benchmark instrument (and selling it on
+/// withdrawals) to answer the question whether the portfolio has beaten the benchmark with exactly
+/// the same cash flows. Taxes and trading commissions are not emulated.
+pub struct Benchmark {
+    symbol: String,
+    exchanges: Vec<Exchange>,
+    cash_flows: Vec<(CashAssets, Decimal)>,
+}
+
+impl Benchmark {
+    pub fn new(symbol: &str, exchanges: Vec<Exchange>) -> Benchmark {
+        Benchmark {
+            symbol: symbol.to_owned(),
+            exchanges,
+            cash_flows: Vec::new(),
+        }
+    }
+
+    /// Registers a deposit or withdrawal: the invested assets and the investments amount in the
+    /// analysis currency (which may include deposit commissions).
+    pub fn cash_flow(&mut self, assets: CashAssets, investments: Decimal) {
+        self.cash_flows.push((assets, investments));
+    }
+
+    pub fn analyse(
+        mut self, currency: &str, converter: &CurrencyConverter, quotes: &Quotes, today: Date,
+    ) -> GenericResult<InstrumentPerformanceAnalysis> {
+        trace!("Analysing {} benchmark performance...", self.symbol);
+
+        if self.cash_flows.is_empty() {
+            return Err!("there are no deposits");
+        }
+        self.cash_flows.sort_by_key(|(assets, _)| assets.date);
+
+        let mut quantity = dec!(0);
+        let mut transactions = Vec::new();
+        let mut valuations = BTreeMap::new();
+
+        for (assets, investments) in &self.cash_flows {
+            let price = self.get_price(quotes, assets.date)?;
+            let amount = converter.convert_to(assets.date, assets.cash, price.currency)?;
+            quantity += amount / price.amount;
+
+            if quantity.is_sign_negative() {
+                return Err!(
+                    "{} withdrawal exceeds the benchmark assets", formatting::format_date(assets.date));
+            }
+
+            trace!("* {}: {} -> {} {}", formatting::format_date(assets.date),
+                   assets.cash, quantity.normalize(), self.symbol);
+
+            transactions.push(Transaction::new(assets.date, *investments));
+            valuations.insert(assets.date, converter.convert_to(assets.date, price * quantity, currency)?);
+        }
+
+        let price = self.get_price(quotes, today)?;
+        let result = converter.real_time_convert_to(price * quantity, currency)?;
+
+        let xirr = returns::calculate_xirr(&transactions, today, result);
+        let twr = returns::calculate_twr(&transactions, &valuations, today, result);
+
+        let first_date = transactions.first().unwrap().date;
+        let days = (today - first_date).num_days() as u32;
+        let investments = transactions.iter().map(|transaction| transaction.amount).sum();
+
+        Ok(InstrumentPerformanceAnalysis {
+            name: format!("{} benchmark", self.symbol),
+            days, investments, result,
+            interest: None, xirr, twr,
+            inactive: false,
+        })
+    }
+
+    fn get_price(&self, quotes: &Quotes, date: Date) -> GenericResult<Cash> {
+        let query = QuoteQuery::Stock(self.symbol.clone(), self.exchanges.clone());
+        Ok(quotes.get_historical(query, date).map_err(|e| format!(
+            "Failed to get {} price for {}: {}", self.symbol, formatting::format_date(date), e))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn benchmark(cash_flows: &[(Date, Decimal)]) -> Benchmark {
+        let mut benchmark = Benchmark::new("VTI", vec![Exchange::Us]);
+        for &(date, amount) in cash_flows {
+            benchmark.cash_flow(CashAssets::new(date, "USD", amount), amount);
+        }
+        benchmark
+    }
+
+    fn quotes(today: Date) -> (Vec<tempfile::NamedTempFile>, Quotes) {
+        Quotes::mock(today, hashmap!{
+            s!("VTI") => btreemap!{
+                date!(2020, 1, 10) => Cash::new("USD", dec!(100)),
+                date!(2020, 6, 10) => Cash::new("USD", dec!(120)),
+                date!(2021, 1, 8) => Cash::new("USD", dec!(150)),
+            },
+        })
+    }
+
+    #[test]
+    fn analyse() {
+        let today = date!(2021, 1, 9);
+        let (_databases, quotes) = quotes(today);
+
+        let analysis = benchmark(&[
+            (date!(2020, 6, 10), dec!(-600)),
+            (date!(2020, 1, 10), dec!(1000)),
+        ]).analyse("USD", &CurrencyConverter::mock(), &quotes, today).unwrap();
+
+        assert_eq!(analysis.name, "VTI benchmark");
+        assert_eq!(analysis.days, 365);
+        assert_eq!(analysis.investments, dec!(400));
+        assert_eq!(analysis.result, dec!(750));
+        assert_eq!(analysis.twr, Some(dec!(50)));
+        assert!(analysis.xirr.unwrap() > dec!(50));
+    }
+
+    #[test]
+    fn withdrawal_exceeding_assets() {
+        let today = date!(2021, 1, 9);
+        let (_databases, quotes) = quotes(today);
+
+        let result = benchmark(&[
+            (date!(2020, 1, 10), dec!(1000)),
+            (date!(2020, 6, 10), dec!(-1500)),
+        ]).analyse("USD", &CurrencyConverter::mock(), &quotes, today);
+
+        assert_eq!(result.err().unwrap().to_string(), "10.06.2020 withdrawal exceeds the benchmark assets");
+    }
+
+    #[test]
+    fn missing_quotes() {
+        let today = date!(2021, 1, 9);
+        let (_databases, quotes) = quotes(today);
+
+        let result = benchmark(&[
+            (date!(2019, 1, 10), dec!(1000)),
+        ]).analyse("USD", &CurrencyConverter::mock(), &quotes, today);
+
+        assert!(result.err().unwrap().to_string().starts_with("Failed to get VTI price for 10.01.2019: "));
+    }
+}
@@ -15,6 +15,7 @@ pub struct InstrumentDepositView {
     symbol: String,
     pub name: Option<String>,
     trades: BTreeMap<Date, HashMap<String, Decimal>>,
+    prices: BTreeMap<Date, HashMap<String, Decimal>>,
     pub transactions: Vec<Transaction>,
     pub interest_periods: Vec<InterestPeriod>,
     pub closed: bool,
@@ -26,6 +27,7 @@ impl InstrumentDepositView {
             symbol: symbol.to_owned(),
             name: None,
             trades: BTreeMap::new(),
+            prices: BTreeMap::new(),
             transactions: Vec::new(),
             interest_periods: Vec::new(),
             closed: true,
@@ -36,7 +38,7 @@ impl InstrumentDepositView {
         // We should handle each portfolio separately to work properly with stock splits (different
         // portfolios with different open position periods may have different stock split
         // information and as a consequence - different quantity multipliers.
-        let instrument_id = format!("{}:{}", portfolio_id, symbol);
+        let instrument_id = get_instrument_id(portfolio_id, symbol);
 
         let position = self.trades.entry(time.date).or_default()
             .entry(instrument_id).or_default();
@@ -44,6 +46,39 @@ impl InstrumentDepositView {
         *position += quantity;
     }
 
+    // Registers price (in terms of the current quantity multiplier) at which the trade has been made
+    pub fn price(&mut self, portfolio_id: &str, symbol: &str, time: DateOptTime, price: Decimal) {
+        let instrument_id = get_instrument_id(portfolio_id, symbol);
+        self.prices.entry(time.date).or_default().insert(instrument_id, price);
+    }
+
+    /// Returns position value at the end of the days for which prices of all held symbols are known
+    pub fn valuations(&self) -> BTreeMap<Date, Decimal> {
+        let mut positions: HashMap<&str, Decimal> = HashMap::new();
+        let mut valuations = BTreeMap::new();
+
+        for (&date, trades) in &self.trades {
+            for (instrument_id, &quantity) in trades {
+                let position = positions.entry(instrument_id.as_str()).or_default();
+                *position += quantity;
+                if position.is_zero() {
+                    positions.remove(instrument_id.as_str());
+                }
+            }
+
+            let prices = self.prices.get(&date);
+            let value: Option<Decimal> = positions.iter().map(|(instrument_id, &quantity)| {
+                prices.and_then(|prices| prices.get(*instrument_id)).map(|&price| price * quantity)
+            }).sum();
+
+            if let Some(value) = value {
+                valuations.insert(date, value);
+            }
+        }
+
+        valuations
+    }
+
     pub fn transaction(&mut self, time: DateOptTime, amount: Decimal) {
         // Some assets can be acquired for free due to corporate actions or other non-trading
         // operations.
@@ -100,6 +135,10 @@ impl InstrumentDepositView {
     }
 }
 
+fn get_instrument_id(portfolio_id: &str, symbol: &str) -> String {
+    format!("{}:{}", portfolio_id, symbol)
+}
+
 // Represents a logical opened position consisting from a few real symbols
 struct OpenPosition<'a> {
     open_date: Date,
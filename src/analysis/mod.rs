@@ -1,9 +1,13 @@
+mod benchmark;
 pub mod config;
 pub mod deposit_emulator;
 mod deposit_performance;
 mod instrument_view;
+pub mod nav;
 mod portfolio_analysis;
 mod portfolio_performance;
+mod returns;
+mod risk;
 mod sell_simulation;
 
 use std::collections::BTreeMap;
@@ -13,7 +17,9 @@ use easy_logging::GlobalContext;
 use log::warn;
 
 use crate::brokers::Broker;
-use crate::broker_statement::{BrokerStatement, ReadingStrictness, StockSellType};
+use crate::broker_statement::{
+    BrokerStatement, ReadingStrictness, StockSellType, StockSourceDetails, VariationMarginProfit,
+    calculate_variation_margin_profit};
 use crate::commissions::CommissionCalc;
 use crate::config::{Config, PortfolioConfig};
 use crate::core::{GenericResult, EmptyResult};
@@ -21,14 +27,18 @@ use crate::currency::converter::{CurrencyConverter, CurrencyConverterRc};
 use crate::db;
 use crate::localities::Country;
 use crate::quotes::Quotes;
-use crate::taxes::{IncomeType, LtoDeductionCalculator, LtoDeduction, NetLtoDeduction};
+use crate::taxes::{LtoDeductionCalculator, LtoDeduction, NetLtoDeduction, NetTaxCalculator, TaxpayerIncome};
 use crate::telemetry::TelemetryRecordBuilder;
 use crate::types::Decimal;
 
 use self::config::PerformanceMergingConfig;
+use self::nav::NavFrequency;
 use self::portfolio_analysis::PortfolioPerformanceAnalysis;
 use self::portfolio_performance::PortfolioPerformanceAnalyser;
 
+pub use self::portfolio_analysis::InstrumentPerformanceAnalysis;
+pub use self::risk::RiskMetrics;
+
 pub struct PortfolioStatistics {
     country: Country,
     pub currencies: Vec<PortfolioCurrencyStatistics>,
@@ -51,6 +61,7 @@ impl PortfolioStatistics {
                     assets: BTreeMap::new(),
                     brokers: BTreeMap::new(),
                     performance: None,
+                    risk: None,
 
                     projected_taxes: dec!(0),
                     projected_tax_deductions: dec!(0),
@@ -79,6 +90,10 @@ impl PortfolioStatistics {
         for statistics in &self.currencies {
             statistics.performance.as_ref().unwrap().print(&format!(
                 "Average rate of return from cash investments in {}", &statistics.currency));
+
+            if let Some(risk) = statistics.risk.as_ref() {
+                risk.print(&format!("Risk metrics in {}", &statistics.currency));
+            }
         }
 
         if !lto.projected.deduction.is_zero() {
@@ -104,6 +119,7 @@ pub struct PortfolioCurrencyStatistics {
     pub assets: BTreeMap<String, Decimal>,
     pub brokers: BTreeMap<Broker, Decimal>,
     pub performance: Option<PortfolioPerformanceAnalysis>,
+    pub risk: Option<RiskMetrics>,
 
     pub projected_taxes: Decimal,
     pub projected_tax_deductions: Decimal,
@@ -119,7 +135,7 @@ impl PortfolioCurrencyStatistics {
 
 pub fn analyse(
     config: &Config, portfolio_name: Option<&str>, include_closed_positions: bool,
-    merge_performance: Option<&PerformanceMergingConfig>, interactive: bool,
+    merge_performance: Option<&PerformanceMergingConfig>, risk_metrics: bool, interactive: bool,
 ) -> GenericResult<(PortfolioStatistics, CurrencyConverterRc, TelemetryRecordBuilder)> {
     let mut telemetry = TelemetryRecordBuilder::new();
 
@@ -135,6 +151,33 @@ pub fn analyse(
         statement.batch_quotes(&quotes)?;
     }
 
+    // Progressive tax rates and loss carryforward are applied to the taxpayer's income received
+    // through all portfolios
+    let taxpayer_income = TaxpayerIncome::calculate(config, &country, &converter, &portfolios.iter()
+        .map(|(portfolio, statement)| (*portfolio, statement))
+        .collect::<Vec<_>>())?;
+
+    // Risk metrics require historical quotes which may be unavailable for some instruments, so
+    // don't fail the whole analysis because of them
+    let nav_history = if risk_metrics {
+        match portfolios.iter().map(|(portfolio, statement)| {
+            let currencies: Vec<&str> = statistics.currencies.iter()
+                .map(|statistics| statistics.currency.as_str())
+                .collect();
+
+            nav::calculate_portfolio(
+                &portfolio.name, statement, NavFrequency::Monthly, &currencies, &converter, &quotes)
+        }).collect::<GenericResult<Vec<_>>>() {
+            Ok(nav_history) => Some(nav_history),
+            Err(e) => {
+                warn!("Unable to calculate portfolio risk metrics: {}.", e);
+                None
+            },
+        }
+    } else {
+        None
+    };
+
     for (portfolio, statement) in &mut portfolios {
         let _logging_context = multiple.then(|| GlobalContext::new(&portfolio.name));
 
@@ -157,8 +200,13 @@ pub fn analyse(
             converter.clone(), statement.broker.commission_spec.clone(), net_value)?;
 
         for (symbol, quantity) in statement.open_positions.clone() {
-            let price = quotes.get(statement.get_quote_query(&symbol))?;
-            statement.emulate_sell(&symbol, quantity, price, &mut commission_calc)?;
+            let price = statement.get_price(&quotes, &symbol)?;
+
+            if quantity.is_sign_negative() {
+                statement.emulate_buy(&symbol, -quantity, price, &mut commission_calc)?;
+            } else {
+                statement.emulate_sell(&symbol, quantity, price, &mut commission_calc)?;
+            }
         }
 
         let additional_commissions = statement.emulate_commissions(commission_calc)?;
@@ -172,35 +220,67 @@ pub fn analyse(
 
         statement.process_trades(None)?;
 
-        for trade in statement.stock_sells.iter().rev() {
-            if !trade.emulation {
-                break;
-            }
+        // Projected taxes are calculated as a difference between taxes with and without the
+        // emulated sellout to take into account results of the current tax year, losses of the
+        // previous years and progressive tax rates (which also depend on non-trading income).
+        let other_income = taxpayer_income.other(&portfolio.name);
+        let mut tax_base = other_income.tax_base();
+        tax_base.extend(&statement.non_trading_tax_base(&country, &converter)?);
+
+        let new_tax_calculator = || NetTaxCalculator::new(country.clone(), portfolio.tax_payment_day())
+            .with_loss_carryforward(other_income.loss_carryforward())
+            .with_tax_base(tax_base.clone());
+        let mut actual_taxes = new_tax_calculator();
+        let mut projected_taxes = new_tax_calculator();
+
+        for margin in calculate_variation_margin_profit(
+            &statement.variation_margin, country.currency, &converter)?
+        {
+            let VariationMarginProfit {date, local_profit} = margin;
+            actual_taxes.add_profit(date, local_profit, local_profit, &[], false);
+            projected_taxes.add_profit(date, local_profit, local_profit, &[], false);
+        }
 
+        for trade in &statement.stock_sells {
             let (volume, commission) = match trade.type_ {
                 StockSellType::Trade {volume, commission, ..} => (volume, commission),
-                _ => unreachable!(),
+                StockSellType::CorporateAction | StockSellType::Transfer => continue,
             };
 
-            let (tax_year, _) = portfolio.tax_payment_day().get(trade.execution_date, true);
+            let realization_date = trade.realization_date();
+            let (tax_year, _) = portfolio.tax_payment_day().get(realization_date, true);
             let details = trade.calculate(&country, tax_year, &portfolio.tax_exemptions, &converter)?;
 
-            let mut taxable_local_profit = details.taxable_local_profit;
+            let mut lto_deductibles = Vec::new();
             for source in &details.fifo {
                 if let Some(deductible) = source.long_term_ownership_deductible {
-                    lto_calc.add(deductible.profit, deductible.years, false);
-                    taxable_local_profit.amount -= deductible.profit;
+                    if trade.emulation {
+                        lto_calc.add(deductible.profit, deductible.years, false);
+                    }
+                    lto_deductibles.push(deductible);
                 }
             }
 
-            let tax_without_deduction = country.tax_to_pay(
-                IncomeType::Trading, tax_year, details.local_profit, None);
+            projected_taxes.add_profit(
+                realization_date, details.local_profit, details.taxable_local_profit,
+                &lto_deductibles, trade.emulation);
 
-            let tax_to_pay = country.tax_to_pay(
-                IncomeType::Trading, tax_year, taxable_local_profit, None);
+            if !trade.emulation {
+                actual_taxes.add_profit(
+                    realization_date, details.local_profit, details.taxable_local_profit,
+                    &lto_deductibles, false);
+                continue;
+            }
 
-            let tax_deduction = tax_without_deduction - tax_to_pay;
-            assert!(!tax_deduction.is_negative());
+            // Short position is closed by the emulated buy, so it decreases the assets by its cost
+            let (volume, commission) = if trade.is_short() {
+                match details.fifo.first().map(|source| &source.source) {
+                    Some(&StockSourceDetails::Trade {cost, commission, ..}) => (-cost, commission),
+                    _ => unreachable!(),
+                }
+            } else {
+                (volume, commission)
+            };
 
             statistics.process(|statistics| {
                 let currency = &statistics.currency;
@@ -208,11 +288,30 @@ pub fn analyse(
                 let volume = converter.real_time_convert_to(volume, currency)?;
                 let commission = converter.real_time_convert_to(commission, currency)?;
 
+                statistics.add_assets(broker, &trade.symbol, volume);
+                statistics.projected_commissions += commission;
+
+                Ok(())
+            })?;
+        }
+
+        let actual_taxes = actual_taxes.calculate();
+
+        for (tax_year, projected) in projected_taxes.calculate() {
+            let (tax_to_pay, tax_deduction) = match actual_taxes.get(&tax_year) {
+                Some(actual) => (
+                    projected.tax_to_pay - actual.tax_to_pay,
+                    projected.tax_deduction - actual.tax_deduction,
+                ),
+                None => (projected.tax_to_pay, projected.tax_deduction),
+            };
+
+            statistics.process(|statistics| {
+                let currency = &statistics.currency;
+
                 let tax_to_pay = converter.real_time_convert_to(tax_to_pay, currency)?;
                 let tax_deduction = converter.real_time_convert_to(tax_deduction, currency)?;
 
-                statistics.add_assets(broker, &trade.symbol, volume);
-                statistics.projected_commissions += commission;
                 statistics.projected_taxes += tax_to_pay;
                 statistics.projected_tax_deductions += tax_deduction;
 
@@ -225,19 +324,26 @@ pub fn analyse(
 
     statistics.process(|statistics| {
         let mut analyser = PortfolioPerformanceAnalyser::new(
-            &country, &statistics.currency, &converter, include_closed_positions);
+            &country, &statistics.currency, &converter, &quotes, include_closed_positions);
 
         for (portfolio, statement) in &mut portfolios {
             let mut performance_merging_config = portfolio.merge_performance.clone();
             if let Some(merge_performance) = merge_performance {
                 performance_merging_config.add(merge_performance)?;
             }
-            analyser.add(portfolio, statement, performance_merging_config)?;
+            analyser.add(
+                portfolio, statement, performance_merging_config,
+                &taxpayer_income.other(&portfolio.name))?;
         }
 
         let (performance, lto) = analyser.analyse()?;
         statistics.performance.replace(performance);
 
+        if let Some(nav_history) = nav_history.as_ref() {
+            statistics.risk = risk::analyse(
+                config, &statistics.currency, &portfolios, nav_history, &converter)?;
+        }
+
         if let Some(prev) = applied_lto.take() {
             assert_eq!(prev, lto);
         }
@@ -295,11 +401,7 @@ fn load_portfolios<'a>(config: &'a Config, name: Option<&str>) -> GenericResult<
 }
 
 fn load_portfolio(config: &Config, portfolio: &PortfolioConfig, strictness: ReadingStrictness) -> GenericResult<BrokerStatement> {
-    let broker = portfolio.broker.get_info(config, portfolio.plan.as_ref())?;
-    BrokerStatement::read(
-        broker, portfolio.statements_path()?, &portfolio.symbol_remapping, &portfolio.instrument_internal_ids,
-        &portfolio.instrument_names, portfolio.get_tax_remapping()?, &portfolio.corporate_actions,
-        strictness)
+    BrokerStatement::read_portfolio(config, portfolio, strictness)
 }
 
 fn load_tools(config: &Config) -> GenericResult<(CurrencyConverterRc, Rc<Quotes>)> {
@@ -0,0 +1,234 @@
+use std::collections::{BTreeSet, HashMap};
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+use chrono::Datelike;
+use log::{debug, warn};
+use static_table_derive::StaticTable;
+
+use crate::broker_statement::BrokerStatement;
+use crate::cash_flow::map_broker_statement_to_cash_flow;
+use crate::config::Config;
+use crate::core::GenericResult;
+use crate::currency::MultiCurrencyCashAccount;
+use crate::currency::converter::CurrencyConverter;
+use crate::formatting;
+use crate::quotes::Quotes;
+use crate::telemetry::TelemetryRecordBuilder;
+use crate::time::{self, Date, DateOptTime};
+use crate::types::Decimal;
+use crate::util;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum NavFrequency {
+    Daily,
+    Monthly,
+}
+
+/// Portfolio net asset value at the end of the day
+pub struct NetAssetValue {
+    pub portfolio: String,
+    pub currency: String,
+    pub date: Date,
+    pub cash: Decimal,
+    pub securities: Decimal,
+}
+
+impl NetAssetValue {
+    pub fn total(&self) -> Decimal {
+        self.cash + self.securities
+    }
+}
+
+/// Reconstructs net asset value history of the specified portfolios (all portfolios if not
+/// specified) from their trades, cash flows and historical quotes. Each portfolio is valued in each
+/// of the specified currencies.
+pub fn calculate(
+    config: &Config, portfolio_name: Option<&str>, frequency: NavFrequency, currencies: &[&str],
+) -> GenericResult<(Vec<NetAssetValue>, TelemetryRecordBuilder)> {
+    let mut telemetry = TelemetryRecordBuilder::new();
+    let portfolios = super::load_portfolios(config, portfolio_name)?;
+    let (converter, quotes) = super::load_tools(config)?;
+
+    let mut result = Vec::new();
+
+    for (portfolio, statement) in &portfolios {
+        telemetry.add_broker(statement.broker.type_);
+        result.extend(calculate_portfolio(
+            &portfolio.name, statement, frequency, currencies, &converter, &quotes)?);
+    }
+
+    Ok((result, telemetry))
+}
+
+#[derive(StaticTable)]
+#[table(name="NavTable")]
+struct NavRow {
+    #[column(name="Date")]
+    date: Date,
+    #[column(name="Portfolio")]
+    portfolio: String,
+    #[column(name="Currency")]
+    currency: String,
+    #[column(name="Cash")]
+    cash: Decimal,
+    #[column(name="Securities")]
+    securities: Decimal,
+    #[column(name="Total")]
+    total: Decimal,
+}
+
+pub fn show(
+    config: &Config, portfolio_name: Option<&str>, frequency: NavFrequency, currency: Option<&str>,
+) -> GenericResult<TelemetryRecordBuilder> {
+    let currencies = match currency {
+        Some(currency) => vec![currency],
+        None => vec!["USD", "RUB"],
+    };
+
+    let (values, telemetry) = calculate(config, portfolio_name, frequency, &currencies)?;
+    let mut table = NavTable::new();
+
+    for value in values {
+        table.add_row(NavRow {
+            date: value.date,
+            total: util::round(value.total(), 2),
+            portfolio: value.portfolio,
+            currency: value.currency,
+            cash: util::round(value.cash, 2),
+            securities: util::round(value.securities, 2),
+        });
+    }
+
+    table.print("Net asset value");
+    Ok(telemetry)
+}
+
+pub(super) fn calculate_portfolio(
+    name: &str, statement: &BrokerStatement, frequency: NavFrequency, currencies: &[&str],
+    converter: &CurrencyConverter, quotes: &Quotes,
+) -> GenericResult<Vec<NetAssetValue>> {
+    debug!("Calculating {:?} portfolio net asset value history...", name);
+
+    let mut cash_flows = map_broker_statement_to_cash_flow(statement).into_iter().peekable();
+    let mut cash_assets = MultiCurrencyCashAccount::new();
+    let mut positions = PositionsTracker::new(statement);
+    let mut skipped_derivatives = BTreeSet::new();
+    let mut result = Vec::new();
+
+    let mut date = statement.period.first_date();
+    let end_date = std::cmp::min(statement.period.last_date(), time::today());
+
+    while date <= end_date {
+        while let Some(cash_flow) = cash_flows.next_if(|cash_flow| cash_flow.time.date <= date) {
+            cash_assets.deposit(cash_flow.amount);
+            if let Some(amount) = cash_flow.sibling_amount {
+                cash_assets.deposit(amount);
+            }
+        }
+
+        let next_date = date.succ();
+        let is_valuation_date = match frequency {
+            NavFrequency::Daily => true,
+            NavFrequency::Monthly => next_date.month() != date.month(),
+        } || date == end_date;
+
+        if is_valuation_date {
+            let mut values = Vec::new();
+
+            for (&symbol, &quantity) in positions.advance(date) {
+                // Derivatives have no historical quotes
+                if is_derivative(statement, symbol) {
+                    skipped_derivatives.insert(symbol);
+                    continue;
+                }
+
+                let price = quotes.get_historical(statement.get_quote_query(symbol), date).map_err(|e| format!(
+                    "Failed to get {} price for {}: {}", symbol, formatting::format_date(date), e))?;
+                values.push(price * quantity);
+            }
+
+            for &currency in currencies {
+                let cash = cash_assets.total_assets(date, currency, converter)?;
+
+                let mut securities = dec!(0);
+                for &value in &values {
+                    securities += converter.convert_to(date, value, currency)?;
+                }
+
+                result.push(NetAssetValue {
+                    portfolio: name.to_owned(),
+                    currency: currency.to_owned(),
+                    date, cash, securities,
+                });
+            }
+        }
+
+        date = next_date;
+    }
+
+    if !skipped_derivatives.is_empty() {
+        warn!("{:?} portfolio net asset value doesn't include derivatives which have no historical quotes: {}.",
+              name, skipped_derivatives.into_iter().collect::<Vec<_>>().join(", "));
+    }
+
+    Ok(result)
+}
+
+fn is_derivative(statement: &BrokerStatement, symbol: &str) -> bool {
+    statement.instrument_info.get(symbol).map_or(false, |instrument| instrument.type_.is_derivative())
+}
+
+/// Tracks open positions walking through the trades in chronological order
+struct PositionsTracker<'a> {
+    statement: &'a BrokerStatement,
+    trades: Peekable<IntoIter<(DateOptTime, &'a str, Decimal)>>,
+    positions: HashMap<&'a str, Decimal>,
+    time: Option<DateOptTime>,
+}
+
+impl<'a> PositionsTracker<'a> {
+    fn new(statement: &'a BrokerStatement) -> PositionsTracker<'a> {
+        let mut trades = Vec::with_capacity(statement.stock_buys.len() + statement.stock_sells.len());
+
+        for trade in &statement.stock_buys {
+            trades.push((trade.statement_time(), trade.symbol.as_str(), trade.quantity));
+        }
+
+        for trade in &statement.stock_sells {
+            trades.push((trade.conclusion_time, trade.symbol.as_str(), -trade.quantity));
+        }
+
+        trades.sort_by_key(|&(time, ..)| time);
+
+        PositionsTracker {
+            statement,
+            trades: trades.into_iter().peekable(),
+            positions: HashMap::new(),
+            time: None,
+        }
+    }
+
+    // Returns open positions at the end of the specified day (in shares which are actual for this
+    // day). The dates must be passed in ascending order.
+    fn advance(&mut self, date: Date) -> &HashMap<&'a str, Decimal> {
+        let stock_splits = &self.statement.stock_splits;
+        let time = DateOptTime::new_max_time(date);
+
+        if let Some(prev_time) = self.time.replace(time) {
+            for (symbol, quantity) in self.positions.iter_mut() {
+                *quantity *= stock_splits.get_multiplier(symbol, prev_time, time);
+            }
+        }
+
+        while let Some((trade_time, symbol, quantity)) = self.trades.next_if(|&(trade_time, ..)| {
+            trade_time.date <= date
+        }) {
+            let multiplier = stock_splits.get_multiplier(symbol, trade_time, time);
+            *self.positions.entry(symbol).or_default() += multiplier * quantity;
+        }
+
+        self.positions.retain(|_, quantity| !quantity.is_zero());
+        &self.positions
+    }
+}
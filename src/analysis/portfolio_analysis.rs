@@ -10,6 +10,7 @@ pub struct PortfolioPerformanceAnalysis {
     pub income_structure: IncomeStructure,
     pub instruments: BTreeMap<String, InstrumentPerformanceAnalysis>,
     pub portfolio: InstrumentPerformanceAnalysis,
+    pub benchmarks: Vec<InstrumentPerformanceAnalysis>,
 }
 
 impl PortfolioPerformanceAnalysis {
@@ -21,6 +22,10 @@ impl PortfolioPerformanceAnalysis {
         }
         self.portfolio.format(&mut table, "");
 
+        for benchmark in &self.benchmarks {
+            benchmark.format(&mut table, &benchmark.name);
+        }
+
         table.print(name);
     }
 }
@@ -74,6 +79,8 @@ pub struct InstrumentPerformanceAnalysis {
     pub investments: Decimal,
     pub result: Decimal,
     pub interest: Option<Decimal>,
+    pub xirr: Option<Decimal>, // Money-weighted rate of return
+    pub twr: Option<Decimal>, // Time-weighted rate of return
     pub inactive: bool,
 }
 
@@ -91,6 +98,10 @@ struct Row {
     duration: String,
     #[column(name="Interest", align="right")]
     interest: Option<String>,
+    #[column(name="XIRR", align="right")]
+    xirr: Option<String>,
+    #[column(name="TWR", align="right")]
+    twr: Option<String>,
 }
 
 impl InstrumentPerformanceAnalysis {
@@ -121,6 +132,8 @@ impl InstrumentPerformanceAnalysis {
             result: Cell::new_round_decimal(result),
             duration: duration,
             interest: self.interest.map(|interest| format!("{}%", interest)),
+            xirr: self.xirr.map(|xirr| format!("{}%", xirr)),
+            twr: self.twr.map(|twr| format!("{}%", twr)),
         });
 
         if self.inactive {
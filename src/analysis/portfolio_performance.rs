@@ -1,25 +1,29 @@
-use std::collections::{HashMap, BTreeMap};
+use std::collections::{HashMap, BTreeMap, BTreeSet};
 
+use chrono::Datelike;
 use itertools::Itertools;
-use log::{self, log_enabled, trace};
+use log::{self, log_enabled, trace, warn};
 
-use crate::broker_statement::{BrokerStatement, StockSource, StockSellType};
+use crate::broker_statement::{BrokerStatement, StockSource, StockSellType, calculate_variation_margin_profit};
 use crate::config::PortfolioConfig;
 use crate::core::{EmptyResult, GenericResult};
 use crate::currency::Cash;
 use crate::currency::converter::CurrencyConverter;
 use crate::formatting;
 use crate::localities::Country;
-use crate::taxes::{NetTax, NetTaxCalculator, NetLtoDeduction, NetLtoDeductionCalculator};
+use crate::quotes::Quotes;
+use crate::taxes::{NetTax, NetTaxCalculator, NetLtoDeduction, NetLtoDeductionCalculator, OtherIncome, TaxBase};
 use crate::time::{self, Date, DateOptTime};
 use crate::types::Decimal;
 
+use super::benchmark::Benchmark;
 use super::config::PerformanceMergingConfig;
 use super::deposit_emulator::{Transaction, InterestPeriod};
 use super::deposit_performance;
 use super::instrument_view::InstrumentDepositView;
 use super::portfolio_analysis::{
     PortfolioPerformanceAnalysis, InstrumentPerformanceAnalysis, IncomeStructure};
+use super::returns;
 
 /// Calculates average rate of return from cash investments by comparing portfolio performance to
 /// performance of a bank deposit with exactly the same investments and monthly capitalization.
@@ -28,10 +32,13 @@ pub struct PortfolioPerformanceAnalyser<'a> {
     country: &'a Country,
     currency: &'a str,
     converter: &'a CurrencyConverter,
+    quotes: &'a Quotes,
     include_closed_positions: bool,
     performance_merging_config: Option<PerformanceMergingConfig>,
 
     transactions: Vec<Transaction>,
+    benchmarks: BTreeMap<String, Benchmark>,
+    valuations: Vec<(Date, BTreeMap<Date, Decimal>)>,
     income_structure: IncomeStructure,
     instruments: Option<BTreeMap<String, InstrumentDepositView>>,
     net_lto_calc: NetLtoDeductionCalculator,
@@ -40,7 +47,7 @@ pub struct PortfolioPerformanceAnalyser<'a> {
 
 impl <'a> PortfolioPerformanceAnalyser<'a> {
     pub fn new(
-        country: &'a Country, currency: &'a str, converter: &'a CurrencyConverter,
+        country: &'a Country, currency: &'a str, converter: &'a CurrencyConverter, quotes: &'a Quotes,
         include_closed_positions: bool,
     ) -> PortfolioPerformanceAnalyser<'a> {
         PortfolioPerformanceAnalyser {
@@ -48,10 +55,13 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
             country,
             currency,
             converter,
+            quotes,
             include_closed_positions,
             performance_merging_config: None,
 
             transactions: Vec::new(),
+            benchmarks: BTreeMap::new(),
+            valuations: Vec::new(),
             income_structure: Default::default(),
             instruments: Some(BTreeMap::new()),
             net_lto_calc: NetLtoDeductionCalculator::new(),
@@ -61,15 +71,17 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
 
     pub fn add(
         &mut self, portfolio: &PortfolioConfig, statement: &BrokerStatement,
-        merge_performance: PerformanceMergingConfig,
+        merge_performance: PerformanceMergingConfig, other_income: &OtherIncome,
     ) -> EmptyResult {
         self.performance_merging_config.replace(merge_performance);
-        let result = self.add_inner(portfolio, statement);
+        let result = self.add_inner(portfolio, statement, other_income);
         self.performance_merging_config.take();
         result
     }
 
-    fn add_inner(&mut self, portfolio: &PortfolioConfig, statement: &BrokerStatement) -> EmptyResult {
+    fn add_inner(
+        &mut self, portfolio: &PortfolioConfig, statement: &BrokerStatement, other_income: &OtherIncome,
+    ) -> EmptyResult {
         // Assume that the caller has simulated sellout and just check it here
         if !statement.open_positions.is_empty() {
             return Err!(
@@ -77,10 +89,16 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
         }
 
         trace!("Deposit emulator transactions for {:?}:", portfolio.name);
-        self.process_deposits_and_withdrawals(statement)?;
-        self.process_positions(statement, portfolio)?;
-        self.process_dividends(statement, portfolio)?;
-        self.process_interest(statement, portfolio)?;
+        self.process_deposits_and_withdrawals(portfolio, statement)?;
+        self.process_historical_assets(statement)?;
+        // Progressive tax rates are applied to the total income of the taxpayer: income from the
+        // other portfolios is considered as received first, then dividends, coupons and interest
+        // and trading profit - at the end of the year.
+        let mut tax_base = other_income.tax_base();
+        self.process_positions(statement, portfolio, other_income)?;
+        self.process_dividends(statement, portfolio, &mut tax_base)?;
+        self.process_coupons(statement, portfolio, &mut tax_base)?;
+        self.process_interest(statement, portfolio, &mut tax_base)?;
         self.process_fees(statement)?;
         self.process_tax_agent_withholdings(statement)?;
         self.process_tax_deductions(portfolio)?;
@@ -112,10 +130,20 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
         let portfolio_performance = self.analyse_portfolio_performance()?;
         self.income_structure.net_profit = portfolio_performance.net_profit();
 
+        let mut benchmarks = Vec::new();
+        for (symbol, benchmark) in std::mem::take(&mut self.benchmarks) {
+            // Benchmark is just an informational row, so don't fail the whole analysis because of it
+            match benchmark.analyse(self.currency, self.converter, self.quotes, self.today) {
+                Ok(analysis) => benchmarks.push(analysis),
+                Err(e) => warn!("Unable to analyse {} benchmark performance: {}.", symbol, e),
+            }
+        }
+
         Ok((PortfolioPerformanceAnalysis {
             income_structure: self.income_structure,
             instruments: instrument_performance,
             portfolio: portfolio_performance,
+            benchmarks,
         }, self.net_lto_calc.calculate()))
     }
 
@@ -135,6 +163,11 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
             Ok(interest)
         }).transpose()?;
 
+        let end_date = self.get_end_date(&deposit_view.transactions);
+        let xirr = returns::calculate_xirr(&deposit_view.transactions, end_date, dec!(0));
+        let twr = returns::calculate_twr(
+            &deposit_view.transactions, &deposit_view.valuations(), end_date, dec!(0));
+
         let name = deposit_view.name.unwrap();
         let days = get_total_activity_duration(&deposit_view.interest_periods);
 
@@ -150,7 +183,7 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
         }
 
         Ok(InstrumentPerformanceAnalysis {
-            name, days, investments, result, interest,
+            name, days, investments, result, interest, xirr, twr,
             inactive: deposit_view.closed,
         })
     }
@@ -175,6 +208,11 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
             Ok(interest)
         }).transpose()?;
 
+        let end_date = self.get_end_date(&self.transactions);
+        let xirr = returns::calculate_xirr(&self.transactions, end_date, self.current_assets);
+        let twr = returns::calculate_twr(
+            &self.transactions, &self.get_portfolio_valuations(), end_date, self.current_assets);
+
         let days = get_total_activity_duration(&activity_periods);
         let investments = self.transactions.iter()
             .map(|transaction| transaction.amount)
@@ -184,11 +222,36 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
             name: s!("Portfolio"),
             days, investments,
             result: self.current_assets,
-            interest,
+            interest, xirr, twr,
             inactive: false
         })
     }
 
+    // Tax payments may be scheduled after today
+    fn get_end_date(&self, transactions: &[Transaction]) -> Date {
+        transactions.iter().map(|transaction| transaction.date).fold(self.today, std::cmp::max)
+    }
+
+    // Portfolio value is known only for the dates when all the portfolios have a valuation or
+    // haven't been opened yet
+    fn get_portfolio_valuations(&self) -> BTreeMap<Date, Decimal> {
+        let dates: BTreeSet<Date> = self.valuations.iter()
+            .flat_map(|(_, valuations)| valuations.keys().copied())
+            .collect();
+
+        dates.into_iter().filter_map(|date| {
+            let value: Option<Decimal> = self.valuations.iter().map(|(first_date, valuations)| {
+                if date < *first_date {
+                    Some(dec!(0))
+                } else {
+                    valuations.get(&date).copied()
+                }
+            }).sum();
+
+            value.map(|value| (date, value))
+        }).collect()
+    }
+
     fn calculate_open_position_periods(&mut self) -> EmptyResult {
         trace!("Open positions periods:");
 
@@ -209,13 +272,25 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
         Ok(())
     }
 
-    fn process_deposits_and_withdrawals(&mut self, statement: &BrokerStatement) -> EmptyResult {
-        for mut assets in statement.deposits_and_withdrawals.iter().cloned() {
-            if assets.cash.is_positive() {
-                assets.cash.amount += statement.broker.get_deposit_commission(assets)?;
+    fn process_deposits_and_withdrawals(
+        &mut self, portfolio: &PortfolioConfig, statement: &BrokerStatement,
+    ) -> EmptyResult {
+        let mut benchmark = portfolio.benchmark.as_ref().map(|symbol| {
+            self.benchmarks.entry(symbol.clone()).or_insert_with(|| {
+                Benchmark::new(symbol, statement.broker.exchanges())
+            })
+        });
+
+        for &assets in &statement.deposits_and_withdrawals {
+            let mut investments = assets;
+            if investments.cash.is_positive() {
+                investments.cash.amount += statement.broker.get_deposit_commission(assets)?;
             }
 
-            let amount = self.converter.convert_to(assets.date, assets.cash, self.currency)?;
+            let amount = self.converter.convert_to(investments.date, investments.cash, self.currency)?;
+            if let Some(benchmark) = benchmark.as_mut() {
+                benchmark.cash_flow(assets, amount);
+            }
 
             trace!("* {} {}: {}", if amount.is_sign_positive() {
                 "Deposit"
@@ -229,17 +304,56 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
         Ok(())
     }
 
-    fn process_positions(&mut self, statement: &BrokerStatement, portfolio: &PortfolioConfig) -> EmptyResult {
-        let mut taxes = NetTaxCalculator::new(self.country.clone(), portfolio.tax_payment_day());
+    // Net asset value is provided not by all brokers and is used only for time-weighted return
+    // calculation
+    fn process_historical_assets(&mut self, statement: &BrokerStatement) -> EmptyResult {
+        let mut valuations = BTreeMap::new();
+
+        for (&date, assets) in &statement.historical_assets {
+            if let Some(other) = assets.other {
+                let cash = assets.cash.total_assets(date, self.currency, self.converter)?;
+                let other = self.converter.convert_to(date, other, self.currency)?;
+                valuations.insert(date, cash + other);
+            }
+        }
+
+        self.valuations.push((statement.period.first_date(), valuations));
+        Ok(())
+    }
+
+    fn process_positions(
+        &mut self, statement: &BrokerStatement, portfolio: &PortfolioConfig, other_income: &OtherIncome,
+    ) -> EmptyResult {
+        let mut tax_base = other_income.tax_base();
+        tax_base.extend(&statement.non_trading_tax_base(self.country, self.converter)?);
+
+        let mut taxes = NetTaxCalculator::new(self.country.clone(), portfolio.tax_payment_day())
+            .with_loss_carryforward(other_income.loss_carryforward())
+            .with_tax_base(tax_base.clone());
         let mut stock_taxes = HashMap::new();
 
         for trade in &statement.stock_buys {
             let multiplier = statement.stock_splits.get_multiplier(
-                &trade.symbol, trade.conclusion_time, DateOptTime::new_max_time(self.today));
+                &trade.symbol, trade.statement_time(), DateOptTime::new_max_time(self.today));
             let quantity = multiplier * trade.quantity;
 
+            // Transferred securities are treated as a deposit valued at their cost basis
+            if let Some(transfer_time) = trade.transfer_time {
+                let cost = trade.total_cost(self.currency, self.converter)?.amount;
+
+                let deposit_view = self.get_deposit_view(&trade.symbol);
+                deposit_view.trade(&portfolio.name, &trade.symbol, transfer_time, quantity);
+                deposit_view.transaction(transfer_time, cost);
+
+                self.transaction(transfer_time.date, cost);
+                continue;
+            }
+
             match trade.type_ {
-                StockSource::Trade {volume, commission, ..} => {
+                StockSource::Trade {price, volume, commission} => {
+                    let price = self.converter.convert_to(
+                        trade.conclusion_time.date, price, self.currency)? / multiplier;
+
                     let volume = self.converter.convert_to(
                         trade.execution_date, volume, self.currency)?;
 
@@ -249,8 +363,13 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
 
                     let deposit_view = self.get_deposit_view(&trade.symbol);
                     deposit_view.trade(&portfolio.name, &trade.symbol, trade.conclusion_time, quantity);
+                    deposit_view.price(&portfolio.name, &trade.symbol, trade.conclusion_time, price);
                     deposit_view.transaction(trade.conclusion_time, volume);
                     deposit_view.transaction(trade.conclusion_time, commission);
+
+                    if trade.emulation {
+                        deposit_view.closed = false;
+                    }
                 },
 
                 StockSource::CorporateAction | StockSource::Grant => {
@@ -266,7 +385,10 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
             let quantity = multiplier * trade.quantity;
 
             match trade.type_ {
-                StockSellType::Trade {volume, commission, ..} => {
+                StockSellType::Trade {price, volume, commission} => {
+                    let price = self.converter.convert_to(
+                        trade.conclusion_time.date, price, self.currency)? / multiplier;
+
                     let volume = self.converter.convert_to(
                         trade.execution_date, volume, self.currency)?;
 
@@ -278,6 +400,7 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
                         let deposit_view = self.get_deposit_view(&trade.symbol);
 
                         deposit_view.trade(&portfolio.name, &trade.symbol, trade.conclusion_time, -quantity);
+                        deposit_view.price(&portfolio.name, &trade.symbol, trade.conclusion_time, price);
                         deposit_view.transaction(trade.conclusion_time, -volume);
                         deposit_view.transaction(trade.conclusion_time, commission);
 
@@ -286,7 +409,8 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
                         }
                     }
 
-                    let (tax_year, _) = portfolio.tax_payment_day().get(trade.execution_date, true);
+                    let realization_date = trade.realization_date();
+                    let (tax_year, _) = portfolio.tax_payment_day().get(realization_date, true);
                     let details = trade.calculate(self.country, tax_year, &portfolio.tax_exemptions, self.converter)?;
 
                     let mut lto_deductibles = Vec::new();
@@ -300,22 +424,40 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
 
                     stock_taxes.entry(&trade.symbol)
                         .or_insert_with(|| NetTaxCalculator::new(
-                            self.country.clone(), portfolio.tax_payment_day()))
+                            self.country.clone(), portfolio.tax_payment_day(),
+                        ).with_tax_base(tax_base.clone()))
                         .add_profit(
-                            trade.execution_date, details.local_profit, details.taxable_local_profit,
+                            realization_date, details.local_profit, details.taxable_local_profit,
                             &lto_deductibles, trade.emulation);
 
                     taxes.add_profit(
-                        trade.execution_date, details.local_profit, details.taxable_local_profit,
+                        realization_date, details.local_profit, details.taxable_local_profit,
                         &lto_deductibles, trade.emulation);
                 },
                 StockSellType::CorporateAction => {
                     self.get_deposit_view(&trade.symbol).trade(
                         &portfolio.name, &trade.symbol, trade.conclusion_time, -quantity);
                 },
+                StockSellType::Transfer => {
+                    // Transferred securities are treated as a withdrawal valued at their cost basis
+                    let cost = trade.total_cost(self.currency, self.converter)?.amount;
+
+                    let deposit_view = self.get_deposit_view(&trade.symbol);
+                    deposit_view.trade(&portfolio.name, &trade.symbol, trade.conclusion_time, -quantity);
+                    deposit_view.transaction(trade.conclusion_time, -cost);
+
+                    self.transaction(trade.conclusion_time.date, -cost);
+                },
             };
         }
 
+        // Futures are settled daily via variation margin which is taxed as trading income
+        for margin in calculate_variation_margin_profit(
+            &statement.variation_margin, self.country.currency, &self.converter)?
+        {
+            taxes.add_profit(margin.date, margin.local_profit, margin.local_profit, &[], false);
+        }
+
         for (symbol, symbol_taxes) in stock_taxes.into_iter() {
             for (_, NetTax{tax_payment_date, tax_to_pay, ..}) in symbol_taxes.calculate().into_iter() {
                 if let Some(amount) = self.map_tax_to_deposit_amount(tax_payment_date, tax_to_pay)? {
@@ -348,7 +490,9 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
         Ok(())
     }
 
-    fn process_dividends(&mut self, statement: &BrokerStatement, portfolio: &PortfolioConfig) -> EmptyResult {
+    fn process_dividends(
+        &mut self, statement: &BrokerStatement, portfolio: &PortfolioConfig, tax_base: &mut TaxBase,
+    ) -> EmptyResult {
         for dividend in &statement.dividends {
             let income = dividend.amount.sub(dividend.paid_tax).map_err(|e| format!(
                 "{}: The tax is paid in currency different from the dividend currency: {}",
@@ -358,9 +502,13 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
             self.get_deposit_view(&dividend.issuer).transaction(dividend.date.into(), -income);
             self.income_structure.dividends += income;
 
-            let tax_to_pay = dividend.tax_to_pay(self.country, self.converter)?;
+            let tax_to_pay = dividend.tax_to_pay(self.country, self.converter, tax_base)?;
             let (_, tax_payment_date) = portfolio.tax_payment_day().get(dividend.date, false);
 
+            let local_amount = self.converter.convert_to_cash_rounding(
+                dividend.date, dividend.amount, self.country.currency)?;
+            tax_base.add(dividend.date.year(), local_amount.amount);
+
             if let Some(amount) = self.map_tax_to_deposit_amount(tax_payment_date, tax_to_pay)? {
                 trace!("* {} {} dividend {} tax: {}",
                     dividend.original_issuer, formatting::format_date(dividend.date),
@@ -375,14 +523,49 @@ impl <'a> PortfolioPerformanceAnalyser<'a> {
         Ok(())
     }
 
-    fn process_interest(&mut self, statement: &BrokerStatement, portfolio: &PortfolioConfig) -> EmptyResult {
+    fn process_coupons(
+        &mut self, statement: &BrokerStatement, portfolio: &PortfolioConfig, tax_base: &mut TaxBase,
+    ) -> EmptyResult {
+        for coupon in &statement.coupons {
+            let income = self.converter.convert_to(coupon.date, coupon.amount, self.currency)?;
+            self.get_deposit_view(&coupon.symbol).transaction(coupon.date.into(), -income);
+            self.income_structure.interest += income;
+
+            let tax_to_pay = coupon.tax_to_pay(self.country, self.converter, tax_base)?;
+            let (_, tax_payment_date) = portfolio.tax_payment_day().get(coupon.date, false);
+
+            let local_amount = self.converter.convert_to_cash_rounding(
+                coupon.date, coupon.amount, self.country.currency)?;
+            tax_base.add(coupon.date.year(), local_amount.amount);
+
+            if let Some(amount) = self.map_tax_to_deposit_amount(tax_payment_date, tax_to_pay)? {
+                trace!("* {} {} coupon {} tax: {}",
+                    coupon.original_symbol, formatting::format_date(coupon.date),
+                    formatting::format_date(tax_payment_date), amount);
+
+                self.get_deposit_view(&coupon.symbol).transaction(tax_payment_date.into(), amount);
+                self.transaction(tax_payment_date, amount);
+                self.income_structure.interest_taxes += amount;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn process_interest(
+        &mut self, statement: &BrokerStatement, portfolio: &PortfolioConfig, tax_base: &mut TaxBase,
+    ) -> EmptyResult {
         for interest in &statement.idle_cash_interest {
             self.income_structure.interest += self.converter.convert_to(
                 interest.date, interest.amount, self.currency)?;
 
-            let tax_to_pay = interest.tax_to_pay(self.country, self.converter)?;
+            let tax_to_pay = interest.tax_to_pay(self.country, self.converter, tax_base)?;
             let (_, tax_payment_date) = portfolio.tax_payment_day().get(interest.date, false);
 
+            let local_amount = self.converter.convert_to_cash_rounding(
+                interest.date, interest.amount, self.country.currency)?;
+            tax_base.add(interest.date.year(), local_amount.amount);
+
             if let Some(amount) = self.map_tax_to_deposit_amount(tax_payment_date, tax_to_pay)? {
                 trace!("* {} idle cash interest {} tax: {}",
                        formatting::format_date(interest.date),
@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+
+use num_traits::{FromPrimitive, ToPrimitive};
+
+use crate::time::Date;
+use crate::types::Decimal;
+use crate::util;
+
+use super::deposit_emulator::Transaction;
+
+/// Calculates money-weighted rate of return (XIRR): annual rate (in percents) at which the
+/// transactions (positive for investments and negative for withdrawals) result in the specified
+/// assets at the end date.
+pub fn calculate_xirr(transactions: &[Transaction], end_date: Date, result: Decimal) -> Option<Decimal> {
+    let start_date = transactions.iter().map(|transaction| transaction.date).min()?;
+    if end_date <= start_date {
+        return None;
+    }
+
+    let mut cash_flows: Vec<(f64, f64)> = transactions.iter().map(|transaction| {
+        (get_years(start_date, transaction.date), -transaction.amount.to_f64().unwrap())
+    }).collect();
+    cash_flows.push((get_years(start_date, end_date), result.to_f64().unwrap()));
+
+    let npv = |rate: f64| -> f64 {
+        cash_flows.iter().map(|&(years, amount)| amount / (1.0 + rate).powf(years)).sum()
+    };
+
+    let mut low = -0.9999;
+    let mut high = 1.0;
+    let low_npv = npv(low);
+
+    while npv(high).signum() == low_npv.signum() {
+        if high >= 1e6 {
+            return None;
+        }
+        high *= 10.0;
+    }
+
+    for _ in 0..200 {
+        let rate = (low + high) / 2.0;
+        if npv(rate).signum() == low_npv.signum() {
+            low = rate;
+        } else {
+            high = rate;
+        }
+    }
+
+    to_percent((low + high) / 2.0)
+}
+
+/// Calculates time-weighted rate of return (in percents), annualized for periods longer than a
+/// year. Returns between the known valuations (assets value at the end of the day) are calculated
+/// using Modified Dietz method and then chain-linked, so precision of the result depends on how
+/// often the valuations are available. Periods without invested capital are skipped.
+///
+/// Returns `None` if there are cash flows after the first day, but no valuations at all: the result
+/// would be a plain Modified Dietz return for the whole period then.
+pub fn calculate_twr(
+    transactions: &[Transaction], valuations: &BTreeMap<Date, Decimal>, end_date: Date, result: Decimal,
+) -> Option<Decimal> {
+    let start_date = transactions.iter().map(|transaction| transaction.date).min()?;
+
+    if end_date <= start_date {
+        return None;
+    }
+
+    if valuations.range(start_date.succ()..end_date).next().is_none() &&
+        transactions.iter().any(|transaction| transaction.date != start_date) {
+        return None;
+    }
+
+    // The first period starts with zero assets at the beginning of the first day, all others - at
+    // the end of the day.
+    let mut points = vec![(start_date, dec!(0))];
+    points.extend(valuations.range(start_date.succ()..end_date).map(|(&date, &value)| (date, value)));
+    points.push((end_date, result));
+
+    let mut growth = dec!(1);
+    let mut days = 0;
+
+    for (index, period) in points.windows(2).enumerate() {
+        let (start, start_value) = period[0];
+        let (end, end_value) = period[1];
+
+        let period_days = (end - start).num_days();
+        if start_value.is_zero() && end_value.is_zero() {
+            continue;
+        }
+
+        let mut cash_flow = dec!(0);
+        let mut weighted_cash_flow = dec!(0);
+
+        for transaction in transactions.iter().filter(|transaction| {
+            (start < transaction.date || (index == 0 && start == transaction.date)) && transaction.date <= end
+        }) {
+            let weight = Decimal::from((end - transaction.date).num_days()) / Decimal::from(period_days);
+            cash_flow += transaction.amount;
+            weighted_cash_flow += transaction.amount * weight;
+        }
+
+        let capital = start_value + weighted_cash_flow;
+        if !capital.is_sign_positive() || capital.is_zero() {
+            continue;
+        }
+
+        growth *= dec!(1) + (end_value - start_value - cash_flow) / capital;
+        days += period_days;
+    }
+
+    if days == 0 || !growth.is_sign_positive() || growth.is_zero() {
+        return None;
+    }
+
+    let growth = growth.to_f64().unwrap();
+    if days < 365 {
+        return to_percent(growth - 1.0);
+    }
+
+    to_percent(growth.powf(365.0 / days as f64) - 1.0)
+}
+
+fn get_years(start_date: Date, date: Date) -> f64 {
+    (date - start_date).num_days() as f64 / 365.0
+}
+
+fn to_percent(rate: f64) -> Option<Decimal> {
+    if !rate.is_finite() {
+        return None;
+    }
+    Decimal::from_f64(rate * 100.0).map(|rate| util::round(rate, 2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xirr() {
+        let transactions = [
+            Transaction::new(date!(2020, 1, 1), dec!(1000)),
+            Transaction::new(date!(2021, 1, 1), dec!(1000)),
+        ];
+
+        assert_eq!(calculate_xirr(&transactions[..1], date!(2020, 12, 31), dec!(1100)), Some(dec!(10)));
+        assert_eq!(calculate_xirr(&transactions, date!(2021, 12, 31), dec!(2000)), Some(dec!(0)));
+        assert_eq!(calculate_xirr(&transactions[..1], date!(2020, 1, 1), dec!(1000)), None);
+    }
+
+    #[test]
+    fn twr() {
+        // The first year gives +50%, the second one gives -20% and the deposit before the loss
+        // doesn't affect the result.
+        let transactions = [
+            Transaction::new(date!(2020, 1, 1), dec!(1000)),
+            Transaction::new(date!(2020, 12, 31), dec!(8500)),
+        ];
+        let valuations = btreemap!{date!(2020, 12, 31) => dec!(10000)};
+
+        let twr = calculate_twr(&transactions, &valuations, date!(2021, 12, 31), dec!(8000)).unwrap();
+        assert_eq!(twr, dec!(9.54));
+
+        let xirr = calculate_xirr(&transactions, date!(2021, 12, 31), dec!(8000)).unwrap();
+        assert!(xirr < dec!(0));
+    }
+
+    #[test]
+    fn twr_without_valuations() {
+        let transactions = [
+            Transaction::new(date!(2020, 1, 1), dec!(1000)),
+            Transaction::new(date!(2020, 6, 1), dec!(1000)),
+        ];
+        let valuations = BTreeMap::new();
+
+        // Without cash flows a single period return is a true time-weighted return
+        let twr = calculate_twr(&transactions[..1], &valuations, date!(2021, 12, 31), dec!(1210)).unwrap();
+        assert_eq!(twr, dec!(10));
+
+        assert_eq!(calculate_twr(&transactions, &valuations, date!(2021, 12, 31), dec!(2500)), None);
+    }
+
+    #[test]
+    fn twr_short_period() {
+        // Periods shorter than a year aren't annualized
+        let transactions = [
+            Transaction::new(date!(2020, 1, 1), dec!(1000)),
+            Transaction::new(date!(2020, 1, 31), dec!(1000)),
+        ];
+        let valuations = btreemap!{date!(2020, 1, 31) => dec!(2100)};
+
+        let twr = calculate_twr(&transactions, &valuations, date!(2020, 3, 31), dec!(2541)).unwrap();
+        assert_eq!(twr, dec!(21));
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use num_traits::{FromPrimitive, ToPrimitive};
+use static_table_derive::StaticTable;
+
+use crate::broker_statement::BrokerStatement;
+use crate::config::{Config, PortfolioConfig};
+use crate::core::GenericResult;
+use crate::currency::converter::CurrencyConverter;
+use crate::formatting;
+use crate::localities;
+use crate::time::Date;
+use crate::types::Decimal;
+use crate::util;
+
+use super::deposit_emulator::Transaction;
+use super::nav::NetAssetValue;
+
+/// Portfolio risk metrics which are calculated from its periodic net asset value. All values except
+/// the ratios are in percents.
+pub struct RiskMetrics {
+    pub volatility: Decimal,
+    pub max_drawdown: Decimal,
+    pub drawdown_period: Option<(Date, Date)>,
+    pub sharpe_ratio: Option<Decimal>,
+    pub sortino_ratio: Option<Decimal>,
+}
+
+impl RiskMetrics {
+    pub fn print(&self, name: &str) {
+        let mut table = RiskTable::new();
+
+        table.add_row(RiskRow {
+            volatility: format!("{}%", self.volatility),
+            max_drawdown: format!("{}%", self.max_drawdown),
+            drawdown_period: self.drawdown_period.map(|(peak, trough)| format!(
+                "{} - {}", formatting::format_date(peak), formatting::format_date(trough))),
+            sharpe_ratio: self.sharpe_ratio,
+            sortino_ratio: self.sortino_ratio,
+        });
+
+        table.print(name);
+    }
+}
+
+#[derive(StaticTable)]
+#[table(name="RiskTable")]
+struct RiskRow {
+    #[column(name="Volatility", align="right")]
+    volatility: String,
+    #[column(name="Max drawdown", align="right")]
+    max_drawdown: String,
+    #[column(name="Drawdown period", align="center")]
+    drawdown_period: Option<String>,
+    #[column(name="Sharpe ratio")]
+    sharpe_ratio: Option<Decimal>,
+    #[column(name="Sortino ratio")]
+    sortino_ratio: Option<Decimal>,
+}
+
+/// Calculates risk metrics of the specified portfolios using their net asset value history (which
+/// is expected to be in the same order as the portfolios).
+pub fn analyse(
+    config: &Config, currency: &str, portfolios: &[(&PortfolioConfig, BrokerStatement)],
+    nav_history: &[Vec<NetAssetValue>], converter: &CurrencyConverter,
+) -> GenericResult<Option<RiskMetrics>> {
+    let mut histories = Vec::new();
+    let mut dates = BTreeSet::new();
+    let mut transactions = Vec::new();
+
+    for ((_, statement), history) in portfolios.iter().zip(nav_history) {
+        let history: BTreeMap<Date, Decimal> = history.iter()
+            .filter(|value| value.currency == currency)
+            .map(|value| (value.date, value.total()))
+            .collect();
+
+        dates.extend(history.keys().cloned());
+        histories.push(history);
+
+        for assets in &statement.deposits_and_withdrawals {
+            let amount = converter.convert_to(assets.date, assets.cash, currency)?;
+            transactions.push(Transaction::new(assets.date, amount));
+        }
+    }
+
+    let mut values = BTreeMap::new();
+
+    'dates: for date in dates {
+        let mut total = dec!(0);
+
+        for history in &histories {
+            let (&first_date, _) = match history.iter().next() {
+                Some(first) => first,
+                None => continue,
+            };
+            let (&last_date, &last_value) = history.iter().next_back().unwrap();
+
+            total += match history.get(&date) {
+                Some(&value) => value,
+
+                // The portfolio isn't opened yet or is closed already
+                None if date < first_date || (date > last_date && last_value.is_zero()) => continue,
+
+                // The portfolio has no valuation for this date
+                None => continue 'dates,
+            };
+        }
+
+        values.insert(date, total);
+    }
+
+    let risk_free_rates = values.keys().map(|&date| {
+        Ok((date, get_risk_free_rate(config, currency, date)?))
+    }).collect::<GenericResult<BTreeMap<Date, Option<Decimal>>>>()?;
+
+    Ok(calculate(&values, &transactions, &|date| risk_free_rates[&date]))
+}
+
+/// Returns annual risk-free rate (in percents) for the specified currency: the configured one or
+/// Central Bank of Russia key rate for RUB.
+fn get_risk_free_rate(config: &Config, currency: &str, date: Date) -> GenericResult<Option<Decimal>> {
+    Ok(match config.risk_free_rates.get(currency) {
+        Some(&rate) => Some(rate),
+        None if currency == "RUB" => Some(localities::get_russian_central_bank_key_rate(date).ok_or_else(|| format!(
+            "Central Bank of Russia key rate is unknown for {}. Please specify RUB risk-free rate in the configuration file",
+            formatting::format_date(date)))?),
+        None => None,
+    })
+}
+
+/// Calculates risk metrics from the net asset value series. Returns for periods between the
+/// valuations are calculated using Modified Dietz method to exclude the deposits and withdrawals
+/// impact. Sharpe and Sortino ratios are calculated only if risk-free rate is known for each period.
+fn calculate(
+    values: &BTreeMap<Date, Decimal>, transactions: &[Transaction],
+    risk_free_rate: &dyn Fn(Date) -> Option<Decimal>,
+) -> Option<RiskMetrics> {
+    struct Return {
+        start: Date,
+        end: Date,
+        rate: f64,
+        risk_free_rate: Option<f64>,
+    }
+
+    let mut returns = Vec::new();
+    let mut days = 0;
+
+    for ((&start, &start_value), (&end, &end_value)) in values.iter().zip(values.iter().skip(1)) {
+        let period_days = (end - start).num_days();
+
+        let mut cash_flow = dec!(0);
+        let mut weighted_cash_flow = dec!(0);
+
+        for transaction in transactions.iter().filter(|transaction| {
+            start < transaction.date && transaction.date <= end
+        }) {
+            let weight = Decimal::from((end - transaction.date).num_days()) / Decimal::from(period_days);
+            cash_flow += transaction.amount;
+            weighted_cash_flow += transaction.amount * weight;
+        }
+
+        let capital = start_value + weighted_cash_flow;
+        if !capital.is_sign_positive() || capital.is_zero() {
+            continue;
+        }
+
+        returns.push(Return {
+            start, end,
+            rate: ((end_value - start_value - cash_flow) / capital).to_f64().unwrap(),
+            risk_free_rate: risk_free_rate(start).map(|rate| {
+                rate.to_f64().unwrap() / 100.0 * period_days as f64 / 365.0
+            }),
+        });
+        days += period_days;
+    }
+
+    if returns.len() < 2 {
+        return None;
+    }
+
+    let count = returns.len() as f64;
+    let annualization = (count * 365.0 / days as f64).sqrt();
+
+    let mean = returns.iter().map(|period| period.rate).sum::<f64>() / count;
+    let deviation = (returns.iter().map(|period| (period.rate - mean).powi(2)).sum::<f64>() / (count - 1.0)).sqrt();
+
+    let mut sharpe_ratio = None;
+    let mut sortino_ratio = None;
+
+    if let Some(excess_returns) = returns.iter().map(|period| {
+        period.risk_free_rate.map(|risk_free_rate| period.rate - risk_free_rate)
+    }).collect::<Option<Vec<f64>>>() {
+        let mean_excess = excess_returns.iter().sum::<f64>() / count;
+        let downside_deviation = (excess_returns.iter()
+            .map(|&excess| excess.min(0.0).powi(2))
+            .sum::<f64>() / count).sqrt();
+
+        if deviation > 0.0 {
+            sharpe_ratio = to_decimal(mean_excess / deviation * annualization);
+        }
+
+        if downside_deviation > 0.0 {
+            sortino_ratio = to_decimal(mean_excess / downside_deviation * annualization);
+        }
+    }
+
+    let mut index = 1.0;
+    let mut peak = (returns.first().unwrap().start, index);
+    let mut max_drawdown = 0.0;
+    let mut drawdown_period = None;
+
+    for period in &returns {
+        index *= 1.0 + period.rate;
+
+        if index > peak.1 {
+            peak = (period.end, index);
+            continue;
+        }
+
+        let drawdown = 1.0 - index / peak.1;
+        if drawdown > max_drawdown {
+            max_drawdown = drawdown;
+            drawdown_period = Some((peak.0, period.end));
+        }
+    }
+
+    Some(RiskMetrics {
+        volatility: to_decimal(deviation * annualization * 100.0)?,
+        max_drawdown: to_decimal(max_drawdown * 100.0)?,
+        drawdown_period, sharpe_ratio, sortino_ratio,
+    })
+}
+
+fn to_decimal(value: f64) -> Option<Decimal> {
+    if !value.is_finite() {
+        return None;
+    }
+    Decimal::from_f64(value).map(|value| util::round(value, 2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn risk_metrics() {
+        // +10%, -10% and +10% with a deposit at the end of the last period which doesn't affect the
+        // returns.
+        let values = btreemap!{
+            date!(2021, 1, 31) => dec!(1000),
+            date!(2021, 2, 28) => dec!(1100),
+            date!(2021, 3, 31) => dec!(990),
+            date!(2021, 4, 30) => dec!(2089),
+        };
+        let transactions = [
+            Transaction::new(date!(2021, 1, 31), dec!(1000)),
+            Transaction::new(date!(2021, 4, 30), dec!(1000)),
+        ];
+
+        let metrics = calculate(&values, &transactions, &|_| Some(dec!(0))).unwrap();
+        assert_eq!(metrics.volatility, dec!(40.50));
+        assert_eq!(metrics.max_drawdown, dec!(10));
+        assert_eq!(metrics.drawdown_period, Some((date!(2021, 2, 28), date!(2021, 3, 31))));
+        assert_eq!(metrics.sharpe_ratio, Some(dec!(1.01)));
+        assert!(metrics.sortino_ratio.unwrap() > metrics.sharpe_ratio.unwrap());
+
+        let metrics = calculate(&values, &transactions, &|_| None).unwrap();
+        assert_eq!(metrics.sharpe_ratio, None);
+        assert_eq!(metrics.sortino_ratio, None);
+
+        assert!(calculate(&BTreeMap::new(), &[], &|_| None).is_none());
+    }
+}
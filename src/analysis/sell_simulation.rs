@@ -23,8 +23,9 @@ pub fn simulate_sell(
 ) -> EmptyResult {
     let all_positions = positions.is_none();
     let positions = positions.unwrap_or_else(|| {
-        statement.open_positions.keys()
-            .map(|symbol| (symbol.to_owned(), None))
+        statement.open_positions.iter()
+            .filter(|(_, quantity)| quantity.is_sign_positive()) // Short positions aren't supported here
+            .map(|(symbol, _)| (symbol.to_owned(), None))
             .sorted_unstable()
             .collect()
     });
@@ -35,7 +36,7 @@ pub fn simulate_sell(
                 return Err!("The portfolio has no open {:?} positions", symbol);
             }
         }
-        quotes.batch(statement.get_quote_query(symbol))?;
+        statement.batch_quote(quotes, symbol)?;
     }
 
     let net_value = statement.net_value(
@@ -53,7 +54,7 @@ pub fn simulate_sell(
                 "The portfolio has no open {:?} positions", symbol))?,
         };
 
-        let mut price = quotes.get(statement.get_quote_query(symbol))?;
+        let mut price = statement.get_price(quotes, symbol)?;
         if let Some(base_currency) = base_currency {
             price = trades::convert_price(price, quantity, base_currency, &converter)?;
         }
@@ -205,7 +206,7 @@ fn print_results(
         converter.real_time_date(), total_purchase_cost, total_purchase_local_cost,
         total_profit.clone(), total_local_profit, tax_to_pay, converter)?;
 
-    let mut totals = trades_table.add_empty_row();
+    let mut totals = trades_table.add_totals_row();
     totals.set_commission(total_commission);
     totals.set_revenue(total_revenue);
     totals.set_local_revenue(total_local_revenue);
@@ -1,5 +1,7 @@
 use std::path::PathBuf;
 
+use investments::analysis::nav::NavFrequency;
+use investments::portfolio::{CashAssetsSpec, RebalancingOptions};
 use investments::time::Date;
 use investments::types::Decimal;
 
@@ -7,6 +9,12 @@ pub enum Action {
     Analyse {
         name: Option<String>,
         show_closed_positions: bool,
+        risk_metrics: bool,
+    },
+    Nav {
+        name: Option<String>,
+        frequency: NavFrequency,
+        currency: Option<String>,
     },
     SimulateSell {
         name: String,
@@ -18,23 +26,29 @@ pub enum Action {
     Buy {
         name: String,
         positions: Vec<(String, Decimal)>,
-        cash_assets: Decimal,
+        cash_assets: CashAssetsSpec,
     },
     Sell {
         name: String,
         positions: Vec<(String, Option<Decimal>)>,
-        cash_assets: Decimal,
+        cash_assets: CashAssetsSpec,
     },
-    SetCashAssets(String, Decimal),
+    SetCashAssets(String, CashAssetsSpec),
 
     Show {
         name: String,
         flat: bool,
     },
+    Exposure {
+        name: Option<String>,
+        currency: Option<String>,
+    },
     Rebalance {
         name: String,
+        options: RebalancingOptions,
         flat: bool,
     },
+    RebalanceHousehold(String),
 
     TaxStatement {
         name: String,
@@ -19,6 +19,7 @@ use investments::config::Config;
 use investments::core::EmptyResult;
 use investments::db;
 use investments::deposits;
+use investments::formatting::output;
 use investments::metrics;
 use investments::portfolio;
 use investments::tax_statement;
@@ -55,7 +56,14 @@ fn main_inner(global: GlobalOptions, parser: Parser) -> EmptyResult {
     config.db_path = config_dir_path.join("db.sqlite").to_str().unwrap().to_owned();
 
     let (command, action) = parser.parse(&mut config)?;
-    run(config, &command, action)
+
+    output::set_format(global.output_format);
+    if let Some(ref path) = global.xlsx_path {
+        output::set_xlsx_path(path);
+    }
+
+    run(config, &command, action)?;
+    output::flush()
 }
 
 fn run(config: Config, command: &str, action: Action) -> EmptyResult {
@@ -74,25 +82,30 @@ fn run(config: Config, command: &str, action: Action) -> EmptyResult {
     };
 
     let record: TelemetryRecordBuilder = match action {
-        Action::Analyse {name, show_closed_positions} => {
+        Action::Analyse {name, show_closed_positions, risk_metrics} => {
             let (statistics, _, telemetry) = analysis::analyse(
-                &config, name.as_deref(), show_closed_positions, None, true)?;
+                &config, name.as_deref(), show_closed_positions, None, risk_metrics, true)?;
             statistics.print();
             telemetry
         },
+        Action::Nav {name, frequency, currency} => analysis::nav::show(
+            &config, name.as_deref(), frequency, currency.as_deref())?,
         Action::SimulateSell {name, positions, base_currency} => analysis::simulate_sell(
             &config, &name, positions, base_currency.as_deref())?,
 
         Action::Sync(name) => portfolio::sync(&config, &name)?,
         Action::Buy {name, positions, cash_assets} =>
-            portfolio::buy(&config, &name, &positions, cash_assets)?,
+            portfolio::buy(&config, &name, &positions, &cash_assets)?,
         Action::Sell {name, positions, cash_assets} =>
-            portfolio::sell(&config, &name, &positions, cash_assets)?,
+            portfolio::sell(&config, &name, &positions, &cash_assets)?,
         Action::SetCashAssets(name, cash_assets) =>
-            portfolio::set_cash_assets(&config, &name, cash_assets)?,
+            portfolio::set_cash_assets(&config, &name, &cash_assets)?,
 
         Action::Show {name, flat} => portfolio::show(&config, &name, flat)?,
-        Action::Rebalance {name, flat} => portfolio::rebalance(&config, &name, flat)?,
+        Action::Exposure {name, currency} => portfolio::show_exposure(
+            &config, name.as_deref(), currency.as_deref())?,
+        Action::Rebalance {name, options, flat} => portfolio::rebalance(&config, &name, options, flat)?,
+        Action::RebalanceHousehold(name) => portfolio::rebalance_household(&config, &name)?,
 
         Action::TaxStatement {name, year, tax_statement_path} =>
             tax_statement::generate_tax_statement(
@@ -1,17 +1,19 @@
-use std::str::FromStr;
-
 use clap::{Arg, ArgMatches, ArgEnum};
 use clap_complete::{self, Shell};
 use const_format::formatcp;
 
+use investments::analysis::nav::NavFrequency;
 use investments::cli;
 use investments::config::Config;
 use investments::core::GenericResult;
+use investments::formatting::output::OutputFormat;
+use investments::portfolio::RebalancingOptions;
 use investments::time;
-use investments::types::{Date, Decimal};
+use investments::types::Date;
+use investments::util::{self, DecimalRestrictions};
 
 use super::action::Action;
-use super::positions::PositionsParser;
+use super::positions::{PositionsParser, parse_cash_assets};
 
 pub struct Parser {
     matches: Option<ArgMatches>,
@@ -25,6 +27,8 @@ pub struct Parser {
 pub struct GlobalOptions {
     pub log_level: log::Level,
     pub config_dir: String,
+    pub output_format: OutputFormat,
+    pub xlsx_path: Option<String>,
 }
 
 impl Parser {
@@ -33,8 +37,8 @@ impl Parser {
             matches: None,
             completion: None,
 
-            bought: PositionsParser::new("Bought shares", false, true),
-            sold: PositionsParser::new("Sold shares", true, true),
+            bought: PositionsParser::new("Bought shares", false, true).with_cash_assets(),
+            sold: PositionsParser::new("Sold shares", true, true).with_cash_assets(),
             to_sell: PositionsParser::new("Positions to sell", true, false),
         }
     }
@@ -66,6 +70,14 @@ impl Parser {
                     .short('v').long("verbose")
                     .multiple_occurrences(true)
                     .max_occurrences(2),
+
+                cli::new_arg("format", "Output format of the command results")
+                    .long("format").value_name("FORMAT")
+                    .possible_values(["text", "json", "csv"])
+                    .default_value("text"),
+
+                cli::new_arg("xlsx", "Export the result tables to XLSX workbook")
+                    .long("xlsx").value_name("PATH"),
             ])
 
             .subcommand(cli::new_subcommand(
@@ -78,11 +90,32 @@ impl Parser {
                     cli::new_arg("all", "Don't hide closed positions")
                         .short('a').long("all"),
 
+                    cli::new_arg("risk", "Calculate risk metrics (requires historical quotes)")
+                        .short('r').long("risk"),
+
                     cli::new_arg(
                         "PORTFOLIO",
                         "Portfolio name (omit to show an aggregated result for all portfolios)"),
                 ]))
 
+            .subcommand(cli::new_subcommand(
+                "nav", "Show portfolio net asset value history")
+                .long_about("\
+                    Reconstructs portfolio net asset value history from trades, cash flows and \
+                    historical quotes. Use global --format option to get it in CSV or JSON format.")
+                .args([
+                    cli::new_arg("monthly", "Export values only for the last day of each month")
+                        .short('m').long("monthly"),
+
+                    cli::new_arg("currency", "Currency to calculate the value in [default: USD and RUB]")
+                        .short('C').long("currency")
+                        .value_name("CURRENCY"),
+
+                    cli::new_arg(
+                        "PORTFOLIO",
+                        "Portfolio name (omit to export the data for all portfolios)"),
+                ]))
+
             .subcommand(cli::new_subcommand(
                 "show", "Show portfolio asset allocation")
                 .args([
@@ -92,6 +125,21 @@ impl Parser {
                     portfolio::arg(),
                 ]))
 
+            .subcommand(cli::new_subcommand(
+                "exposure", "Show real exposure by country, asset class, sector and currency")
+                .long_about("\
+                    Aggregates real exposure of the portfolios by country, asset class, sector and \
+                    currency looking through the instruments using their configured composition.")
+                .args([
+                    cli::new_arg("currency", "Currency to calculate the value in [default: tax country currency]")
+                        .short('C').long("currency")
+                        .value_name("CURRENCY"),
+
+                    cli::new_arg(
+                        "PORTFOLIO",
+                        "Portfolio name (omit to show an aggregated result for all portfolios)"),
+                ]))
+
             .subcommand(cli::new_subcommand(
                 "sync", "Sync portfolio with broker statement")
                 .arg(portfolio::arg()))
@@ -116,7 +164,7 @@ impl Parser {
                 "cash", "Set current cash assets")
                 .args([
                     portfolio::arg(),
-                    cash_assets::arg(),
+                    cash_assets::arg().multiple_values(true),
                 ]))
 
             .subcommand(cli::new_subcommand(
@@ -125,9 +173,34 @@ impl Parser {
                     cli::new_arg("flat", "Flat view")
                         .short('f').long("flat"),
 
+                    cli::new_arg("buy_only", "Don't sell anything: only buy underweight assets")
+                        .short('b').long("buy-only"),
+
+                    cli::new_arg("contribution", "Cash amount which is going to be deposited to the account")
+                        .long("contribution")
+                        .value_name("AMOUNT"),
+
+                    cli::new_arg("cron", "cron mode (rebalance only if some asset is out of its tolerance band)")
+                        .long("cron"),
+
+                    cli::new_arg("orders", "Export the resulting orders to the specified file")
+                        .short('o').long("orders")
+                        .value_name("PATH"),
+
+                    cli::new_arg("ib_basket", "Export the orders as Interactive Brokers basket file")
+                        .long("ib-basket").requires("orders"),
+
+                    cli::new_arg("tax_aware", "Minimize taxes: sell only the lots with low tax to pay")
+                        .short('t').long("tax-aware"),
+
                     portfolio::arg(),
                 ]))
 
+            .subcommand(cli::new_subcommand(
+                "rebalance-household",
+                "Rebalance several portfolios according to the household asset allocation configuration")
+                .arg(household::arg()))
+
             .subcommand(cli::new_subcommand(
                 "simulate-sell", "Simulate stock selling (calculates revenue, profit and taxes)")
                 .args([
@@ -214,7 +287,16 @@ impl Parser {
 
         self.matches = Some(matches);
 
-        Ok(GlobalOptions {log_level, config_dir})
+        let output_format = match matches.value_of("format").unwrap() {
+            "text" => OutputFormat::Text,
+            "json" => OutputFormat::Json,
+            "csv" => OutputFormat::Csv,
+            _ => unreachable!(),
+        };
+
+        let xlsx_path = matches.value_of("xlsx").map(ToOwned::to_owned);
+
+        Ok(GlobalOptions {log_level, config_dir, output_format, xlsx_path})
     }
 
     pub fn parse(mut self, config: &mut Config) -> GenericResult<(String, Action)> {
@@ -226,39 +308,67 @@ impl Parser {
         };
 
         let (command, matches) = matches.subcommand().unwrap();
-        let action = self.parse_command(command, matches)?;
+        let action = self.parse_command(config, command, matches)?;
 
         Ok((command.to_owned(), action))
     }
 
-    fn parse_command(&self, command: &str, matches: &ArgMatches) -> GenericResult<Action> {
+    fn parse_command(&self, config: &Config, command: &str, matches: &ArgMatches) -> GenericResult<Action> {
         Ok(match command {
             "analyse" => Action::Analyse {
                 name: matches.value_of("PORTFOLIO").map(ToOwned::to_owned),
                 show_closed_positions: matches.is_present("all"),
+                risk_metrics: matches.is_present("risk"),
+            },
+
+            "nav" => Action::Nav {
+                name: matches.value_of("PORTFOLIO").map(ToOwned::to_owned),
+                frequency: if matches.is_present("monthly") {
+                    NavFrequency::Monthly
+                } else {
+                    NavFrequency::Daily
+                },
+                currency: matches.value_of("currency").map(ToOwned::to_owned),
+            },
+
+            "exposure" => Action::Exposure {
+                name: matches.value_of("PORTFOLIO").map(ToOwned::to_owned),
+                currency: matches.value_of("currency").map(ToOwned::to_owned),
             },
 
             "sync" => Action::Sync(portfolio::get(matches)),
-            "buy" | "sell" | "cash" => {
+            "buy" | "sell" => {
                 let name = portfolio::get(matches);
-                let cash_assets = Decimal::from_str(&cash_assets::get(matches))
-                    .map_err(|_| "Invalid cash assets value")?;
+                let cash_assets = cash_assets::get(matches);
+
+                // Trailing $amount $currency pairs are told apart from the positions by the symbol
+                let stock_symbols = config.get_portfolio(&name)?.get_stock_symbols();
+                let is_stock = |symbol: &str| stock_symbols.contains(symbol);
 
                 match command {
-                    "buy" => Action::Buy {
-                        name, cash_assets,
-                        positions: self.bought.parse(matches)?.unwrap().into_iter().map(|(symbol, shares)| {
-                            (symbol, shares.unwrap())
-                        }).collect(),
+                    "buy" => {
+                        let (positions, cash_assets) = self.bought.parse_with_cash_assets(
+                            matches, &cash_assets, is_stock)?;
+
+                        Action::Buy {
+                            name, cash_assets,
+                            positions: positions.into_iter().map(|(symbol, shares)| {
+                                (symbol, shares.unwrap())
+                            }).collect(),
+                        }
                     },
-                    "sell" => Action::Sell {
-                        name, cash_assets,
-                        positions: self.sold.parse(matches)?.unwrap(),
+                    "sell" => {
+                        let (positions, cash_assets) = self.sold.parse_with_cash_assets(
+                            matches, &cash_assets, is_stock)?;
+                        Action::Sell {name, positions, cash_assets}
                     },
-                    "cash" => Action::SetCashAssets(name, cash_assets),
                     _ => unreachable!(),
                 }
             },
+            "cash" => {
+                let cash_assets: Vec<&str> = matches.values_of("CASH_ASSETS").unwrap().collect();
+                Action::SetCashAssets(portfolio::get(matches), parse_cash_assets(&cash_assets)?)
+            },
 
             "show" => Action::Show {
                 name: portfolio::get(matches),
@@ -267,8 +377,20 @@ impl Parser {
 
             "rebalance" => Action::Rebalance {
                 name: portfolio::get(matches),
+                options: RebalancingOptions {
+                    tax_aware: matches.is_present("tax_aware"),
+                    buy_only: matches.is_present("buy_only"),
+                    contribution: matches.value_of("contribution").map(|contribution| {
+                        util::parse_decimal(contribution, DecimalRestrictions::StrictlyPositive)
+                            .map_err(|_| format!("Invalid contribution amount: {:?}", contribution))
+                    }).transpose()?,
+                    cron_mode: matches.is_present("cron"),
+                    orders_path: matches.value_of("orders").map(ToOwned::to_owned),
+                    ib_basket: matches.is_present("ib_basket"),
+                },
                 flat: matches.is_present("flat"),
             },
+            "rebalance-household" => Action::RebalanceHousehold(household::get(matches)),
 
             "simulate-sell" => Action::SimulateSell {
                 name: portfolio::get(matches),
@@ -345,4 +467,5 @@ macro_rules! arg {
 }
 
 arg!(portfolio, "PORTFOLIO", "Portfolio name");
-arg!(cash_assets, "CASH_ASSETS", "Current cash assets");
\ No newline at end of file
+arg!(household, "HOUSEHOLD", "Household name");
+arg!(cash_assets, "CASH_ASSETS", "Current cash assets: $amount in portfolio currency or $amount $currency pairs");
\ No newline at end of file
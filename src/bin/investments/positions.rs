@@ -1,7 +1,11 @@
+use std::str::FromStr;
+
 use clap::{Arg, ArgMatches};
 
 use investments::cli;
 use investments::core::GenericResult;
+use investments::currency;
+use investments::portfolio::CashAssetsSpec;
 use investments::types::Decimal;
 use investments::util::{self, DecimalRestrictions};
 
@@ -11,6 +15,7 @@ pub struct PositionsParser {
 
     allow_all: bool,
     required: bool,
+    with_cash_assets: bool,
 }
 
 impl PositionsParser {
@@ -23,22 +28,75 @@ impl PositionsParser {
             "$quantity"
         });
 
-        PositionsParser {name, help, allow_all, required}
+        PositionsParser {name, help, allow_all, required, with_cash_assets: false}
+    }
+
+    /// Positions are followed by cash assets which may be specified as $amount $currency pairs, so
+    /// they can't be told apart by clap and are parsed by `parse_with_cash_assets()`.
+    pub fn with_cash_assets(mut self) -> PositionsParser {
+        self.with_cash_assets = true;
+        self
     }
 
     pub fn arg(&self) -> Arg {
-        cli::new_arg(PositionsParser::ARG_NAME, self.help.as_str())
-            .value_names(&["SHARES", "SYMBOL"])
-            .multiple_occurrences(true)
-            .required(self.required)
+        let arg = cli::new_arg(PositionsParser::ARG_NAME, self.help.as_str())
+            .required(self.required);
+
+        if self.with_cash_assets {
+            arg.value_name("SHARES SYMBOL").multiple_values(true)
+        } else {
+            arg.value_names(&["SHARES", "SYMBOL"]).multiple_occurrences(true)
+        }
     }
 
     pub fn parse(&self, matches: &ArgMatches) -> GenericResult<Option<Vec<(String, Option<Decimal>)>>> {
-        let mut args = match matches.values_of(PositionsParser::ARG_NAME) {
-            Some(args) => args,
+        let args: Vec<&str> = match matches.values_of(PositionsParser::ARG_NAME) {
+            Some(args) => args.collect(),
             None => return Ok(None),
         };
 
+        Ok(Some(self.parse_values(&args)?))
+    }
+
+    /// Parses positions followed by cash assets which are specified either as a single amount in
+    /// portfolio currency or as trailing $amount $currency pairs. The pairs are told apart from the
+    /// positions by their symbol which must be a known currency and not a stock of the portfolio.
+    pub fn parse_with_cash_assets<F: Fn(&str) -> bool>(
+        &self, matches: &ArgMatches, cash_assets: &str, is_stock: F,
+    ) -> GenericResult<(Vec<(String, Option<Decimal>)>, CashAssetsSpec)> {
+        let mut args: Vec<&str> = matches.values_of(PositionsParser::ARG_NAME)
+            .map(Iterator::collect).unwrap_or_default();
+        args.push(cash_assets);
+        self.parse_values_with_cash_assets(&args, is_stock)
+    }
+
+    fn parse_values_with_cash_assets<F: Fn(&str) -> bool>(
+        &self, args: &[&str], is_stock: F,
+    ) -> GenericResult<(Vec<(String, Option<Decimal>)>, CashAssetsSpec)> {
+        let mut cash_assets_start = args.len() - 1;
+        if args.len() % 2 == 0 {
+            cash_assets_start = args.len();
+            while cash_assets_start >= 2 {
+                let symbol = args[cash_assets_start - 1];
+                if !currency::is_known(symbol) || is_stock(symbol) {
+                    break;
+                }
+                cash_assets_start -= 2;
+            }
+        }
+
+        let positions = self.parse_values(&args[..cash_assets_start])?;
+        if positions.is_empty() {
+            return Err!("{} are not specified", self.name);
+        }
+
+        let cash_assets = parse_cash_assets(&args[cash_assets_start..])?;
+
+        Ok((positions, cash_assets))
+    }
+
+    fn parse_values(&self, args: &[&str]) -> GenericResult<Vec<(String, Option<Decimal>)>> {
+        let mut args = args.iter().copied();
         let mut positions = Vec::new();
 
         while let Some(quantity) = args.next() {
@@ -58,6 +116,92 @@ impl PositionsParser {
             positions.push((symbol.to_owned(), quantity));
         }
 
-        Ok(Some(positions))
+        Ok(positions)
+    }
+}
+
+/// Parses cash assets specified either as a single amount in portfolio currency or as
+/// $amount $currency pairs
+pub fn parse_cash_assets(args: &[&str]) -> GenericResult<CashAssetsSpec> {
+    let parse_amount = |amount: &str| -> GenericResult<Decimal> {
+        Ok(Decimal::from_str(amount).map_err(|_| format!("Invalid cash assets value: {:?}", amount))?)
+    };
+
+    match args {
+        [] => Err!("Cash assets are not specified"),
+        [amount] => Ok(CashAssetsSpec::Total(parse_amount(amount)?)),
+        _ if args.len() % 2 != 0 => Err!(
+            "Cash assets must be specified as a single amount or as $amount $currency pairs"),
+        _ => {
+            let mut assets: Vec<(String, Decimal)> = Vec::new();
+
+            for pair in args.chunks(2) {
+                let (amount, currency) = (parse_amount(pair[0])?, pair[1]);
+
+                if !currency::is_known(currency) {
+                    return Err!("Invalid cash assets currency: {:?}", currency);
+                } else if assets.iter().any(|(other, _)| other == currency) {
+                    return Err!("Duplicated {} cash assets", currency);
+                }
+
+                assets.push((currency.to_owned(), amount));
+            }
+
+            Ok(CashAssetsSpec::PerCurrency(assets))
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use super::*;
+
+    #[rstest(args, expected_positions, expected_cash_assets,
+        case(&["1000"], &[], &[]),
+        case(&["1000", "USD"], &[], &[]),
+        case(&["10", "VTI", "1000"], &[("VTI", "10")], &[("", "1000")]),
+        case(&["10", "VTI", "1000", "USD"], &[("VTI", "10")], &[("USD", "1000")]),
+        case(&["10", "BND", "1000", "USD"], &[("BND", "10")], &[("USD", "1000")]),
+        case(&["10", "VTI", "5.5", "BND", "100", "USD", "-5000", "RUB"],
+             &[("VTI", "10"), ("BND", "5.5")], &[("USD", "100"), ("RUB", "-5000")]),
+    )]
+    fn positions_with_cash_assets(
+        args: &[&str], expected_positions: &[(&str, &str)], expected_cash_assets: &[(&str, &str)],
+    ) {
+        let parser = PositionsParser::new("Bought shares", false, true).with_cash_assets();
+        let result = parser.parse_values_with_cash_assets(args, |symbol| symbol == "VTI");
+
+        if expected_positions.is_empty() {
+            assert_eq!(result.err().unwrap().to_string(), "Bought shares are not specified");
+            return;
+        }
+
+        let (positions, cash_assets) = result.unwrap();
+        assert_eq!(positions, expected_positions.iter().map(|&(symbol, quantity)| {
+            (symbol.to_owned(), Some(Decimal::from_str(quantity).unwrap()))
+        }).collect::<Vec<_>>());
+        assert_eq!(get_cash_assets(cash_assets), expected_cash_assets.iter().map(|&(currency, amount)| {
+            (currency.to_owned(), Decimal::from_str(amount).unwrap())
+        }).collect::<Vec<_>>());
+    }
+
+    #[rstest(args, error,
+        case(&[], "Cash assets are not specified"),
+        case(&["100", "USD", "200"], "Cash assets must be specified as a single amount or as $amount $currency pairs"),
+        case(&["1k"], r#"Invalid cash assets value: "1k""#),
+        case(&["100", "usd"], r#"Invalid cash assets currency: "usd""#),
+        case(&["100", "BND"], r#"Invalid cash assets currency: "BND""#),
+        case(&["100", "USD", "200", "USD"], "Duplicated USD cash assets"),
+    )]
+    fn invalid_cash_assets(args: &[&str], error: &str) {
+        assert_eq!(parse_cash_assets(args).err().unwrap().to_string(), error);
     }
-}
\ No newline at end of file
+
+    fn get_cash_assets(spec: CashAssetsSpec) -> Vec<(String, Decimal)> {
+        match spec {
+            CashAssetsSpec::Total(amount) => vec![(String::new(), amount)],
+            CashAssetsSpec::PerCurrency(assets) => assets,
+        }
+    }
+}
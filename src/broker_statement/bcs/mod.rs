@@ -98,7 +98,7 @@ mod tests {
         let statement = BrokerStatement::read(
             broker, &format!("testdata/bcs/{}", name),
             &Default::default(), &Default::default(), &Default::default(), TaxRemapping::new(),
-            corporate_actions, ReadingStrictness::all()).unwrap();
+            corporate_actions, None, &[], &Default::default(), ReadingStrictness::all()).unwrap();
 
         assert!(!statement.assets.cash.is_empty());
         assert!(statement.assets.other.is_none()); // TODO(konishchev): Get it from statements
@@ -171,7 +171,14 @@ impl TradeRow {
 
         let volume = util::validate_named_decimal("trade volume", volume, DecimalRestrictions::StrictlyPositive)
             .map(|volume| Cash::new(currency, volume))?;
-        debug_assert_eq!(volume, (price * quantity).round());
+
+        // Bond prices are specified in percents of face value and trade volume includes accrued
+        // coupon interest, so volume doesn't match price and quantity for them.
+        if volume != (price * quantity).round() {
+            return Err!(concat!(
+                "Trade volume doesn't match price and quantity: {} vs {} * {}. ",
+                "Bond trades aren't supported for BCS statements yet"), volume, price, quantity);
+        }
 
         let commission = Cash::zero(currency);
 
@@ -0,0 +1,114 @@
+use chrono::Datelike;
+
+use crate::core::GenericResult;
+use crate::currency::Cash;
+use crate::currency::converter::CurrencyConverter;
+use crate::formatting;
+use crate::instruments::{InstrumentId, IssuerTaxationType};
+use crate::localities::{Country, Jurisdiction};
+use crate::taxes::{IncomeType, TaxBase};
+use crate::time::Date;
+use crate::types::Decimal;
+use crate::util::{self, DecimalRestrictions};
+
+use super::trades::StockSell;
+
+// Bonds are represented as ordinary instruments: accrued coupon interest (НКД) is included into
+// trade volume (it's paid to the seller on buy and received from the buyer on sell), so it's taken
+// into account in FIFO cost calculation. Coupon payments are stored separately and principal
+// redemptions are emulated via sell trades at redemption price.
+
+pub struct Coupon {
+    pub date: Date,
+    pub symbol: String,
+    pub original_symbol: String,
+    pub amount: Cash,
+    pub taxation_type: IssuerTaxationType,
+}
+
+impl Coupon {
+    pub fn tax_to_pay(
+        &self, country: &Country, converter: &CurrencyConverter, tax_base: &TaxBase,
+    ) -> GenericResult<Cash> {
+        Ok(match self.taxation_type {
+            IssuerTaxationType::Manual(_) => {
+                let year = self.date.year();
+                let amount = converter.convert_to_cash_rounding(self.date, self.amount, country.currency)?;
+                country.tax_to_pay_with_base(IncomeType::Interest, year, amount, tax_base.get(year), None)
+            },
+            IssuerTaxationType::TaxAgent => Cash::zero(country.currency),
+        })
+    }
+}
+
+// Coupon payment from broker statement which isn't mapped to instrument symbol yet
+pub struct CouponPayment {
+    pub date: Date,
+    pub issuer: InstrumentId,
+    pub amount: Cash,
+}
+
+impl CouponPayment {
+    pub fn new(date: Date, issuer: InstrumentId, amount: Cash) -> GenericResult<CouponPayment> {
+        util::validate_named_cash(
+            "coupon amount", amount.currency, amount.amount, DecimalRestrictions::StrictlyPositive)?;
+        Ok(CouponPayment {date, issuer, amount})
+    }
+
+    // Russian brokers are tax agents for coupon income of any bonds
+    pub fn resolve(self, symbol: &str, broker_jurisdiction: Jurisdiction) -> Coupon {
+        let taxation_type = match broker_jurisdiction {
+            Jurisdiction::Russia => IssuerTaxationType::TaxAgent,
+            Jurisdiction::Usa => IssuerTaxationType::Manual(None),
+        };
+
+        Coupon {
+            date: self.date,
+            symbol: symbol.to_owned(),
+            original_symbol: symbol.to_owned(),
+            amount: self.amount,
+            taxation_type,
+        }
+    }
+}
+
+// Principal redemption from broker statement which isn't mapped to instrument symbol yet
+pub struct BondRedemption {
+    pub date: Date,
+    pub issuer: InstrumentId,
+    pub quantity: Option<Decimal>, // Full redemption of the current position if not specified
+    pub amount: Cash,
+}
+
+impl BondRedemption {
+    pub fn new(
+        date: Date, issuer: InstrumentId, quantity: Option<Decimal>, amount: Cash,
+    ) -> GenericResult<BondRedemption> {
+        util::validate_named_cash(
+            "bond redemption amount", amount.currency, amount.amount,
+            DecimalRestrictions::StrictlyPositive)?;
+
+        if let Some(quantity) = quantity {
+            util::validate_named_decimal(
+                "bond redemption quantity", quantity, DecimalRestrictions::StrictlyPositive)?;
+        }
+
+        Ok(BondRedemption {date, issuer, quantity, amount})
+    }
+
+    pub fn to_sell(&self, symbol: &str, position: Decimal) -> GenericResult<StockSell> {
+        let quantity = self.quantity.unwrap_or(position);
+        if quantity.is_zero() || quantity > position {
+            return Err!(
+                "Got {} redemption of {} bonds on {} when the portfolio has {} of them",
+                symbol, quantity, formatting::format_date(self.date), position);
+        }
+
+        let price = self.amount / quantity;
+        let commission = Cash::zero(self.amount.currency);
+
+        Ok(StockSell::new_trade(
+            symbol, quantity, price, self.amount, commission,
+            self.date.into(), self.date, false))
+    }
+}
@@ -253,7 +253,7 @@ fn process_stock_split(
 
     if ratio.from == 1 && statement.broker.type_.jurisdiction() != Jurisdiction::Russia {
         if !statement.stock_buys.iter().any(|trade| {
-            trade.symbol == symbol && !trade.is_sold() && trade.conclusion_time < split_time
+            trade.symbol == symbol && !trade.is_sold() && trade.statement_time() < split_time
         }) {
             return Err!("The portfolio has no open {} position at {}", symbol, format_date(split_time));
         }
@@ -278,7 +278,7 @@ fn process_complex_stock_split(
     let mut sell_sources = Vec::new();
 
     for stock_buy in &mut statement.stock_buys {
-        if stock_buy.symbol != symbol || stock_buy.is_sold() || stock_buy.conclusion_time >= split_time {
+        if stock_buy.symbol != symbol || stock_buy.is_sold() || stock_buy.statement_time() >= split_time {
             continue;
         }
 
@@ -0,0 +1,90 @@
+use crate::core::GenericResult;
+use crate::currency::Cash;
+use crate::currency::converter::CurrencyConverter;
+use crate::time::Date;
+
+// Futures positions are settled daily: profit or loss of each trading day is credited to or debited
+// from the account as variation margin.
+pub struct VariationMargin {
+    pub date: Date,
+    pub amount: Cash, // Positive for profit and negative for loss
+}
+
+impl VariationMargin {
+    pub fn new(date: Date, amount: Cash) -> VariationMargin {
+        VariationMargin {date, amount}
+    }
+}
+
+pub struct VariationMarginProfit {
+    pub date: Date,
+    pub local_profit: Cash,
+}
+
+// Futures results are taxed as trading income: both profit and loss are added to the same tax base
+// as results of trading with securities, so they are netted with each other.
+pub fn calculate_variation_margin_profit(
+    margins: &[VariationMargin], currency: &str, converter: &CurrencyConverter,
+) -> GenericResult<Vec<VariationMarginProfit>> {
+    margins.iter().map(|margin| {
+        Ok(VariationMarginProfit {
+            date: margin.date,
+            local_profit: converter.convert_to_cash_rounding(margin.date, margin.amount, currency)?,
+        })
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::localities::{self, Jurisdiction};
+    use crate::taxes::{NetTaxCalculator, TaxPaymentDay, TaxPaymentDaySpec};
+    use crate::types::Decimal;
+
+    use super::*;
+
+    #[test]
+    fn variation_margin_profit() {
+        let margins = [
+            mock_margin(date!(2020, 3, 2), dec!(100)),
+            mock_margin(date!(2020, 3, 3), dec!(-300)),
+            mock_margin(date!(2021, 5, 4), dec!(200.555)),
+        ];
+
+        let profits = calculate_variation_margin_profit(&margins, "RUB", &CurrencyConverter::mock()).unwrap();
+        let profits = profits.iter().map(|profit| (profit.date, profit.local_profit.amount)).collect::<Vec<_>>();
+
+        assert_eq!(profits, vec![
+            (date!(2020, 3, 2), dec!(100)),
+            (date!(2020, 3, 3), dec!(-300)),
+            (date!(2021, 5, 4), dec!(200.56)),
+        ]);
+    }
+
+    #[test]
+    fn netting_with_securities() {
+        let country = localities::russia(&Default::default(), &Default::default(), &Default::default());
+        let tax_payment_day = TaxPaymentDay::new(Jurisdiction::Russia, TaxPaymentDaySpec::default());
+        let mut calculator = NetTaxCalculator::new(country, tax_payment_day);
+
+        // Securities gain
+        let profit = Cash::new("RUB", dec!(1000));
+        calculator.add_profit(date!(2020, 2, 3), profit, profit, &[], false);
+
+        // Futures loss
+        let margins = [
+            mock_margin(date!(2020, 3, 2), dec!(100)),
+            mock_margin(date!(2020, 3, 3), dec!(-500)),
+        ];
+        for margin in calculate_variation_margin_profit(&margins, "RUB", &CurrencyConverter::mock()).unwrap() {
+            calculator.add_profit(margin.date, margin.local_profit, margin.local_profit, &[], false);
+        }
+
+        let taxes = calculator.calculate();
+        assert_eq!(taxes.keys().copied().collect::<Vec<_>>(), vec![2020]);
+        assert_eq!(taxes[&2020].tax_to_pay, Cash::new("RUB", dec!(78)));
+    }
+
+    fn mock_margin(date: Date, amount: Decimal) -> VariationMargin {
+        VariationMargin::new(date, Cash::new("RUB", amount))
+    }
+}
@@ -8,7 +8,7 @@ use crate::currency::converter::CurrencyConverter;
 use crate::formatting;
 use crate::instruments::{InstrumentId, IssuerTaxationType};
 use crate::localities::Country;
-use crate::taxes::IncomeType;
+use crate::taxes::{IncomeType, TaxBase};
 use crate::time::Date;
 
 use super::cash_flows::{CashFlow, CashFlowType};
@@ -27,11 +27,14 @@ pub struct Dividend {
 }
 
 impl Dividend {
-    pub fn tax(&self, country: &Country, converter: &CurrencyConverter) -> GenericResult<Cash> {
+    pub fn tax(
+        &self, country: &Country, converter: &CurrencyConverter, tax_base: &TaxBase,
+    ) -> GenericResult<Cash> {
         Ok(match self.taxation_type {
             IssuerTaxationType::Manual(_) => {
+                let year = self.date.year();
                 let amount = converter.convert_to_cash_rounding(self.date, self.amount, country.currency)?;
-                country.tax_to_pay(IncomeType::Dividends, self.date.year(), amount, None)
+                country.tax_to_pay_with_base(IncomeType::Dividends, year, amount, tax_base.get(year), None)
             },
             IssuerTaxationType::TaxAgent => {
                 if self.paid_tax.currency != country.currency {
@@ -44,12 +47,16 @@ impl Dividend {
         })
     }
 
-    pub fn tax_to_pay(&self, country: &Country, converter: &CurrencyConverter) -> GenericResult<Cash> {
+    pub fn tax_to_pay(
+        &self, country: &Country, converter: &CurrencyConverter, tax_base: &TaxBase,
+    ) -> GenericResult<Cash> {
         Ok(match self.taxation_type {
             IssuerTaxationType::Manual(_) => {
+                let year = self.date.year();
                 let amount = converter.convert_to_cash_rounding(self.date, self.amount, country.currency)?;
                 let paid_tax = converter.convert_to_cash_rounding(self.date, self.paid_tax, country.currency)?;
-                country.tax_to_pay(IncomeType::Dividends, self.date.year(), amount, Some(paid_tax))
+                country.tax_to_pay_with_base(
+                    IncomeType::Dividends, year, amount, tax_base.get(year), Some(paid_tax))
             },
             IssuerTaxationType::TaxAgent => {
                 Cash::zero(country.currency)
@@ -87,7 +87,7 @@ mod tests {
         let statement = BrokerStatement::read(
             broker, "testdata/firstrade/my",
             &Default::default(), &Default::default(), &Default::default(), TaxRemapping::new(),
-            &[], ReadingStrictness::all()).unwrap();
+            &[], None, &[], &Default::default(), ReadingStrictness::all()).unwrap();
 
         assert!(!statement.assets.cash.is_empty());
         assert!(statement.assets.other.is_none()); // TODO(konishchev): Get it from statements
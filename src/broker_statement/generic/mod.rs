@@ -0,0 +1,407 @@
+// Broker-agnostic statement format for brokers which aren't supported natively. The statements are
+// YAML files (with optional CSV files for trades) which are supposed to be converted by user from
+// their own broker exports. See docs/brokers.md#generic for the format description.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::Deserialize;
+use serde::de::{Deserializer, Error as _};
+
+use crate::core::{EmptyResult, GenericResult};
+use crate::currency::{Cash, CashAssets};
+use crate::exchanges::Exchange;
+use crate::instruments::{InstrumentId, parse_isin};
+use crate::localities::Jurisdiction;
+use crate::time::{Date, DateOptTime, Period, deserialize_date, deserialize_date_opt_time};
+use crate::types::{Decimal, TradeType};
+use crate::util::{self, DecimalRestrictions};
+
+use super::{BrokerStatementReader, PartialBrokerStatement};
+use super::corporate_actions::CorporateAction;
+use super::fees::Fee;
+use super::interest::IdleCashInterest;
+use super::taxes::TaxWithholding;
+use super::trades::{ForexTrade, StockBuy, StockSell};
+
+pub struct StatementReader {
+    jurisdiction: Jurisdiction,
+}
+
+impl StatementReader {
+    pub fn new(jurisdiction: Jurisdiction) -> GenericResult<Box<dyn BrokerStatementReader>> {
+        Ok(Box::new(StatementReader{jurisdiction}))
+    }
+}
+
+impl BrokerStatementReader for StatementReader {
+    fn check(&mut self, path: &str) -> GenericResult<bool> {
+        Ok(path.ends_with(".yaml") || path.ends_with(".yml"))
+    }
+
+    fn read(&mut self, path: &str, _is_last: bool) -> GenericResult<PartialBrokerStatement> {
+        let data = std::fs::read(path)?;
+        let mut statement: Statement = serde_yaml::from_slice(&data)?;
+
+        if let Some(trades_file) = statement.trades_file.take() {
+            let trades_path = Path::new(path).parent().unwrap().join(trades_file);
+            statement.trades.extend(read_trades(&trades_path).map_err(|e| format!(
+                "Error while reading {:?}: {}", trades_path, e))?);
+        }
+
+        statement.parse(self.jurisdiction)?.validate()
+    }
+}
+
+fn read_trades(path: &Path) -> GenericResult<Vec<Trade>> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let mut trades = Vec::new();
+
+    for trade in reader.deserialize() {
+        trades.push(trade?);
+    }
+
+    Ok(trades)
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Statement {
+    period: StatementPeriod,
+    has_starting_assets: bool,
+
+    #[serde(default)]
+    cash_assets: BTreeMap<String, Decimal>,
+    #[serde(default)]
+    open_positions: BTreeMap<String, Decimal>,
+    #[serde(default)]
+    instruments: Vec<Instrument>,
+
+    #[serde(default)]
+    deposits_and_withdrawals: Vec<CashOperation>,
+    #[serde(default)]
+    trades: Vec<Trade>,
+    // Path to CSV file with additional trades (relative to the statement)
+    trades_file: Option<String>,
+    #[serde(default)]
+    forex_trades: Vec<ForexTradeInfo>,
+
+    #[serde(default)]
+    dividends: Vec<DividendInfo>,
+    #[serde(default)]
+    fees: Vec<FeeInfo>,
+    #[serde(default)]
+    idle_cash_interest: Vec<CashOperation>,
+    #[serde(default)]
+    tax_agent_withholdings: Vec<TaxWithholdingInfo>,
+
+    #[serde(default)]
+    corporate_actions: Vec<CorporateAction>,
+}
+
+impl Statement {
+    fn parse(self, jurisdiction: Jurisdiction) -> GenericResult<PartialBrokerStatement> {
+        let exchanges: &[Exchange] = match jurisdiction {
+            Jurisdiction::Russia => &[Exchange::Moex, Exchange::Spb],
+            Jurisdiction::Usa => &[Exchange::Us],
+        };
+
+        let mut statement = PartialBrokerStatement::new(exchanges, true);
+        statement.set_period(Period::new(self.period.first, self.period.last)?)?;
+        statement.set_has_starting_assets(self.has_starting_assets)?;
+
+        let cash_assets = statement.assets.cash.as_mut().unwrap();
+        for (currency, amount) in self.cash_assets {
+            cash_assets.deposit(Cash::new(&currency, amount));
+        }
+
+        for (symbol, quantity) in self.open_positions {
+            statement.add_open_position(&symbol, quantity)?;
+        }
+
+        for instrument in self.instruments {
+            instrument.parse(&mut statement).map_err(|e| format!(
+                "Invalid {} instrument information: {}", instrument.symbol, e))?;
+        }
+
+        for operation in self.deposits_and_withdrawals {
+            let amount = util::validate_named_cash(
+                "deposit amount", &operation.currency, operation.amount, DecimalRestrictions::NonZero)?;
+            statement.deposits_and_withdrawals.push(CashAssets::new_from_cash(operation.date, amount));
+        }
+
+        for trade in self.trades {
+            trade.parse(&mut statement).map_err(|e| format!(
+                "Invalid {} trade: {}", trade.symbol, e))?;
+        }
+
+        for trade in self.forex_trades {
+            trade.parse(&mut statement)?;
+        }
+
+        for dividend in self.dividends {
+            dividend.parse(&mut statement).map_err(|e| format!(
+                "Invalid {} dividend: {}", dividend.symbol, e))?;
+        }
+
+        for fee in self.fees {
+            let amount = util::validate_named_cash(
+                "fee amount", &fee.currency, fee.amount, DecimalRestrictions::NonZero)?;
+            statement.fees.push(Fee::new(fee.date, amount, fee.description));
+        }
+
+        for interest in self.idle_cash_interest {
+            let amount = util::validate_named_cash(
+                "interest amount", &interest.currency, interest.amount, DecimalRestrictions::NonZero)?;
+            statement.idle_cash_interest.push(IdleCashInterest::new(interest.date, amount));
+        }
+
+        for withholding in self.tax_agent_withholdings {
+            let amount = util::validate_named_cash(
+                "withheld tax amount", &withholding.currency, withholding.amount, DecimalRestrictions::NonZero)?;
+            statement.tax_agent_withholdings.push(TaxWithholding::new(withholding.date, withholding.year, amount)?);
+        }
+
+        statement.corporate_actions.extend(self.corporate_actions);
+
+        Ok(statement)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StatementPeriod {
+    #[serde(rename = "from", deserialize_with = "deserialize_date")]
+    first: Date,
+    #[serde(rename = "to", deserialize_with = "deserialize_date")]
+    last: Date,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Instrument {
+    symbol: String,
+    name: Option<String>,
+    isin: Option<String>,
+    exchange: Option<String>,
+}
+
+impl Instrument {
+    fn parse(&self, statement: &mut PartialBrokerStatement) -> EmptyResult {
+        let instrument = statement.instrument_info.get_or_add(&self.symbol);
+
+        if let Some(name) = self.name.as_ref() {
+            instrument.set_name(name);
+        }
+
+        if let Some(isin) = self.isin.as_ref() {
+            instrument.add_isin(parse_isin(isin)?);
+        }
+
+        if let Some(exchange) = self.exchange.as_ref() {
+            instrument.exchanges.add_prioritized(match exchange.as_str() {
+                "moex" => Exchange::Moex,
+                "spb" => Exchange::Spb,
+                "us" => Exchange::Us,
+                _ => return Err!("Unsupported exchange: {:?}", exchange),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CashOperation {
+    #[serde(deserialize_with = "deserialize_date")]
+    date: Date,
+    amount: Decimal,
+    currency: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Trade {
+    #[serde(deserialize_with = "deserialize_date_opt_time")]
+    time: DateOptTime,
+    #[serde(deserialize_with = "deserialize_date")]
+    execution_date: Date,
+
+    symbol: String,
+    #[serde(rename = "type", deserialize_with = "deserialize_trade_type")]
+    type_: TradeType,
+    quantity: Decimal,
+    price: Decimal,
+    volume: Option<Decimal>,
+    #[serde(default)]
+    commission: Decimal,
+    currency: String,
+}
+
+impl Trade {
+    fn parse(&self, statement: &mut PartialBrokerStatement) -> EmptyResult {
+        let quantity = util::validate_named_decimal(
+            "quantity", self.quantity, DecimalRestrictions::StrictlyPositive)?;
+        let price = util::validate_named_cash(
+            "price", &self.currency, self.price, DecimalRestrictions::StrictlyPositive)?;
+        let commission = util::validate_named_cash(
+            "commission", &self.currency, self.commission, DecimalRestrictions::PositiveOrZero)?;
+
+        let volume = match self.volume {
+            Some(volume) => util::validate_named_cash(
+                "volume", &self.currency, volume, DecimalRestrictions::StrictlyPositive)?,
+            None => (price * quantity).round(),
+        };
+
+        match self.type_ {
+            TradeType::Buy => statement.stock_buys.push(StockBuy::new_trade(
+                &self.symbol, quantity, price, volume, commission, self.time, self.execution_date)),
+
+            TradeType::Sell => statement.stock_sells.push(StockSell::new_trade(
+                &self.symbol, quantity, price, volume, commission, self.time, self.execution_date, false)),
+        };
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ForexTradeInfo {
+    #[serde(deserialize_with = "deserialize_date_opt_time")]
+    time: DateOptTime,
+
+    from: Decimal,
+    from_currency: String,
+    to: Decimal,
+    to_currency: String,
+
+    #[serde(default)]
+    commission: Decimal,
+    commission_currency: Option<String>,
+}
+
+impl ForexTradeInfo {
+    fn parse(&self, statement: &mut PartialBrokerStatement) -> EmptyResult {
+        let from = util::validate_named_cash(
+            "forex trade source amount", &self.from_currency, self.from, DecimalRestrictions::StrictlyPositive)?;
+        let to = util::validate_named_cash(
+            "forex trade target amount", &self.to_currency, self.to, DecimalRestrictions::StrictlyPositive)?;
+
+        let commission_currency = self.commission_currency.as_ref().unwrap_or(&self.from_currency);
+        let commission = util::validate_named_cash(
+            "forex trade commission", commission_currency, self.commission, DecimalRestrictions::PositiveOrZero)?;
+
+        statement.forex_trades.push(ForexTrade::new(self.time, from, to, commission));
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DividendInfo {
+    #[serde(deserialize_with = "deserialize_date")]
+    date: Date,
+    symbol: String,
+    amount: Decimal,
+    #[serde(default)]
+    paid_tax: Decimal,
+    currency: String,
+}
+
+impl DividendInfo {
+    fn parse(&self, statement: &mut PartialBrokerStatement) -> EmptyResult {
+        let amount = util::validate_named_cash(
+            "amount", &self.currency, self.amount, DecimalRestrictions::StrictlyPositive)?;
+        let paid_tax = util::validate_named_cash(
+            "paid tax", &self.currency, self.paid_tax, DecimalRestrictions::PositiveOrZero)?;
+
+        let issuer = InstrumentId::Symbol(self.symbol.clone());
+        statement.dividend_accruals(self.date, issuer.clone(), true).add(self.date, amount);
+
+        if !paid_tax.is_zero() {
+            statement.tax_accruals(self.date, issuer, true).add(self.date, paid_tax);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FeeInfo {
+    #[serde(deserialize_with = "deserialize_date")]
+    date: Date,
+    amount: Decimal,
+    currency: String,
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TaxWithholdingInfo {
+    #[serde(deserialize_with = "deserialize_date")]
+    date: Date,
+    year: i32,
+    amount: Decimal,
+    currency: String,
+}
+
+fn deserialize_trade_type<'de, D>(deserializer: D) -> Result<TradeType, D::Error>
+    where D: Deserializer<'de>
+{
+    let value: String = Deserialize::deserialize(deserializer)?;
+
+    Ok(match value.as_str() {
+        "buy" => TradeType::Buy,
+        "sell" => TradeType::Sell,
+        _ => return Err(D::Error::unknown_variant(&value, &["buy", "sell"])),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::*;
+
+    #[test]
+    fn parse_statement() {
+        let path = Path::new(file!()).parent().unwrap().join("testdata/statement.yaml");
+        let statement = StatementReader::new(Jurisdiction::Russia).unwrap()
+            .read(path.to_str().unwrap(), true).unwrap();
+
+        assert_eq!(statement.get_period().unwrap(), Period::new(date!(2021, 1, 1), date!(2021, 12, 31)).unwrap());
+        assert!(!statement.get_has_starting_assets().unwrap());
+        assert!(statement.assets.cash.as_ref().unwrap().has_assets("RUB"));
+
+        assert_eq!(statement.deposits_and_withdrawals.len(), 2);
+        assert_eq!(statement.forex_trades.len(), 1);
+        assert_eq!(statement.fees.len(), 1);
+        assert_eq!(statement.idle_cash_interest.len(), 1);
+        assert_eq!(statement.tax_agent_withholdings.len(), 1);
+
+        assert_eq!(statement.stock_buys.len(), 2);
+        let buy = statement.stock_buys.first().unwrap();
+        assert_eq!(buy.symbol, "SBER");
+        assert_eq!(buy.conclusion_time.date, date!(2021, 1, 12));
+        assert_eq!(buy.execution_date, date!(2021, 1, 14));
+
+        let buy = statement.stock_buys.last().unwrap();
+        assert_eq!(buy.symbol, "FXUS");
+        assert_eq!(buy.quantity, dec!(1));
+        assert_eq!(buy.execution_date, date!(2021, 2, 3));
+
+        assert_eq!(statement.stock_sells.len(), 1);
+        let sell = statement.stock_sells.first().unwrap();
+        assert_eq!(sell.symbol, "SBER");
+        assert_eq!(sell.execution_date, date!(2021, 11, 3));
+
+        assert_eq!(statement.dividend_accruals.len(), 1);
+        assert_eq!(statement.tax_accruals.len(), 1);
+        assert_eq!(statement.corporate_actions.len(), 1);
+
+        assert_eq!(statement.open_positions.len(), 2);
+        assert!(!statement.instrument_info.get("SBER").unwrap().isin.is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::broker_statement::derivatives::VariationMargin;
 use crate::core::EmptyResult;
 use crate::currency::{Cash, CashAssets};
 use crate::util::{self, DecimalRestrictions};
@@ -17,8 +18,11 @@ impl RecordParser for CashReportParser {
         let currency = record.get_value("Currency")?;
         let amount = record.parse_amount("Total", DecimalRestrictions::No)?;
 
-        record.check_value("Futures", "0")?;
-        record.check_value("Total", record.get_value("Securities")?)?;
+        let securities = record.parse_amount("Securities", DecimalRestrictions::No)?;
+        let futures = record.parse_amount("Futures", DecimalRestrictions::No)?;
+        if securities + futures != amount {
+            return Err!("Got an unexpected total cash amount: {} vs {} + {}", amount, securities, futures);
+        }
 
         if currency == "Base Currency Summary" {
             let summary = Cash::new(parser.base_currency()?, amount);
@@ -86,6 +90,12 @@ impl RecordParser for StatementOfFundsParser {
         };
         let amount = Cash::new(currency, util::parse_decimal(amount, restrictions)?);
 
+        // Daily futures settlement
+        if description.starts_with("Position MTM") || description.starts_with("Cash Settling MTM") {
+            parser.statement.variation_margin.push(VariationMargin::new(date, amount));
+            return Ok(());
+        }
+
         let id = CashFlowId::new(statement_date, description, amount);
         parser.cash_flows.add(id, date);
 
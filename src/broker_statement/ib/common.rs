@@ -204,6 +204,21 @@ pub fn parse_symbol(symbol: &str) -> GenericResult<String> {
     Ok(symbol.replace(' ', "-").to_uppercase())
 }
 
+// Option symbols have the following format: "AAPL 17DEC21 150 C" (underlying, expiration date,
+// strike price and option type).
+pub fn parse_option_symbol(symbol: &str) -> GenericResult<String> {
+    lazy_static! {
+        static ref OPTION_SYMBOL_REGEX: Regex = Regex::new(&format!(
+            r"^{} \d{{2}}[A-Z]{{3}}\d{{2}} \d+(?:\.\d+)? [CP]$", STOCK_SYMBOL_REGEX)).unwrap();
+    }
+
+    if !OPTION_SYMBOL_REGEX.is_match(symbol) {
+        return Err!("Got an option symbol with an unsupported format: {:?}", symbol);
+    }
+
+    Ok(symbol.replace(' ', "-").to_uppercase())
+}
+
 fn parse_quantity(quantity: &str) -> GenericResult<Decimal> {
     // See https://github.com/KonishchevDmitry/investments/issues/34
 
@@ -292,4 +307,19 @@ mod tests {
     fn symbol_parsing(value: &str, expected: &str) {
         assert_eq!(parse_symbol(value).unwrap(), expected);
     }
+
+    #[rstest(value, expected,
+        case("AAPL 17DEC21 150 C",   Some("AAPL-17DEC21-150-C")),
+        case("SPY 15JAN21 322.5 P",  Some("SPY-15JAN21-322.5-P")),
+        case("RDS B 15JAN21 40 C",   Some("RDS-B-15JAN21-40-C")),
+        case("AAPL",                 None),
+        case("AAPL 17DEC21 150",     None),
+        case("AAPL 17DEC2021 150 C", None),
+    )]
+    fn option_symbol_parsing(value: &str, expected: Option<&str>) {
+        match expected {
+            Some(expected) => assert_eq!(parse_option_symbol(value).unwrap(), expected),
+            None => assert!(parse_option_symbol(value).is_err()),
+        }
+    }
 }
\ No newline at end of file
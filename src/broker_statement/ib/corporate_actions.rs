@@ -5,7 +5,7 @@ use regex::Regex;
 use crate::broker_statement::corporate_actions::{CorporateAction, CorporateActionType, StockSplitRatio};
 use crate::core::{EmptyResult, GenericResult};
 use crate::formatting::format_date;
-#[cfg(test)] use crate::types::{Date, DateTime, Decimal};
+use crate::types::{Date, DateTime, Decimal};
 use crate::util::{self, DecimalRestrictions};
 
 use super::StatementParser;
@@ -35,35 +35,40 @@ impl CorporateActionsParser {
     }
 
     pub fn commit(self, parser: &mut StatementParser) -> EmptyResult {
-        // Here we postprocess parsed corporate actions:
-        // * Complex stock splits are represented by two records, so we join them here
-
-        let mut stock_splits = Vec::<CorporateAction>::new();
-
-        for action in self.corporate_actions {
-            match action.action {
-                CorporateActionType::StockSplit {..} => {
-                    if let Some(last) = stock_splits.last() {
-                        if action.time == last.time && action.symbol == last.symbol {
-                            stock_splits.push(action);
-                        } else {
-                            parser.statement.corporate_actions.push(join_stock_splits(stock_splits)?);
-                            stock_splits = vec![action];
-                        }
-                    } else {
+        parser.statement.corporate_actions.extend(postprocess(self.corporate_actions)?);
+        Ok(())
+    }
+}
+
+// Here we postprocess parsed corporate actions:
+// * Complex stock splits are represented by two records, so we join them here
+pub fn postprocess(corporate_actions: Vec<CorporateAction>) -> GenericResult<Vec<CorporateAction>> {
+    let mut result = Vec::with_capacity(corporate_actions.len());
+    let mut stock_splits = Vec::<CorporateAction>::new();
+
+    for action in corporate_actions {
+        match action.action {
+            CorporateActionType::StockSplit {..} => {
+                if let Some(last) = stock_splits.last() {
+                    if action.time == last.time && action.symbol == last.symbol {
                         stock_splits.push(action);
+                    } else {
+                        result.push(join_stock_splits(stock_splits)?);
+                        stock_splits = vec![action];
                     }
-                },
-                _ => parser.statement.corporate_actions.push(action),
-            }
-        }
-
-        if !stock_splits.is_empty() {
-            parser.statement.corporate_actions.push(join_stock_splits(stock_splits)?);
+                } else {
+                    stock_splits.push(action);
+                }
+            },
+            _ => result.push(action),
         }
+    }
 
-        Ok(())
+    if !stock_splits.is_empty() {
+        result.push(join_stock_splits(stock_splits)?);
     }
+
+    Ok(result)
 }
 
 fn parse(record: &Record) -> GenericResult<CorporateAction> {
@@ -73,9 +78,20 @@ fn parse(record: &Record) -> GenericResult<CorporateAction> {
     }
 
     let time = record.parse_date_time("Date/Time")?;
-    let report_date = Some(record.parse_date("Report Date")?);
+    let report_date = record.parse_date("Report Date")?;
+    let description = record.get_value("Description")?;
+    let quantity = record.parse_quantity("Quantity", DecimalRestrictions::NonZero)?;
+    let currency = record.get_value("Currency")?;
+
+    parse_corporate_action(time, report_date, description, quantity, currency)
+}
 
-    let description = util::fold_spaces(record.get_value("Description")?);
+// Corporate action descriptions have the same format in Activity Statements and Flex Query reports
+pub fn parse_corporate_action(
+    time: DateTime, report_date: Date, description: &str, quantity: Decimal, currency: &str,
+) -> GenericResult<CorporateAction> {
+    let report_date = Some(report_date);
+    let description = util::fold_spaces(description);
     let description = description.as_ref();
 
     lazy_static! {
@@ -106,7 +122,7 @@ fn parse(record: &Record) -> GenericResult<CorporateAction> {
             let to: u32 = captures.name("to").unwrap().as_str().parse()?;
             let ratio = StockSplitRatio::new(from, to);
 
-            let change = record.parse_quantity("Quantity", DecimalRestrictions::NonZero)?;
+            let change = quantity;
             let (from_change, to_change) = if change.is_sign_positive() {
                 (None, Some(change))
             } else {
@@ -121,13 +137,15 @@ fn parse(record: &Record) -> GenericResult<CorporateAction> {
                 return error();
             }
 
-            let quantity = record.parse_quantity("Quantity", DecimalRestrictions::StrictlyPositive)?;
+            let quantity = util::validate_named_decimal(
+                "quantity", quantity, DecimalRestrictions::StrictlyPositive)?;
             CorporateActionType::StockDividend {quantity}
         },
 
         "Spinoff" => {
-            let quantity = record.parse_quantity("Quantity", DecimalRestrictions::StrictlyPositive)?;
-            let currency = record.get_value("Currency")?.to_owned();
+            let quantity = util::validate_named_decimal(
+                "quantity", quantity, DecimalRestrictions::StrictlyPositive)?;
+            let currency = currency.to_owned();
 
             CorporateActionType::Spinoff {
                 symbol: other_symbol,
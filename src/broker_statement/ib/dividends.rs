@@ -46,7 +46,7 @@ impl RecordParser for DividendsParser {
     }
 }
 
-fn parse_dividend_description(description: &str) -> GenericResult<(String, SecurityID)> {
+pub fn parse_dividend_description(description: &str) -> GenericResult<(String, SecurityID)> {
     lazy_static! {
         static ref DESCRIPTION_REGEX: Regex = Regex::new(&format!(
             r"^(?P<issuer>{symbol}) ?\((?P<id>{id})\) ",
@@ -0,0 +1,714 @@
+// Flex Query reports are the format IB recommends for automation. Unlike Activity Statements they
+// carry trade settle dates and actual cash flow dates, so no additional reports are required.
+//
+// The reports must be generated with the following sections: Account Information, Change in NAV,
+// Cash Report, Cash Transactions, Corporate Actions, Financial Instrument Information, Open
+// Positions (with Summary level of detail) and Trades (with Execution level of detail).
+
+use std::fs::File;
+use std::io::BufReader;
+
+use serde::Deserialize;
+use xml::reader::{EventReader, XmlEvent};
+
+use crate::broker_statement::fees::Fee;
+use crate::broker_statement::interest::IdleCashInterest;
+use crate::broker_statement::partial::PartialBrokerStatement;
+use crate::broker_statement::trades::{ForexTrade, StockBuy, StockSell};
+use crate::core::{EmptyResult, GenericResult};
+use crate::currency::{Cash, CashAssets};
+use crate::exchanges::Exchange;
+use crate::instruments::{InstrumentId, InstrumentType};
+use crate::taxes::TaxRemapping;
+use crate::time::{self, Date, DateTime, Period};
+use crate::types::Decimal;
+use crate::util::{self, DecimalRestrictions};
+
+use super::common::{SecurityID, parse_option_symbol, parse_symbol};
+use super::corporate_actions;
+use super::dividends::parse_dividend_description;
+use super::taxes::parse_tax_description;
+
+pub fn is_flex_query_report(path: &str) -> GenericResult<bool> {
+    if !path.ends_with(".xml") {
+        return Ok(false);
+    }
+
+    for event in EventReader::new(BufReader::new(File::open(path)?)) {
+        if let XmlEvent::StartElement {name, ..} = event? {
+            if name.local_name != "FlexQueryResponse" {
+                return Err!(
+                    "Got an unexpected XML file with <{}> root element: Flex Query report is expected",
+                    name.local_name);
+            }
+            return Ok(true);
+        }
+    }
+
+    Err!("Got an XML file without root element: Flex Query report is expected")
+}
+
+pub fn read(path: &str, tax_remapping: &mut TaxRemapping) -> GenericResult<PartialBrokerStatement> {
+    let data = std::fs::read_to_string(path)?;
+
+    let response: FlexQueryResponse = serde_xml_rs::from_str(&data)?;
+    let mut statements = response.statements.statements;
+
+    // Statements for several accounts have to be processed as separate portfolios
+    let statement = match statements.len() {
+        1 => statements.pop().unwrap(),
+        count => return Err!("The report contains {} statements when exactly one is expected", count),
+    };
+
+    statement.parse(tax_remapping)?.validate()
+}
+
+#[derive(Deserialize)]
+struct FlexQueryResponse {
+    #[serde(rename = "FlexStatements")]
+    statements: FlexStatements,
+}
+
+#[derive(Deserialize)]
+struct FlexStatements {
+    #[serde(rename = "FlexStatement", default)]
+    statements: Vec<FlexStatement>,
+}
+
+#[derive(Deserialize)]
+struct FlexStatement {
+    #[serde(rename = "fromDate")]
+    from_date: String,
+    #[serde(rename = "toDate")]
+    to_date: String,
+
+    #[serde(rename = "AccountInformation")]
+    account_information: AccountInformation,
+
+    #[serde(rename = "ChangeInNAV")]
+    change_in_nav: ChangeInNav,
+
+    #[serde(rename = "CashReport")]
+    cash_report: CashReport,
+
+    #[serde(rename = "CashTransactions")]
+    cash_transactions: Option<CashTransactions>,
+
+    #[serde(rename = "CorporateActions")]
+    corporate_actions: Option<CorporateActions>,
+
+    #[serde(rename = "SecuritiesInfo")]
+    securities_info: Option<SecuritiesInfo>,
+
+    #[serde(rename = "OpenPositions")]
+    open_positions: Option<OpenPositions>,
+
+    #[serde(rename = "Trades")]
+    trades: Option<Trades>,
+}
+
+impl FlexStatement {
+    fn parse(self, tax_remapping: &mut TaxRemapping) -> GenericResult<PartialBrokerStatement> {
+        let mut statement = PartialBrokerStatement::new(&[Exchange::Us], false);
+        statement.set_period(Period::new(parse_date(&self.from_date)?, parse_date(&self.to_date)?)?)?;
+        statement.set_has_starting_assets(!self.change_in_nav.starting_value.is_zero())?;
+
+        let base_currency = &self.account_information.currency;
+        self.cash_report.parse(&mut statement, base_currency)?;
+
+        // Instrument information goes first to be able to determine instrument types
+        if let Some(securities_info) = self.securities_info {
+            for info in securities_info.securities {
+                info.parse(&mut statement)?;
+            }
+        }
+
+        if let Some(trades) = self.trades {
+            for trade in trades.trades {
+                trade.parse(&mut statement, base_currency).map_err(|e| format!(
+                    "Failed to parse {} trade from {}: {}", trade.symbol, trade.date_time, e))?;
+            }
+        }
+
+        if let Some(cash_transactions) = self.cash_transactions {
+            for transaction in cash_transactions.transactions {
+                transaction.parse(&mut statement, tax_remapping).map_err(|e| format!(
+                    "Failed to parse {:?} cash transaction: {}", transaction.description, e))?;
+            }
+        }
+
+        if let Some(corporate_actions) = self.corporate_actions {
+            let mut actions = Vec::new();
+            for action in corporate_actions.actions {
+                actions.push(action.parse().map_err(|e| format!(
+                    "Failed to parse {:?} corporate action: {}", action.description, e))?);
+            }
+            statement.corporate_actions.extend(corporate_actions::postprocess(actions)?);
+        }
+
+        if let Some(open_positions) = self.open_positions {
+            for position in open_positions.positions {
+                position.parse(&mut statement)?;
+            }
+        }
+
+        Ok(statement)
+    }
+}
+
+#[derive(Deserialize)]
+struct AccountInformation {
+    currency: String,
+}
+
+#[derive(Deserialize)]
+struct ChangeInNav {
+    #[serde(rename = "startingValue")]
+    starting_value: Decimal,
+}
+
+#[derive(Deserialize)]
+struct CashReport {
+    #[serde(rename = "CashReportCurrency", default)]
+    currencies: Vec<CashReportCurrency>,
+}
+
+#[derive(Deserialize)]
+struct CashReportCurrency {
+    currency: String,
+    #[serde(rename = "endingCash")]
+    ending_cash: Decimal,
+}
+
+impl CashReport {
+    fn parse(&self, statement: &mut PartialBrokerStatement, base_currency: &str) -> EmptyResult {
+        let mut base_currency_summary = None;
+        let cash_assets = statement.assets.cash.get_or_insert_with(Default::default);
+
+        for report in &self.currencies {
+            if report.currency == "BASE_SUMMARY" {
+                base_currency_summary.replace(Cash::new(base_currency, report.ending_cash));
+                continue;
+            }
+
+            if cash_assets.has_assets(&report.currency) {
+                return Err!("Got duplicated {} assets", report.currency);
+            }
+            cash_assets.deposit(Cash::new(&report.currency, report.ending_cash));
+        }
+
+        // When statement has no non-base currency activity it contains only base currency summary
+        if cash_assets.is_empty() {
+            let summary = base_currency_summary.ok_or("Unable to find base currency summary")?;
+            cash_assets.deposit(summary);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct SecuritiesInfo {
+    #[serde(rename = "SecurityInfo", default)]
+    securities: Vec<SecurityInfo>,
+}
+
+#[derive(Deserialize)]
+struct SecurityInfo {
+    #[serde(rename = "assetCategory")]
+    asset_category: String,
+    symbol: String,
+    description: String,
+    isin: Option<String>,
+    cusip: Option<String>,
+}
+
+impl SecurityInfo {
+    fn parse(&self, statement: &mut PartialBrokerStatement) -> EmptyResult {
+        let (symbol, type_) = parse_instrument(&self.asset_category, &self.symbol, &self.description)?;
+
+        let instrument = statement.instrument_info.get_or_add(&symbol);
+        instrument.set_type(type_);
+        if type_.is_derivative() {
+            return Ok(());
+        }
+        instrument.set_name(&self.description);
+
+        if let Some(isin) = non_empty(&self.isin) {
+            match isin.parse::<SecurityID>()? {
+                SecurityID::Isin(isin) => instrument.add_isin(isin),
+                _ => return Err!("Got an invalid ISIN for {}: {:?}", symbol, isin),
+            }
+        } else if let Some(cusip) = non_empty(&self.cusip) {
+            match cusip.parse::<SecurityID>()? {
+                SecurityID::Cusip(cusip) => instrument.add_cusip(cusip),
+                _ => return Err!("Got an invalid CUSIP for {}: {:?}", symbol, cusip),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct Trades {
+    #[serde(rename = "Trade", default)]
+    trades: Vec<Trade>,
+}
+
+#[derive(Deserialize)]
+struct Trade {
+    #[serde(rename = "assetCategory")]
+    asset_category: String,
+    symbol: String,
+    description: String,
+    currency: String,
+
+    #[serde(rename = "levelOfDetail")]
+    level_of_detail: Option<String>,
+
+    #[serde(rename = "dateTime")]
+    date_time: String,
+    #[serde(rename = "settleDateTarget")]
+    settle_date: String,
+
+    quantity: Decimal,
+    #[serde(rename = "tradePrice")]
+    price: Decimal,
+    proceeds: Decimal,
+
+    #[serde(rename = "ibCommission")]
+    commission: Decimal,
+    #[serde(rename = "ibCommissionCurrency")]
+    commission_currency: String,
+}
+
+impl Trade {
+    fn parse(&self, statement: &mut PartialBrokerStatement, base_currency: &str) -> EmptyResult {
+        match self.level_of_detail.as_deref() {
+            None | Some("EXECUTION") => {},
+            Some("SYMBOL_SUMMARY" | "ASSET_SUMMARY") => return Ok(()),
+            Some(level) => return Err!(
+                "Got trades with unsupported level of detail: {}. Execution level of detail is expected",
+                level),
+        }
+
+        let conclusion_time = parse_date_time(&self.date_time)?;
+        let execution_date = parse_date(&self.settle_date)?;
+
+        let quantity = util::validate_named_decimal("quantity", self.quantity, DecimalRestrictions::NonZero)?;
+        let commission = -util::validate_named_cash(
+            "commission", &self.commission_currency, self.commission, DecimalRestrictions::NegativeOrZero)?;
+
+        if self.asset_category == "CASH" {
+            return self.parse_forex(statement, conclusion_time, quantity, commission, base_currency);
+        }
+
+        let (symbol, type_) = parse_instrument(&self.asset_category, &self.symbol, &self.description)?;
+        statement.instrument_info.get_or_add(&symbol).set_type(type_);
+
+        let (price, volume) = match type_ {
+            InstrumentType::Stock => {
+                let price = util::validate_named_cash(
+                    "price", &self.currency, self.price, DecimalRestrictions::StrictlyPositive)?;
+
+                let volume = util::validate_named_cash(
+                    "volume", &self.currency, self.proceeds, if quantity.is_sign_positive() {
+                        DecimalRestrictions::StrictlyNegative
+                    } else {
+                        DecimalRestrictions::StrictlyPositive
+                    })?;
+
+                (price, volume)
+            },
+
+            // Option price is specified per share, so calculate contract price which includes the
+            // multiplier. Expirations, assignments and exercises have zero price.
+            InstrumentType::Option => {
+                let volume = util::validate_named_cash(
+                    "volume", &self.currency, self.proceeds, if quantity.is_sign_positive() {
+                        DecimalRestrictions::NegativeOrZero
+                    } else {
+                        DecimalRestrictions::PositiveOrZero
+                    })?;
+
+                (volume / -quantity, volume)
+            },
+
+            InstrumentType::Future => {
+                return Err!(concat!(
+                    "Futures aren't supported for Flex Query reports yet: ",
+                    "variation margin can be found only in Activity Statements"));
+            },
+        };
+
+        if quantity.is_sign_positive() {
+            statement.stock_buys.push(StockBuy::new_trade(
+                &symbol, quantity, price, -volume, commission,
+                conclusion_time.into(), execution_date));
+        } else {
+            statement.stock_sells.push(StockSell::new_trade(
+                &symbol, -quantity, price, volume, commission,
+                conclusion_time.into(), execution_date, false));
+        }
+
+        Ok(())
+    }
+
+    fn parse_forex(
+        &self, statement: &mut PartialBrokerStatement, conclusion_time: DateTime, quantity: Decimal,
+        commission: Cash, base_currency: &str,
+    ) -> EmptyResult {
+        let (base, quote) = self.symbol.split_once('.').ok_or_else(|| format!(
+            "Invalid forex pair: {}", self.symbol))?;
+
+        if quote != self.currency {
+            return Err!("Got an unexpected forex trade currency: {}", self.currency);
+        }
+
+        let quantity = Cash::new(base, quantity);
+        let volume = util::validate_named_cash(
+            "volume", quote, self.proceeds, DecimalRestrictions::NonZero)?;
+
+        let (from, to) = if quantity.is_positive() {
+            (-volume, quantity)
+        } else {
+            (-quantity, volume)
+        };
+        if from.is_negative() || to.is_negative() {
+            return Err!("Unexpected Forex quantity/volume values: {}/{}", quantity, volume);
+        }
+
+        if commission.currency != base_currency {
+            return Err!("Got an unexpected forex trade commission currency: {}", commission.currency);
+        }
+
+        statement.forex_trades.push(ForexTrade::new(conclusion_time.into(), from, to, commission));
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct CashTransactions {
+    #[serde(rename = "CashTransaction", default)]
+    transactions: Vec<CashTransaction>,
+}
+
+#[derive(Deserialize)]
+struct CashTransaction {
+    #[serde(rename = "type")]
+    type_: String,
+    currency: String,
+    amount: Decimal,
+    description: String,
+
+    #[serde(rename = "dateTime")]
+    date_time: String,
+    #[serde(rename = "settleDate")]
+    settle_date: String,
+    #[serde(rename = "reportDate")]
+    report_date: String,
+}
+
+impl CashTransaction {
+    fn parse(&self, statement: &mut PartialBrokerStatement, tax_remapping: &mut TaxRemapping) -> EmptyResult {
+        let date = parse_date(&self.date_time)?;
+        let amount = util::validate_named_cash(
+            "amount", &self.currency, self.amount, DecimalRestrictions::NonZero)?;
+
+        match self.type_.as_str() {
+            "Deposits/Withdrawals" | "Deposits & Withdrawals" => {
+                let date = parse_date(&self.settle_date)?;
+                statement.deposits_and_withdrawals.push(CashAssets::new_from_cash(date, amount));
+            },
+
+            "Dividends" | "Payment In Lieu Of Dividends" => {
+                let (symbol, security_id) = parse_dividend_description(&self.description)?;
+
+                match security_id {
+                    SecurityID::Isin(isin) => statement.instrument_info.get_or_add(&symbol).add_isin(isin),
+                    SecurityID::Cusip(cusip) => statement.instrument_info.get_or_add(&symbol).add_cusip(cusip),
+                    SecurityID::Conid(_) => {},
+                }
+
+                // Report date is the actual date of the cash flow (it differs from the payment date
+                // for dividend reclassifications).
+                let cash_flow_date = parse_date(&self.report_date)?;
+                let accruals = statement.dividend_accruals(date, InstrumentId::Symbol(symbol), true);
+
+                if amount.is_negative() {
+                    accruals.reverse(cash_flow_date, -amount);
+                } else {
+                    accruals.add(cash_flow_date, amount);
+                }
+            },
+
+            "Withholding Tax" => {
+                let issuer = parse_tax_description(&self.description)?;
+                let date = tax_remapping.map(date, &self.description);
+
+                let cash_flow_date = parse_date(&self.report_date)?;
+                let accruals = statement.tax_accruals(date, InstrumentId::Symbol(issuer), true);
+
+                if amount.is_positive() {
+                    accruals.reverse(cash_flow_date, amount);
+                } else {
+                    accruals.add(cash_flow_date, -amount);
+                }
+            },
+
+            "Broker Interest Received" | "Broker Interest Paid" => {
+                statement.idle_cash_interest.push(IdleCashInterest::new(date, amount));
+            },
+
+            "Other Fees" | "Commission Adjustments" => {
+                statement.fees.push(Fee::new(date, -amount, None));
+            },
+
+            _ => return Err!("Unsupported cash transaction type: {:?}", self.type_),
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct CorporateActions {
+    #[serde(rename = "CorporateAction", default)]
+    actions: Vec<CorporateAction>,
+}
+
+#[derive(Deserialize)]
+struct CorporateAction {
+    #[serde(rename = "assetCategory")]
+    asset_category: String,
+    currency: String,
+
+    #[serde(rename = "dateTime")]
+    date_time: String,
+    #[serde(rename = "reportDate")]
+    report_date: String,
+
+    #[serde(rename = "actionDescription")]
+    description: String,
+    quantity: Decimal,
+}
+
+impl CorporateAction {
+    fn parse(&self) -> GenericResult<crate::broker_statement::CorporateAction> {
+        if self.asset_category != "STK" {
+            return Err!("Unsupported asset category of corporate action: {:?}", self.asset_category);
+        }
+
+        let time = parse_date_time(&self.date_time)?;
+        let report_date = parse_date(&self.report_date)?;
+        let quantity = util::validate_named_decimal("quantity", self.quantity, DecimalRestrictions::NonZero)?;
+
+        corporate_actions::parse_corporate_action(time, report_date, &self.description, quantity, &self.currency)
+    }
+}
+
+#[derive(Deserialize)]
+struct OpenPositions {
+    #[serde(rename = "OpenPosition", default)]
+    positions: Vec<OpenPosition>,
+}
+
+#[derive(Deserialize)]
+struct OpenPosition {
+    #[serde(rename = "assetCategory")]
+    asset_category: String,
+    symbol: String,
+    description: String,
+    currency: String,
+
+    #[serde(rename = "levelOfDetail")]
+    level_of_detail: Option<String>,
+
+    position: Decimal,
+    #[serde(rename = "markPrice")]
+    price: Decimal,
+    multiplier: Decimal,
+}
+
+impl OpenPosition {
+    fn parse(&self, statement: &mut PartialBrokerStatement) -> EmptyResult {
+        match self.level_of_detail.as_deref() {
+            None | Some("SUMMARY") => {},
+            Some(level) => return Err!(
+                "Got open positions with unsupported level of detail: {}. Summary level of detail is expected",
+                level),
+        }
+
+        let (symbol, type_) = parse_instrument(&self.asset_category, &self.symbol, &self.description)?;
+
+        match type_ {
+            InstrumentType::Stock => {
+                if self.multiplier != dec!(1) {
+                    return Err!("Got an unexpected {} multiplier: {}", symbol, self.multiplier);
+                }
+            },
+
+            // Options have no quotes, so remember their current price
+            InstrumentType::Option => {
+                let price = util::validate_named_cash(
+                    "price", &self.currency, self.price, DecimalRestrictions::PositiveOrZero)?;
+                statement.derivative_prices.insert(symbol.clone(), price * self.multiplier);
+            },
+
+            InstrumentType::Future => {
+                return Err!("Futures aren't supported for Flex Query reports yet");
+            },
+        }
+
+        statement.instrument_info.get_or_add(&symbol).set_type(type_);
+        statement.add_open_position(&symbol, self.position)
+    }
+}
+
+// Flex Query reports use OCC option symbols, but we use the same symbols as in Activity Statements
+// ("AAPL 17DEC21 150 C") which are specified in description for options.
+fn parse_instrument(asset_category: &str, symbol: &str, description: &str) -> GenericResult<(String, InstrumentType)> {
+    Ok(match asset_category {
+        "STK" => (parse_symbol(symbol)?, InstrumentType::Stock),
+        "OPT" => (parse_option_symbol(description)?, InstrumentType::Option),
+        "FUT" => (parse_symbol(symbol)?, InstrumentType::Future),
+        _ => return Err!("Unsupported asset category: {:?}", asset_category),
+    })
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().filter(|value| !value.is_empty())
+}
+
+// Date and time formats are configurable in Flex Query settings, so support the most reasonable ones
+fn parse_date(date: &str) -> GenericResult<Date> {
+    // Cash transactions may have date and time even if only date makes sense for them
+    let date = date.split(&[';', ',', ' '][..]).next().unwrap();
+
+    time::parse_date(date, "%Y%m%d").or_else(|_|
+        time::parse_date(date, "%Y-%m-%d"))
+}
+
+fn parse_date_time(date_time: &str) -> GenericResult<DateTime> {
+    for format in ["%Y%m%d;%H%M%S", "%Y-%m-%d;%H:%M:%S", "%Y-%m-%d, %H:%M:%S", "%Y-%m-%d %H:%M:%S"] {
+        if let Ok(date_time) = time::parse_date_time(date_time, format) {
+            return Ok(date_time);
+        }
+    }
+
+    Err!("Invalid time: {:?}", date_time)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+
+    use rstest::rstest;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[rstest(value,
+        case("20210105"),
+        case("2021-01-05"),
+        case("20210105;093012"),
+    )]
+    fn date_parsing(value: &str) {
+        assert_eq!(parse_date(value).unwrap(), date!(2021, 1, 5));
+    }
+
+    #[rstest(value,
+        case("20210105;093012"),
+        case("2021-01-05;09:30:12"),
+        case("2021-01-05, 09:30:12"),
+    )]
+    fn time_parsing(value: &str) {
+        assert_eq!(parse_date_time(value).unwrap(), date!(2021, 1, 5).and_hms(9, 30, 12));
+    }
+
+    #[test]
+    fn parse_report() {
+        let mut tax_remapping = TaxRemapping::new();
+        let path = get_test_report_path();
+        let statement = read(path.to_str().unwrap(), &mut tax_remapping).unwrap();
+
+        assert_eq!(statement.get_period().unwrap(), Period::new(date!(2021, 1, 1), date!(2021, 12, 31)).unwrap());
+        assert!(!statement.get_has_starting_assets().unwrap());
+        assert!(statement.assets.cash.as_ref().unwrap().has_assets("USD"));
+
+        assert_eq!(statement.deposits_and_withdrawals.len(), 1);
+        assert_eq!(statement.fees.len(), 1);
+        assert_eq!(statement.idle_cash_interest.len(), 1);
+        assert_eq!(statement.forex_trades.len(), 1);
+
+        assert_eq!(statement.stock_buys.len(), 1);
+        let buy = statement.stock_buys.first().unwrap();
+        assert_eq!(buy.symbol, "VTI");
+        assert_eq!(buy.conclusion_time.date, date!(2021, 1, 5));
+        assert_eq!(buy.execution_date, date!(2021, 1, 7));
+
+        assert_eq!(statement.stock_sells.len(), 1);
+        let sell = statement.stock_sells.first().unwrap();
+        assert_eq!(sell.symbol, "VTI-17DEC21-200-C");
+        assert_eq!(sell.execution_date, date!(2021, 2, 2));
+
+        assert_eq!(statement.dividend_accruals.len(), 1);
+        assert_eq!(statement.tax_accruals.len(), 1);
+        assert_eq!(statement.corporate_actions.len(), 1);
+
+        assert_eq!(statement.open_positions.len(), 2);
+        assert_eq!(statement.derivative_prices.len(), 1);
+        assert_eq!(statement.instrument_info.get_type("VTI-17DEC21-200-C"), InstrumentType::Option);
+    }
+
+    #[test]
+    fn report_detection() {
+        assert!(is_flex_query_report(get_test_report_path().to_str().unwrap()).unwrap());
+        assert!(!is_flex_query_report("statement.csv").unwrap());
+
+        let file = mock_report("<?xml version=\"1.0\"?>\n<Statement></Statement>\n");
+        assert_eq!(
+            is_flex_query_report(file.path().to_str().unwrap()).unwrap_err().to_string(),
+            "Got an unexpected XML file with <Statement> root element: Flex Query report is expected");
+    }
+
+    #[rstest(from, to, error,
+        case(r#"levelOfDetail="EXECUTION""#, r#"levelOfDetail="ORDER""#,
+             "Got trades with unsupported level of detail: ORDER. Execution level of detail is expected"),
+        case(r#"levelOfDetail="SUMMARY""#, r#"levelOfDetail="LOT""#,
+             "Got open positions with unsupported level of detail: LOT. Summary level of detail is expected"),
+    )]
+    fn unsupported_level_of_detail(from: &str, to: &str, error: &str) {
+        let data = std::fs::read_to_string(get_test_report_path()).unwrap();
+        let file = mock_report(&data.replacen(from, to, 1));
+
+        let result = read(file.path().to_str().unwrap(), &mut TaxRemapping::new());
+        assert_eq!(result.err().unwrap().to_string(), error);
+    }
+
+    #[test]
+    fn summary_trades() {
+        let data = std::fs::read_to_string(get_test_report_path()).unwrap();
+        let trade = data.lines().find(|line| {
+            line.starts_with("<Trade ") && line.contains(r#"symbol="VTI""#)
+        }).unwrap();
+
+        let summary = trade.replace(r#"levelOfDetail="EXECUTION""#, r#"levelOfDetail="SYMBOL_SUMMARY""#);
+        let file = mock_report(&data.replace(trade, &format!("{}\n{}", trade, summary)));
+
+        let statement = read(file.path().to_str().unwrap(), &mut TaxRemapping::new()).unwrap();
+        assert_eq!(statement.stock_buys.len(), 1);
+    }
+
+    fn get_test_report_path() -> PathBuf {
+        Path::new(file!()).parent().unwrap().join("testdata/flex-query.xml")
+    }
+
+    fn mock_report(data: &str) -> NamedTempFile {
+        let mut file = tempfile::Builder::new().suffix(".xml").tempfile().unwrap();
+        file.write_all(data.as_bytes()).unwrap();
+        file
+    }
+}
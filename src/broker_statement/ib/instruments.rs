@@ -1,10 +1,11 @@
 use chrono::Datelike;
 
 use crate::core::EmptyResult;
+use crate::instruments::InstrumentType;
 use crate::util::DecimalRestrictions;
 
 use super::StatementParser;
-use super::common::{self, Record, RecordParser, SecurityID, parse_symbol};
+use super::common::{self, Record, RecordParser, SecurityID, parse_option_symbol, parse_symbol};
 
 pub struct OpenPositionsParser {}
 
@@ -17,10 +18,7 @@ impl RecordParser for OpenPositionsParser {
         let data_type_field = "DataDiscriminator";
         match record.get_value(data_type_field)? {
             // Default Activity Statement contains only this type
-            "Summary" => record.check_values(&[
-                ("Asset Category", "Stocks"),
-                ("Mult", "1"),
-            ])?,
+            "Summary" => {},
 
             // Custom Activity Statement types:
             // * Lot - open position calculation
@@ -29,9 +27,37 @@ impl RecordParser for OpenPositionsParser {
             value => return Err!("Got an unexpected {:?} field value: {:?}", data_type_field, value),
         };
 
-        let symbol = record.parse_symbol("Symbol")?;
-        let quantity = record.parse_quantity("Quantity", DecimalRestrictions::StrictlyPositive)?;
-        parser.statement.add_open_position(&symbol, quantity)
+        let quantity = record.parse_quantity("Quantity", DecimalRestrictions::NonZero)?;
+
+        match record.get_value("Asset Category")? {
+            "Stocks" => {
+                record.check_value("Mult", "1")?;
+                let symbol = record.parse_symbol("Symbol")?;
+                parser.statement.add_open_position(&symbol, quantity)?;
+            },
+
+            "Equity and Index Options" => {
+                let symbol = parse_option_symbol(record.get_value("Symbol")?)?;
+                let currency = record.get_value("Currency")?;
+                let multiplier = record.parse_quantity("Mult", DecimalRestrictions::StrictlyPositive)?;
+
+                // Options have no quotes, so remember their current price which is specified per share
+                let price = record.parse_cash("Close Price", currency, DecimalRestrictions::PositiveOrZero)?;
+                parser.statement.derivative_prices.insert(symbol.clone(), price * multiplier);
+
+                parser.statement.instrument_info.get_or_add(&symbol).set_type(InstrumentType::Option);
+                parser.statement.add_open_position(&symbol, quantity)?;
+            },
+
+            // Futures positions are settled daily via variation margin, so they have no value
+            "Futures" => {
+                parser.has_futures = true;
+            },
+
+            asset_category => return Err!("Unsupported asset category: {}", asset_category),
+        }
+
+        Ok(())
     }
 }
 
@@ -39,7 +65,33 @@ pub struct FinancialInstrumentInformationParser {
 }
 
 impl RecordParser for FinancialInstrumentInformationParser {
+    // The section has its own header for each asset category
+    fn allow_multiple(&self) -> bool {
+        true
+    }
+
     fn parse(&mut self, parser: &mut StatementParser, record: &Record) -> EmptyResult {
+        let instrument_type = match record.get_value("Asset Category")? {
+            "Stocks" => InstrumentType::Stock,
+            "Equity and Index Options" => InstrumentType::Option,
+            "Futures" => InstrumentType::Future,
+            asset_category => return Err!("Unsupported asset category: {}", asset_category),
+        };
+
+        // Derivatives have no security ID
+        if instrument_type.is_derivative() {
+            let symbol = match instrument_type {
+                InstrumentType::Option => parse_option_symbol(record.get_value("Symbol")?)?,
+                _ => record.parse_symbol("Symbol")?,
+            };
+
+            let instrument = parser.statement.instrument_info.get_or_add(&symbol);
+            instrument.set_name(record.get_value("Description")?);
+            instrument.set_type(instrument_type);
+
+            return Ok(());
+        }
+
         // If symbol renames save its ISIN the column contains both symbols
         // (see https://github.com/KonishchevDmitry/investments/issues/29)
 
@@ -5,6 +5,7 @@ mod confirmation;
 mod corporate_actions;
 mod dividends;
 mod fees;
+mod flex;
 mod grants;
 mod interest;
 mod instruments;
@@ -59,7 +60,9 @@ impl StatementReader {
 
 impl BrokerStatementReader for StatementReader {
     fn check(&mut self, path: &str) -> GenericResult<bool> {
-        if !path.ends_with(".csv") {
+        if flex::is_flex_query_report(path).map_err(|e| format!("Error while reading {:?}: {}", path, e))? {
+            return Ok(true);
+        } else if !path.ends_with(".csv") {
             return Ok(false)
         }
 
@@ -70,12 +73,17 @@ impl BrokerStatementReader for StatementReader {
     }
 
     fn read(&mut self, path: &str, _is_last: bool) -> GenericResult<PartialBrokerStatement> {
+        if flex::is_flex_query_report(path)? {
+            return flex::read(path, &mut self.tax_remapping);
+        }
+
         StatementParser {
             statement: PartialBrokerStatement::new(&[Exchange::Us], false),
 
             base_currency: None,
             base_currency_summary: None,
             cash_flows: CashFlows::new(self.warn_on_missing_cash_flow_info),
+            has_futures: false,
 
             tax_remapping: &mut self.tax_remapping,
             trade_execution_dates: &self.trade_execution_dates,
@@ -103,6 +111,7 @@ pub struct StatementParser<'a> {
     base_currency: Option<String>,
     base_currency_summary: Option<Cash>,
     cash_flows: CashFlows,
+    has_futures: bool,
 
     tax_remapping: &'a mut TaxRemapping,
     trade_execution_dates: &'a TradeExecutionDates,
@@ -347,6 +356,6 @@ mod tests {
         let tax_remapping = tax_remapping.unwrap_or_else(TaxRemapping::new);
         BrokerStatement::read(
             broker, &path, &Default::default(), &Default::default(), &Default::default(),
-            tax_remapping, &[], ReadingStrictness::all()).unwrap()
+            tax_remapping, &[], None, &[], &Default::default(), ReadingStrictness::all()).unwrap()
     }
 }
\ No newline at end of file
@@ -136,6 +136,14 @@ impl SectionParsers {
             parser.statement.assets.cash.get_or_insert_with(Default::default).deposit(amount);
         }
 
+        // Futures results are settled daily via variation margin which can be found only in account
+        // cash flows.
+        if parser.has_futures && !self.parsed_sections.contains_key("Statement of Funds") {
+            return Err!(concat!(
+                "The broker statement contains futures, but misses Statement of Funds section ",
+                "which is required for variation margin calculation"));
+        }
+
         self.corporate_actions_parser.commit(parser)
     }
 }
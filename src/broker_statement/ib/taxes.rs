@@ -59,7 +59,7 @@ impl RecordParser for WithholdingTaxParser {
     }
 }
 
-fn parse_tax_description(description: &str) -> GenericResult<String> {
+pub fn parse_tax_description(description: &str) -> GenericResult<String> {
     lazy_static! {
         static ref DESCRIPTION_REGEX: Regex = Regex::new(&format!(
             r"^(?P<issuer>{symbol}) ?\({id}\) .+ - [A-Z]{{2}} Tax$",
@@ -1,12 +1,14 @@
 use std::ops::Deref;
 
+use crate::broker_statement::fees::Fee;
 use crate::broker_statement::trades::{ForexTrade, StockBuy, StockSell};
 use crate::core::EmptyResult;
+use crate::instruments::InstrumentType;
 use crate::time::DateTime;
 use crate::util::DecimalRestrictions;
 
 use super::StatementParser;
-use super::common::{Record, RecordParser, parse_symbol};
+use super::common::{Record, RecordParser, parse_option_symbol, parse_symbol};
 
 pub struct TradesParser {}
 
@@ -40,6 +42,8 @@ impl RecordParser for TradesParser {
         match asset_category {
             "Forex" => parse_forex_record(parser, record, symbol, conclusion_time),
             "Stocks" => parse_stock_record(parser, record, symbol, conclusion_time),
+            "Equity and Index Options" => parse_option_record(parser, record, symbol, conclusion_time),
+            "Futures" => parse_futures_record(parser, record, symbol, conclusion_time),
             _ => return Err!("Unsupported asset category: {}", asset_category)
         }
     }
@@ -121,4 +125,60 @@ fn parse_stock_record(
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+// Options are processed as ordinary securities: written options are short positions and
+// expirations, assignments and exercises are trades with zero price.
+fn parse_option_record(
+    parser: &mut StatementParser, record: &Record, symbol: &str, conclusion_time: DateTime,
+) -> EmptyResult {
+    let symbol = parse_option_symbol(symbol)?;
+    let currency = record.get_value("Currency")?;
+    let commission = -record.parse_cash("Comm/Fee", currency, DecimalRestrictions::NegativeOrZero)?;
+    let execution_date = parser.get_execution_date(&symbol, conclusion_time);
+    let quantity = record.parse_quantity("Quantity", DecimalRestrictions::NonZero)?;
+
+    let volume = record.parse_cash("Proceeds", currency, if quantity.is_sign_positive() {
+        DecimalRestrictions::NegativeOrZero
+    } else {
+        DecimalRestrictions::PositiveOrZero
+    })?;
+
+    // Trade price is specified per share, so calculate contract price which includes the multiplier
+    let price = volume / -quantity;
+
+    parser.statement.instrument_info.get_or_add(&symbol).set_type(InstrumentType::Option);
+
+    if quantity.is_sign_positive() {
+        parser.statement.stock_buys.push(StockBuy::new_trade(
+            &symbol, quantity, price, -volume, commission,
+            conclusion_time.into(), execution_date));
+    } else {
+        parser.statement.stock_sells.push(StockSell::new_trade(
+            &symbol, -quantity, price, volume, commission,
+            conclusion_time.into(), execution_date, false));
+    }
+
+    Ok(())
+}
+
+// Futures positions are settled daily via variation margin which is parsed from account cash flows,
+// so here we only have to take trade commissions into account.
+fn parse_futures_record(
+    parser: &mut StatementParser, record: &Record, symbol: &str, conclusion_time: DateTime,
+) -> EmptyResult {
+    let symbol = parse_symbol(symbol)?;
+    let currency = record.get_value("Currency")?;
+    let commission = -record.parse_cash("Comm/Fee", currency, DecimalRestrictions::NegativeOrZero)?;
+
+    parser.statement.instrument_info.get_or_add(&symbol).set_type(InstrumentType::Future);
+    parser.has_futures = true;
+
+    if !commission.is_zero() {
+        parser.statement.fees.push(Fee::new(
+            conclusion_time.date(), commission,
+            Some(format!("Комиссия за сделку с фьючерсом {}", symbol))));
+    }
+
+    Ok(())
+}
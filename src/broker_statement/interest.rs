@@ -2,7 +2,7 @@ use crate::core::GenericResult;
 use crate::currency::Cash;
 use crate::currency::converter::CurrencyConverter;
 use crate::localities::Country;
-use crate::taxes::IncomeType;
+use crate::taxes::{IncomeType, TaxBase};
 use crate::time::Date;
 use chrono::Datelike;
 
@@ -18,8 +18,11 @@ impl IdleCashInterest {
         }
     }
 
-    pub fn tax_to_pay(&self, country: &Country, converter: &CurrencyConverter) -> GenericResult<Cash> {
+    pub fn tax_to_pay(
+        &self, country: &Country, converter: &CurrencyConverter, tax_base: &TaxBase,
+    ) -> GenericResult<Cash> {
+        let year = self.date.year();
         let amount = converter.convert_to_cash_rounding(self.date, self.amount, country.currency)?;
-        Ok(country.tax_to_pay(IncomeType::Interest, self.date.year(), amount, None))
+        Ok(country.tax_to_pay_with_base(IncomeType::Interest, year, amount, tax_base.get(year), None))
     }
 }
\ No newline at end of file
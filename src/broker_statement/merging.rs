@@ -3,7 +3,7 @@ use chrono::{Datelike, Weekday};
 use crate::core::EmptyResult;
 use crate::time::{Date, Month, Period};
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub enum StatementsMergingStrategy {
     ContinuousOnly,
     SparseOnHolidays(usize),
@@ -11,17 +11,21 @@ pub enum StatementsMergingStrategy {
     // Some brokers allow to generate only daily statements for the current month. Monthly
     // statements become available later.
     SparseSingleDaysLastMonth(u32),
+
+    // Statements may overlap (monthly, quarterly, yearly, etc.): overlapping parts are checked to
+    // match and deduplicated, and the rest is merged using the underlying strategy.
+    Overlapping(Box<StatementsMergingStrategy>),
 }
 
 impl StatementsMergingStrategy {
-    pub fn validate(self, first: Period, second: Period, last_date: Date) -> EmptyResult {
+    pub fn validate(&self, first: Period, second: Period, last_date: Date) -> EmptyResult {
         let error = |message| Err!("{}: {}, {}", message, first.format(), second.format());
 
         if second.first_date() <= first.last_date() {
             return error("Overlapping periods");
         }
 
-        match self {
+        match *self {
             StatementsMergingStrategy::ContinuousOnly => {
                 if second.first_date() != first.next_date() {
                     return error("Non-continuous periods");
@@ -73,6 +77,8 @@ impl StatementsMergingStrategy {
 
                 Ok(())
             }
+
+            StatementsMergingStrategy::Overlapping(ref strategy) => strategy.validate(first, second, last_date),
         }
     }
 }
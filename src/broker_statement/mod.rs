@@ -1,19 +1,25 @@
+mod bonds;
 mod cash_flows;
 mod corporate_actions;
+mod derivatives;
 mod dividends;
 mod fees;
 mod grants;
 mod interest;
 mod merging;
+mod opening_balance;
+mod overlapping;
 mod partial;
 mod payments;
 mod reader;
 mod taxes;
 mod trades;
+mod transfers;
 mod validators;
 
 mod bcs;
 mod firstrade;
+mod generic;
 mod ib;
 mod open;
 mod tinkoff;
@@ -21,19 +27,21 @@ mod tinkoff;
 use std::cmp::Ordering;
 use std::collections::{HashMap, BTreeMap, BTreeSet, hash_map::Entry};
 
+use chrono::Datelike;
 use log::warn;
 
 use crate::brokers::{BrokerInfo, Broker};
 use crate::commissions::CommissionCalc;
+use crate::config::{Config, PortfolioConfig};
 use crate::core::{EmptyResult, GenericResult};
 use crate::currency::{Cash, CashAssets, MultiCurrencyCashAccount};
 use crate::currency::converter::CurrencyConverter;
 use crate::exchanges::Exchanges;
 use crate::formatting;
 use crate::instruments::{InstrumentInternalIds, InstrumentInfo};
-use crate::localities;
+use crate::localities::{self, Country};
 use crate::quotes::{Quotes, QuoteQuery};
-use crate::taxes::TaxRemapping;
+use crate::taxes::{TaxBase, TaxRemapping};
 use crate::time::{self, Date, DateOptTime, Period};
 use crate::types::{Decimal, TradeType};
 use crate::util;
@@ -44,16 +52,20 @@ use self::reader::BrokerStatementReader;
 use self::taxes::{TaxId, TaxAccruals};
 use self::validators::{DateValidator, sort_and_validate_trades};
 
+pub use self::bonds::{BondRedemption, Coupon, CouponPayment};
 pub use self::cash_flows::{CashFlow, CashFlowType};
 pub use self::corporate_actions::{
     CorporateAction, CorporateActionType, StockSplitController, process_corporate_actions};
+pub use self::derivatives::{VariationMargin, VariationMarginProfit, calculate_variation_margin_profit};
 pub use self::dividends::Dividend;
 pub use self::fees::Fee;
 pub use self::grants::{StockGrant, process_grants};
 pub use self::interest::IdleCashInterest;
 pub use self::merging::StatementsMergingStrategy;
+pub use self::opening_balance::OpeningBalance;
 pub use self::reader::ReadingStrictness;
 pub use self::taxes::TaxWithholding;
+pub use self::transfers::SecurityTransfer;
 pub use self::trades::{
     ForexTrade, StockBuy, StockSource, StockSell, StockSellType, StockSellSource, StockSourceDetails,
     SellDetails, FifoDetails};
@@ -75,13 +87,16 @@ pub struct BrokerStatement {
     pub forex_trades: Vec<ForexTrade>,
     pub stock_buys: Vec<StockBuy>,
     pub stock_sells: Vec<StockSell>,
+    pub variation_margin: Vec<VariationMargin>,
     pub dividends: Vec<Dividend>,
+    pub coupons: Vec<Coupon>,
 
     stock_grants: Vec<StockGrant>,
     corporate_actions: Vec<CorporateAction>,
     pub stock_splits: StockSplitController,
 
     pub open_positions: HashMap<String, Decimal>,
+    derivative_prices: HashMap<String, Cash>,
     pub instrument_info: InstrumentInfo,
 }
 
@@ -90,23 +105,36 @@ impl BrokerStatement {
         broker: BrokerInfo, statement_dir_path: &str,
         symbol_remapping: &HashMap<String, String>, instrument_internal_ids: &InstrumentInternalIds,
         instrument_names: &HashMap<String, String>, tax_remapping: TaxRemapping,
-        corporate_actions: &[CorporateAction], strictness: ReadingStrictness,
+        corporate_actions: &[CorporateAction], opening_balance: Option<&OpeningBalance>,
+        security_transfers: &[SecurityTransfer], transfer_sources: &HashMap<String, BrokerStatement>,
+        strictness: ReadingStrictness,
     ) -> GenericResult<BrokerStatement> {
         let broker_jurisdiction = broker.type_.jurisdiction();
 
         let mut statements = reader::read(broker.type_, statement_dir_path, tax_remapping, strictness)?;
-        statements.sort_by_key(|statement| statement.period.unwrap());
+        if let Some(opening_balance) = opening_balance {
+            statements.push(opening_balance.to_statement()?);
+        }
 
-        let mut last_period = statements.first().unwrap().period.unwrap();
-        for statement in &statements[1..] {
-            let period = statement.period.unwrap();
-            if period.first_date() <= last_period.last_date() {
-                return Err!(
-                    "Overlapping broker statement periods: {} and {}",
-                    last_period.format(), period.format());
+        let statements = if let StatementsMergingStrategy::Overlapping(_) = broker.statements_merging_strategy {
+            overlapping::deduplicate(statements)?
+        } else {
+            statements.sort_by_key(|statement| statement.period.unwrap());
+
+            let mut last_period = statements.first().unwrap().period.unwrap();
+            for statement in &statements[1..] {
+                let period = statement.period.unwrap();
+                if period.first_date() <= last_period.last_date() {
+                    return Err!(
+                        "Overlapping broker statement periods: {} and {}",
+                        last_period.format(), period.format());
+                }
+                last_period = period;
             }
-            last_period = period;
-        }
+
+            statements
+        };
+        let last_period = statements.last().unwrap().period.unwrap();
 
         let last_index = statements.len() - 1;
         let mut statement = BrokerStatement::new_empty_from(broker, statements.first().unwrap())?;
@@ -114,6 +142,8 @@ impl BrokerStatement {
 
         let mut dividend_accruals = HashMap::new();
         let mut tax_accruals = HashMap::new();
+        let mut coupon_payments = Vec::new();
+        let mut bond_redemptions = Vec::new();
 
         for (index, mut partial) in statements.into_iter().enumerate() {
             for (dividend_id, accruals) in partial.dividend_accruals.drain() {
@@ -128,6 +158,9 @@ impl BrokerStatement {
                     .or_insert(accruals);
             }
 
+            coupon_payments.append(&mut partial.coupon_payments);
+            bond_redemptions.append(&mut partial.bond_redemptions);
+
             statement.merge(partial, last_period.last_date(), index == 0, index == last_index).map_err(|e| format!(
                 "Failed to merge broker statements: {}", e))?;
         }
@@ -164,6 +197,18 @@ impl BrokerStatement {
             return Err!("Unable to find origin operations for the following taxes:\n{}{}", taxes, hint);
         }
 
+        for payment in coupon_payments {
+            let symbol = statement.instrument_info.get_or_add_by_id(&payment.issuer)?.symbol.clone();
+            statement.coupons.push(payment.resolve(&symbol, statement.broker.type_.jurisdiction()));
+        }
+
+        for redemption in bond_redemptions {
+            let symbol = statement.instrument_info.get_or_add_by_id(&redemption.issuer)?.symbol.clone();
+            let position = statement.get_position(&symbol, redemption.date);
+            let sell = redemption.to_sell(&symbol, position)?;
+            statement.stock_sells.push(sell);
+        }
+
         process_grants(&mut statement, strictness.contains(ReadingStrictness::GRANTS))?;
 
         for (symbol, new_symbol) in symbol_remapping.iter() {
@@ -171,6 +216,7 @@ impl BrokerStatement {
                 "Failed to remap {} to {}: {}", symbol, new_symbol, e))?;
         }
         statement.corporate_actions.extend(corporate_actions.iter().cloned());
+        transfers::process_security_transfers(&mut statement, security_transfers, transfer_sources)?;
 
         for (symbol, name) in instrument_names {
             statement.instrument_info.get_or_add(symbol).set_name(name);
@@ -184,6 +230,54 @@ impl BrokerStatement {
         Ok(statement)
     }
 
+    /// Reads broker statements of the specified portfolio and of all portfolios it has incoming
+    /// security transfers from.
+    pub fn read_portfolio(
+        config: &Config, portfolio: &PortfolioConfig, strictness: ReadingStrictness,
+    ) -> GenericResult<BrokerStatement> {
+        BrokerStatement::read_portfolio_impl(config, portfolio, strictness, &mut Vec::new())
+    }
+
+    fn read_portfolio_impl(
+        config: &Config, portfolio: &PortfolioConfig, strictness: ReadingStrictness,
+        reading_stack: &mut Vec<String>,
+    ) -> GenericResult<BrokerStatement> {
+        if reading_stack.contains(&portfolio.name) {
+            return Err!(
+                "Got cyclic security transfers between the following portfolios: {}",
+                reading_stack.join(", "));
+        }
+        reading_stack.push(portfolio.name.clone());
+
+        let mut transfer_sources = HashMap::new();
+        for name in transfers::get_transfer_sources(&portfolio.security_transfers) {
+            let source = config.get_portfolio(name)?;
+            let statement = BrokerStatement::read_portfolio_impl(
+                config, source, ReadingStrictness::empty(), reading_stack,
+            ).map_err(|e| format!("Failed to read {:?} portfolio broker statements: {}", name, e))?;
+            transfer_sources.insert(name.to_owned(), statement);
+        }
+
+        reading_stack.pop();
+
+        let broker = portfolio.broker.get_info(config, portfolio.plan.as_ref())?;
+        BrokerStatement::read(
+            broker, portfolio.statements_path()?, &portfolio.symbol_remapping, &portfolio.instrument_internal_ids,
+            &portfolio.instrument_names, portfolio.get_tax_remapping()?, &portfolio.corporate_actions,
+            portfolio.opening_balance.as_ref(), &portfolio.security_transfers, &transfer_sources, strictness)
+    }
+
+    #[cfg(test)]
+    fn mock(broker: Broker, period: Period) -> BrokerStatement {
+        let broker = broker.get_info(&Config::mock(), None).unwrap();
+
+        let mut statement = PartialBrokerStatement::new(&[], true);
+        statement.set_period(period).unwrap();
+        statement.set_has_starting_assets(false).unwrap();
+
+        BrokerStatement::new_empty_from(broker, &statement).unwrap()
+    }
+
     fn new_empty_from(broker: BrokerInfo, statement: &PartialBrokerStatement) -> GenericResult<BrokerStatement> {
         let period = statement.get_period()?;
 
@@ -191,7 +285,7 @@ impl BrokerStatement {
             return Err!(concat!(
                 "The first broker statement ({}) has a non-zero starting assets. ",
                 "Make sure that broker statements directory contains statements for all periods ",
-                "starting from account opening",
+                "starting from account opening or specify opening balance in the portfolio configuration",
             ), period.format());
         }
 
@@ -211,13 +305,16 @@ impl BrokerStatement {
             forex_trades: Vec::new(),
             stock_buys: Vec::new(),
             stock_sells: Vec::new(),
+            variation_margin: Vec::new(),
             dividends: Vec::new(),
+            coupons: Vec::new(),
 
             stock_grants: Vec::new(),
             corporate_actions: Vec::new(),
             stock_splits: StockSplitController::default(),
 
             open_positions: HashMap::new(),
+            derivative_prices: HashMap::new(),
             instrument_info: InstrumentInfo::new(),
         })
     }
@@ -256,13 +353,54 @@ impl BrokerStatement {
         )
     }
 
+    /// Returns non-trading (dividends, interest and coupons) taxable income by tax years which is
+    /// considered as received before trading income when progressive tax rates are applied.
+    pub fn non_trading_tax_base(
+        &self, country: &Country, converter: &CurrencyConverter,
+    ) -> GenericResult<TaxBase> {
+        let mut tax_base = TaxBase::new();
+
+        for dividend in &self.dividends {
+            let amount = converter.convert_to_cash_rounding(dividend.date, dividend.amount, country.currency)?;
+            tax_base.add(dividend.date.year(), amount.amount);
+        }
+
+        for interest in &self.idle_cash_interest {
+            let amount = converter.convert_to_cash_rounding(interest.date, interest.amount, country.currency)?;
+            tax_base.add(interest.date.year(), amount.amount);
+        }
+
+        for coupon in &self.coupons {
+            let amount = converter.convert_to_cash_rounding(coupon.date, coupon.amount, country.currency)?;
+            tax_base.add(coupon.date.year(), amount.amount);
+        }
+
+        Ok(tax_base)
+    }
+
     pub fn batch_quotes(&self, quotes: &Quotes) -> EmptyResult {
         for symbol in self.open_positions.keys() {
+            self.batch_quote(quotes, symbol)?;
+        }
+        Ok(())
+    }
+
+    pub fn batch_quote(&self, quotes: &Quotes, symbol: &str) -> EmptyResult {
+        if !self.derivative_prices.contains_key(symbol) {
             quotes.batch(self.get_quote_query(symbol))?;
         }
         Ok(())
     }
 
+    /// Returns current price of the open position. Derivatives have no quotes, so their prices are
+    /// taken from the last broker statement.
+    pub fn get_price(&self, quotes: &Quotes, symbol: &str) -> GenericResult<Cash> {
+        if let Some(&price) = self.derivative_prices.get(symbol) {
+            return Ok(price);
+        }
+        quotes.get(self.get_quote_query(symbol))
+    }
+
     pub fn get_quote_query(&self, symbol: &str) -> QuoteQuery {
         let exchanges = match self.instrument_info.get(symbol) {
             Some(instrument) if !instrument.exchanges.is_empty() => &instrument.exchanges,
@@ -284,7 +422,7 @@ impl BrokerStatement {
                 self.batch_quotes(quotes)?;
 
                 for (symbol, &quantity) in &self.open_positions {
-                    let price = quotes.get(self.get_quote_query(symbol))?;
+                    let price = self.get_price(quotes, symbol)?;
                     net_value.deposit(price * quantity);
                 }
             },
@@ -320,6 +458,9 @@ impl BrokerStatement {
 
         if let Entry::Occupied(mut open_position) = self.open_positions.entry(symbol.to_owned()) {
             let available = open_position.get_mut();
+            if available.is_sign_negative() {
+                return Err!("{} is a short position", symbol);
+            }
 
             match quantity.cmp(available) {
                 Ordering::Equal => {
@@ -343,6 +484,58 @@ impl BrokerStatement {
         Ok(())
     }
 
+    // Emulates closing of a short position
+    pub fn emulate_buy(
+        &mut self, symbol: &str, quantity: Decimal, price: Cash,
+        commission_calc: &mut CommissionCalc,
+    ) -> EmptyResult {
+        let conclusion_time = time::today_trade_conclusion_time();
+        let mut execution_date = time::today_trade_execution_date();
+
+        for trade in self.stock_buys.iter().rev() {
+            if trade.execution_date > execution_date {
+                execution_date = trade.execution_date;
+            }
+
+            if trade.symbol == symbol {
+                break
+            }
+        }
+
+        let volume = price * quantity;
+        let commission = commission_calc.add_trade(
+            conclusion_time.date, TradeType::Buy, quantity, price)?;
+
+        let mut stock_buy = StockBuy::new_trade(
+            symbol, quantity, price, volume, commission,
+            conclusion_time, execution_date);
+        stock_buy.emulation = true;
+
+        if let Entry::Occupied(mut open_position) = self.open_positions.entry(symbol.to_owned()) {
+            let available = open_position.get_mut();
+
+            match quantity.cmp(&-*available) {
+                Ordering::Equal => {
+                    open_position.remove();
+                },
+                Ordering::Less => {
+                    *available = (*available + quantity).normalize();
+                },
+                Ordering::Greater => {
+                    return Err!("The portfolio has not enough short positions for {}", symbol);
+                },
+            }
+        } else {
+            return Err!("The portfolio has no short {} position", symbol);
+        }
+
+        self.assets.cash.withdraw(volume);
+        self.assets.cash.withdraw(commission);
+        self.stock_buys.push(stock_buy);
+
+        Ok(())
+    }
+
     pub fn emulate_commissions(&mut self, commission_calc: CommissionCalc) -> GenericResult<MultiCurrencyCashAccount> {
         let mut total = MultiCurrencyCashAccount::new();
 
@@ -361,7 +554,7 @@ impl BrokerStatement {
 
         for (index, stock_buy) in self.stock_buys.iter().enumerate().rev() {
             if let Some(time) = until {
-                if stock_buy.conclusion_time >= time {
+                if stock_buy.statement_time() >= time {
                     continue;
                 }
             }
@@ -378,6 +571,11 @@ impl BrokerStatement {
             symbol_buys.push(index);
         }
 
+        // A sell without open positions is considered as a short sell only for brokers which
+        // support short selling: for others it most likely means missing or truncated statements.
+        let short_selling = self.broker.short_selling;
+        let mut short_sells = Vec::new();
+
         for stock_sell in &mut self.stock_sells {
             if let Some(time) = until {
                 if stock_sell.conclusion_time >= time {
@@ -391,17 +589,20 @@ impl BrokerStatement {
 
             let mut remaining_quantity = stock_sell.quantity;
             let mut sources = Vec::new();
-
-            let symbol_buys = unsold_buys.get_mut(&stock_sell.symbol).ok_or_else(|| format!(
-                "Error while processing {} position closing: There are no open positions for it",
-                stock_sell.original_symbol
-            ))?;
+            let mut symbol_buys = unsold_buys.get_mut(&stock_sell.symbol);
 
             while !remaining_quantity.is_zero() {
-                let index = symbol_buys.last().copied().ok_or_else(|| format!(
-                    "Error while processing {} position closing: There are no open positions for it",
-                    stock_sell.original_symbol
-                ))?;
+                // Lots are ordered by their purchase time, but transferred lots may appear in the
+                // portfolio later than the following ones.
+                let stock_buys = &self.stock_buys;
+                let position = match symbol_buys.as_ref().and_then(|symbol_buys| {
+                    symbol_buys.iter().rposition(|&index| !is_later(
+                        stock_buys[index].statement_time(), stock_sell.conclusion_time))
+                }) {
+                    Some(position) => position,
+                    None => break,
+                };
+                let index = symbol_buys.as_ref().unwrap()[position];
 
                 let stock_buy = &mut self.stock_buys[index];
                 let multiplier = self.stock_splits.get_multiplier(
@@ -417,12 +618,71 @@ impl BrokerStatement {
                 sources.push(stock_buy.sell(source_quantity, multiplier));
                 remaining_quantity -= sell_quantity;
 
+                if stock_buy.is_sold() {
+                    symbol_buys.as_mut().unwrap().remove(position);
+                }
+            }
+
+            if remaining_quantity.is_zero() {
+                stock_sell.process(sources);
+                continue;
+            } else if !sources.is_empty() {
+                return Err!(concat!(
+                    "Error while processing {} position closing: There are not enough open positions ",
+                    "for it (opening a short position by the same trade isn't supported yet)",
+                ), stock_sell.original_symbol);
+            } else if !short_selling || !matches!(stock_sell.type_, StockSellType::Trade {..}) {
+                return Err!(
+                    "Error while processing {} position closing: There are no open positions for it",
+                    stock_sell.original_symbol);
+            }
+
+            // The sell opens a short position which is closed by the following buys. The position
+            // may be left open if there are no such buys yet.
+            while let Some(symbol_buys) = symbol_buys.as_mut() {
+                let index = match symbol_buys.last() {
+                    Some(&index) => index,
+                    None => break,
+                };
+
+                let stock_buy = &mut self.stock_buys[index];
+                if let Some(time) = until {
+                    if stock_buy.conclusion_time >= time {
+                        break;
+                    }
+                }
+
+                if self.stock_splits.get_multiplier(
+                    &stock_sell.symbol, stock_sell.conclusion_time, stock_buy.conclusion_time,
+                ) != dec!(1) {
+                    return Err!(concat!(
+                        "Error while processing {} short position closing: ",
+                        "Stock splits for short positions aren't supported yet",
+                    ), stock_sell.original_symbol);
+                }
+
+                let quantity = std::cmp::min(stock_sell.quantity, stock_buy.get_unsold());
+                let source = stock_buy.sell(quantity, dec!(1));
+                let emulation = stock_buy.emulation;
+
                 if stock_buy.is_sold() {
                     symbol_buys.pop();
                 }
+
+                if quantity == stock_sell.quantity {
+                    stock_sell.process_short(source, emulation);
+                    break;
+                }
+
+                let mut short_sell = stock_sell.split(quantity);
+                short_sell.process_short(source, emulation);
+                short_sells.push(short_sell);
             }
+        }
 
-            stock_sell.process(sources);
+        if !short_sells.is_empty() {
+            self.stock_sells.extend(short_sells);
+            sort_and_validate_trades("sell", &mut self.stock_sells)?;
         }
 
         if until.is_none() {
@@ -459,10 +719,12 @@ impl BrokerStatement {
         self.forex_trades.extend(statement.forex_trades.into_iter());
         self.stock_buys.extend(statement.stock_buys.into_iter());
         self.stock_sells.extend(statement.stock_sells.into_iter());
+        self.variation_margin.extend(statement.variation_margin.into_iter());
         self.stock_grants.extend(statement.stock_grants.into_iter());
 
         self.corporate_actions.extend(statement.corporate_actions.into_iter());
         self.open_positions = statement.open_positions;
+        self.derivative_prices = statement.derivative_prices;
         self.instrument_info.merge(statement.instrument_info);
 
         Ok(())
@@ -504,6 +766,10 @@ impl BrokerStatement {
                 self.open_positions.insert(new_symbol.to_owned(), quantity);
             }
 
+            if let Some(price) = self.derivative_prices.remove(symbol) {
+                self.derivative_prices.insert(new_symbol.to_owned(), price);
+            }
+
             self.instrument_info.remap(symbol, new_symbol)?;
         } else {
             self.stock_splits.rename(symbol, new_symbol)?;
@@ -521,6 +787,10 @@ impl BrokerStatement {
             rename(dividend.date.into(), &mut dividend.issuer, &mut dividend.original_issuer);
         }
 
+        for coupon in &mut self.coupons {
+            rename(coupon.date.into(), &mut coupon.symbol, &mut coupon.original_symbol);
+        }
+
         if remapping {
             for cash_flow in &mut self.cash_flows {
                 if let Some(original_symbol) = cash_flow.mut_symbol() {
@@ -573,17 +843,37 @@ impl BrokerStatement {
 
         self.sort_and_validate_stock_buys()?;
         self.sort_and_validate_stock_sells()?;
+        validator.sort_and_validate(
+            "a variation margin", &mut self.variation_margin, |margin| margin.date)?;
         validator.sort_and_validate("a stock grant", &mut self.stock_grants, |grant| grant.date)?;
 
         self.dividends.sort_by(|a, b| (a.date, &a.issuer).cmp(&(b.date, &b.original_issuer)));
         validator.validate("a dividend", &self.dividends, |dividend| dividend.date)?;
 
+        self.coupons.sort_by(|a, b| (a.date, &a.original_symbol).cmp(&(b.date, &b.original_symbol)));
+        validator.validate("a coupon", &self.coupons, |coupon| coupon.date)?;
+
         validator.sort_and_validate(
             "a corporate action", &mut self.corporate_actions, |action| action.time)?;
 
         Ok(())
     }
 
+    // Calculates position by the trades from the statements (corporate actions aren't processed yet)
+    fn get_position(&self, symbol: &str, date: Date) -> Decimal {
+        let bought: Decimal = self.stock_buys.iter()
+            .filter(|trade| trade.symbol == symbol && trade.execution_date <= date)
+            .map(|trade| trade.quantity)
+            .sum();
+
+        let sold: Decimal = self.stock_sells.iter()
+            .filter(|trade| trade.symbol == symbol && trade.execution_date <= date)
+            .map(|trade| trade.quantity)
+            .sum();
+
+        bought - sold
+    }
+
     fn sort_and_alter_fees(&mut self, max_date: Date) {
         if self.broker.allow_future_fees {
             for fee in &mut self.fees {
@@ -599,7 +889,11 @@ impl BrokerStatement {
     fn sort_and_validate_stock_buys(&mut self) -> EmptyResult {
         let date_validator = DateValidator::new(self.period);
         sort_and_validate_trades("buy", &mut self.stock_buys)?;
-        date_validator.validate("a stock buy", &self.stock_buys, |trade| trade.conclusion_time)
+
+        // Transferred lots have their original purchase time which is outside of statement period
+        let mut times: Vec<DateOptTime> = self.stock_buys.iter().map(StockBuy::statement_time).collect();
+        times.sort();
+        date_validator.validate("a stock buy", &times, |&time| time)
     }
 
     fn sort_and_validate_stock_sells(&mut self) -> EmptyResult {
@@ -627,6 +921,25 @@ impl BrokerStatement {
                 .or_insert(quantity);
         }
 
+        // Unprocessed sells are open short positions
+        for stock_sell in &self.stock_sells {
+            if stock_sell.is_processed() {
+                continue;
+            }
+
+            let multiplier = self.stock_splits.get_multiplier(
+                &stock_sell.symbol, stock_sell.conclusion_time,
+                DateOptTime::new_max_time(self.period.last_date()));
+
+            let quantity = multiplier * stock_sell.quantity;
+
+            let position = open_positions.entry(&stock_sell.symbol).or_default();
+            *position -= quantity;
+            if position.is_zero() {
+                open_positions.remove(stock_sell.symbol.as_str());
+            }
+        }
+
         let symbols: BTreeSet<&str> = self.open_positions.keys().map(String::as_str)
             .chain(open_positions.keys().copied())
             .collect();
@@ -650,8 +963,109 @@ impl BrokerStatement {
     }
 }
 
+// Trades are considered to be in the proper order if their time is unknown
+fn is_later(time: DateOptTime, other: DateOptTime) -> bool {
+    match (time.time, other.time) {
+        (Some(_), Some(_)) => time > other,
+        _ => time.date > other.date,
+    }
+}
+
 #[derive(Clone, Default)]
 pub struct NetAssets {
     pub cash: MultiCurrencyCashAccount,
     pub other: Option<Cash>, // Supported only for some brokers
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_position_opening() {
+        let mut statement = mock_statement(Broker::InteractiveBrokers);
+        statement.stock_sells.push(mock_sell(date!(2020, 2, 3), 10));
+        statement.open_positions.insert(s!("AAPL"), dec!(-10));
+        statement.process_trades(None).unwrap();
+
+        assert_eq!(statement.stock_sells.len(), 1);
+        assert!(!statement.stock_sells[0].is_processed());
+    }
+
+    #[test]
+    fn short_position_partial_covering() {
+        let mut statement = mock_statement(Broker::InteractiveBrokers);
+        statement.stock_sells.push(mock_sell(date!(2020, 2, 3), 10));
+        statement.stock_buys.push(mock_buy(date!(2020, 3, 2), 4));
+        statement.open_positions.insert(s!("AAPL"), dec!(-6));
+        statement.process_trades(None).unwrap();
+
+        let sells = &statement.stock_sells;
+        assert_eq!(sells.len(), 2);
+        assert!(sells.iter().any(|sell| !sell.is_processed() && sell.quantity == dec!(6)));
+
+        let covered = sells.iter().find(|sell| sell.is_processed()).unwrap();
+        assert!(covered.is_short());
+        assert_eq!(covered.quantity, dec!(4));
+        assert_eq!(covered.realization_date(), date!(2020, 3, 4));
+        assert!(statement.stock_buys[0].is_sold());
+    }
+
+    #[test]
+    fn short_position_full_covering() {
+        let mut statement = mock_statement(Broker::InteractiveBrokers);
+        statement.stock_sells.push(mock_sell(date!(2020, 2, 3), 10));
+        statement.stock_buys.push(mock_buy(date!(2020, 3, 2), 4));
+        statement.stock_buys.push(mock_buy(date!(2020, 4, 1), 6));
+        statement.process_trades(None).unwrap();
+
+        let sells = &statement.stock_sells;
+        assert_eq!(sells.len(), 2);
+        assert!(sells.iter().all(|sell| sell.is_processed() && sell.is_short()));
+        assert_eq!(sells.iter().map(|sell| sell.quantity).sum::<Decimal>(), dec!(10));
+        assert!(statement.stock_buys.iter().all(StockBuy::is_sold));
+    }
+
+    #[test]
+    fn short_position_covering_until() {
+        let mut statement = mock_statement(Broker::InteractiveBrokers);
+        statement.stock_sells.push(mock_sell(date!(2020, 2, 3), 10));
+        statement.stock_buys.push(mock_buy(date!(2020, 3, 2), 10));
+        statement.process_trades(Some(date!(2020, 3, 1).into())).unwrap();
+
+        assert!(!statement.stock_sells[0].is_processed());
+        assert!(!statement.stock_buys[0].is_sold());
+    }
+
+    #[test]
+    fn short_selling_unsupported() {
+        let mut statement = mock_statement(Broker::Tinkoff);
+        statement.stock_sells.push(mock_sell(date!(2020, 2, 3), 10));
+        statement.open_positions.insert(s!("AAPL"), dec!(-10));
+
+        let error = statement.process_trades(None).unwrap_err().to_string();
+        assert!(error.contains("There are no open positions for it"), "{}", error);
+    }
+
+    fn mock_statement(broker: Broker) -> BrokerStatement {
+        BrokerStatement::mock(broker, Period::new(date!(2020, 1, 1), date!(2020, 12, 31)).unwrap())
+    }
+
+    fn mock_buy(date: Date, quantity: u32) -> StockBuy {
+        let (price, volume, commission) = mock_trade(quantity);
+        StockBuy::new_trade(
+            "AAPL", quantity.into(), price, volume, commission, date.into(), date + chrono::Duration::days(2))
+    }
+
+    fn mock_sell(date: Date, quantity: u32) -> StockSell {
+        let (price, volume, commission) = mock_trade(quantity);
+        StockSell::new_trade(
+            "AAPL", quantity.into(), price, volume, commission, date.into(), date + chrono::Duration::days(2),
+            false)
+    }
+
+    fn mock_trade(quantity: u32) -> (Cash, Cash, Cash) {
+        let price = Cash::new("USD", dec!(100));
+        (price, price * Decimal::from(quantity), Cash::new("USD", dec!(1)))
+    }
+}
@@ -139,7 +139,8 @@ mod tests {
         BrokerStatement::read(
             broker, &format!("testdata/open-broker/{}", name),
             &Default::default(), &portfolio.instrument_internal_ids, &Default::default(),
-            TaxRemapping::new(), &portfolio.corporate_actions, ReadingStrictness::all(),
+            TaxRemapping::new(), &portfolio.corporate_actions, None,
+            &[], &Default::default(), ReadingStrictness::all(),
         ).unwrap()
     }
 }
@@ -2,6 +2,7 @@ use lazy_static::lazy_static;
 use regex::Regex;
 use serde::Deserialize;
 
+use crate::broker_statement::bonds::{BondRedemption, CouponPayment};
 use crate::broker_statement::fees::Fee;
 use crate::broker_statement::open::common::deserialize_date;
 use crate::broker_statement::partial::PartialBrokerStatement;
@@ -71,6 +72,20 @@ impl CashFlow {
                 statement.dividend_accruals(date, issuer_id, true).add(date, amount);
             },
 
+            CashFlowType::Coupon(issuer) => {
+                let issuer_id = InstrumentId::InternalId(issuer);
+                let amount = util::validate_named_cash(
+                    "coupon amount", currency, amount, DecimalRestrictions::StrictlyPositive)?;
+                statement.coupon_payments.push(CouponPayment::new(date, issuer_id, amount)?);
+            },
+
+            CashFlowType::BondRedemption(issuer) => {
+                let issuer_id = InstrumentId::InternalId(issuer);
+                let amount = util::validate_named_cash(
+                    "bond redemption amount", currency, amount, DecimalRestrictions::StrictlyPositive)?;
+                statement.bond_redemptions.push(BondRedemption::new(date, issuer_id, None, amount)?);
+            },
+
             CashFlowType::DividendTax(issuer) => {
                 let issuer_id = InstrumentId::InternalId(issuer);
                 let amount = -util::validate_named_cash(
@@ -92,6 +107,9 @@ enum CashFlowType {
 
     Dividend(String),
     DividendTax(String),
+
+    Coupon(String),
+    BondRedemption(String),
 }
 
 impl CashFlowType {
@@ -147,6 +165,24 @@ impl CashFlowType {
             }
         }
 
+        lazy_static! {
+            static ref COUPON_REGEX: Regex = Regex::new(
+                r"^Выплата дохода клиент [^ ]+ купон (?P<issuer>[^,]+?)(?:,| налог к удержанию|$)").unwrap();
+
+            static ref BOND_REDEMPTION_REGEX: Regex = Regex::new(
+                r"^Выплата дохода клиент [^ ]+ погашение (?P<issuer>[^,]+?)(?:,|$)").unwrap();
+        }
+
+        if let Some(captures) = COUPON_REGEX.captures(&description) {
+            let issuer = captures.name("issuer").unwrap().as_str().to_owned();
+            return Ok(CashFlowType::Coupon(issuer));
+        }
+
+        if let Some(captures) = BOND_REDEMPTION_REGEX.captures(&description) {
+            let issuer = captures.name("issuer").unwrap().as_str().to_owned();
+            return Ok(CashFlowType::BondRedemption(issuer));
+        }
+
         lazy_static! {
             static ref DIVIDEND_TAX_REGEX: Regex = Regex::new(
                 r"^Удержан налог на доход по дивидендам (?P<issuer>.+) с клиента").unwrap();
@@ -233,6 +269,30 @@ mod tests {
         );
     }
 
+    #[rstest(description, expected,
+        case("Выплата дохода клиент 123456 купон ОФЗ 26207 налог к удержанию 52.00 рублей",
+             "ОФЗ 26207"),
+        case("Выплата дохода клиент 123456 купон РЖД-32 обл",
+             "РЖД-32 обл"),
+    )]
+    fn coupon_description_parsing(description: &str, expected: &str) {
+        assert_matches!(
+            CashFlowType::parse(description).unwrap(),
+            CashFlowType::Coupon(issuer) if issuer == expected
+        );
+    }
+
+    #[rstest(description, expected,
+        case("Выплата дохода клиент 123456 погашение ОФЗ 26207",
+             "ОФЗ 26207"),
+    )]
+    fn bond_redemption_description_parsing(description: &str, expected: &str) {
+        assert_matches!(
+            CashFlowType::parse(description).unwrap(),
+            CashFlowType::BondRedemption(issuer) if issuer == expected
+        );
+    }
+
     #[rstest(description, expected,
         case("Удержан налог на доход  по дивидендам Татнфт 3ап с клиента 123456",
              "Татнфт 3ап"),
@@ -53,7 +53,10 @@ impl CorporateActions {
 
                 OperationType::StockSplitDeposit(_) => {
                     return Err!("Got an unexpected corporate action: {}", operation.comment);
-                }
+                },
+
+                // It's processed using the corresponding cash flow
+                OperationType::BondRedemption => {},
             };
         }
 
@@ -126,6 +129,7 @@ fn parse_ratio(withdrawal: Decimal, deposit: Decimal) -> Option<StockSplitRatio>
 enum OperationType {
     StockSplitWithdrawal(Date),
     StockSplitDeposit(Date),
+    BondRedemption,
 }
 
 impl OperationType {
@@ -148,6 +152,17 @@ impl OperationType {
             })
         }
 
+        lazy_static! {
+            static ref BOND_REDEMPTION_REGEX: Regex = Regex::new(concat!(
+                r"^Отчет депозитария б/н от \d{2}.\d{2}.\d{4}. ",
+                r"Снятие ЦБ с учета. Погашение - "
+            )).unwrap();
+        }
+
+        if BOND_REDEMPTION_REGEX.is_match(description) {
+            return Ok(OperationType::BondRedemption);
+        }
+
         return Err!("Unsupported corporate action: {:?}", description);
     }
 }
@@ -162,6 +177,8 @@ mod tests {
              OperationType::StockSplitWithdrawal(date!(2021, 10, 7))),
         case("Отчет депозитария б/н от 07.10.2021. Прием ЦБ на учет. Дробление - FinEx MSCI USA UCITS ETF-ип",
              OperationType::StockSplitDeposit(date!(2021, 10, 7))),
+        case("Отчет депозитария б/н от 17.03.2021. Снятие ЦБ с учета. Погашение - ОФЗ 26207",
+             OperationType::BondRedemption),
     )]
     fn operation_type_parsing(description: &str, expected: OperationType) {
         assert_eq!(OperationType::parse(description).unwrap(), expected);
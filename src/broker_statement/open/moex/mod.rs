@@ -6,7 +6,7 @@ mod forex;
 mod securities;
 mod trades;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use log::error;
 use serde::Deserialize;
@@ -69,10 +69,10 @@ impl BrokerReport {
         let mut statement = PartialBrokerStatement::new(&[Exchange::Moex], true);
         statement.period.replace(Period::new(self.date_from, self.date_to)?);
 
-        let securities = if let Some(ref securities) = self.securities {
+        let (securities, bonds) = if let Some(ref securities) = self.securities {
             securities.parse(&mut statement)?
         } else {
-            HashMap::new()
+            (HashMap::new(), HashSet::new())
         };
 
         let mut has_starting_assets = self.account_summary.parse()?;
@@ -96,11 +96,11 @@ impl BrokerReport {
         };
 
         if let Some(ref trades) = self.concluded_trades {
-            trades.parse(&mut statement, &securities, &mut trades_with_shifted_execution_date)?;
+            trades.parse(&mut statement, &securities, &bonds, &mut trades_with_shifted_execution_date)?;
         }
 
         if let Some(ref trades) = self.concluded_repo_trades {
-            trades.parse(&mut statement, &securities, &mut HashMap::new())?;
+            trades.parse(&mut statement, &securities, &bonds, &mut HashMap::new())?;
         }
 
         if let Some(ref cash_flow) = self.cash_flow {
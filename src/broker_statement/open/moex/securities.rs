@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use serde::Deserialize;
 
@@ -30,13 +30,17 @@ struct Security {
 }
 
 impl Securities {
-    pub fn parse(&self, statement: &mut PartialBrokerStatement) -> GenericResult<HashMap<String, String>> {
+    pub fn parse(
+        &self, statement: &mut PartialBrokerStatement,
+    ) -> GenericResult<(HashMap<String, String>, HashSet<String>)> {
         let mut securities = HashMap::new();
+        let mut bonds = HashSet::new();
 
         for security in &self.securities {
-            let name = match InstrumentType::parse(&security.type_)? {
+            let type_ = InstrumentType::parse(&security.type_)?;
+            let name = match type_ {
                 InstrumentType::Stock => parse_issuer_name(&security.issuer),
-                InstrumentType::DepositaryReceipt => parse_security_name(&security.name),
+                InstrumentType::DepositaryReceipt | InstrumentType::Bond => parse_security_name(&security.name),
             };
 
             let exchange = match security.exchange.as_str() {
@@ -48,13 +52,17 @@ impl Securities {
                 return Err!("Duplicated security name: {:?}", security.name);
             }
 
+            if matches!(type_, InstrumentType::Bond) {
+                bonds.insert(security.symbol.clone());
+            }
+
             let instrument = statement.instrument_info.add(&security.symbol)?;
             instrument.set_name(name);
             instrument.add_isin(parse_isin(&security.isin)?);
             instrument.exchanges.add_prioritized(exchange);
         }
 
-        Ok(securities)
+        Ok((securities, bonds))
     }
 }
 
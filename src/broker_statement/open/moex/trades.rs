@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use log::warn;
 use serde::Deserialize;
@@ -24,11 +24,12 @@ pub struct ConcludedTrades<const REPO: bool> {
 impl<const REPO: bool> ConcludedTrades<REPO> {
     pub fn parse(
         &self, statement: &mut PartialBrokerStatement, securities: &HashMap<String, String>,
-        trades_with_shifted_execution_date: &mut HashMap<u64, Date>,
+        bonds: &HashSet<String>, trades_with_shifted_execution_date: &mut HashMap<u64, Date>,
     ) -> EmptyResult {
         for trade in &self.trades {
             let symbol = get_symbol(securities, &trade.security_name)?;
-            trade.parse(statement, symbol, trades_with_shifted_execution_date)?;
+            let bond = bonds.contains(symbol);
+            trade.parse(statement, symbol, bond, trades_with_shifted_execution_date)?;
         }
         Ok(())
     }
@@ -63,6 +64,10 @@ struct ConcludedTrade<const REPO: bool> {
     #[serde(alias="volume_currency")]
     volume: Decimal,
 
+    // Accrued coupon interest for bonds
+    #[serde(rename = "nkd")]
+    accrued_interest: Option<Decimal>,
+
     #[serde(rename = "accounting_currency_code")]
     accounting_currency: String,
 
@@ -75,7 +80,7 @@ struct ConcludedTrade<const REPO: bool> {
 
 impl<const REPO: bool> ConcludedTrade<REPO> {
     fn parse(
-        &self, statement: &mut PartialBrokerStatement, symbol: &str,
+        &self, statement: &mut PartialBrokerStatement, symbol: &str, bond: bool,
         trades_with_shifted_execution_date: &mut HashMap<u64, Date>,
     ) -> EmptyResult {
         // Just don't know which one exactly is
@@ -85,14 +90,28 @@ impl<const REPO: bool> ConcludedTrade<REPO> {
                  symbol);
         }
 
-        let price = util::validate_named_cash(
+        let mut price = util::validate_named_cash(
             "price", &self.price_currency, self.price,
             DecimalRestrictions::StrictlyPositive)?.normalize();
 
-        let volume = util::validate_named_cash(
+        let mut volume = util::validate_named_cash(
             "trade volume", &self.price_currency, self.volume,
             DecimalRestrictions::StrictlyPositive)?.normalize();
 
+        // Bond prices are specified in percents of face value and accrued coupon interest is a part
+        // of purchase cost / sell revenue, so we use full price calculated from the trade volume.
+        let accrued_interest = util::validate_named_decimal(
+            "accrued coupon interest", self.accrued_interest.unwrap_or_default(),
+            DecimalRestrictions::PositiveOrZero)?;
+
+        if !bond && !accrued_interest.is_zero() {
+            return Err!("Got accrued coupon interest for {} which is not a bond", symbol);
+        }
+
+        if bond {
+            volume.amount += accrued_interest;
+        }
+
         let commission = util::validate_named_decimal(
             "commission", self.commission, DecimalRestrictions::PositiveOrZero)?;
 
@@ -118,7 +137,10 @@ impl<const REPO: bool> ConcludedTrade<REPO> {
             (Some(quantity), None) => {
                 let quantity = util::validate_decimal(
                     parse_quantity(quantity), DecimalRestrictions::StrictlyPositive)?;
-                debug_assert_eq!(volume, price * quantity);
+                if bond {
+                    price = volume / quantity;
+                }
+                debug_assert!(bond || volume == price * quantity);
 
                 if REPO {
                     statement.cash_flows.push(CashFlow::new(self.conclusion_time.into(), -volume, CashFlowType::Repo {
@@ -135,7 +157,10 @@ impl<const REPO: bool> ConcludedTrade<REPO> {
             (None, Some(quantity)) => {
                 let quantity = util::validate_decimal(
                     parse_quantity(quantity), DecimalRestrictions::StrictlyPositive)?;
-                debug_assert_eq!(volume, price * quantity);
+                if bond {
+                    price = volume / quantity;
+                }
+                debug_assert!(bond || volume == price * quantity);
 
                 if REPO {
                     statement.cash_flows.push(CashFlow::new(self.conclusion_time.into(), volume, CashFlowType::Repo {
@@ -62,6 +62,7 @@ impl Trade {
     fn parse(&self, statement: &mut PartialBrokerStatement) -> EmptyResult {
         match InstrumentType::parse(&self.category)? {
             InstrumentType::Stock | InstrumentType::DepositaryReceipt => {},
+            InstrumentType::Bond => return Err!("Bonds aren't supported for SPB statements yet"),
         }
 
         let symbol = parse_security_code(&self.security_code)?;
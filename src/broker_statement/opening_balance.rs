@@ -0,0 +1,145 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Deserialize;
+
+use crate::core::{EmptyResult, GenericResult};
+use crate::currency::{Cash, CashAssets};
+use crate::formatting;
+use crate::time::{Date, Period, deserialize_date};
+use crate::types::Decimal;
+use crate::util::{self, DecimalRestrictions};
+
+use super::partial::PartialBrokerStatement;
+use super::trades::StockBuy;
+
+/// Account state for the day preceding the first available broker statement. Allows to use
+/// statements of old accounts whose early statements are lost.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct OpeningBalance {
+    #[serde(deserialize_with = "deserialize_date")]
+    pub date: Date,
+
+    #[serde(default)]
+    pub cash: BTreeMap<String, Decimal>,
+
+    #[serde(default)]
+    pub positions: Vec<OpeningLot>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct OpeningLot {
+    pub symbol: String,
+    pub quantity: Decimal,
+
+    // Original purchase date and cost basis (including commissions) in original currency
+    #[serde(rename = "purchase_date", deserialize_with = "deserialize_date")]
+    pub date: Date,
+    pub cost: Decimal,
+    pub currency: String,
+}
+
+impl OpeningBalance {
+    pub fn validate(&self) -> EmptyResult {
+        for (currency, &amount) in &self.cash {
+            util::validate_named_cash("opening cash balance", currency, amount, DecimalRestrictions::PositiveOrZero)?;
+        }
+
+        for lot in &self.positions {
+            util::validate_named_decimal(
+                &format!("{} opening position quantity", lot.symbol), lot.quantity,
+                DecimalRestrictions::StrictlyPositive)?;
+
+            util::validate_named_cash(
+                &format!("{} opening position cost", lot.symbol), &lot.currency, lot.cost,
+                DecimalRestrictions::StrictlyPositive)?;
+
+            if lot.date > self.date {
+                return Err!(
+                    "Invalid {} opening position purchase date: {} is after the opening balance date",
+                    lot.symbol, formatting::format_date(lot.date));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Emulates a broker statement which precedes the first actual one. Opening positions are
+    /// emulated as purchased on their original purchase dates with cost basis deposited on the same
+    /// day, and cash - as deposited on the opening balance date.
+    pub fn to_statement(&self) -> GenericResult<PartialBrokerStatement> {
+        let first_date = self.positions.iter()
+            .map(|lot| lot.date)
+            .min()
+            .map_or(self.date, |date| std::cmp::min(date, self.date));
+
+        let mut statement = PartialBrokerStatement::new(&[], true);
+        statement.set_period(Period::new(first_date, self.date)?)?;
+        statement.set_has_starting_assets(false)?;
+
+        let cash_assets = statement.assets.cash.as_mut().unwrap();
+        for (currency, &amount) in &self.cash {
+            let amount = Cash::new(currency, amount);
+            if amount.is_zero() {
+                continue;
+            }
+
+            cash_assets.deposit(amount);
+            statement.deposits_and_withdrawals.push(CashAssets::new_from_cash(self.date, amount));
+        }
+
+        let mut open_positions: HashMap<&str, Decimal> = HashMap::new();
+
+        for lot in &self.positions {
+            let volume = Cash::new(&lot.currency, lot.cost);
+            let price = volume / lot.quantity;
+
+            statement.stock_buys.push(StockBuy::new_trade(
+                &lot.symbol, lot.quantity, price, volume, Cash::zero(&lot.currency),
+                lot.date.into(), lot.date));
+            statement.deposits_and_withdrawals.push(CashAssets::new_from_cash(lot.date, volume));
+
+            *open_positions.entry(&lot.symbol).or_default() += lot.quantity;
+        }
+
+        for (symbol, quantity) in open_positions {
+            statement.add_open_position(symbol, quantity)?;
+        }
+
+        Ok(statement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opening_statement() {
+        let balance = OpeningBalance {
+            date: date!(2020, 12, 31),
+            cash: btreemap!{s!("RUB") => dec!(1000), s!("USD") => dec!(0)},
+            positions: vec![
+                OpeningLot {
+                    symbol: s!("FXUS"), quantity: dec!(10), date: date!(2018, 5, 14),
+                    cost: dec!(30000), currency: s!("RUB"),
+                },
+                OpeningLot {
+                    symbol: s!("FXUS"), quantity: dec!(5), date: date!(2019, 3, 1),
+                    cost: dec!(20000), currency: s!("RUB"),
+                },
+            ],
+        };
+        balance.validate().unwrap();
+
+        let statement = balance.to_statement().unwrap();
+        assert_eq!(statement.get_period().unwrap(), Period::new(date!(2018, 5, 14), date!(2020, 12, 31)).unwrap());
+        assert!(!statement.get_has_starting_assets().unwrap());
+
+        assert_eq!(statement.deposits_and_withdrawals.len(), 3);
+        assert_eq!(statement.stock_buys.len(), 2);
+        assert_eq!(statement.stock_buys.first().unwrap().conclusion_time.date, date!(2018, 5, 14));
+        assert_eq!(statement.open_positions, hashmap!{s!("FXUS") => dec!(15)});
+    }
+}
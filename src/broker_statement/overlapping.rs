@@ -0,0 +1,286 @@
+use std::cmp::{self, Reverse};
+use std::collections::BTreeMap;
+
+use crate::core::{EmptyResult, GenericResult};
+use crate::currency::Cash;
+use crate::formatting;
+use crate::time::{Date, Period};
+use crate::types::Decimal;
+
+use super::partial::PartialBrokerStatement;
+use super::trades::{StockSource, StockSellType};
+
+/// Brokers often provide overlapping statements (monthly, quarterly, yearly, etc.), so allow
+/// statements directory to contain all of them: operations of the overlapping parts are checked to
+/// match the operations of the preceding statements for the same period, after which statements
+/// that are fully covered by the preceding ones are dropped and the partially covered ones are
+/// trimmed to their uncovered part.
+pub fn deduplicate(mut statements: Vec<PartialBrokerStatement>) -> GenericResult<Vec<PartialBrokerStatement>> {
+    // Place the longest statement first to make it a covering one
+    statements.sort_by_key(|statement| {
+        let period = statement.period.unwrap();
+        (period.first_date(), Reverse(period.last_date()))
+    });
+
+    let mut result: Vec<PartialBrokerStatement> = Vec::with_capacity(statements.len());
+
+    for mut statement in statements {
+        let period = statement.period.unwrap();
+        let mut last_covered_date = None;
+
+        // The resulting statements are sorted and don't overlap
+        for covering in result.iter().rev() {
+            let covering_period = covering.period.unwrap();
+            if covering_period.last_date() < period.first_date() {
+                break;
+            } else if covering_period.first_date() > period.last_date() {
+                continue;
+            }
+
+            let overlap = Period::new(
+                cmp::max(period.first_date(), covering_period.first_date()),
+                cmp::min(period.last_date(), covering_period.last_date()),
+            ).unwrap();
+
+            compare(covering, &statement, overlap).map_err(|e| format!(
+                "{} broker statement overlaps with {} one, but they don't match: {}",
+                period.format(), covering_period.format(), e))?;
+
+            last_covered_date = cmp::max(last_covered_date, Some(covering_period.last_date()));
+        }
+
+        match last_covered_date {
+            Some(date) if date >= period.last_date() => continue,
+            Some(date) => trim(&mut statement, date),
+            None => {},
+        }
+
+        result.push(statement);
+    }
+
+    Ok(result)
+}
+
+// Drops all operations covered by the preceding statements
+fn trim(statement: &mut PartialBrokerStatement, last_covered_date: Date) {
+    let period = statement.period.unwrap();
+    statement.period.replace(Period::new(last_covered_date.succ(), period.last_date()).unwrap());
+
+    let keep = |date: Date| date > last_covered_date;
+
+    statement.deposits_and_withdrawals.retain(|assets| keep(assets.date));
+    statement.cash_flows.retain(|cash_flow| keep(cash_flow.date.date));
+    statement.fees.retain(|fee| keep(fee.date));
+    statement.idle_cash_interest.retain(|interest| keep(interest.date));
+    statement.tax_agent_withholdings.retain(|withholding| keep(withholding.date));
+
+    statement.forex_trades.retain(|trade| keep(trade.conclusion_time.date));
+    statement.stock_buys.retain(|trade| keep(trade.conclusion_time.date));
+    statement.stock_sells.retain(|trade| keep(trade.conclusion_time.date));
+    statement.variation_margin.retain(|margin| keep(margin.date));
+
+    for accruals in statement.dividend_accruals.values_mut() {
+        accruals.retain(|accrual| keep(accrual.date));
+    }
+    statement.dividend_accruals.retain(|_, accruals| !accruals.transactions().is_empty());
+
+    for accruals in statement.tax_accruals.values_mut() {
+        accruals.retain(|accrual| keep(accrual.date));
+    }
+    statement.tax_accruals.retain(|_, accruals| !accruals.transactions().is_empty());
+
+    statement.coupon_payments.retain(|coupon| keep(coupon.date));
+    statement.bond_redemptions.retain(|redemption| keep(redemption.date));
+    statement.stock_grants.retain(|grant| keep(grant.date));
+    statement.corporate_actions.retain(|action| keep(action.time.date));
+}
+
+fn compare(covering: &PartialBrokerStatement, statement: &PartialBrokerStatement, period: Period) -> EmptyResult {
+    let mut operations: BTreeMap<(Date, String), i32> = BTreeMap::new();
+
+    for (date, description) in get_operations(covering, period) {
+        *operations.entry((date, description)).or_default() += 1;
+    }
+
+    for (date, description) in get_operations(statement, period) {
+        *operations.entry((date, description)).or_default() -= 1;
+    }
+
+    for ((date, description), count) in operations {
+        if count > 0 {
+            return Err!("{} ({}) is missing", description, formatting::format_date(date));
+        } else if count < 0 {
+            return Err!("Got an unexpected {} ({})", description, formatting::format_date(date));
+        }
+    }
+
+    if statement.period.unwrap().last_date() == covering.period.unwrap().last_date() &&
+        statement.open_positions != covering.open_positions {
+        return Err!("Open positions don't match");
+    }
+
+    Ok(())
+}
+
+fn get_operations(statement: &PartialBrokerStatement, period: Period) -> Vec<(Date, String)> {
+    let mut operations = Vec::new();
+    let mut add = |date: Date, description: String| {
+        if period.contains(date) {
+            operations.push((date, description));
+        }
+    };
+
+    for assets in &statement.deposits_and_withdrawals {
+        add(assets.date, format!("{} deposit/withdrawal", assets.cash));
+    }
+
+    for cash_flow in &statement.cash_flows {
+        add(cash_flow.date.date, format!("{} cash flow", cash_flow.amount));
+    }
+
+    for fee in &statement.fees {
+        add(fee.date, format!("{} fee", fee.amount));
+    }
+
+    for interest in &statement.idle_cash_interest {
+        add(interest.date, format!("{} idle cash interest", interest.amount));
+    }
+
+    for withholding in &statement.tax_agent_withholdings {
+        add(withholding.date, format!("{} tax agent withholding for {}", withholding.amount, withholding.year));
+    }
+
+    for trade in &statement.forex_trades {
+        add(trade.conclusion_time.date, format!(
+            "{} -> {} forex trade with {} commission", trade.from, trade.to, trade.commission));
+    }
+
+    for trade in &statement.stock_buys {
+        add(trade.conclusion_time.date, match trade.type_ {
+            StockSource::Trade {volume, commission, ..} => trade_description(
+                "buy", &trade.original_symbol, trade.quantity, volume, commission),
+            StockSource::CorporateAction | StockSource::Grant => format!(
+                "{} {} acquisition", trade.quantity.normalize(), trade.original_symbol),
+        });
+    }
+
+    for trade in &statement.stock_sells {
+        add(trade.conclusion_time.date, match trade.type_ {
+            StockSellType::Trade {volume, commission, ..} => trade_description(
+                "sell", &trade.original_symbol, trade.quantity, volume, commission),
+            StockSellType::CorporateAction | StockSellType::Transfer => format!(
+                "{} {} disposal", trade.quantity.normalize(), trade.original_symbol),
+        });
+    }
+
+    for margin in &statement.variation_margin {
+        add(margin.date, format!("{} variation margin", margin.amount));
+    }
+
+    for (dividend, accruals) in &statement.dividend_accruals {
+        for accrual in accruals.transactions() {
+            add(accrual.date, format!("{} accrual of {}", accrual.cash, dividend.description()));
+        }
+    }
+
+    for (tax, accruals) in &statement.tax_accruals {
+        for accrual in accruals.transactions() {
+            add(accrual.date, format!("{} accrual of {}", accrual.cash, tax.description()));
+        }
+    }
+
+    for coupon in &statement.coupon_payments {
+        add(coupon.date, format!("{} coupon payment for {}", coupon.amount, coupon.issuer));
+    }
+
+    for redemption in &statement.bond_redemptions {
+        add(redemption.date, format!("{} redemption of {}", redemption.amount, redemption.issuer));
+    }
+
+    for grant in &statement.stock_grants {
+        add(grant.date, format!("{} {} grant", grant.quantity.normalize(), grant.symbol));
+    }
+
+    for action in &statement.corporate_actions {
+        add(action.time.date, format!("{} corporate action", action.symbol));
+    }
+
+    operations
+}
+
+fn trade_description(name: &str, symbol: &str, quantity: Decimal, volume: Cash, commission: Cash) -> String {
+    format!("{} {} {} for {} with {} commission", symbol, name, quantity.normalize(), volume, commission)
+}
+
+#[cfg(test)]
+mod tests {
+    use matches::assert_matches;
+    use crate::broker_statement::fees::Fee;
+    use super::*;
+
+    fn statement(first: Date, last: Date, fees: &[(Date, u32)]) -> PartialBrokerStatement {
+        let mut statement = PartialBrokerStatement::new(&[], true);
+        statement.set_period(Period::new(first, last).unwrap()).unwrap();
+        statement.set_has_starting_assets(true).unwrap();
+
+        for &(date, amount) in fees {
+            statement.fees.push(Fee::new(date, Cash::new("USD", amount.into()), None));
+        }
+
+        statement
+    }
+
+    #[test]
+    fn covered_statements() {
+        let statements = deduplicate(vec![
+            statement(date!(2021, 2, 1), date!(2021, 2, 28), &[(date!(2021, 2, 10), 2)]),
+            statement(date!(2021, 1, 1), date!(2021, 1, 31), &[(date!(2021, 1, 10), 1)]),
+            statement(date!(2021, 1, 1), date!(2021, 2, 28), &[(date!(2021, 1, 10), 1), (date!(2021, 2, 10), 2)]),
+            statement(date!(2021, 3, 1), date!(2021, 3, 31), &[]),
+        ]).unwrap();
+
+        let periods: Vec<Period> = statements.iter().map(|statement| statement.period.unwrap()).collect();
+        assert_eq!(periods, vec![
+            Period::new(date!(2021, 1, 1), date!(2021, 2, 28)).unwrap(),
+            Period::new(date!(2021, 3, 1), date!(2021, 3, 31)).unwrap(),
+        ]);
+    }
+
+    #[test]
+    fn mismatching_statements() {
+        let result = deduplicate(vec![
+            statement(date!(2021, 1, 1), date!(2021, 12, 31), &[(date!(2021, 1, 10), 1)]),
+            statement(date!(2021, 1, 1), date!(2021, 1, 31), &[(date!(2021, 1, 10), 2)]),
+        ]);
+        assert_matches!(result, Err(e) if e.to_string().ends_with(" fee (10.01.2021) is missing"));
+    }
+
+    #[test]
+    fn partially_overlapping_statements() {
+        let statements = deduplicate(vec![
+            statement(date!(2021, 1, 15), date!(2021, 2, 15), &[(date!(2021, 1, 20), 2), (date!(2021, 2, 10), 3)]),
+            statement(date!(2021, 1, 1), date!(2021, 1, 31), &[(date!(2021, 1, 10), 1), (date!(2021, 1, 20), 2)]),
+            statement(date!(2021, 1, 20), date!(2021, 1, 25), &[(date!(2021, 1, 20), 2)]),
+        ]).unwrap();
+
+        let periods: Vec<Period> = statements.iter().map(|statement| statement.period.unwrap()).collect();
+        assert_eq!(periods, vec![
+            Period::new(date!(2021, 1, 1), date!(2021, 1, 31)).unwrap(),
+            Period::new(date!(2021, 2, 1), date!(2021, 2, 15)).unwrap(),
+        ]);
+
+        let fees: Vec<Date> = statements.iter()
+            .flat_map(|statement| statement.fees.iter().map(|fee| fee.date))
+            .collect();
+        assert_eq!(fees, vec![date!(2021, 1, 10), date!(2021, 1, 20), date!(2021, 2, 10)]);
+    }
+
+    #[test]
+    fn mismatching_partially_overlapping_statements() {
+        let result = deduplicate(vec![
+            statement(date!(2021, 1, 1), date!(2021, 1, 31), &[(date!(2021, 1, 20), 2)]),
+            statement(date!(2021, 1, 15), date!(2021, 2, 15), &[(date!(2021, 2, 10), 3)]),
+        ]);
+        assert_matches!(result, Err(e) if e.to_string().ends_with(" fee (20.01.2021) is missing"));
+    }
+}
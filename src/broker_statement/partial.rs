@@ -11,8 +11,10 @@ use crate::time::{Date, Period};
 use crate::types::Decimal;
 use crate::util::{DecimalRestrictions, validate_named_decimal};
 
+use super::bonds::{BondRedemption, CouponPayment};
 use super::cash_flows::CashFlow;
 use super::corporate_actions::CorporateAction;
+use super::derivatives::VariationMargin;
 use super::dividends::{DividendId, DividendAccruals};
 use super::fees::Fee;
 use super::grants::StockGrant;
@@ -36,10 +38,14 @@ pub struct PartialBrokerStatement {
     pub forex_trades: Vec<ForexTrade>,
     pub stock_buys: Vec<StockBuy>,
     pub stock_sells: Vec<StockSell>,
+    pub variation_margin: Vec<VariationMargin>,
 
     pub dividend_accruals: HashMap<DividendId, DividendAccruals>,
     pub tax_accruals: HashMap<TaxId, TaxAccruals>,
 
+    pub coupon_payments: Vec<CouponPayment>,
+    pub bond_redemptions: Vec<BondRedemption>,
+
     pub stock_grants: Vec<StockGrant>,
     pub corporate_actions: Vec<CorporateAction>,
 
@@ -47,6 +53,7 @@ pub struct PartialBrokerStatement {
     // statement (current date).
     pub assets: NetAssets,
     pub open_positions: HashMap<String, Decimal>,
+    pub derivative_prices: HashMap<String, Cash>, // Derivatives have no quotes, so take them from statement
     pub instrument_info: InstrumentInfo,
 }
 
@@ -71,10 +78,14 @@ impl PartialBrokerStatement {
             forex_trades: Vec::new(),
             stock_buys: Vec::new(),
             stock_sells: Vec::new(),
+            variation_margin: Vec::new(),
 
             dividend_accruals: HashMap::new(),
             tax_accruals: HashMap::new(),
 
+            coupon_payments: Vec::new(),
+            bond_redemptions: Vec::new(),
+
             stock_grants: Vec::new(),
             corporate_actions: Vec::new(),
 
@@ -87,6 +98,7 @@ impl PartialBrokerStatement {
                 other: None
             },
             open_positions: HashMap::new(),
+            derivative_prices: HashMap::new(),
             instrument_info: InstrumentInfo::new(),
         }
     }
@@ -113,7 +125,7 @@ impl PartialBrokerStatement {
 
     pub fn add_open_position(&mut self, symbol: &str, quantity: Decimal) -> EmptyResult {
         validate_named_decimal(
-            &format!("{} open position", symbol), quantity, DecimalRestrictions::StrictlyPositive)?;
+            &format!("{} open position", symbol), quantity, DecimalRestrictions::NonZero)?;
 
         match self.open_positions.entry(symbol.to_owned()) {
             Entry::Vacant(entry) => entry.insert(quantity),
@@ -27,6 +27,14 @@ impl Payments {
         self.transactions.push(CashAssets::new_from_cash(date, -amount));
     }
 
+    pub fn transactions(&self) -> &[CashAssets] {
+        &self.transactions
+    }
+
+    pub fn retain<F: FnMut(&CashAssets) -> bool>(&mut self, filter: F) {
+        self.transactions.retain(filter);
+    }
+
     pub fn merge(&mut self, other: &Payments) {
         assert_eq!(self.strict, other.strict);
         self.transactions.extend(other.transactions.iter());
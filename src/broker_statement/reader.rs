@@ -7,7 +7,7 @@ use crate::core::{GenericResult, EmptyResult};
 use crate::brokers::Broker;
 use crate::taxes::TaxRemapping;
 
-use super::{bcs, firstrade, ib, open, tinkoff};
+use super::{bcs, firstrade, generic, ib, open, tinkoff};
 use super::PartialBrokerStatement;
 
 bitflags! {
@@ -34,6 +34,7 @@ pub fn read(
     let mut statement_reader = match broker {
         Broker::Bcs => bcs::StatementReader::new(),
         Broker::Firstrade => firstrade::StatementReader::new(),
+        Broker::Generic(jurisdiction) => generic::StatementReader::new(jurisdiction),
         Broker::InteractiveBrokers => ib::StatementReader::new(
             tax_remapping.take().unwrap(), strictness),
         Broker::Open => open::StatementReader::new(),
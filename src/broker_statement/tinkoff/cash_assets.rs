@@ -5,6 +5,7 @@ use chrono::Datelike;
 
 use xls_table_derive::XlsTableRow;
 
+use crate::broker_statement::bonds::{BondRedemption, CouponPayment};
 use crate::broker_statement::fees::Fee;
 use crate::broker_statement::partial::{PartialBrokerStatement, PartialBrokerStatementRc};
 use crate::broker_statement::taxes::TaxWithholding;
@@ -224,6 +225,21 @@ impl CashFlowRow {
                 statement.tax_accruals(self.execution_date, issuer_id, true).add(date, amount);
             },
 
+            "Выплата купонов" => {
+                let description = self.comment.as_deref().unwrap_or_default();
+                let (issuer_name, _) = parse_security_description(description)?;
+                let issuer_id = InstrumentId::Name(issuer_name.to_owned());
+                let amount = check_amount(deposit)?;
+                statement.coupon_payments.push(CouponPayment::new(date, issuer_id, amount)?);
+            },
+            "Погашение облигации" => {
+                let description = self.comment.as_deref().unwrap_or_default();
+                let (issuer_name, quantity) = parse_security_description(description)?;
+                let issuer_id = InstrumentId::Name(issuer_name.to_owned());
+                let amount = check_amount(deposit)?;
+                statement.bond_redemptions.push(BondRedemption::new(date, issuer_id, quantity, amount)?);
+            },
+
             "Налог" => {
                 let year = date.year();
                 let amount = check_amount(withdrawal)?;
@@ -239,15 +255,24 @@ impl CashFlowRow {
 }
 
 fn parse_dividend_description(description: &str) -> GenericResult<&str> {
+    Ok(parse_security_description(description)?.0)
+}
+
+// Parses "{name}/ {quantity} шт." descriptions
+fn parse_security_description(description: &str) -> GenericResult<(&str, Option<Decimal>)> {
     let mut parts = description.rsplitn(2, '/');
-    parts.next();
+    let quantity = parts.next().unwrap_or_default().trim();
 
     let issuer = parts.next().unwrap_or_default().trim();
     if issuer.is_empty() {
-        return Err!("Unexpected dividend description: {:?}", description);
+        return Err!("Unexpected security operation description: {:?}", description);
     }
 
-    Ok(issuer)
+    let quantity = quantity.strip_suffix("шт.")
+        .and_then(|quantity| quantity.trim().parse::<Decimal>().ok())
+        .filter(|quantity| quantity.is_sign_positive() && !quantity.is_zero());
+
+    Ok((issuer, quantity))
 }
 
 #[cfg(test)]
@@ -261,4 +286,12 @@ mod tests {
             "Ростел -ап",
         );
     }
+
+    #[test]
+    fn bond_redemption_parsing() {
+        assert_eq!(
+            parse_security_description("ОФЗ 26207/ 10 шт.").unwrap(),
+            ("ОФЗ 26207", Some(dec!(10))),
+        );
+    }
 }
\ No newline at end of file
@@ -273,7 +273,8 @@ mod tests {
         BrokerStatement::read(
             broker, &format!("testdata/tinkoff/{}", name),
             &Default::default(), &Default::default(), &Default::default(),
-            TaxRemapping::new(), &portfolio.corporate_actions, ReadingStrictness::all(),
+            TaxRemapping::new(), &portfolio.corporate_actions, None,
+            &[], &Default::default(), ReadingStrictness::all(),
         ).unwrap()
     }
 }
\ No newline at end of file
@@ -16,7 +16,7 @@ use crate::forex::parse_forex_code;
 use crate::formatting::format_date;
 use crate::time::{Date, Time, DateTime};
 use crate::types::Decimal;
-use crate::util::DecimalRestrictions;
+use crate::util::{self, DecimalRestrictions};
 use crate::xls::{self, XlsStatementParser, SectionParser, SheetReader, Cell, SkipCell, TableReader};
 
 use super::common::{
@@ -97,7 +97,7 @@ struct TradeRow {
     #[column(name="Торговая площадка")]
     exchange: String,
     #[column(name="Режим торгов")]
-    _6: SkipCell,
+    board: Option<String>,
     #[column(name="Вид сделки")]
     operation: String,
     #[column(name="Сокращенное наименование актива")]
@@ -162,9 +162,7 @@ impl TableReader for TradeRow {
 
 impl TradeRow {
     fn parse(self, statement: &mut PartialBrokerStatement) -> EmptyResult {
-        if !self.accumulated_coupon_income.is_zero() {
-            return Err!("Bonds aren't supported yet");
-        } else if self.leverage_rate.is_some() {
+        if self.leverage_rate.is_some() {
             return Err!("Leverage is not supported yet");
         }
 
@@ -173,12 +171,29 @@ impl TradeRow {
             return Err!("Invalid {} trade quantity: {:?}", self.symbol, self.quantity);
         }
 
-        let price = parse_cash(
-            &self.price_currency, self.price, DecimalRestrictions::StrictlyPositive)?;
-
         let volume = parse_cash(
             &self.settlement_currency, self.volume, DecimalRestrictions::StrictlyPositive)?;
-        debug_assert_eq!(volume, (price * self.quantity).round());
+
+        util::validate_named_decimal(
+            "accrued coupon interest", self.accumulated_coupon_income,
+            DecimalRestrictions::PositiveOrZero)?;
+
+        // Bond prices are specified in percents of face value and trade volume includes accrued
+        // coupon interest which is a part of purchase cost / sell revenue, so we use full price
+        // calculated from the trade volume for them.
+        let price = if self.board.as_deref().map(is_bond_board).unwrap_or(false) {
+            volume / Decimal::from(self.quantity)
+        } else if self.accumulated_coupon_income.is_zero() {
+            let price = parse_cash(
+                &self.price_currency, self.price, DecimalRestrictions::StrictlyPositive)?;
+            debug_assert_eq!(volume, (price * self.quantity).round());
+            price
+        } else {
+            return Err!(
+                "Got accrued coupon interest for {} trade on {} board which is not a bond board",
+                self.symbol, self.board.as_deref().map(|board| format!("{:?}", board))
+                    .unwrap_or_else(|| s!("an unknown")));
+        };
 
         let commission = match self.commission_currency {
             Some(currency) => {
@@ -239,4 +254,25 @@ impl TradeRow {
 fn parse_trade_id(cell: &Cell) -> GenericResult<u64> {
     let value = xls::get_string_cell(cell)?;
     Ok(value.parse().map_err(|_| format!("Got an unexpected trade ID: {:?}", value))?)
+}
+
+// MOEX bond trading boards (government, corporate and foreign currency bonds)
+fn is_bond_board(board: &str) -> bool {
+    matches!(board, "TQOB" | "TQCB" | "TQOD" | "TQOE" | "TQOY" | "TQIR" | "TQRD")
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use super::*;
+
+    #[rstest(board, bond,
+        case("TQBR", false),
+        case("TQTF", false),
+        case("TQOB", true),
+        case("TQCB", true),
+    )]
+    fn bond_board_detection(board: &str, bond: bool) {
+        assert_eq!(is_bond_board(board), bond);
+    }
 }
\ No newline at end of file
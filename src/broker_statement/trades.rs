@@ -51,6 +51,10 @@ pub struct StockBuy {
     pub execution_date: Date,
     pub out_of_order_execution: bool,
 
+    // Lots transferred from another broker keep their original purchase dates
+    pub transfer_time: Option<DateOptTime>,
+
+    pub emulation: bool,
     sold: Decimal,
 }
 
@@ -66,7 +70,7 @@ impl StockBuy {
             symbol: symbol.to_owned(), original_symbol: symbol.to_owned(),
             quantity, type_: StockSource::Trade {price, volume, commission}, cost,
             conclusion_time, execution_date, out_of_order_execution: false,
-            sold: dec!(0),
+            transfer_time: None, emulation: false, sold: dec!(0),
         }
     }
 
@@ -75,7 +79,7 @@ impl StockBuy {
             symbol: symbol.to_owned(), original_symbol: symbol.to_owned(),
             quantity, type_: StockSource::Grant, cost: PurchaseTotalCost::new(),
             out_of_order_execution: true, conclusion_time: date.into(), execution_date: date,
-            sold: dec!(0),
+            transfer_time: None, emulation: false, sold: dec!(0),
         }
     }
 
@@ -86,10 +90,37 @@ impl StockBuy {
         StockBuy {
             symbol: symbol.to_owned(), original_symbol: symbol.to_owned(),
             quantity, type_: StockSource::CorporateAction, cost, out_of_order_execution: true,
-            conclusion_time, execution_date, sold: dec!(0),
+            conclusion_time, execution_date, transfer_time: None, emulation: false, sold: dec!(0),
+        }
+    }
+
+    // Creates a lot transferred from another broker with preserved purchase dates and cost
+    pub fn new_transfer(symbol: &str, source: &StockSellSource, transfer_time: DateOptTime) -> StockBuy {
+        let type_ = match source.type_ {
+            StockSource::Trade {price, volume, commission} => StockSource::Trade {
+                price: price / source.multiplier, volume, commission,
+            },
+            StockSource::CorporateAction | StockSource::Grant => source.type_,
+        };
+
+        StockBuy {
+            symbol: symbol.to_owned(), original_symbol: symbol.to_owned(),
+            quantity: source.quantity * source.multiplier, type_, cost: source.cost.clone(),
+            conclusion_time: source.conclusion_time, execution_date: source.execution_date,
+            out_of_order_execution: true, transfer_time: Some(transfer_time),
+            emulation: false, sold: dec!(0),
         }
     }
 
+    // Time when the lot has appeared in the portfolio
+    pub fn statement_time(&self) -> DateOptTime {
+        self.transfer_time.unwrap_or(self.conclusion_time)
+    }
+
+    pub fn total_cost(&self, currency: &str, converter: &CurrencyConverter) -> GenericResult<Cash> {
+        self.cost.calculate(None, currency, converter)
+    }
+
     pub fn is_sold(&self) -> bool {
         self.sold == self.quantity
     }
@@ -143,6 +174,10 @@ pub enum StockSellType {
     // lead to any taxes:
     // * Emulated sell to convert position during stock split
     CorporateAction,
+
+    // Outgoing transfer to another broker which closes FIFO lots without any taxes. The lots are
+    // moved to the receiving portfolio with their original purchase dates and cost.
+    Transfer,
 }
 
 #[derive(Clone)]
@@ -158,6 +193,7 @@ pub struct StockSell {
     pub out_of_order_execution: bool,
 
     pub emulation: bool,
+    short: bool,
     sources: Vec<StockSellSource>,
 }
 
@@ -170,7 +206,7 @@ impl StockSell {
             symbol: symbol.to_owned(), original_symbol: symbol.to_owned(),
             quantity, type_: StockSellType::Trade {price, volume, commission},
             conclusion_time, execution_date, out_of_order_execution: false,
-            emulation, sources: Vec::new(),
+            emulation, short: false, sources: Vec::new(),
         }
     }
 
@@ -181,10 +217,31 @@ impl StockSell {
             symbol: symbol.to_owned(), original_symbol: symbol.to_owned(),
             quantity, type_: StockSellType::CorporateAction,
             conclusion_time, execution_date, out_of_order_execution: true,
-            emulation: false, sources: Vec::new(),
+            emulation: false, short: false, sources: Vec::new(),
         }
     }
 
+    pub fn new_transfer(symbol: &str, quantity: Decimal, date: Date) -> StockSell {
+        StockSell {
+            symbol: symbol.to_owned(), original_symbol: symbol.to_owned(),
+            quantity, type_: StockSellType::Transfer,
+            conclusion_time: date.into(), execution_date: date, out_of_order_execution: true,
+            emulation: false, short: false, sources: Vec::new(),
+        }
+    }
+
+    pub fn sources(&self) -> &[StockSellSource] {
+        &self.sources
+    }
+
+    pub fn total_cost(&self, currency: &str, converter: &CurrencyConverter) -> GenericResult<Cash> {
+        let mut total_cost = Cash::zero(currency);
+        for source in &self.sources {
+            total_cost += source.cost.calculate(None, currency, converter)?;
+        }
+        Ok(total_cost)
+    }
+
     pub fn is_processed(&self) -> bool {
         !self.sources.is_empty()
     }
@@ -200,6 +257,59 @@ impl StockSell {
         self.sources = sources;
     }
 
+    // Short sells are processed against the following buys which close the position. Each short sell
+    // is closed by exactly one buy (the sell is split if needed), so it has a single realization date.
+    pub fn process_short(&mut self, source: StockSellSource, emulation: bool) {
+        assert!(!self.is_processed());
+        assert_eq!(source.multiplier * source.quantity, self.quantity);
+        self.short = true;
+        self.emulation |= emulation;
+        self.sources.push(source);
+    }
+
+    pub fn is_short(&self) -> bool {
+        self.short
+    }
+
+    // Profit from short selling is realized when the position is closed
+    pub fn realization_date(&self) -> Date {
+        if self.short {
+            self.sources.iter().map(|source| source.execution_date).max().unwrap()
+        } else {
+            self.execution_date
+        }
+    }
+
+    pub fn split(&mut self, quantity: Decimal) -> StockSell {
+        assert!(!self.is_processed());
+        assert!(quantity < self.quantity);
+
+        let type_ = match self.type_ {
+            StockSellType::Trade {price, volume, commission} => {
+                let split_volume = price * quantity;
+                let split_commission = commission / self.quantity * quantity;
+
+                self.type_ = StockSellType::Trade {
+                    price,
+                    volume: volume - split_volume,
+                    commission: commission - split_commission,
+                };
+
+                StockSellType::Trade {price, volume: split_volume, commission: split_commission}
+            },
+            StockSellType::CorporateAction | StockSellType::Transfer => self.type_,
+        };
+
+        self.quantity -= quantity;
+
+        StockSell {
+            symbol: self.symbol.clone(), original_symbol: self.original_symbol.clone(),
+            quantity, type_, conclusion_time: self.conclusion_time, execution_date: self.execution_date,
+            out_of_order_execution: self.out_of_order_execution,
+            emulation: self.emulation, short: false, sources: Vec::new(),
+        }
+    }
+
     pub fn calculate(
         &self, country: &Country, tax_year: i32, tax_exemptions: &[TaxExemption],
         converter: &CurrencyConverter,
@@ -241,6 +351,8 @@ impl StockSell {
 
             for tax_exemption in tax_exemptions {
                 match tax_exemption {
+                    // There is no ownership in case of short selling
+                    TaxExemption::LongTermOwnership if self.short => {},
                     TaxExemption::LongTermOwnership => {
                         if let Some(years) = taxes::long_term_ownership::is_deductible(source.execution_date, self.execution_date) {
                             let source_local_revenue = local_execution(price * source_quantity)?;
@@ -306,7 +418,7 @@ impl StockSell {
         assert!(!tax_deduction.is_negative());
 
         Ok(SellDetails {
-            execution_date: self.execution_date,
+            execution_date: self.realization_date(),
 
             revenue,
             local_revenue,
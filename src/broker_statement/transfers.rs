@@ -0,0 +1,201 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Deserialize;
+
+use crate::core::EmptyResult;
+use crate::formatting::format_date;
+use crate::time::{Date, deserialize_date};
+use crate::types::Decimal;
+use crate::util::{self, DecimalRestrictions};
+
+use super::BrokerStatement;
+use super::trades::{StockBuy, StockSell, StockSellType};
+
+/// Security transfer between brokers (ACATS, depository transfer, etc.).
+///
+/// Outgoing transfer closes FIFO lots without any taxes. Incoming transfer takes the closed lots
+/// from the outgoing transfer of the source portfolio with their original purchase dates and cost,
+/// so FIFO, LTO and taxes are calculated as if the securities have never been moved.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SecurityTransfer {
+    #[serde(deserialize_with = "deserialize_date")]
+    pub date: Date,
+    pub symbol: String,
+    pub quantity: Decimal,
+
+    // Source portfolio name for incoming transfers
+    pub from: Option<String>,
+
+    // Destination for outgoing transfers. It's informational only, so it may be any broker - not
+    // necessarily the one which is configured as a portfolio.
+    pub to: Option<String>,
+}
+
+impl SecurityTransfer {
+    pub fn validate(&self) -> EmptyResult {
+        util::validate_named_decimal(
+            &format!("{} transfer quantity", self.symbol), self.quantity,
+            DecimalRestrictions::StrictlyPositive)?;
+
+        if self.from.is_some() == self.to.is_some() {
+            return Err!(
+                "Invalid {} transfer from {}: either source or destination must be specified",
+                self.symbol, format_date(self.date));
+        }
+
+        Ok(())
+    }
+}
+
+pub fn process_security_transfers(
+    statement: &mut BrokerStatement, transfers: &[SecurityTransfer],
+    sources: &HashMap<String, BrokerStatement>,
+) -> EmptyResult {
+    let mut matched_transfers: HashMap<&str, HashSet<usize>> = HashMap::new();
+
+    for transfer in transfers {
+        let source_name = match transfer.from {
+            Some(ref portfolio) => portfolio,
+            None => {
+                statement.stock_sells.push(StockSell::new_transfer(
+                    &transfer.symbol, transfer.quantity, transfer.date));
+                continue;
+            },
+        };
+
+        let source = sources.get(source_name).ok_or_else(|| format!(
+            "Unable to find {:?} portfolio which is the source of {} transfer",
+            source_name, transfer.symbol))?;
+
+        let matched = matched_transfers.entry(source_name).or_default();
+        let index = find_outgoing_transfer(source, transfer, matched).ok_or_else(|| format!(
+            "Unable to find outgoing transfer of {} {} in {:?} portfolio matching the incoming transfer from {}",
+            transfer.quantity, transfer.symbol, source_name, format_date(transfer.date)))?;
+        matched.insert(index);
+
+        for lot in source.stock_sells[index].sources() {
+            statement.stock_buys.push(StockBuy::new_transfer(&transfer.symbol, lot, transfer.date.into()));
+        }
+    }
+
+    Ok(())
+}
+
+// Incoming transfer is matched with the latest preceding outgoing transfer of the same quantity
+fn find_outgoing_transfer(
+    source: &BrokerStatement, transfer: &SecurityTransfer, matched: &HashSet<usize>,
+) -> Option<usize> {
+    source.stock_sells.iter().enumerate().rev().find(|(index, sell)| {
+        matches!(sell.type_, StockSellType::Transfer) &&
+            sell.symbol == transfer.symbol && sell.quantity == transfer.quantity &&
+            sell.conclusion_time.date <= transfer.date &&
+            !matched.contains(index)
+    }).map(|(index, _)| index)
+}
+
+pub fn get_transfer_sources(transfers: &[SecurityTransfer]) -> Vec<&str> {
+    let mut sources: Vec<&str> = transfers.iter()
+        .filter_map(|transfer| transfer.from.as_deref())
+        .collect();
+
+    sources.sort_unstable();
+    sources.dedup();
+
+    sources
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::brokers::Broker;
+    use crate::broker_statement::StockSource;
+    use crate::currency::Cash;
+    use crate::time::Period;
+    use super::*;
+
+    #[test]
+    fn matched_transfer() {
+        let source = mock_source(&[(date!(2020, 1, 10), 10)], 10);
+
+        let mut statement = mock_statement();
+        process_security_transfers(&mut statement, &[incoming_transfer(10)], &hashmap!{
+            s!("source") => source,
+        }).unwrap();
+
+        assert_eq!(statement.stock_buys.len(), 1);
+        let buy = &statement.stock_buys[0];
+        assert_eq!(buy.quantity, dec!(10));
+        assert_eq!(buy.conclusion_time.date, date!(2020, 1, 10));
+        assert_eq!(buy.transfer_time.unwrap().date, date!(2020, 6, 2));
+        assert!(matches!(buy.type_, StockSource::Trade {price, ..} if price == Cash::new("USD", dec!(100))));
+    }
+
+    #[test]
+    fn unmatched_transfer() {
+        let source = mock_source(&[(date!(2020, 1, 10), 10)], 5);
+
+        let mut statement = mock_statement();
+        let error = process_security_transfers(&mut statement, &[incoming_transfer(10)], &hashmap!{
+            s!("source") => source,
+        }).unwrap_err().to_string();
+
+        assert!(error.starts_with("Unable to find outgoing transfer"), "{}", error);
+        assert!(statement.stock_buys.is_empty());
+    }
+
+    #[test]
+    fn partial_transfer() {
+        let source = mock_source(&[(date!(2020, 1, 10), 6), (date!(2020, 2, 10), 4)], 8);
+        assert_eq!(source.stock_buys.iter().filter(|buy| !buy.is_sold()).count(), 1);
+
+        let mut statement = mock_statement();
+        process_security_transfers(&mut statement, &[incoming_transfer(8)], &hashmap!{
+            s!("source") => source,
+        }).unwrap();
+
+        let lots: Vec<_> = statement.stock_buys.iter()
+            .map(|buy| (buy.conclusion_time.date, buy.quantity))
+            .collect();
+
+        assert_eq!(lots, vec![
+            (date!(2020, 1, 10), dec!(6)),
+            (date!(2020, 2, 10), dec!(2)),
+        ]);
+    }
+
+    fn mock_source(buys: &[(Date, u32)], transferred: u32) -> BrokerStatement {
+        let mut statement = mock_statement();
+
+        for &(date, quantity) in buys {
+            let price = Cash::new("USD", dec!(100));
+            let volume = price * Decimal::from(quantity);
+            statement.stock_buys.push(StockBuy::new_trade(
+                "AAPL", quantity.into(), price, volume, Cash::zero("USD"), date.into(), date));
+        }
+
+        let total: u32 = buys.iter().map(|&(_, quantity)| quantity).sum();
+        if total != transferred {
+            statement.open_positions.insert(s!("AAPL"), Decimal::from(total - transferred));
+        }
+
+        statement.stock_sells.push(StockSell::new_transfer("AAPL", transferred.into(), date!(2020, 6, 1)));
+        statement.process_trades(None).unwrap();
+
+        statement
+    }
+
+    fn mock_statement() -> BrokerStatement {
+        BrokerStatement::mock(
+            Broker::InteractiveBrokers, Period::new(date!(2020, 1, 1), date!(2020, 12, 31)).unwrap())
+    }
+
+    fn incoming_transfer(quantity: u32) -> SecurityTransfer {
+        SecurityTransfer {
+            date: date!(2020, 6, 2),
+            symbol: s!("AAPL"),
+            quantity: quantity.into(),
+            from: Some(s!("source")),
+            to: None,
+        }
+    }
+}
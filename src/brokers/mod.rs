@@ -14,11 +14,14 @@ use crate::currency::CashAssets;
 use crate::exchanges::Exchange;
 use crate::localities::Jurisdiction;
 use crate::types::Decimal;
+use crate::util;
 
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Clone, Copy)]
 pub enum Broker {
     Bcs,
     Firstrade,
+    // Jurisdiction is taken from portfolio configuration
+    Generic(Jurisdiction),
     InteractiveBrokers,
     Open,
     Tinkoff,
@@ -32,13 +35,18 @@ impl Broker {
                 "{} broker configuration is not set in the configuration file", self.brief_name()))?
             .clone();
 
-        let statements_merging_strategy = match self {
+        let mut statements_merging_strategy = match self {
             Broker::Bcs => StatementsMergingStrategy::SparseSingleDaysLastMonth(9),
             Broker::InteractiveBrokers => StatementsMergingStrategy::SparseOnHolidays(1),
             Broker::Open => StatementsMergingStrategy::SparseSingleDaysLastMonth(0),
             _ => StatementsMergingStrategy::ContinuousOnly,
         };
 
+        if config.overlapping_statements {
+            statements_merging_strategy = StatementsMergingStrategy::Overlapping(
+                Box::new(statements_merging_strategy));
+        }
+
         Ok(BrokerInfo {
             type_: self,
             name: self.name(),
@@ -48,6 +56,7 @@ impl Broker {
             commission_spec: self.get_commission_spec(plan)?,
             allow_future_fees: matches!(self, Broker::Tinkoff),
             fractional_shares_trading: matches!(self, Broker::InteractiveBrokers),
+            short_selling: matches!(self, Broker::InteractiveBrokers),
             statements_merging_strategy: statements_merging_strategy,
         })
     }
@@ -56,6 +65,7 @@ impl Broker {
         match self {
             Broker::Bcs => "bcs",
             Broker::Firstrade => "firstrade",
+            Broker::Generic(_) => "generic",
             Broker::InteractiveBrokers => "interactive-brokers",
             Broker::Open => "open",
             Broker::Tinkoff => "tinkoff",
@@ -66,6 +76,7 @@ impl Broker {
         match self {
             Broker::Bcs => "ООО «Компания БКС»",
             Broker::Firstrade => "Firstrade Securities Inc.",
+            Broker::Generic(_) => "Брокер",
             Broker::InteractiveBrokers => "Interactive Brokers LLC",
             Broker::Open => "АО «Открытие Брокер»",
             Broker::Tinkoff => "АО «Тинькофф Банк»",
@@ -76,6 +87,7 @@ impl Broker {
         match self {
             Broker::Bcs => "БКС",
             Broker::Firstrade => "Firstrade",
+            Broker::Generic(_) => "Брокер",
             Broker::InteractiveBrokers => "Interactive Brokers",
             Broker::Open => "Открытие",
             Broker::Tinkoff => "Тинькофф",
@@ -86,6 +98,7 @@ impl Broker {
         match self {
             Broker::Bcs | Broker::Open | Broker::Tinkoff => Jurisdiction::Russia,
             Broker::Firstrade | Broker::InteractiveBrokers => Jurisdiction::Usa,
+            Broker::Generic(jurisdiction) => jurisdiction,
         }
     }
 
@@ -93,6 +106,7 @@ impl Broker {
         match self {
             Broker::Bcs => &config.bcs,
             Broker::Firstrade => &config.firstrade,
+            Broker::Generic(_) => &config.generic,
             Broker::InteractiveBrokers => &config.interactive_brokers,
             Broker::Open => &config.open_broker,
             Broker::Tinkoff => &config.tinkoff,
@@ -109,6 +123,8 @@ impl Broker {
                 "Профессиональный" => plans::bcs::professional as PlanFn,
             }),
             Broker::Firstrade => (plans::firstrade::free, btreemap!{}),
+            Broker::Generic(Jurisdiction::Russia) => (plans::generic::free_russia, btreemap!{}),
+            Broker::Generic(Jurisdiction::Usa) => (plans::generic::free_usa, btreemap!{}),
             Broker::InteractiveBrokers => (plans::ib::fixed, btreemap!{
                 "Fixed" => plans::ib::fixed as PlanFn,
             }),
@@ -144,12 +160,14 @@ impl<'de> Deserialize<'de> for Broker {
         Ok(match value.as_str() {
             "bcs" => Broker::Bcs,
             "firstrade" => Broker::Firstrade,
+            // The actual jurisdiction is set on configuration loading
+            "generic" => Broker::Generic(Jurisdiction::Russia),
             "interactive-brokers" => Broker::InteractiveBrokers,
             "open-broker" => Broker::Open,
             "tinkoff" => Broker::Tinkoff,
 
             _ => return Err(D::Error::unknown_variant(&value, &[
-                "bcs", "firstrade", "interactive-brokers", "open-broker", "tinkoff",
+                "bcs", "firstrade", "generic", "interactive-brokers", "open-broker", "tinkoff",
             ])),
         })
     }
@@ -165,6 +183,7 @@ pub struct BrokerInfo {
     pub commission_spec: CommissionSpec,
     pub allow_future_fees: bool,
     pub fractional_shares_trading: bool,
+    pub short_selling: bool,
     pub statements_merging_strategy: StatementsMergingStrategy,
 }
 
@@ -182,10 +201,21 @@ impl BrokerInfo {
         Ok(commission_spec.fixed_amount)
     }
 
+    /// Returns commission for currency conversion of the specified volume (in portfolio currency)
+    pub fn get_forex_commission(&self, volume: Decimal) -> Decimal {
+        let spec = match self.config.forex_commission {
+            Some(ref spec) => spec,
+            None => return dec!(0),
+        };
+
+        let commission = volume * spec.percent / dec!(100);
+        util::round(std::cmp::max(commission, spec.minimum), 2)
+    }
+
     pub fn exchanges(&self) -> Vec<Exchange> {
-        match self.type_ {
-            Broker::Bcs | Broker::Open | Broker::Tinkoff => vec![Exchange::Moex, Exchange::Spb],
-            Broker::Firstrade | Broker::InteractiveBrokers => vec![Exchange::Us],
+        match self.type_.jurisdiction() {
+            Jurisdiction::Russia => vec![Exchange::Moex, Exchange::Spb],
+            Jurisdiction::Usa => vec![Exchange::Us],
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,12 @@
+use crate::commissions::{CommissionSpec, CommissionSpecBuilder};
+
+// Actual commissions are specified in the statements, so the plans are used only for trade
+// simulation and differ only in currency of the broker's jurisdiction.
+
+pub fn free_russia() -> CommissionSpec {
+    CommissionSpecBuilder::new("RUB").build()
+}
+
+pub fn free_usa() -> CommissionSpec {
+    CommissionSpecBuilder::new("USD").build()
+}
@@ -1,5 +1,6 @@
 pub mod bcs;
 pub mod firstrade;
+pub mod generic;
 pub mod ib;
 pub mod open;
 pub mod tinkoff;
\ No newline at end of file
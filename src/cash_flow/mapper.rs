@@ -1,8 +1,8 @@
 use std::cmp::Ordering;
 
 use crate::broker_statement::{
-    BrokerStatement, ForexTrade, StockBuy, StockSource, StockSell, StockSellType, Dividend, Fee,
-    IdleCashInterest, TaxWithholding, CashFlow as CashFlowDetails, CashFlowType};
+    BrokerStatement, Coupon, ForexTrade, StockBuy, StockSource, StockSell, StockSellType, Dividend, Fee,
+    IdleCashInterest, TaxWithholding, VariationMargin, CashFlow as CashFlowDetails, CashFlowType};
 use crate::currency::{Cash, CashAssets};
 use crate::formatting;
 use crate::time::DateOptTime;
@@ -37,6 +37,10 @@ impl CashFlowMapper {
             self.dividend(statement, dividend);
         }
 
+        for coupon in &statement.coupons {
+            self.coupon(&statement.instrument_info.get_name(&coupon.original_symbol), coupon);
+        }
+
         for cash_flow in &statement.cash_flows {
             self.cash_flow(statement, cash_flow);
         }
@@ -53,6 +57,10 @@ impl CashFlowMapper {
             self.stock_buy(&statement.instrument_info.get_name(&trade.original_symbol), trade);
         }
 
+        for margin in &statement.variation_margin {
+            self.variation_margin(margin);
+        }
+
         for fee in &statement.fees {
             self.fee(fee);
         }
@@ -69,6 +77,10 @@ impl CashFlowMapper {
         self.add_static(fee.date.into(), Operation::Fee, -fee.amount, fee.local_description());
     }
 
+    fn variation_margin(&mut self, margin: &VariationMargin) {
+        self.add_static(margin.date.into(), Operation::VariationMargin, margin.amount, "Вариационная маржа");
+    }
+
     fn deposit_or_withdrawal(&mut self, assets: &CashAssets) {
         let (operation, description) = if assets.cash.is_positive() {
             (Operation::Deposit, "Ввод денежных средств")
@@ -84,6 +96,11 @@ impl CashFlowMapper {
             "Проценты на остаток по счету");
     }
 
+    fn coupon(&mut self, name: &str, coupon: &Coupon) {
+        let description = format!("Купон по {}", name);
+        self.add(coupon.date.into(), Operation::Coupon, coupon.amount, description);
+    }
+
     fn forex_trade(&mut self, trade: &ForexTrade) {
         let description = format!("Конвертация {} -> {}", trade.from, trade.to);
         let cash_flow = self.add(trade.conclusion_time, Operation::ForexTrade, -trade.from, description);
@@ -96,6 +113,11 @@ impl CashFlowMapper {
     }
 
     fn stock_buy(&mut self, name: &str, trade: &StockBuy) {
+        // Securities transferred from another broker don't affect cash flow
+        if trade.transfer_time.is_some() {
+            return;
+        }
+
         match trade.type_ {
             StockSource::Trade {volume, commission, ..} => {
                 let description = format!("Покупка {} {}", trade.quantity, name);
@@ -121,7 +143,7 @@ impl CashFlowMapper {
                     self.add(trade.conclusion_time, Operation::Commission, -commission, description);
                 };
             },
-            StockSellType::CorporateAction => {},
+            StockSellType::CorporateAction | StockSellType::Transfer => {},
         }
     }
 
@@ -211,11 +233,13 @@ pub enum Operation {
     Deposit,
     Interest,
     Dividend,
+    Coupon,
 
     ForexTrade,
     SellTrade,
     BuyTrade,
     RepoDeal,
+    VariationMargin,
     Commission,
 
     Fee,
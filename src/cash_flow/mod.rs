@@ -20,17 +20,14 @@ use crate::time::{Date, Period};
 use self::calculator::CashFlowSummary;
 use self::mapper::{CashFlow, Operation};
 
+pub(crate) use self::mapper::map_broker_statement_to_cash_flow;
+
 pub fn generate_cash_flow_report(config: &Config, portfolio_name: &str, year: Option<i32>) -> GenericResult<TelemetryRecordBuilder> {
     let portfolio = config.get_portfolio(portfolio_name)?;
-    let broker = portfolio.broker.get_info(config, portfolio.plan.as_ref())?;
-
     let database = db::connect(&config.db_path)?;
     let converter = CurrencyConverter::new(database, None, year.is_some());
 
-    let statement = BrokerStatement::read(
-        broker, portfolio.statements_path()?, &portfolio.symbol_remapping, &portfolio.instrument_internal_ids,
-        &portfolio.instrument_names, portfolio.get_tax_remapping()?, &portfolio.corporate_actions,
-        ReadingStrictness::CASH_FLOW_DATES)?;
+    let statement = BrokerStatement::read_portfolio(config, portfolio, ReadingStrictness::CASH_FLOW_DATES)?;
 
     let period = match year {
         Some(year) => statement.check_period_against_tax_year(year)?,
@@ -3,13 +3,13 @@ use std::fs::File;
 use std::io::Read;
 use std::str::FromStr;
 
-use chrono::Duration;
+use chrono::{Datelike, Duration};
 use serde::Deserialize;
 use serde::de::{Deserializer, IgnoredAny, Error};
 use serde_yaml::Value;
 
 use crate::analysis::config::PerformanceMergingConfig;
-use crate::broker_statement::CorporateAction;
+use crate::broker_statement::{CorporateAction, OpeningBalance, SecurityTransfer};
 use crate::brokers::Broker;
 use crate::core::{GenericResult, EmptyResult};
 use crate::formatting;
@@ -39,11 +39,17 @@ pub struct Config {
 
     #[serde(default)]
     pub portfolios: Vec<PortfolioConfig>,
+    #[serde(default)]
+    pub households: Vec<HouseholdConfig>,
     pub brokers: Option<BrokersConfig>,
     #[serde(default)]
     pub tax_rates: TaxRates,
     #[serde(default)]
     pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub risk_free_rates: HashMap<String, Decimal>,
+    #[serde(default)]
+    pub instrument_compositions: HashMap<String, InstrumentCompositionConfig>,
 
     pub alphavantage: Option<AlphaVantageConfig>,
     pub fcsapi: Option<FcsApiConfig>,
@@ -68,9 +74,12 @@ impl Config {
             notify_deposit_closing_days: None,
 
             portfolios: Vec::new(),
+            households: Vec::new(),
             brokers: Some(BrokersConfig::mock()),
             tax_rates: Default::default(),
             metrics: Default::default(),
+            risk_free_rates: HashMap::new(),
+            instrument_compositions: HashMap::new(),
 
             alphavantage: None,
             fcsapi: None,
@@ -100,11 +109,31 @@ impl Config {
                 portfolio.statements = portfolio.statements.as_ref().map(|path|
                     shellexpand::tilde(path).to_string());
 
+                portfolio.set_jurisdiction().map_err(|e| format!(
+                    "{:?} portfolio: {}", portfolio.name, e))?;
+
                 portfolio.validate().map_err(|e| format!(
                     "{:?} portfolio: {}", portfolio.name, e))?;
             }
         }
 
+        {
+            let mut household_names = HashSet::new();
+
+            for household in &config.households {
+                if !household_names.insert(&household.name) {
+                    return Err!("Duplicate household name: {:?}", household.name);
+                }
+
+                household.validate(&config).map_err(|e| format!(
+                    "{:?} household: {}", household.name, e))?;
+            }
+        }
+
+        for (symbol, composition) in &config.instrument_compositions {
+            composition.validate().map_err(|e| format!("{} composition: {}", symbol, e))?;
+        }
+
         for &tax_rates in &[
             &config.tax_rates.trading,
             &config.tax_rates.dividends,
@@ -119,6 +148,12 @@ impl Config {
             }
         }
 
+        for (currency, &rate) in &config.risk_free_rates {
+            if rate < dec!(0) || rate > dec!(100) {
+                return Err!("Invalid {} risk-free rate: {}", currency, rate);
+            }
+        }
+
         Ok(config)
     }
 
@@ -136,6 +171,16 @@ impl Config {
         Err!("{:?} portfolio is not defined in the configuration file", name)
     }
 
+    pub fn get_household(&self, name: &str) -> GenericResult<&HouseholdConfig> {
+        for household in &self.households {
+            if household.name == name {
+                return Ok(household)
+            }
+        }
+
+        Err!("{:?} household is not defined in the configuration file", name)
+    }
+
     fn read(path: &str) -> GenericResult<Config> {
         let mut data = Vec::new();
         File::open(path)?.read_to_end(&mut data)?;
@@ -210,6 +255,8 @@ pub struct PortfolioConfig {
     pub name: String,
     pub broker: Broker,
     pub plan: Option<String>,
+    // Is required for generic broker
+    jurisdiction: Option<Jurisdiction>,
 
     pub statements: Option<String>,
     #[serde(default)]
@@ -222,15 +269,22 @@ pub struct PortfolioConfig {
     tax_remapping: Vec<TaxRemappingConfig>,
     #[serde(default)]
     pub corporate_actions: Vec<CorporateAction>,
+    pub opening_balance: Option<OpeningBalance>,
+    #[serde(default)]
+    pub security_transfers: Vec<SecurityTransfer>,
 
     pub currency: Option<String>,
     pub min_trade_volume: Option<Decimal>,
     pub min_cash_assets: Option<Decimal>,
     pub restrict_buying: Option<bool>,
     pub restrict_selling: Option<bool>,
+    pub max_sell_tax: Option<Decimal>,
+    // Is used for age-based asset weights
+    pub birth_year: Option<i32>,
 
     #[serde(default)]
     pub merge_performance: PerformanceMergingConfig,
+    pub benchmark: Option<String>,
 
     #[serde(default)]
     pub assets: Vec<AssetAllocationConfig>,
@@ -243,6 +297,11 @@ pub struct PortfolioConfig {
 
     #[serde(default, deserialize_with = "deserialize_cash_flows")]
     pub tax_deductions: Vec<(Date, Decimal)>,
+
+    // Portfolios of the same taxpayer have combined income for progressive tax rates and loss carryforward
+    pub taxpayer: Option<String>,
+    #[serde(default)]
+    pub loss_carryforward: BTreeMap<i32, Decimal>,
 }
 
 impl PortfolioConfig {
@@ -255,13 +314,7 @@ impl PortfolioConfig {
     }
 
     pub fn get_stock_symbols(&self) -> HashSet<String> {
-        let mut symbols = HashSet::new();
-
-        for asset in &self.assets {
-            asset.get_stock_symbols(&mut symbols);
-        }
-
-        symbols
+        get_stock_symbols(&self.assets)
     }
 
     pub fn tax_payment_day(&self) -> TaxPaymentDay {
@@ -282,6 +335,16 @@ impl PortfolioConfig {
         time::today()
     }
 
+    fn set_jurisdiction(&mut self) -> EmptyResult {
+        match (&mut self.broker, self.jurisdiction) {
+            (Broker::Generic(jurisdiction), Some(configured)) => *jurisdiction = configured,
+            (Broker::Generic(_), None) => return Err!("Jurisdiction must be specified for generic broker"),
+            (_, Some(_)) => return Err!("Jurisdiction can be specified only for generic broker"),
+            (_, None) => {},
+        }
+        Ok(())
+    }
+
     fn validate(&self) -> EmptyResult {
         if let Some(ref currency) = self.currency {
             match currency.as_str() {
@@ -305,6 +368,28 @@ impl PortfolioConfig {
 
         taxes::validate_tax_exemptions(self.broker, &self.tax_exemptions)?;
 
+        if let Some(ref opening_balance) = self.opening_balance {
+            opening_balance.validate().map_err(|e| format!("Invalid opening balance: {}", e))?;
+        }
+
+        for transfer in &self.security_transfers {
+            transfer.validate()?;
+        }
+
+        for (&year, &loss) in &self.loss_carryforward {
+            if year < 0 {
+                return Err!("Invalid loss carryforward year: {}", year);
+            } else if loss <= dec!(0) {
+                return Err!("Invalid {} loss carryforward amount: {}", year, loss);
+            }
+        }
+
+        if let Some(max_sell_tax) = self.max_sell_tax {
+            if max_sell_tax < dec!(0) || max_sell_tax > dec!(100) {
+                return Err!("Invalid maximum sell tax: {}", max_sell_tax);
+            }
+        }
+
         Ok(())
     }
 }
@@ -319,19 +404,253 @@ struct TaxRemappingConfig {
     pub to_date: Date,
 }
 
+/// Instrument composition (for ETFs and mutual funds) which is used for exposure report. Weights may
+/// not sum up to 100% - the rest is treated as unknown.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct InstrumentCompositionConfig {
+    #[serde(default, deserialize_with = "deserialize_weights")]
+    pub countries: BTreeMap<String, Decimal>,
+    #[serde(default, deserialize_with = "deserialize_weights")]
+    pub asset_classes: BTreeMap<String, Decimal>,
+    #[serde(default, deserialize_with = "deserialize_weights")]
+    pub sectors: BTreeMap<String, Decimal>,
+    #[serde(default, deserialize_with = "deserialize_weights")]
+    pub currencies: BTreeMap<String, Decimal>,
+}
+
+impl InstrumentCompositionConfig {
+    fn validate(&self) -> EmptyResult {
+        for (name, weights) in [
+            ("countries", &self.countries),
+            ("asset classes", &self.asset_classes),
+            ("sectors", &self.sectors),
+            ("currencies", &self.currencies),
+        ] {
+            if weights.values().sum::<Decimal>() > dec!(1) {
+                return Err!("{} weights exceed 100%", name);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Several portfolios (possibly in different brokers) with a combined asset allocation
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HouseholdConfig {
+    pub name: String,
+    pub currency: String,
+    pub portfolios: Vec<HouseholdPortfolioConfig>,
+    // Is used for age-based asset weights
+    pub birth_year: Option<i32>,
+    pub assets: Vec<AssetAllocationConfig>,
+}
+
+impl HouseholdConfig {
+    fn validate(&self, config: &Config) -> EmptyResult {
+        match self.currency.as_str() {
+            "RUB" | "USD" => (),
+            _ => return Err!("Unsupported household currency: {}", self.currency),
+        };
+
+        if self.portfolios.is_empty() {
+            return Err!("The household has no portfolios");
+        }
+
+        let mut portfolio_names = HashSet::new();
+        for portfolio in &self.portfolios {
+            if !portfolio_names.insert(&portfolio.name) {
+                return Err!("Duplicate portfolio: {:?}", portfolio.name);
+            }
+            config.get_portfolio(&portfolio.name)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HouseholdPortfolioConfig {
+    pub name: String,
+    // Instruments that are allowed to be bought in this portfolio (all instruments from the
+    // portfolio's own asset allocation configuration by default)
+    pub symbols: Option<Vec<String>>,
+}
+
 #[derive(Deserialize)]
 pub struct AssetAllocationConfig {
     pub name: String,
     pub symbol: Option<String>,
 
-    #[serde(deserialize_with = "deserialize_weight")]
-    pub weight: Decimal,
+    pub weight: WeightConfig,
     pub restrict_buying: Option<bool>,
     pub restrict_selling: Option<bool>,
 
+    // Tolerance band: absolute (in percentage points of the weight) and relative (in percents of
+    // the weight)
+    #[serde(default, deserialize_with = "deserialize_optional_weight")]
+    pub absolute_band: Option<Decimal>,
+    #[serde(default, deserialize_with = "deserialize_optional_weight")]
+    pub relative_band: Option<Decimal>,
+    pub rebalance_to: Option<BandRebalancingTarget>,
+
+    // Minimum number of shares which can be traded on the exchange (one by default)
+    pub lot_size: Option<Decimal>,
+
     pub assets: Option<Vec<AssetAllocationConfig>>,
 }
 
+pub fn get_stock_symbols(assets: &[AssetAllocationConfig]) -> HashSet<String> {
+    let mut symbols = HashSet::new();
+
+    for asset in assets {
+        asset.get_stock_symbols(&mut symbols);
+    }
+
+    symbols
+}
+
+/// Returns weights of the asset group effective on the specified date
+pub fn get_asset_weights(
+    assets: &[AssetAllocationConfig], date: Date, birth_year: Option<i32>,
+) -> GenericResult<Vec<Decimal>> {
+    let mut weights = assets.iter().map(|asset| {
+        asset.weight.get(date, birth_year).map_err(|e| format!("{:?} assets: {}", asset.name, e))
+    }).collect::<Result<Vec<Decimal>, String>>()?;
+
+    // Interpolated weights are rounded, so they may not sum up to exactly 100%: attribute the
+    // rounding error to the largest weight.
+    if assets.iter().any(|asset| !matches!(asset.weight, WeightConfig::Fixed(_))) {
+        let error = dec!(1) - weights.iter().sum::<Decimal>();
+
+        if !error.is_zero() && error.abs() <= Decimal::new(1, WEIGHT_PRECISION) * Decimal::from(weights.len()) {
+            if let Some(weight) = weights.iter_mut().max() {
+                *weight += error;
+            }
+        }
+    }
+
+    Ok(weights)
+}
+
+// Precision of interpolated weights (0.01%)
+const WEIGHT_PRECISION: u32 = 4;
+
+/// Asset weight which may change over time (glide path)
+pub enum WeightConfig {
+    Fixed(Decimal),
+    // Weights on the specified dates which are linearly interpolated between them
+    Schedule(BTreeMap<Date, Decimal>),
+    // Weight in percents is calculated from the investor's age as `offset - age` or `age - offset`
+    AgeBased {offset: Decimal, increasing: bool},
+}
+
+impl WeightConfig {
+    pub fn get(&self, date: Date, birth_year: Option<i32>) -> GenericResult<Decimal> {
+        Ok(match self {
+            WeightConfig::Fixed(weight) => *weight,
+
+            WeightConfig::Schedule(schedule) => {
+                let prev = schedule.range(..=date).next_back();
+                let next = schedule.range(date..).next();
+
+                match (prev, next) {
+                    (Some((&prev_date, &prev_weight)), Some((&next_date, &next_weight))) if prev_date != next_date => {
+                        let ratio = Decimal::from((date - prev_date).num_days())
+                            / Decimal::from((next_date - prev_date).num_days());
+                        let weight = prev_weight + (next_weight - prev_weight) * ratio;
+                        util::round(weight, WEIGHT_PRECISION)
+                    },
+                    (Some((_, &weight)), _) | (None, Some((_, &weight))) => weight,
+                    (None, None) => unreachable!(),
+                }
+            },
+
+            WeightConfig::AgeBased {offset, increasing} => {
+                let birth_year = birth_year.ok_or(
+                    "Age-based weight requires birth_year to be specified in the configuration")?;
+
+                let age = Decimal::from(date.year() - birth_year);
+                let weight = if *increasing {
+                    age - offset
+                } else {
+                    offset - age
+                };
+
+                std::cmp::min(dec!(100), std::cmp::max(dec!(0), weight)) / dec!(100)
+            },
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for WeightConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<WeightConfig, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawWeight {
+            Value(String),
+            Schedule(BTreeMap<String, String>),
+        }
+
+        Ok(match RawWeight::deserialize(deserializer)? {
+            RawWeight::Value(value) => {
+                parse_weight(&value).map(WeightConfig::Fixed)
+                    .or_else(|| parse_age_based_weight(&value))
+                    .ok_or_else(|| D::Error::custom(format!("Invalid weight: {}", value)))?
+            },
+
+            RawWeight::Schedule(raw_schedule) => {
+                if raw_schedule.is_empty() {
+                    return Err(D::Error::custom("Invalid weight: empty schedule"));
+                }
+
+                let mut schedule = BTreeMap::new();
+
+                for (date, weight) in raw_schedule {
+                    let date = time::parse_user_date(&date).map_err(D::Error::custom)?;
+                    let weight = parse_weight(&weight).ok_or_else(|| D::Error::custom(format!(
+                        "Invalid weight: {}", weight)))?;
+
+                    schedule.insert(date, weight);
+                }
+
+                WeightConfig::Schedule(schedule)
+            },
+        })
+    }
+}
+
+fn parse_age_based_weight(weight: &str) -> Option<WeightConfig> {
+    let weight: String = weight.chars().filter(|c| !c.is_whitespace()).collect();
+
+    let parse_offset = |offset: &str| {
+        offset.strip_suffix('%')
+            .and_then(|offset| Decimal::from_str(offset).ok())
+            .filter(|offset| offset.is_sign_positive())
+    };
+
+    if weight == "age" {
+        Some(WeightConfig::AgeBased {offset: dec!(0), increasing: true})
+    } else if let Some(offset) = weight.strip_prefix("age-") {
+        parse_offset(offset).map(|offset| WeightConfig::AgeBased {offset, increasing: true})
+    } else if let Some(offset) = weight.strip_suffix("-age") {
+        parse_offset(offset).map(|offset| WeightConfig::AgeBased {offset, increasing: false})
+    } else {
+        None
+    }
+}
+
+/// Where to trade the asset which is out of its tolerance band
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all="kebab-case")]
+pub enum BandRebalancingTarget {
+    Target,
+    BandEdge,
+}
+
 impl AssetAllocationConfig {
     fn get_stock_symbols(&self, symbols: &mut HashSet<String>) {
         if let Some(ref symbol) = self.symbol {
@@ -351,6 +670,7 @@ impl AssetAllocationConfig {
 pub struct BrokersConfig {
     pub bcs: Option<BrokerConfig>,
     pub firstrade: Option<BrokerConfig>,
+    pub generic: Option<BrokerConfig>,
     pub interactive_brokers: Option<BrokerConfig>,
     pub open_broker: Option<BrokerConfig>,
     pub tinkoff: Option<BrokerConfig>,
@@ -362,6 +682,7 @@ impl BrokersConfig {
         BrokersConfig {
             bcs: Some(BrokerConfig::mock()),
             firstrade: Some(BrokerConfig::mock()),
+            generic: Some(BrokerConfig::mock()),
             interactive_brokers: Some(BrokerConfig::mock()),
             open_broker: Some(BrokerConfig::mock()),
             tinkoff: Some(BrokerConfig::mock()),
@@ -373,6 +694,9 @@ impl BrokersConfig {
 #[serde(deny_unknown_fields)]
 pub struct BrokerConfig {
     pub deposit_commissions: HashMap<String, TransactionCommissionSpec>,
+    pub forex_commission: Option<ForexCommissionSpec>,
+    #[serde(default)]
+    pub overlapping_statements: bool,
 }
 
 impl BrokerConfig {
@@ -380,6 +704,8 @@ impl BrokerConfig {
     pub fn mock() -> BrokerConfig {
         BrokerConfig {
             deposit_commissions: HashMap::new(),
+            forex_commission: None,
+            overlapping_statements: false,
         }
     }
 }
@@ -400,6 +726,8 @@ pub struct TaxRates {
 pub struct MetricsConfig {
     #[serde(default)]
     pub merge_performance: PerformanceMergingConfig,
+    #[serde(default)]
+    pub risk_metrics: bool,
 }
 
 #[derive(Deserialize, Clone)]
@@ -408,6 +736,16 @@ pub struct TransactionCommissionSpec {
     pub fixed_amount: Decimal,
 }
 
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ForexCommissionSpec {
+    #[serde(default)]
+    pub percent: Decimal,
+    /// Minimum commission in portfolio currency
+    #[serde(default)]
+    pub minimum: Decimal,
+}
+
 fn default_expire_time() -> Duration {
     Duration::minutes(1)
 }
@@ -435,17 +773,92 @@ fn deserialize_weight<'de, D>(deserializer: D) -> Result<Decimal, D::Error>
     where D: Deserializer<'de>
 {
     let weight: String = Deserialize::deserialize(deserializer)?;
+    parse_weight(&weight).ok_or_else(|| D::Error::custom(format!("Invalid weight: {}", weight)))
+}
 
-    let weight = Some(weight.as_str())
+fn parse_weight(weight: &str) -> Option<Decimal> {
+    Some(weight)
         .and_then(|weight| weight.strip_suffix('%'))
         .and_then(|weight| Decimal::from_str(weight).ok())
         .and_then(|weight| {
             if weight.is_sign_positive() && util::decimal_precision(weight) <= 2 && weight <= dec!(100) {
-                Some(weight.normalize())
+                Some(weight.normalize() / dec!(100))
             } else {
                 None
             }
-        }).ok_or_else(|| D::Error::custom(format!("Invalid weight: {}", weight)))?;
+        })
+}
 
-    Ok(weight / dec!(100))
-}
\ No newline at end of file
+fn deserialize_optional_weight<'de, D>(deserializer: D) -> Result<Option<Decimal>, D::Error>
+    where D: Deserializer<'de>
+{
+    #[derive(Deserialize)]
+    struct Weight(#[serde(deserialize_with = "deserialize_weight")] Decimal);
+
+    let weight: Option<Weight> = Deserialize::deserialize(deserializer)?;
+    Ok(weight.map(|weight| weight.0))
+}
+
+fn deserialize_weights<'de, D>(deserializer: D) -> Result<BTreeMap<String, Decimal>, D::Error>
+    where D: Deserializer<'de>
+{
+    #[derive(Deserialize)]
+    struct Weight(#[serde(deserialize_with = "deserialize_weight")] Decimal);
+
+    let weights: BTreeMap<String, Weight> = Deserialize::deserialize(deserializer)?;
+    Ok(weights.into_iter().map(|(name, weight)| (name, weight.0)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weight_schedule() {
+        let asset = |weight| AssetAllocationConfig {
+            name: s!("Asset"),
+            symbol: None,
+            weight,
+            restrict_buying: None,
+            restrict_selling: None,
+            absolute_band: None,
+            relative_band: None,
+            rebalance_to: None,
+            lot_size: None,
+            assets: None,
+        };
+
+        let schedule = |first, last| WeightConfig::Schedule(btreemap!{
+            date!(2020, 1, 1) => first,
+            date!(2020, 1, 4) => last,
+        });
+
+        let assets = [
+            asset(schedule(dec!(0.5), dec!(0.4))),
+            asset(schedule(dec!(0.25), dec!(0.3))),
+            asset(schedule(dec!(0.25), dec!(0.3))),
+        ];
+
+        for (date, expected) in [
+            (date!(2019, 1, 1), vec![dec!(0.5), dec!(0.25), dec!(0.25)]),
+            (date!(2020, 1, 2), vec![dec!(0.4666), dec!(0.2667), dec!(0.2667)]),
+            (date!(2021, 1, 1), vec![dec!(0.4), dec!(0.3), dec!(0.3)]),
+        ] {
+            assert_eq!(get_asset_weights(&assets, date, None).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn age_based_weight() {
+        let stocks = parse_age_based_weight("110% - age").unwrap();
+        let bonds = parse_age_based_weight("age - 10%").unwrap();
+        let date = date!(2021, 6, 1);
+
+        assert_eq!(stocks.get(date, Some(1981)).unwrap(), dec!(0.7));
+        assert_eq!(bonds.get(date, Some(1981)).unwrap(), dec!(0.3));
+        assert_eq!(bonds.get(date, Some(2011)).unwrap(), dec!(0));
+        assert!(stocks.get(date, None).is_err());
+
+        assert!(parse_age_based_weight("age + 10%").is_none());
+    }
+}
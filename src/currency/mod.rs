@@ -38,6 +38,13 @@ impl CashAssets {
     }
 }
 
+/// Currencies which are traded by the supported brokers
+const KNOWN_CURRENCIES: &[&str] = &["AUD", "CAD", "CHF", "CNY", "EUR", "GBP", "HKD", "JPY", "RUB", "USD"];
+
+pub fn is_known(currency: &str) -> bool {
+    KNOWN_CURRENCIES.contains(&currency)
+}
+
 pub fn round(amount: Decimal) -> Decimal {
     util::round(amount, 2)
 }
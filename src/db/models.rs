@@ -1,4 +1,4 @@
-use crate::db::schema::{AssetType, assets, currency_rates, quotes, settings, telemetry};
+use crate::db::schema::{AssetType, assets, currency_rates, historical_quotes, quotes, settings, telemetry};
 use crate::types::{Date, DateTime};
 
 #[derive(Insertable, Queryable)]
@@ -18,6 +18,15 @@ pub struct NewCurrencyRate<'a> {
     pub price: Option<String>,
 }
 
+#[derive(Insertable)]
+#[table_name="historical_quotes"]
+pub struct NewHistoricalQuote<'a> {
+    pub symbol: &'a str,
+    pub date: Date,
+    pub currency: Option<&'a str>,
+    pub price: Option<String>,
+}
+
 #[derive(Insertable)]
 #[table_name="quotes"]
 pub struct NewQuote<'a> {
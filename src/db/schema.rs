@@ -26,6 +26,15 @@ table! {
     }
 }
 
+table! {
+    historical_quotes (symbol, date) {
+        symbol -> Text,
+        date -> Date,
+        currency -> Nullable<Text>,
+        price -> Nullable<Text>,
+    }
+}
+
 table! {
     quotes (symbol) {
         symbol -> Text,
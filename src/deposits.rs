@@ -5,7 +5,7 @@ use static_table_derive::StaticTable;
 use crate::analysis::deposit_emulator::{DepositEmulator, Transaction};
 use crate::config::DepositConfig;
 use crate::currency::{Cash, MultiCurrencyCashAccount};
-use crate::formatting::{self, table::Style};
+use crate::formatting::{self, output, table::Style};
 use crate::localities::Country;
 use crate::types::{Date, Decimal};
 
@@ -69,7 +69,7 @@ fn print(country: &Country, deposits: Vec<DepositConfig>, today: Date) {
         }
     }
 
-    let mut totals = table.add_empty_row();
+    let mut totals = table.add_totals_row();
     totals.set_amount(total_amount);
     totals.set_current_amount(total_current_amount);
 
@@ -90,6 +90,18 @@ fn print_cron_mode(country: &Country, deposits: Vec<DepositConfig>, today: Date,
         }
     }
 
+    if output::is_structured() {
+        for (title, deposits) in [
+            ("Deposits which are about to close", &expiring_deposits),
+            ("Closed deposits", &closed_deposits),
+        ] {
+            if !deposits.is_empty() {
+                print_closed_deposits_table(country, deposits, title);
+            }
+        }
+        return;
+    }
+
     if !expiring_deposits.is_empty() {
         println!("The following deposits are about to close:");
         for deposit in &expiring_deposits {
@@ -109,6 +121,34 @@ fn print_cron_mode(country: &Country, deposits: Vec<DepositConfig>, today: Date,
     }
 }
 
+#[derive(StaticTable)]
+#[table(name="ClosedDepositsTable")]
+struct ClosedDepositRow {
+    #[column(name="Close date")]
+    close_date: Date,
+    #[column(name="Name")]
+    name: String,
+    #[column(name="Amount")]
+    amount: Cash,
+    #[column(name="Close amount")]
+    close_amount: Cash,
+}
+
+fn print_closed_deposits_table(country: &Country, deposits: &[DepositConfig], title: &str) {
+    let mut table = ClosedDepositsTable::new();
+
+    for deposit in deposits {
+        let (amount, close_amount) = calculate_amounts(country, deposit, deposit.close_date);
+        table.add_row(ClosedDepositRow {
+            close_date: deposit.close_date,
+            name: deposit.name.clone(),
+            amount, close_amount,
+        });
+    }
+
+    table.print(title);
+}
+
 fn print_closed_deposit(country: &Country, deposit: &DepositConfig) {
     let (amount, close_amount) = calculate_amounts(country, deposit, deposit.close_date);
     println!(
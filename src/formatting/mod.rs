@@ -1,6 +1,8 @@
 use crate::time::{DateTime, DateOptTime};
 
+pub mod output;
 pub mod table;
+mod xlsx;
 
 pub fn format_date<T>(date: T) -> String where T: Into<DateOptTime> {
     let date = date.into();
@@ -0,0 +1,336 @@
+use std::io::{self, Write};
+use std::mem;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::core::EmptyResult;
+use crate::currency::Cash;
+use crate::types::{Date, Decimal};
+
+use super::xlsx;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+lazy_static! {
+    static ref OUTPUT: Mutex<Output> = Mutex::new(Output {
+        format: OutputFormat::Text,
+        xlsx_path: None,
+        items: Vec::new(),
+    });
+}
+
+struct Output {
+    format: OutputFormat,
+    xlsx_path: Option<String>,
+    items: Vec<Item>,
+}
+
+enum Item {
+    Table(TableData),
+    Value {
+        title: String,
+        value: Value,
+    },
+}
+
+pub struct TableData {
+    pub title: String,
+    pub columns: Vec<&'static str>,
+    pub rows: Vec<RowData>,
+}
+
+pub struct RowData {
+    pub cells: Vec<CellValue>,
+    pub totals: bool,
+}
+
+/// Typed cell value for machine-readable output
+#[derive(Clone, Debug)]
+pub enum CellValue {
+    Empty,
+    String(String),
+    Bool(bool),
+    Integer(i64),
+    Decimal(Decimal),
+    Ratio(Decimal),
+    Date(Date),
+    Cash(Cash),
+    MultiCurrency(Vec<Cash>),
+}
+
+impl CellValue {
+    fn to_json(&self) -> Value {
+        match self {
+            CellValue::Empty => Value::Null,
+            CellValue::String(value) => Value::from(value.as_str()),
+            CellValue::Bool(value) => Value::from(*value),
+            CellValue::Integer(value) => Value::from(*value),
+            CellValue::Decimal(value) | CellValue::Ratio(value) => decimal(*value),
+            CellValue::Date(date) => Value::from(date.format("%Y-%m-%d").to_string()),
+            CellValue::Cash(amount) => cash(*amount),
+            CellValue::MultiCurrency(amounts) => Value::Object(amounts.iter().map(|amount| {
+                (amount.currency.to_owned(), decimal(amount.amount))
+            }).collect()),
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        match self {
+            CellValue::Empty => String::new(),
+            CellValue::String(value) => value.clone(),
+            CellValue::Cash(amount) => format_csv_cash(*amount),
+            CellValue::MultiCurrency(amounts) => amounts.iter()
+                .map(|&amount| format_csv_cash(amount))
+                .collect::<Vec<_>>()
+                .join(" + "),
+            _ => self.to_json().to_string(),
+        }
+    }
+}
+
+pub fn set_format(format: OutputFormat) {
+    OUTPUT.lock().unwrap().format = format;
+}
+
+pub fn format() -> OutputFormat {
+    OUTPUT.lock().unwrap().format
+}
+
+/// Configures export of all the result tables to the specified XLSX workbook
+pub fn set_xlsx_path(path: &str) {
+    OUTPUT.lock().unwrap().xlsx_path = Some(path.to_owned());
+}
+
+/// Returns true if the results are collected to be written in machine-readable format instead of
+/// being printed to the terminal.
+pub fn is_structured() -> bool {
+    OUTPUT.lock().unwrap().format != OutputFormat::Text
+}
+
+/// Returns true if the tables must be collected for machine-readable output or XLSX export
+pub fn collects_tables() -> bool {
+    let output = OUTPUT.lock().unwrap();
+    output.format != OutputFormat::Text || output.xlsx_path.is_some()
+}
+
+pub fn add_table(table: TableData) {
+    OUTPUT.lock().unwrap().items.push(Item::Table(table));
+}
+
+pub fn add_value<T: Serialize>(title: &str, value: &T) {
+    let value = serde_json::to_value(value).unwrap();
+    OUTPUT.lock().unwrap().items.push(Item::Value {title: title.to_owned(), value});
+}
+
+/// Writes the collected results to stdout and the XLSX workbook
+pub fn flush() -> EmptyResult {
+    let (format, xlsx_path, items) = {
+        let mut output = OUTPUT.lock().unwrap();
+        (output.format, output.xlsx_path.take(), mem::take(&mut output.items))
+    };
+
+    if let Some(ref path) = xlsx_path {
+        let tables: Vec<&TableData> = items.iter().filter_map(|item| match item {
+            Item::Table(table) => Some(table),
+            Item::Value {..} => None,
+        }).collect();
+
+        if tables.is_empty() {
+            return Err!("The command has no tabular results to export to XLSX");
+        }
+
+        xlsx::write(path, &tables).map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+    }
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+
+    match format {
+        OutputFormat::Text => {},
+        OutputFormat::Json => write_json(&mut stdout, items)?,
+        OutputFormat::Csv => write_csv(&mut stdout, items)?,
+    }
+
+    Ok(())
+}
+
+// Decimals are serialized as strings to not lose precision on conversion to floating point numbers
+pub fn decimal(value: Decimal) -> Value {
+    Value::from(value.normalize().to_string())
+}
+
+pub fn cash(value: Cash) -> Value {
+    let mut object = Map::new();
+    object.insert(s!("amount"), decimal(value.amount));
+    object.insert(s!("currency"), Value::from(value.currency));
+    Value::Object(object)
+}
+
+fn write_json<W: Write>(mut writer: W, items: Vec<Item>) -> EmptyResult {
+    let items: Vec<Value> = items.into_iter().map(|item| {
+        let (title, data) = match item {
+            Item::Table(table) => {
+                let rows = table.rows.into_iter().map(|row| {
+                    Value::Object(table.columns.iter().map(|&name| name.to_owned()).zip(
+                        row.cells.iter().map(CellValue::to_json)).collect())
+                }).collect();
+                (table.title, Value::Array(rows))
+            },
+            Item::Value {title, value} => (title, value),
+        };
+
+        let mut object = Map::new();
+        object.insert(s!("title"), Value::from(title));
+        object.insert(s!("data"), data);
+        Value::Object(object)
+    }).collect();
+
+    serde_json::to_writer_pretty(&mut writer, &items)?;
+    writeln!(writer)?;
+
+    Ok(())
+}
+
+// Several tables are separated by an empty line and each one is preceded by its title
+fn write_csv<W: Write>(mut writer: W, items: Vec<Item>) -> EmptyResult {
+    let with_titles = items.len() > 1;
+
+    for (index, item) in items.into_iter().enumerate() {
+        let table = match item {
+            Item::Table(table) => table,
+            Item::Value {title, ..} => return Err!(
+                "{:?} can't be represented in CSV format. Use JSON format instead", title),
+        };
+
+        if index != 0 {
+            writeln!(writer)?;
+        }
+
+        let mut csv_writer = csv::WriterBuilder::new().flexible(true).from_writer(&mut writer);
+        if with_titles {
+            csv_writer.write_record(&[table.title])?;
+        }
+
+        csv_writer.write_record(&table.columns)?;
+        for row in table.rows {
+            csv_writer.write_record(row.cells.iter().map(CellValue::to_csv))?;
+        }
+
+        csv_writer.flush()?;
+    }
+
+    Ok(())
+}
+
+fn format_csv_cash(amount: Cash) -> String {
+    format!("{} {}", amount.amount.normalize(), amount.currency)
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use super::*;
+
+    #[test]
+    fn json_output() {
+        #[derive(Serialize)]
+        struct Summary {
+            name: &'static str,
+            value: Value,
+        }
+
+        let items = vec![mock_table(), Item::Value {
+            title: s!("Summary"),
+            value: serde_json::to_value(Summary {
+                name: "Total",
+                value: cash(Cash::new("RUB", dec!(1000))),
+            }).unwrap(),
+        }];
+
+        let mut buffer = Vec::new();
+        write_json(&mut buffer, items).unwrap();
+
+        // Compare parsed values to not depend on the key order
+        let result: Value = serde_json::from_slice(&buffer).unwrap();
+        let expected: Value = serde_json::from_str(indoc!(r#"
+            [
+              {
+                "data": [
+                  {
+                    "Amount": {
+                      "amount": "10.5",
+                      "currency": "USD"
+                    },
+                    "Name": "A, B",
+                    "Ratio": "0.25"
+                  },
+                  {
+                    "Amount": null,
+                    "Name": null,
+                    "Ratio": null
+                  }
+                ],
+                "title": "Test"
+              },
+              {
+                "data": {
+                  "name": "Total",
+                  "value": {
+                    "amount": "1000",
+                    "currency": "RUB"
+                  }
+                },
+                "title": "Summary"
+              }
+            ]
+        "#)).unwrap();
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn csv_output() {
+        let mut buffer = Vec::new();
+        write_csv(&mut buffer, vec![mock_table()]).unwrap();
+
+        assert_eq!(String::from_utf8(buffer).unwrap(), concat!(
+            "Name,Amount,Ratio\n",
+            "\"A, B\",10.5 USD,0.25\n",
+            ",,\n",
+        ));
+    }
+
+    #[test]
+    fn csv_value_output() {
+        let items = vec![mock_table(), Item::Value {title: s!("Summary"), value: Value::from(1)}];
+        assert_eq!(
+            write_csv(&mut Vec::new(), items).unwrap_err().to_string(),
+            r#""Summary" can't be represented in CSV format. Use JSON format instead"#);
+    }
+
+    fn mock_table() -> Item {
+        Item::Table(TableData {
+            title: s!("Test"),
+            columns: vec!["Name", "Amount", "Ratio"],
+            rows: vec![RowData {
+                cells: vec![
+                    CellValue::String(s!("A, B")),
+                    CellValue::Cash(Cash::new("USD", dec!(10.50))),
+                    CellValue::Ratio(dec!(0.25)),
+                ],
+                totals: false,
+            }, RowData {
+                cells: vec![CellValue::Empty, CellValue::Empty, CellValue::Empty],
+                totals: true,
+            }],
+        })
+    }
+}
@@ -107,6 +107,11 @@ fn static_table_derive_impl(input: TokenStream) -> GenericResult<TokenStream> {
                 #row_proxy_ident {row: row}
             }
 
+            fn add_totals_row(&mut self) -> #row_proxy_ident {
+                let row = self.table.add_totals_row();
+                #row_proxy_ident {row: row}
+            }
+
             fn is_empty(&self) -> bool {
                 self.table.is_empty()
             }
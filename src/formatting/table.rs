@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+
 use num_traits::ToPrimitive;
 use prettytable::{Table as RawTable, Row as RawRow, Cell as RawCell, Attr};
 use prettytable::format::{FormatBuilder, LinePosition, LineSeparator};
@@ -7,6 +9,8 @@ use crate::currency::{Cash, MultiCurrencyCashAccount};
 use crate::types::{Date, Decimal};
 use crate::util;
 
+use super::output::{self, CellValue, RowData, TableData};
+
 pub use ansi_term::Style;
 pub use prettytable::format::Alignment;
 
@@ -14,6 +18,7 @@ pub struct Table {
     columns: Vec<Column>,
     show_titles: bool,
     rows: Vec<Row>,
+    totals_rows: HashSet<usize>,
 }
 
 impl Table {
@@ -22,6 +27,7 @@ impl Table {
             columns,
             show_titles: true,
             rows: Vec::new(),
+            totals_rows: HashSet::new(),
         }
     }
 
@@ -37,6 +43,12 @@ impl Table {
         self.rows.last_mut().unwrap()
     }
 
+    /// Adds an empty row which is highlighted as totals in exported tables
+    pub fn add_totals_row(&mut self) -> &mut Row {
+        self.totals_rows.insert(self.rows.len());
+        self.add_empty_row()
+    }
+
     pub fn hide_titles(&mut self) {
         self.show_titles = false;
     }
@@ -54,6 +66,25 @@ impl Table {
     }
 
     pub fn print(&self, title: &str) {
+        if output::collects_tables() {
+            let columns: Vec<usize> = (0..self.columns.len())
+                .filter(|&index| !self.columns[index].hidden)
+                .collect();
+
+            output::add_table(TableData {
+                title: title.to_owned(),
+                columns: columns.iter().map(|&index| self.columns[index].name).collect(),
+                rows: self.rows.iter().enumerate().map(|(row_index, row)| RowData {
+                    cells: columns.iter().map(|&index| row[index].value.clone()).collect(),
+                    totals: self.totals_rows.contains(&row_index),
+                }).collect(),
+            });
+        }
+
+        if output::is_structured() {
+            return;
+        }
+
         let mut table = RawTable::new();
         let mut columns = Vec::new();
         let mut titles = Vec::new();
@@ -123,25 +154,35 @@ pub type Row = Vec<Cell>;
 
 pub struct Cell {
     text: String,
+    // Typed value for machine-readable output
+    value: CellValue,
     default_alignment: Alignment,
     style: Option<Style>,
 }
 
 impl Cell {
     fn new(text: String, default_alignment: Alignment) -> Cell {
-        Cell {text, default_alignment, style: None}
+        let value = CellValue::String(text.clone());
+        Cell::new_typed(text, value, default_alignment)
+    }
+
+    fn new_typed(text: String, value: CellValue, default_alignment: Alignment) -> Cell {
+        Cell {text, value, default_alignment, style: None}
     }
 
     pub fn new_empty() -> Cell {
-        Cell::new(String::new(), Alignment::LEFT)
+        Cell::new_typed(String::new(), CellValue::Empty, Alignment::LEFT)
     }
 
     pub fn new_ratio(ratio: Decimal) -> Cell {
-        Cell::new(format!("{}%", util::round(ratio * dec!(100), 1)), Alignment::RIGHT)
+        Cell::new_typed(
+            format!("{}%", util::round(ratio * dec!(100), 1)), CellValue::Ratio(ratio),
+            Alignment::RIGHT)
     }
 
     pub fn new_round_decimal(value: Decimal) -> Cell {
-        Cell::new(value.to_i64().unwrap().separated_string(), Alignment::RIGHT)
+        let value = value.to_i64().unwrap();
+        Cell::new_typed(value.separated_string(), CellValue::Integer(value), Alignment::RIGHT)
     }
 
     pub fn style(&mut self, style: Style) -> &mut Cell {
@@ -168,7 +209,7 @@ macro_rules! impl_from_number_to_cell {
     ($T:ty) => {
         impl From<$T> for Cell {
             fn from(value: $T) -> Cell {
-                Cell::new(value.to_string(), Alignment::RIGHT)
+                Cell::new_typed(value.to_string(), CellValue::Integer(value as i64), Alignment::RIGHT)
             }
         }
     };
@@ -176,14 +217,19 @@ macro_rules! impl_from_number_to_cell {
 impl_from_number_to_cell!(i32);
 impl_from_number_to_cell!(u32);
 impl_from_number_to_cell!(usize);
-impl_from_number_to_cell!(Decimal);
+
+impl From<Decimal> for Cell {
+    fn from(value: Decimal) -> Cell {
+        Cell::new_typed(value.to_string(), CellValue::Decimal(value), Alignment::RIGHT)
+    }
+}
 
 impl From<bool> for Cell {
     fn from(value: bool) -> Cell {
         if value {
-            "✔".into()
+            Cell::new_typed(s!("✔"), CellValue::Bool(true), Alignment::LEFT)
         } else {
-            Cell::new_empty()
+            Cell::new_typed(String::new(), CellValue::Bool(false), Alignment::LEFT)
         }
     }
 }
@@ -211,13 +257,13 @@ impl<T: Into<Cell>> From<Option<T>> for Cell {
 
 impl From<Date> for Cell {
     fn from(date: Date) -> Cell {
-        Cell::new(super::format_date(date), Alignment::CENTER)
+        Cell::new_typed(super::format_date(date), CellValue::Date(date), Alignment::CENTER)
     }
 }
 
 impl From<Cash> for Cell {
     fn from(amount: Cash) -> Cell {
-        Cell::new(amount.to_string(), Alignment::RIGHT)
+        Cell::new_typed(amount.to_string(), CellValue::Cash(amount), Alignment::RIGHT)
     }
 }
 
@@ -231,7 +277,7 @@ impl From<MultiCurrencyCashAccount> for Cell {
             .collect::<Vec<_>>()
             .join(" + ");
 
-        Cell::new(result, Alignment::RIGHT)
+        Cell::new_typed(result, CellValue::MultiCurrency(amounts), Alignment::RIGHT)
     }
 }
 
@@ -0,0 +1,517 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Write;
+
+use zip::ZipWriter;
+use zip::write::FileOptions;
+
+use crate::core::EmptyResult;
+use crate::currency::Cash;
+
+use super::output::{CellValue, TableData};
+
+const MAX_SHEET_NAME_LENGTH: usize = 31;
+const MAX_COLUMN_WIDTH: usize = 60;
+
+// The first rows of each sheet are table title, an empty row and column titles
+const HEADER_ROWS: usize = 3;
+
+/// Writes the tables to XLSX workbook: one sheet per table with typed number, date, percent and
+/// currency cells.
+pub fn write(path: &str, tables: &[&TableData]) -> EmptyResult {
+    let mut styles = Styles::new();
+    let mut strings = SharedStrings::default();
+    let mut sheet_names = Vec::new();
+    let mut sheets = Vec::new();
+
+    for table in tables {
+        sheet_names.push(get_sheet_name(&table.title, &sheet_names));
+        sheets.push(write_sheet(table, &mut styles, &mut strings));
+    }
+
+    let mut files = vec![
+        (s!("[Content_Types].xml"), get_content_types(sheets.len())),
+        (s!("_rels/.rels"), get_root_relationships()),
+        (s!("xl/workbook.xml"), get_workbook(&sheet_names)),
+        (s!("xl/_rels/workbook.xml.rels"), get_workbook_relationships(sheets.len())),
+        (s!("xl/styles.xml"), styles.to_xml()),
+        (s!("xl/sharedStrings.xml"), strings.to_xml()),
+    ];
+
+    for (index, sheet) in sheets.into_iter().enumerate() {
+        files.push((format!("xl/worksheets/sheet{}.xml", index + 1), sheet));
+    }
+
+    let mut zip = ZipWriter::new(File::create(path)?);
+
+    for (name, data) in files {
+        zip.start_file(name, FileOptions::default())?;
+        zip.write_all(data.as_bytes())?;
+    }
+
+    zip.finish()?;
+    Ok(())
+}
+
+fn write_sheet(table: &TableData, styles: &mut Styles, strings: &mut SharedStrings) -> String {
+    let title_style = styles.get(None, true);
+    let mut widths: Vec<usize> = table.columns.iter().map(|name| name.chars().count()).collect();
+
+    let mut rows = String::new();
+    write_row(&mut rows, 1, &[(Some(strings.get(&table.title)), title_style, None)]);
+    write_row(&mut rows, HEADER_ROWS, &table.columns.iter().map(|&name| {
+        (Some(strings.get(name)), title_style, None)
+    }).collect::<Vec<_>>());
+
+    for (index, row) in table.rows.iter().enumerate() {
+        let cells: Vec<_> = row.cells.iter().enumerate().map(|(column, value)| {
+            let (cell, width) = get_cell(value, row.totals, styles, strings);
+            widths[column] = std::cmp::max(widths[column], width);
+            cell
+        }).collect();
+
+        write_row(&mut rows, HEADER_ROWS + index + 1, &cells);
+    }
+
+    let mut sheet = String::new();
+    sheet.push_str(XML_HEADER);
+    write!(sheet, concat!(
+        r#"<worksheet xmlns="{}">"#,
+        r#"<sheetViews><sheetView workbookViewId="0">"#,
+        r#"<pane ySplit="{}" topLeftCell="A{}" activePane="bottomLeft" state="frozen"/>"#,
+        r#"</sheetView></sheetViews>"#,
+    ), MAIN_NAMESPACE, HEADER_ROWS, HEADER_ROWS + 1).unwrap();
+
+    if !widths.is_empty() {
+        sheet.push_str("<cols>");
+        for (index, width) in widths.into_iter().enumerate() {
+            write!(sheet, r#"<col min="{index}" max="{index}" width="{width}" customWidth="1"/>"#,
+                   index=index + 1, width=std::cmp::min(width, MAX_COLUMN_WIDTH) + 2).unwrap();
+        }
+        sheet.push_str("</cols>");
+    }
+
+    write!(sheet, "<sheetData>{}</sheetData></worksheet>", rows).unwrap();
+    sheet
+}
+
+// Cell value is either shared string index or a number. Cell type is specified for non-numeric
+// cells.
+type XlsxCell = (Option<usize>, usize, Option<(&'static str, String)>);
+
+fn get_cell(
+    value: &CellValue, totals: bool, styles: &mut Styles, strings: &mut SharedStrings,
+) -> (XlsxCell, usize) {
+    let mut string = |text: String, styles: &mut Styles| -> (XlsxCell, usize) {
+        let width = text.chars().count();
+        ((Some(strings.get(&text)), styles.get(None, totals), None), width)
+    };
+
+    let number = |
+        value: String, format: Option<String>, width: usize, styles: &mut Styles,
+    | -> (XlsxCell, usize) {
+        ((None, styles.get(format, totals), Some(("n", value))), width)
+    };
+
+    match value {
+        CellValue::Empty => ((None, styles.get(None, totals), None), 0),
+        CellValue::String(text) => string(text.clone(), styles),
+        CellValue::Bool(value) => {
+            ((None, styles.get(None, totals), Some(("b", s!(if *value {"1"} else {"0"})))), 5)
+        },
+        CellValue::Integer(value) => number(value.to_string(), None, value.to_string().len(), styles),
+        CellValue::Decimal(value) => {
+            let value = value.normalize().to_string();
+            let width = value.len();
+            number(value, None, width, styles)
+        },
+        CellValue::Ratio(value) => number(value.normalize().to_string(), Some(s!("0.0%")), 7, styles),
+        CellValue::Date(date) => {
+            let serial = (*date - date!(1899, 12, 30)).num_days();
+            number(serial.to_string(), Some(s!("dd.mm.yyyy")), 10, styles)
+        },
+        CellValue::Cash(amount) => {
+            let (format, width) = get_cash_format(*amount);
+            number(amount.amount.normalize().to_string(), Some(format), width, styles)
+        },
+        CellValue::MultiCurrency(amounts) => match amounts.as_slice() {
+            [] => ((None, styles.get(None, totals), None), 0),
+            [amount] => {
+                let (format, width) = get_cash_format(*amount);
+                number(amount.amount.normalize().to_string(), Some(format), width, styles)
+            },
+            _ => string(amounts.iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" + "), styles),
+        },
+    }
+}
+
+fn get_cash_format(amount: Cash) -> (String, usize) {
+    let format = format!(r#"#,##0.00\ "{}""#, amount.currency);
+    let width = amount.to_string().chars().count() + amount.currency.len();
+    (format, width)
+}
+
+fn write_row(buffer: &mut String, row: usize, cells: &[XlsxCell]) {
+    write!(buffer, r#"<row r="{}">"#, row).unwrap();
+
+    for (column, (string, style, value)) in cells.iter().enumerate() {
+        let reference = format!("{}{}", get_column_name(column), row);
+
+        match (string, value) {
+            (Some(index), _) => write!(
+                buffer, r#"<c r="{}" s="{}" t="s"><v>{}</v></c>"#, reference, style, index),
+            (None, Some((type_, value))) => write!(
+                buffer, r#"<c r="{}" s="{}" t="{}"><v>{}</v></c>"#, reference, style, type_, value),
+            (None, None) if *style != 0 => write!(buffer, r#"<c r="{}" s="{}"/>"#, reference, style),
+            (None, None) => Ok(()),
+        }.unwrap();
+    }
+
+    buffer.push_str("</row>");
+}
+
+fn get_column_name(mut index: usize) -> String {
+    let mut name = Vec::new();
+
+    loop {
+        name.push(b'A' + (index % 26) as u8);
+        if index < 26 {
+            break;
+        }
+        index = index / 26 - 1;
+    }
+
+    name.reverse();
+    String::from_utf8(name).unwrap()
+}
+
+fn get_sheet_name(title: &str, existing_names: &[String]) -> String {
+    let name: String = title.chars()
+        .filter(|&char| !matches!(char, '[' | ']' | ':' | '*' | '?' | '/' | '\\'))
+        .collect();
+    let name = name.trim().trim_matches('\'');
+
+    let name = if name.is_empty() {
+        format!("Sheet{}", existing_names.len() + 1)
+    } else {
+        name.to_owned()
+    };
+
+    let mut index = 1;
+
+    loop {
+        let suffix = if index == 1 {
+            String::new()
+        } else {
+            format!(" ({})", index)
+        };
+
+        let max_length = MAX_SHEET_NAME_LENGTH - suffix.len();
+        let mut candidate: String = name.chars().take(max_length).collect::<String>().trim_end().to_owned();
+        candidate.push_str(&suffix);
+
+        if !existing_names.iter().any(|name| name.to_lowercase() == candidate.to_lowercase()) {
+            return candidate;
+        }
+
+        index += 1;
+    }
+}
+
+#[derive(Default)]
+struct SharedStrings {
+    strings: Vec<String>,
+    indices: HashMap<String, usize>,
+}
+
+impl SharedStrings {
+    fn get(&mut self, string: &str) -> usize {
+        if let Some(&index) = self.indices.get(string) {
+            return index;
+        }
+
+        let index = self.strings.len();
+        self.strings.push(string.to_owned());
+        self.indices.insert(string.to_owned(), index);
+        index
+    }
+
+    fn to_xml(&self) -> String {
+        let mut xml = String::new();
+        xml.push_str(XML_HEADER);
+
+        write!(xml, r#"<sst xmlns="{}" count="{count}" uniqueCount="{count}">"#,
+               MAIN_NAMESPACE, count=self.strings.len()).unwrap();
+
+        for string in &self.strings {
+            write!(xml, r#"<si><t xml:space="preserve">{}</t></si>"#, escape(string)).unwrap();
+        }
+
+        xml.push_str("</sst>");
+        xml
+    }
+}
+
+struct Styles {
+    formats: Vec<String>,
+    styles: Vec<(Option<usize>, bool)>,
+}
+
+impl Styles {
+    // Custom number format IDs start from 164
+    const FIRST_CUSTOM_FORMAT_ID: usize = 164;
+
+    fn new() -> Styles {
+        Styles {
+            formats: Vec::new(),
+            styles: vec![(None, false)],
+        }
+    }
+
+    fn get(&mut self, format: Option<String>, bold: bool) -> usize {
+        let format = format.map(|format| {
+            match self.formats.iter().position(|other| *other == format) {
+                Some(index) => index,
+                None => {
+                    self.formats.push(format);
+                    self.formats.len() - 1
+                },
+            }
+        });
+
+        let style = (format, bold);
+
+        match self.styles.iter().position(|&other| other == style) {
+            Some(index) => index,
+            None => {
+                self.styles.push(style);
+                self.styles.len() - 1
+            },
+        }
+    }
+
+    fn to_xml(&self) -> String {
+        let mut xml = String::new();
+        xml.push_str(XML_HEADER);
+        write!(xml, r#"<styleSheet xmlns="{}">"#, MAIN_NAMESPACE).unwrap();
+
+        if !self.formats.is_empty() {
+            write!(xml, r#"<numFmts count="{}">"#, self.formats.len()).unwrap();
+            for (index, format) in self.formats.iter().enumerate() {
+                write!(xml, r#"<numFmt numFmtId="{}" formatCode="{}"/>"#,
+                       Styles::FIRST_CUSTOM_FORMAT_ID + index, escape(format)).unwrap();
+            }
+            xml.push_str("</numFmts>");
+        }
+
+        xml.push_str(concat!(
+            r#"<fonts count="2">"#,
+            r#"<font><sz val="11"/><name val="Calibri"/></font>"#,
+            r#"<font><b/><sz val="11"/><name val="Calibri"/></font>"#,
+            r#"</fonts>"#,
+            r#"<fills count="2">"#,
+            r#"<fill><patternFill patternType="none"/></fill>"#,
+            r#"<fill><patternFill patternType="gray125"/></fill>"#,
+            r#"</fills>"#,
+            r#"<borders count="1"><border><left/><right/><top/><bottom/><diagonal/></border></borders>"#,
+            r#"<cellStyleXfs count="1"><xf numFmtId="0" fontId="0" fillId="0" borderId="0"/></cellStyleXfs>"#,
+        ));
+
+        write!(xml, r#"<cellXfs count="{}">"#, self.styles.len()).unwrap();
+        for &(format, bold) in &self.styles {
+            write!(xml,
+                r#"<xf numFmtId="{}" fontId="{}" fillId="0" borderId="0" xfId="0" applyNumberFormat="1" applyFont="1"/>"#,
+                format.map(|index| Styles::FIRST_CUSTOM_FORMAT_ID + index).unwrap_or(0),
+                if bold {1} else {0},
+            ).unwrap();
+        }
+        xml.push_str("</cellXfs>");
+
+        xml.push_str(r#"<cellStyles count="1"><cellStyle name="Normal" xfId="0" builtinId="0"/></cellStyles>"#);
+        xml.push_str("</styleSheet>");
+        xml
+    }
+}
+
+const XML_HEADER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n";
+const MAIN_NAMESPACE: &str = "http://schemas.openxmlformats.org/spreadsheetml/2006/main";
+const RELATIONSHIPS_NAMESPACE: &str = "http://schemas.openxmlformats.org/package/2006/relationships";
+const DOCUMENT_RELATIONSHIPS_NAMESPACE: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+
+fn get_content_types(sheets: usize) -> String {
+    let mut xml = String::new();
+    xml.push_str(XML_HEADER);
+    xml.push_str(concat!(
+        r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">"#,
+        r#"<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>"#,
+        r#"<Default Extension="xml" ContentType="application/xml"/>"#,
+        r#"<Override PartName="/xl/workbook.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml"/>"#,
+        r#"<Override PartName="/xl/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.styles+xml"/>"#,
+        r#"<Override PartName="/xl/sharedStrings.xml" ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.sharedStrings+xml"/>"#,
+    ));
+
+    for index in 1..=sheets {
+        write!(xml, concat!(
+            r#"<Override PartName="/xl/worksheets/sheet{}.xml" "#,
+            r#"ContentType="application/vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml"/>"#,
+        ), index).unwrap();
+    }
+
+    xml.push_str("</Types>");
+    xml
+}
+
+fn get_root_relationships() -> String {
+    format!(concat!(
+        "{}",
+        r#"<Relationships xmlns="{}">"#,
+        r#"<Relationship Id="rId1" Type="{}/officeDocument" Target="xl/workbook.xml"/>"#,
+        r#"</Relationships>"#,
+    ), XML_HEADER, RELATIONSHIPS_NAMESPACE, DOCUMENT_RELATIONSHIPS_NAMESPACE)
+}
+
+fn get_workbook(sheet_names: &[String]) -> String {
+    let mut xml = String::new();
+    xml.push_str(XML_HEADER);
+    write!(xml, r#"<workbook xmlns="{}" xmlns:r="{}"><sheets>"#,
+           MAIN_NAMESPACE, DOCUMENT_RELATIONSHIPS_NAMESPACE).unwrap();
+
+    for (index, name) in sheet_names.iter().enumerate() {
+        write!(xml, r#"<sheet name="{name}" sheetId="{id}" r:id="rId{id}"/>"#,
+               name=escape(name), id=index + 1).unwrap();
+    }
+
+    xml.push_str("</sheets></workbook>");
+    xml
+}
+
+fn get_workbook_relationships(sheets: usize) -> String {
+    let mut xml = String::new();
+    xml.push_str(XML_HEADER);
+    write!(xml, r#"<Relationships xmlns="{}">"#, RELATIONSHIPS_NAMESPACE).unwrap();
+
+    for index in 1..=sheets {
+        write!(xml, r#"<Relationship Id="rId{index}" Type="{}/worksheet" Target="worksheets/sheet{index}.xml"/>"#,
+               DOCUMENT_RELATIONSHIPS_NAMESPACE, index=index).unwrap();
+    }
+
+    for (offset, (type_, target)) in [("styles", "styles.xml"), ("sharedStrings", "sharedStrings.xml")].iter().enumerate() {
+        write!(xml, r#"<Relationship Id="rId{}" Type="{}/{}" Target="{}"/>"#,
+               sheets + offset + 1, DOCUMENT_RELATIONSHIPS_NAMESPACE, type_, target).unwrap();
+    }
+
+    xml.push_str("</Relationships>");
+    xml
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for char in text.chars() {
+        match char {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(char),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use calamine::{DataType, Reader, open_workbook_auto};
+    use rstest::rstest;
+    use tempfile::NamedTempFile;
+
+    use crate::formatting::output::RowData;
+
+    use super::*;
+
+    #[test]
+    fn column_names() {
+        assert_eq!(get_column_name(0), "A");
+        assert_eq!(get_column_name(25), "Z");
+        assert_eq!(get_column_name(26), "AA");
+        assert_eq!(get_column_name(701), "ZZ");
+        assert_eq!(get_column_name(702), "AAA");
+    }
+
+    #[test]
+    fn sheet_names() {
+        let title = "Расчет прибыли от продажи ценных бумаг: Interactive Brokers";
+        let mut names = Vec::new();
+
+        for _ in 0..2 {
+            names.push(get_sheet_name(title, &names));
+        }
+
+        assert_eq!(names, vec![
+            s!("Расчет прибыли от продажи ценны"),
+            s!("Расчет прибыли от продажи ц (2)"),
+        ]);
+    }
+
+    #[rstest(title, existing_names, expected,
+        case("Taxes: 2021/2022", &[], "Taxes 20212022"),
+        case("'[Portfolio]'", &[], "Portfolio"),
+        case("[?]", &["Deposits"], "Sheet2"),
+        case("DEPOSITS", &["Deposits"], "DEPOSITS (2)"),
+        case("Deposits", &["Deposits", "deposits (2)"], "Deposits (3)"),
+        case("abcdefghijklmnopqrstuvwxyz abcdef", &["abcdefghijklmnopqrstuvwxyz abcd"],
+             "abcdefghijklmnopqrstuvwxyz (2)"),
+    )]
+    fn sheet_name(title: &str, existing_names: &[&str], expected: &str) {
+        let existing_names: Vec<String> = existing_names.iter().map(|&name| name.to_owned()).collect();
+        assert_eq!(get_sheet_name(title, &existing_names), expected);
+    }
+
+    #[test]
+    fn workbook() {
+        let table = TableData {
+            title: s!("Deposits & interest"),
+            columns: vec!["Date", "Name", "Amount", "Interest"],
+            rows: vec![RowData {
+                cells: vec![
+                    CellValue::Date(date!(2020, 1, 2)),
+                    CellValue::String(s!("Deposit <1>")),
+                    CellValue::Cash(Cash::new("RUB", dec!(1000.5))),
+                    CellValue::Ratio(dec!(0.065)),
+                ],
+                totals: false,
+            }, RowData {
+                cells: vec![
+                    CellValue::Empty, CellValue::Empty,
+                    CellValue::Cash(Cash::new("RUB", dec!(1000.5))), CellValue::Empty,
+                ],
+                totals: true,
+            }],
+        };
+
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().with_extension("xlsx");
+        let path = path.to_str().unwrap();
+        write(path, &[&table]).unwrap();
+
+        let mut workbook = open_workbook_auto(path).unwrap();
+        assert_eq!(workbook.sheet_names(), vec![s!("Deposits & interest")]);
+
+        let sheet = workbook.worksheet_range("Deposits & interest").unwrap().unwrap();
+        assert_eq!(sheet.get_value((0, 0)), Some(&DataType::String(s!("Deposits & interest"))));
+        assert_eq!(sheet.get_value((2, 2)), Some(&DataType::String(s!("Amount"))));
+        assert!(!matches!(sheet.get_value((3, 0)), None | Some(DataType::Empty)));
+        assert_eq!(sheet.get_value((3, 1)), Some(&DataType::String(s!("Deposit <1>"))));
+        assert_eq!(sheet.get_value((3, 2)), Some(&DataType::Float(1000.5)));
+        assert_eq!(sheet.get_value((3, 3)), Some(&DataType::Float(0.065)));
+        assert_eq!(sheet.get_value((4, 2)), Some(&DataType::Float(1000.5)));
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
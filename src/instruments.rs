@@ -87,6 +87,10 @@ impl InstrumentInfo {
         }
     }
 
+    pub fn get_type(&self, symbol: &str) -> InstrumentType {
+        self.instruments.get(symbol).map(|instrument| instrument.type_).unwrap_or(InstrumentType::Stock)
+    }
+
     pub fn get(&self, symbol: &str) -> Option<&Instrument> {
         self.instruments.get(symbol)
     }
@@ -178,6 +182,7 @@ impl InstrumentInfo {
 
 pub struct Instrument {
     pub symbol: String,
+    pub type_: InstrumentType,
     name: Option<String>,
     pub isin: HashSet<ISIN>,
     cusip: HashSet<CUSIP>,
@@ -188,6 +193,7 @@ impl Instrument {
     fn new(symbol: &str) -> Instrument {
         Instrument {
             symbol:    symbol.to_owned(),
+            type_:     InstrumentType::Stock,
             name:      None,
             isin:      HashSet::new(),
             cusip:     HashSet::new(),
@@ -199,6 +205,10 @@ impl Instrument {
         self.name.replace(name.to_owned());
     }
 
+    pub fn set_type(&mut self, type_: InstrumentType) {
+        self.type_ = type_;
+    }
+
     pub fn add_isin(&mut self, isin: ISIN) {
         self.isin.insert(isin);
     }
@@ -251,6 +261,9 @@ impl Instrument {
     }
 
     fn merge(&mut self, other: Instrument) {
+        if other.type_ != InstrumentType::Stock {
+            self.type_ = other.type_;
+        }
         if let Some(name) = other.name {
             self.name.replace(name);
         }
@@ -260,6 +273,21 @@ impl Instrument {
     }
 }
 
+// Derivatives are taxed as a separate income category, but their results may be netted with results
+// of trading with securities.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InstrumentType {
+    Stock,
+    Option,
+    Future,
+}
+
+impl InstrumentType {
+    pub fn is_derivative(self) -> bool {
+        self != InstrumentType::Stock
+    }
+}
+
 #[derive(Clone, PartialEq)]
 pub enum IssuerTaxationType {
     Manual(Option<String>),
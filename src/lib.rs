@@ -10,8 +10,10 @@ pub mod analysis;
 pub mod cash_flow;
 pub mod config;
 pub mod cli;
+pub mod currency;
 pub mod db;
 pub mod deposits;
+pub mod formatting;
 pub mod metrics;
 pub mod portfolio;
 pub mod tax_statement;
@@ -22,10 +24,8 @@ pub mod util;
 mod broker_statement;
 mod brokers;
 mod commissions;
-mod currency;
 mod exchanges;
 mod forex;
-mod formatting;
 mod instruments;
 mod localities;
 mod quotes;
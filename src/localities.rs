@@ -2,6 +2,7 @@ use std::collections::{HashMap, BTreeMap};
 use std::ops::Bound;
 
 use chrono::{Datelike, Duration};
+use serde::{Deserialize, Deserializer, de::Error};
 
 use crate::currency::Cash;
 use crate::taxes::IncomeType;
@@ -13,13 +14,28 @@ pub struct Country {
     pub currency: &'static str,
     default_tax_rate: Decimal,
     tax_rates: HashMap<IncomeType, BTreeMap<i32, Decimal>>,
+    progressive_tax_rates: BTreeMap<i32, Vec<TaxBracket>>,
     tax_precision: u32,
 }
 
+/// Income of the tax year above the threshold is taxed with the specified rate
+#[derive(Clone, Copy)]
+struct TaxBracket {
+    threshold: Decimal,
+    rate: Decimal,
+}
+
+impl TaxBracket {
+    fn new(threshold: Decimal, rate: Decimal) -> TaxBracket {
+        TaxBracket {threshold, rate}
+    }
+}
+
 impl Country {
     fn new(
         currency: &'static str, mut default_tax_rate: Decimal,
-        mut tax_rates: HashMap<IncomeType, BTreeMap<i32, Decimal>>, tax_precision: u32,
+        mut tax_rates: HashMap<IncomeType, BTreeMap<i32, Decimal>>,
+        mut progressive_tax_rates: BTreeMap<i32, Vec<TaxBracket>>, tax_precision: u32,
     ) -> Country {
         default_tax_rate /= dec!(100);
 
@@ -29,7 +45,17 @@ impl Country {
             }
         }
 
-        Country {currency, default_tax_rate, tax_rates, tax_precision}
+        for brackets in progressive_tax_rates.values_mut() {
+            let mut prev_threshold = dec!(0);
+
+            for bracket in brackets.iter_mut() {
+                assert!(bracket.threshold > prev_threshold);
+                prev_threshold = bracket.threshold;
+                bracket.rate /= dec!(100);
+            }
+        }
+
+        Country {currency, default_tax_rate, tax_rates, progressive_tax_rates, tax_precision}
     }
 
     pub fn cash(&self, amount: Decimal) -> Cash {
@@ -43,6 +69,16 @@ impl Country {
 
     pub fn tax_to_pay(
         &self, income_type: IncomeType, year: i32, income: Cash, paid_tax: Option<Cash>,
+    ) -> Cash {
+        self.tax_to_pay_with_base(income_type, year, income, dec!(0), paid_tax)
+    }
+
+    /// Calculates tax for the income which is received on top of the specified taxable income
+    /// (tax base) of the same tax year: progressive tax rates are applied to the total income of
+    /// the tax year.
+    pub fn tax_to_pay_with_base(
+        &self, income_type: IncomeType, year: i32, income: Cash, tax_base: Decimal,
+        paid_tax: Option<Cash>,
     ) -> Cash {
         assert_eq!(income.currency, self.currency);
 
@@ -51,7 +87,8 @@ impl Country {
             return Cash::zero(self.currency);
         }
 
-        let tax_to_pay = self.round_tax(income * self.tax_rate(income_type, year));
+        let tax_base = std::cmp::max(tax_base, dec!(0));
+        let tax_to_pay = self.round_tax(self.cash(self.tax(income_type, year, income.amount, tax_base)));
 
         if let Some(paid_tax) = paid_tax {
             assert!(!paid_tax.is_negative());
@@ -68,22 +105,87 @@ impl Country {
         }
     }
 
+    /// Calculates income which is left as the specified amount after the taxation
     pub fn deduce_income(&self, income_type: IncomeType, year: i32, result_income: Cash) -> Cash {
+        self.deduce_income_with_base(income_type, year, result_income, dec!(0))
+    }
+
+    /// Calculates income which is left as the specified amount after the taxation when it's
+    /// received on top of the specified taxable income (tax base) of the same tax year
+    pub fn deduce_income_with_base(
+        &self, income_type: IncomeType, year: i32, result_income: Cash, tax_base: Decimal,
+    ) -> Cash {
         assert_eq!(result_income.currency, self.currency);
-        (result_income / (dec!(1) - self.tax_rate(income_type, year))).round()
+
+        let tax_base = std::cmp::max(tax_base, dec!(0));
+        let mut rate = self.tax_rate(income_type, year);
+        let mut income = tax_base;
+        let mut remaining = result_income.amount;
+
+        // Income is taxed with the bracket's rate until total income of the year reaches the next
+        // threshold
+        for bracket in self.tax_brackets(income_type, year) {
+            if bracket.threshold > income {
+                let net_income = (bracket.threshold - income) * (dec!(1) - rate);
+                if remaining <= net_income {
+                    break;
+                }
+
+                income = bracket.threshold;
+                remaining -= net_income;
+            }
+
+            rate = bracket.rate;
+        }
+
+        self.cash(income - tax_base + remaining / (dec!(1) - rate)).round()
+    }
+
+    fn tax(&self, income_type: IncomeType, year: i32, income: Decimal, tax_base: Decimal) -> Decimal {
+        let mut rate = self.tax_rate(income_type, year);
+        let mut tax = income * rate;
+
+        // Each bracket adds its rate increment for the part of income which is above its threshold
+        for bracket in self.tax_brackets(income_type, year) {
+            let taxable_above_threshold =
+                std::cmp::max(tax_base + income, bracket.threshold) -
+                std::cmp::max(tax_base, bracket.threshold);
+
+            tax += taxable_above_threshold * (bracket.rate - rate);
+            rate = bracket.rate;
+        }
+
+        tax
     }
 
     fn tax_rate(&self, income_type: IncomeType, year: i32) -> Decimal {
+        self.configured_tax_rate(income_type, year).unwrap_or(self.default_tax_rate)
+    }
+
+    fn configured_tax_rate(&self, income_type: IncomeType, year: i32) -> Option<Decimal> {
         self.tax_rates.get(&income_type).and_then(|tax_rates| {
             tax_rates
                 .range((Bound::Unbounded, Bound::Included(year)))
                 .map(|entry| *entry.1)
                 .last()
-        }).unwrap_or(self.default_tax_rate)
+        })
+    }
+
+    // Explicitly configured tax rates are considered as flat ones and disable the progressive scale
+    fn tax_brackets(&self, income_type: IncomeType, year: i32) -> &[TaxBracket] {
+        if self.configured_tax_rate(income_type, year).is_some() {
+            return &[];
+        }
+
+        self.progressive_tax_rates
+            .range((Bound::Unbounded, Bound::Included(year)))
+            .map(|entry| entry.1.as_slice())
+            .last()
+            .unwrap_or(&[])
     }
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Jurisdiction {
     Russia,
     Usa,
@@ -105,6 +207,18 @@ impl Jurisdiction {
     }
 }
 
+impl<'de> Deserialize<'de> for Jurisdiction {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+        let value = String::deserialize(deserializer)?;
+
+        Ok(match value.as_str() {
+            "russia" => Jurisdiction::Russia,
+            "usa" => Jurisdiction::Usa,
+            _ => return Err(D::Error::unknown_variant(&value, &["russia", "usa"])),
+        })
+    }
+}
+
 // When we work with taxes in Russia, the following rounding rules are applied:
 // 1. Result of all calculations must be with kopecks precision
 // 2. If we have income in foreign currency then:
@@ -118,6 +232,9 @@ impl Jurisdiction {
 // It calculates tax for $10.64 income with 65.4244 currency rate as following:
 // 1. income = round(10.64 * 65.4244, 2) = 696.12 (696.115616 without rounding)
 // 2. tax = round(round(696.12 * 0.13, 2), 0) = 91 (90.4956 without rounding)
+//
+// Since 2021 income above 5M RUB per year is taxed with 15% rate. The threshold is applied to the
+// total income of the tax year (trading, dividends and interest together).
 pub fn russia(
     trading_tax_rates: &BTreeMap<i32, Decimal>, dividends_tax_rates: &BTreeMap<i32, Decimal>,
     interest_tax_rates: &BTreeMap<i32, Decimal>,
@@ -126,13 +243,15 @@ pub fn russia(
         IncomeType::Trading => trading_tax_rates.clone(),
         IncomeType::Dividends => dividends_tax_rates.clone(),
         IncomeType::Interest => interest_tax_rates.clone(),
+    }, btreemap!{
+        2021 => vec![TaxBracket::new(dec!(5_000_000), dec!(15))],
     }, 0)
 }
 
 pub fn us() -> Country {
     Country::new("USD", dec!(0), hashmap!{
         IncomeType::Dividends => btreemap!{0 => dec!(10)},
-    }, 2)
+    }, BTreeMap::new(), 2)
 }
 
 pub fn is_valid_execution_date(conclusion: Date, execution: Date) -> bool {
@@ -156,6 +275,60 @@ pub fn get_russian_central_bank_min_last_working_day(today: Date) -> Date {
     }
 }
 
+/// Returns Central Bank of Russia key rate (in percents) which was in effect on the specified date or
+/// None if the date isn't covered by the known rates.
+pub fn get_russian_central_bank_key_rate(date: Date) -> Option<Decimal> {
+    // The last date for which the rates below are known to be actual
+    let last_date = date!(2021, 9, 12);
+    if date > last_date {
+        return None;
+    }
+
+    let rates = [
+        (date!(2013,  9, 13), dec!(5.50)),
+        (date!(2014,  3,  3), dec!(7.00)),
+        (date!(2014,  4, 28), dec!(7.50)),
+        (date!(2014,  7, 28), dec!(8.00)),
+        (date!(2014, 11,  5), dec!(9.50)),
+        (date!(2014, 12, 12), dec!(10.50)),
+        (date!(2014, 12, 16), dec!(17.00)),
+        (date!(2015,  2,  2), dec!(15.00)),
+        (date!(2015,  3, 16), dec!(14.00)),
+        (date!(2015,  5,  5), dec!(12.50)),
+        (date!(2015,  6, 16), dec!(11.50)),
+        (date!(2015,  8,  3), dec!(11.00)),
+        (date!(2016,  6, 14), dec!(10.50)),
+        (date!(2016,  9, 19), dec!(10.00)),
+        (date!(2017,  3, 27), dec!(9.75)),
+        (date!(2017,  5,  2), dec!(9.25)),
+        (date!(2017,  6, 19), dec!(9.00)),
+        (date!(2017,  9, 18), dec!(8.50)),
+        (date!(2017, 10, 30), dec!(8.25)),
+        (date!(2017, 12, 18), dec!(7.75)),
+        (date!(2018,  2, 12), dec!(7.50)),
+        (date!(2018,  3, 26), dec!(7.25)),
+        (date!(2018,  9, 17), dec!(7.50)),
+        (date!(2018, 12, 17), dec!(7.75)),
+        (date!(2019,  6, 17), dec!(7.50)),
+        (date!(2019,  7, 29), dec!(7.25)),
+        (date!(2019,  9,  9), dec!(7.00)),
+        (date!(2019, 10, 28), dec!(6.50)),
+        (date!(2019, 12, 16), dec!(6.25)),
+        (date!(2020,  2, 10), dec!(6.00)),
+        (date!(2020,  4, 27), dec!(5.50)),
+        (date!(2020,  6, 22), dec!(4.50)),
+        (date!(2020,  7, 27), dec!(4.25)),
+        (date!(2021,  3, 22), dec!(4.50)),
+        (date!(2021,  4, 26), dec!(5.00)),
+        (date!(2021,  6, 15), dec!(5.50)),
+        (date!(2021,  7, 26), dec!(6.50)),
+    ];
+
+    rates.iter().rev()
+        .find(|&&(start_date, _)| start_date <= date)
+        .map(|&(_, rate)| rate)
+}
+
 pub fn get_russian_stock_exchange_min_last_working_day(today: Date) -> Date {
     if today.month() == 1 && today.day() < 4 {
         today - Duration::days(4)
@@ -173,4 +346,44 @@ pub fn nearest_possible_account_close_date() -> Date {
     }
 
     close_date
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progressive_tax_rates() {
+        let no_rates = BTreeMap::new();
+        let country = russia(&no_rates, &no_rates, &no_rates);
+        let tax = |year, income, tax_base| country.tax_to_pay_with_base(
+            IncomeType::Trading, year, country.cash(income), tax_base, None).amount;
+
+        assert_eq!(tax(2020, dec!(10_000_000), dec!(0)), dec!(1_300_000));
+
+        assert_eq!(tax(2021, dec!(4_000_000), dec!(0)), dec!(520_000));
+        assert_eq!(tax(2021, dec!(10_000_000), dec!(0)), dec!(1_400_000));
+        assert_eq!(tax(2021, dec!(2_000_000), dec!(4_000_000)), dec!(280_000));
+        assert_eq!(tax(2021, dec!(1_000_000), dec!(6_000_000)), dec!(150_000));
+
+        let deduce = |year, income| country.deduce_income(
+            IncomeType::Trading, year, country.cash(income)).amount;
+
+        assert_eq!(deduce(2020, dec!(870_000)), dec!(1_000_000));
+        assert_eq!(deduce(2021, dec!(870_000)), dec!(1_000_000));
+        assert_eq!(deduce(2021, dec!(8_600_000)), dec!(10_000_000));
+
+        let deduce_with_base = |year, income, tax_base| country.deduce_income_with_base(
+            IncomeType::Trading, year, country.cash(income), tax_base).amount;
+
+        assert_eq!(deduce_with_base(2020, dec!(870_000), dec!(6_000_000)), dec!(1_000_000));
+        assert_eq!(deduce_with_base(2021, dec!(850_000), dec!(6_000_000)), dec!(1_000_000));
+        assert_eq!(deduce_with_base(2021, dec!(860_000), dec!(4_500_000)), dec!(1_000_000));
+        assert_eq!(deduce_with_base(2021, dec!(870_000), dec!(3_000_000)), dec!(1_000_000));
+
+        let flat_rates = btreemap!{2021 => dec!(13)};
+        let country = russia(&flat_rates, &no_rates, &no_rates);
+        assert_eq!(country.tax_to_pay(
+            IncomeType::Trading, 2021, country.cash(dec!(10_000_000)), None).amount, dec!(1_300_000));
+    }
+}
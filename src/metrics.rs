@@ -5,7 +5,7 @@ use lazy_static::lazy_static;
 use num_traits::ToPrimitive;
 use prometheus::{self, TextEncoder, Encoder, Gauge, GaugeVec, register_gauge, register_gauge_vec};
 
-use crate::analysis::{self, PortfolioCurrencyStatistics, LtoStatistics};
+use crate::analysis::{self, PortfolioCurrencyStatistics, InstrumentPerformanceAnalysis, LtoStatistics};
 use crate::config::Config;
 use crate::core::{EmptyResult, GenericError, GenericResult};
 use crate::currency::converter::CurrencyConverter;
@@ -26,6 +26,24 @@ lazy_static! {
     static ref PERFORMANCE: GaugeVec = register_instrument_metric(
         "performance", "Instrument performance");
 
+    static ref XIRR: GaugeVec = register_instrument_metric(
+        "xirr", "Money-weighted rate of return (XIRR)");
+
+    static ref TWR: GaugeVec = register_instrument_metric(
+        "twr", "Annualized time-weighted rate of return");
+
+    static ref VOLATILITY: GaugeVec = register_portfolio_metric(
+        "volatility", "Annualized portfolio volatility");
+
+    static ref MAX_DRAWDOWN: GaugeVec = register_portfolio_metric(
+        "max_drawdown", "Portfolio maximum drawdown");
+
+    static ref SHARPE_RATIO: GaugeVec = register_portfolio_metric(
+        "sharpe_ratio", "Portfolio Sharpe ratio");
+
+    static ref SORTINO_RATIO: GaugeVec = register_portfolio_metric(
+        "sortino_ratio", "Portfolio Sortino ratio");
+
     static ref INCOME_STRUCTURE: GaugeVec = register_structure_metric(
         "income_structure", "Net income structure");
 
@@ -59,7 +77,7 @@ lazy_static! {
 
 pub fn collect(config: &Config, path: &str) -> GenericResult<TelemetryRecordBuilder> {
     let (statistics, converter, telemetry) = analysis::analyse(
-        config, None, false, Some(&config.metrics.merge_performance), false)?;
+        config, None, false, Some(&config.metrics.merge_performance), config.metrics.risk_metrics, false)?;
 
     UPDATE_TIME.set(cast::f64(time::utc_now().timestamp()));
 
@@ -89,13 +107,25 @@ fn collect_portfolio_metrics(statistics: &PortfolioCurrencyStatistics) {
     }
 
     for (instrument, analysis) in &performance.instruments {
-        if let Some(interest) = analysis.interest {
-            set_instrument_metric(&PERFORMANCE, currency, instrument, interest);
-        }
+        collect_performance_metrics(currency, instrument, analysis);
     }
+    collect_performance_metrics(currency, "Portfolio", &performance.portfolio);
+
+    for benchmark in &performance.benchmarks {
+        collect_performance_metrics(currency, &benchmark.name, benchmark);
+    }
+
+    if let Some(risk) = statistics.risk.as_ref() {
+        set_portfolio_metric(&VOLATILITY, currency, risk.volatility);
+        set_portfolio_metric(&MAX_DRAWDOWN, currency, risk.max_drawdown);
 
-    if let Some(interest) = performance.portfolio.interest {
-        set_instrument_metric(&PERFORMANCE, currency, "Portfolio", interest);
+        if let Some(sharpe_ratio) = risk.sharpe_ratio {
+            set_portfolio_metric(&SHARPE_RATIO, currency, sharpe_ratio);
+        }
+
+        if let Some(sortino_ratio) = risk.sortino_ratio {
+            set_portfolio_metric(&SORTINO_RATIO, currency, sortino_ratio);
+        }
     }
 
     set_portfolio_metric(&PROFIT, currency, income_structure.profit());
@@ -114,6 +144,20 @@ fn collect_portfolio_metrics(statistics: &PortfolioCurrencyStatistics) {
     set_portfolio_metric(&PROJECTED_COMMISSIONS, currency, statistics.projected_commissions);
 }
 
+fn collect_performance_metrics(currency: &str, instrument: &str, analysis: &InstrumentPerformanceAnalysis) {
+    if let Some(interest) = analysis.interest {
+        set_instrument_metric(&PERFORMANCE, currency, instrument, interest);
+    }
+
+    if let Some(xirr) = analysis.xirr {
+        set_instrument_metric(&XIRR, currency, instrument, xirr);
+    }
+
+    if let Some(twr) = analysis.twr {
+        set_instrument_metric(&TWR, currency, instrument, twr);
+    }
+}
+
 fn collect_lto_metrics(lto: &LtoStatistics) {
     for (year, result) in &lto.applied {
         let year = year.to_string();
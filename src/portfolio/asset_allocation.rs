@@ -2,15 +2,18 @@ use std::collections::{HashSet, HashMap};
 
 use crate::broker_statement::BrokerStatement;
 use crate::brokers::BrokerInfo;
-use crate::config::{PortfolioConfig, AssetAllocationConfig};
+use crate::config::{self, PortfolioConfig, AssetAllocationConfig, BandRebalancingTarget};
 use crate::core::{EmptyResult, GenericResult};
-use crate::currency::Cash;
+use crate::currency::{Cash, MultiCurrencyCashAccount};
 use crate::currency::converter::CurrencyConverter;
 use crate::quotes::{Quotes, QuoteQuery};
+use crate::time::{self, Date};
 use crate::types::{Decimal, TradeType};
 use crate::util;
 
 use super::Assets;
+use super::forex::CurrencyConversion;
+use super::taxes::TaxLots;
 
 pub struct Portfolio {
     pub name: String,
@@ -19,20 +22,33 @@ pub struct Portfolio {
 
     pub min_trade_volume: Decimal,
     pub min_cash_assets: Decimal,
+    pub max_sell_tax: Decimal,
 
     pub assets: Vec<AssetAllocation>,
+    pub cash_balances: MultiCurrencyCashAccount,
     pub current_cash_assets: Decimal,
     pub current_net_value: Decimal,
 
     pub target_cash_assets: Decimal,
     pub target_net_value: Decimal,
+    pub currency_conversions: Vec<CurrencyConversion>,
     pub commissions: Decimal,
+    pub taxes: Decimal,
 }
 
 impl Portfolio {
     pub fn load(
         config: &PortfolioConfig, broker: BrokerInfo, assets: Assets,
         statement: Option<&BrokerStatement>, converter: &CurrencyConverter, quotes: &Quotes
+    ) -> GenericResult<Portfolio> {
+        Portfolio::load_allocation(config, &config.assets, broker, assets, statement, converter, quotes)
+    }
+
+    /// Loads the portfolio with the specified asset allocation instead of the configured one
+    pub fn load_allocation(
+        config: &PortfolioConfig, allocation: &[AssetAllocationConfig], broker: BrokerInfo,
+        assets: Assets, statement: Option<&BrokerStatement>, converter: &CurrencyConverter,
+        quotes: &Quotes,
     ) -> GenericResult<Portfolio> {
         let currency = config.currency()?;
 
@@ -46,11 +62,11 @@ impl Portfolio {
             return Err!("Invalid minimum free cash assets value")
         }
 
-        if config.assets.is_empty() {
+        if allocation.is_empty() {
             return Err!("The portfolio has no asset allocation configuration");
         }
 
-        for symbol in config.get_stock_symbols() {
+        for symbol in config::get_stock_symbols(allocation) {
             quotes.batch(match statement {
                 Some(statement) => statement.get_quote_query(&symbol),
                 None => QuoteQuery::Stock(symbol, broker.exchanges()),
@@ -64,9 +80,12 @@ impl Portfolio {
         let mut symbols = HashSet::new();
         let mut assets_allocation = Vec::new();
 
-        for assets_config in &config.assets {
+        let context = WeightContext {date: time::today(), birth_year: config.birth_year};
+        let weights = config::get_asset_weights(allocation, context.date, context.birth_year)?;
+
+        for (assets_config, weight) in allocation.iter().zip(weights) {
             let mut asset_allocation = AssetAllocation::load(
-                &broker, assets_config, currency, &mut symbols, &mut stocks,
+                &broker, assets_config, weight, &context, currency, &mut symbols, &mut stocks,
                 statement, converter, quotes)?;
 
             asset_allocation.apply_restrictions(
@@ -83,14 +102,18 @@ impl Portfolio {
 
             min_trade_volume: min_trade_volume,
             min_cash_assets: min_cash_assets,
+            max_sell_tax: config.max_sell_tax.unwrap_or_else(|| dec!(0)),
 
             assets: assets_allocation,
+            cash_balances: assets.cash,
             current_cash_assets: cash_assets,
             current_net_value: net_value,
 
             target_cash_assets: cash_assets,
             target_net_value: net_value,
+            currency_conversions: Vec::new(),
             commissions: dec!(0),
+            taxes: dec!(0),
         };
         check_weights(&portfolio.name, &portfolio.assets)?;
 
@@ -106,6 +129,54 @@ impl Portfolio {
         Ok(portfolio)
     }
 
+    #[cfg(test)]
+    pub fn mock(broker: BrokerInfo, currency: &str, cash: Decimal, assets: Vec<AssetAllocation>) -> Portfolio {
+        let net_value = cash + assets.iter().map(|asset| asset.current_value).sum::<Decimal>();
+
+        let mut cash_balances = MultiCurrencyCashAccount::new();
+        cash_balances.deposit(Cash::new(currency, cash));
+
+        let mut portfolio = Portfolio {
+            name: s!("mock"),
+            broker: broker,
+            currency: currency.to_owned(),
+
+            min_trade_volume: dec!(0),
+            min_cash_assets: dec!(0),
+            max_sell_tax: dec!(0),
+
+            assets: assets,
+            cash_balances: cash_balances,
+            current_cash_assets: cash,
+            current_net_value: net_value,
+
+            target_cash_assets: cash,
+            target_net_value: net_value,
+            currency_conversions: Vec::new(),
+            commissions: dec!(0),
+            taxes: dec!(0),
+        };
+
+        let fractional_shares_trading = portfolio.broker.fractional_shares_trading;
+        for holding in portfolio.stock_holdings() {
+            holding.fractional_shares_trading = fractional_shares_trading;
+        }
+
+        portfolio
+    }
+
+    // 60/40 portfolio of VTI and equally weighted BND and VCIT
+    #[cfg(test)]
+    pub fn mock_stocks_and_bonds(broker: BrokerInfo, bnd_shares: Decimal) -> Portfolio {
+        Portfolio::mock(broker, "USD", dec!(100), vec![
+            AssetAllocation::mock_stock("Stocks", "VTI", dec!(0.6), dec!(200), dec!(3)),
+            AssetAllocation::mock_group("Bonds", dec!(0.4), vec![
+                AssetAllocation::mock_stock("Treasury", "BND", dec!(0.5), dec!(80), bnd_shares),
+                AssetAllocation::mock_stock("Corporate", "VCIT", dec!(0.5), dec!(90), dec!(2)),
+            ]),
+        ])
+    }
+
     pub fn change_commission(&mut self, commission: Decimal) {
         // The commission may be positive in case of withdrawal or negative in case of reverting of
         // previously withdrawn commission.
@@ -114,6 +185,100 @@ impl Portfolio {
         self.target_net_value -= commission;
         self.target_cash_assets -= commission;
     }
+
+    /// Emulates deposit of the specified amount to the account
+    pub fn contribute(&mut self, amount: Decimal) {
+        self.cash_balances.deposit(Cash::new(&self.currency, amount));
+
+        self.current_cash_assets += amount;
+        self.current_net_value += amount;
+
+        self.target_cash_assets += amount;
+        self.target_net_value += amount;
+    }
+
+    /// Restricts selling of all assets regardless of their configuration
+    pub fn restrict_selling(&mut self) {
+        for asset in &mut self.assets {
+            asset.force_selling_restriction();
+        }
+    }
+
+    /// Returns true if some asset has a tolerance band
+    pub fn has_bands(&self) -> bool {
+        fn check(assets: &[AssetAllocation]) -> bool {
+            assets.iter().any(|asset| asset.band.is_some() || match asset.holding {
+                Holding::Group(ref holdings) => check(holdings),
+                Holding::Stock(_) => false,
+            })
+        }
+        check(&self.assets)
+    }
+
+    /// Returns the assets which are out of their tolerance bands
+    pub fn band_breaches(&self) -> Vec<BandBreach> {
+        fn collect(assets: &[AssetAllocation], total_value: Decimal, breaches: &mut Vec<BandBreach>) {
+            for asset in assets {
+                if let (Some(band), Some(position)) = (asset.band, asset.band_position(total_value)) {
+                    if !matches!(position, BandPosition::Inside) {
+                        let width = band.width(asset.expected_weight);
+
+                        breaches.push(BandBreach {
+                            name: asset.full_name(),
+                            current_weight: asset.current_value / total_value,
+                            expected_weight: asset.expected_weight,
+                            lower_edge: std::cmp::max(dec!(0), asset.expected_weight - width),
+                            upper_edge: asset.expected_weight + width,
+                        });
+                    }
+                }
+
+                if let Holding::Group(ref holdings) = asset.holding {
+                    collect(holdings, asset.current_value, breaches);
+                }
+            }
+        }
+
+        let mut breaches = Vec::new();
+        collect(&self.assets, self.target_net_value - self.min_cash_assets, &mut breaches);
+        breaches
+    }
+
+    pub fn get_stock_holdings(&self) -> Vec<&StockHolding> {
+        fn collect<'a>(assets: &'a [AssetAllocation], holdings: &mut Vec<&'a StockHolding>) {
+            for asset in assets {
+                match asset.holding {
+                    Holding::Stock(ref holding) => holdings.push(holding),
+                    Holding::Group(ref assets) => collect(assets, holdings),
+                }
+            }
+        }
+
+        let mut holdings = Vec::new();
+        collect(&self.assets, &mut holdings);
+        holdings
+    }
+
+    pub fn stock_holdings(&mut self) -> Vec<&mut StockHolding> {
+        fn collect<'a>(assets: &'a mut [AssetAllocation], holdings: &mut Vec<&'a mut StockHolding>) {
+            for asset in assets {
+                match asset.holding {
+                    Holding::Stock(ref mut holding) => holdings.push(holding),
+                    Holding::Group(ref mut assets) => collect(assets, holdings),
+                }
+            }
+        }
+
+        let mut holdings = Vec::new();
+        collect(&mut self.assets, &mut holdings);
+        holdings
+    }
+}
+
+// Asset weights are calculated for the specified date
+struct WeightContext {
+    date: Date,
+    birth_year: Option<i32>,
 }
 
 pub enum Holding {
@@ -127,7 +292,9 @@ pub struct StockHolding {
     pub currency_price: Cash,
     pub current_shares: Decimal,
     pub target_shares: Decimal,
+    pub lot_size: Decimal,
     pub fractional_shares_trading: bool,
+    pub tax_lots: Option<TaxLots>,
 }
 
 impl StockHolding {
@@ -135,6 +302,16 @@ impl StockHolding {
         self.trade_precision_volume(self.trade_precision())
     }
 
+    /// Returns minimum number of shares which can be traded or None if fractional shares are
+    /// supported
+    pub fn trade_lot(&self) -> Option<Decimal> {
+        if self.fractional_shares_trading {
+            None
+        } else {
+            Some(self.lot_size)
+        }
+    }
+
     pub fn iterative_trading_granularity(&self, trade_type: TradeType) -> Decimal {
         let mut precision = self.trade_precision();
         let mut volume = self.trade_precision_volume(precision);
@@ -164,7 +341,10 @@ impl StockHolding {
     }
 
     fn trade_precision_volume(&self, precision: u32) -> Decimal {
-        self.price * Decimal::new(1, precision)
+        match self.trade_lot() {
+            Some(lot) => self.price * lot,
+            None => self.price * Decimal::new(1, precision),
+        }
     }
 }
 
@@ -174,6 +354,7 @@ pub struct AssetAllocation {
     pub expected_weight: Decimal,
     pub restrict_buying: Option<bool>,
     pub restrict_selling: Option<bool>,
+    pub band: Option<Band>,
 
     pub holding: Holding,
     pub current_value: Decimal,
@@ -181,14 +362,17 @@ pub struct AssetAllocation {
 
     pub min_value: Decimal,
     pub max_value: Option<Decimal>,
+    pub inside_band: bool,
 
     pub buy_blocked: bool,
     pub sell_blocked: bool,
 }
 
 impl AssetAllocation {
+    #[allow(clippy::too_many_arguments)]
     fn load(
-        broker: &BrokerInfo, config: &AssetAllocationConfig, currency: &str,
+        broker: &BrokerInfo, config: &AssetAllocationConfig, weight: Decimal, context: &WeightContext,
+        currency: &str,
         symbols: &mut HashSet<String>, stocks: &mut HashMap<String, Decimal>,
         statement: Option<&BrokerStatement>, converter: &CurrencyConverter, quotes: &Quotes,
     ) -> GenericResult<AssetAllocation> {
@@ -204,6 +388,11 @@ impl AssetAllocation {
                     None => QuoteQuery::Stock(symbol.to_owned(), broker.exchanges()),
                 })?;
 
+                let lot_size = config.lot_size.unwrap_or_else(|| dec!(1));
+                if !lot_size.is_sign_positive() || lot_size.is_zero() || !lot_size.fract().is_zero() {
+                    return Err!("Invalid {:?} lot size: {}", config.name, lot_size);
+                }
+
                 let price = converter.real_time_convert_to(currency_price, currency)?;
                 let shares = stocks.remove(symbol).unwrap_or_else(|| dec!(0));
                 let current_value = shares * price;
@@ -214,18 +403,28 @@ impl AssetAllocation {
                     currency_price: currency_price,
                     current_shares: shares,
                     target_shares: shares,
+                    lot_size: lot_size,
                     fractional_shares_trading: broker.fractional_shares_trading,
+                    tax_lots: None,
                 };
 
                 (Holding::Stock(holding), current_value)
             },
             (None, Some(assets)) => {
+                if config.lot_size.is_some() {
+                    return Err!("Invalid {:?} assets configuration: lot size can be specified only for stocks",
+                        config.name);
+                }
+
                 let mut holdings = Vec::new();
                 let mut current_value = dec!(0);
 
-                for asset in assets {
+                let weights = config::get_asset_weights(assets, context.date, context.birth_year)?;
+
+                for (asset, weight) in assets.iter().zip(weights) {
                     let holding = AssetAllocation::load(
-                        broker, asset, currency, symbols, stocks, statement, converter, quotes)?;
+                        broker, asset, weight, context, currency, symbols, stocks, statement,
+                        converter, quotes)?;
 
                     current_value += holding.current_value;
                     holdings.push(holding);
@@ -243,9 +442,10 @@ impl AssetAllocation {
         let mut asset_allocation = AssetAllocation {
             name: config.name.clone(),
 
-            expected_weight: config.weight,
+            expected_weight: weight,
             restrict_buying: None,
             restrict_selling: None,
+            band: None,
 
             holding: holding,
             current_value: current_value,
@@ -253,16 +453,64 @@ impl AssetAllocation {
 
             min_value: dec!(0),
             max_value: None,
+            inside_band: false,
 
             buy_blocked: false,
             sell_blocked: false,
         };
 
         asset_allocation.apply_restrictions(config.restrict_buying, config.restrict_selling);
+        if let Some(band) = Band::new(config)? {
+            asset_allocation.apply_band(band);
+        }
 
         Ok(asset_allocation)
     }
 
+    #[cfg(test)]
+    pub fn mock_stock(name: &str, symbol: &str, weight: Decimal, price: Decimal, shares: Decimal) -> AssetAllocation {
+        let holding = StockHolding {
+            symbol: symbol.to_owned(),
+            price: price,
+            currency_price: Cash::new("USD", price),
+            current_shares: shares,
+            target_shares: shares,
+            lot_size: dec!(1),
+            fractional_shares_trading: false,
+            tax_lots: None,
+        };
+        AssetAllocation::mock(name, weight, Holding::Stock(holding), price * shares)
+    }
+
+    #[cfg(test)]
+    pub fn mock_group(name: &str, weight: Decimal, assets: Vec<AssetAllocation>) -> AssetAllocation {
+        let current_value = assets.iter().map(|asset| asset.current_value).sum();
+        AssetAllocation::mock(name, weight, Holding::Group(assets), current_value)
+    }
+
+    #[cfg(test)]
+    fn mock(name: &str, weight: Decimal, holding: Holding, current_value: Decimal) -> AssetAllocation {
+        AssetAllocation {
+            name: name.to_owned(),
+
+            expected_weight: weight,
+            restrict_buying: None,
+            restrict_selling: None,
+            band: None,
+
+            holding: holding,
+            current_value: current_value,
+            target_value: current_value,
+
+            min_value: dec!(0),
+            max_value: None,
+            inside_band: false,
+
+            buy_blocked: false,
+            sell_blocked: false,
+        }
+    }
+
     pub fn full_name(&self) -> String {
         match self.holding {
             Holding::Group(_) => self.name.clone(),
@@ -308,6 +556,52 @@ impl AssetAllocation {
         }
     }
 
+    fn apply_band(&mut self, band: Band) {
+        if self.band.is_some() {
+            return
+        }
+
+        self.band = Some(band);
+
+        if let Holding::Group(ref mut assets) = self.holding {
+            for asset in assets {
+                asset.apply_band(band);
+            }
+        }
+    }
+
+    /// Returns position of the asset relative to its tolerance band calculated from its current
+    /// weight in the group with the specified total value.
+    pub fn band_position(&self, total_value: Decimal) -> Option<BandPosition> {
+        let band = self.band?;
+        if !total_value.is_sign_positive() || total_value.is_zero() {
+            return None;
+        }
+
+        let width = band.width(self.expected_weight);
+        let lower_edge = self.expected_weight - width;
+        let upper_edge = self.expected_weight + width;
+        let weight = self.current_value / total_value;
+
+        Some(if weight < lower_edge {
+            BandPosition::Below(lower_edge)
+        } else if weight > upper_edge {
+            BandPosition::Above(upper_edge)
+        } else {
+            BandPosition::Inside
+        })
+    }
+
+    fn force_selling_restriction(&mut self) {
+        self.restrict_selling = Some(true);
+
+        if let Holding::Group(ref mut assets) = self.holding {
+            for asset in assets {
+                asset.force_selling_restriction();
+            }
+        }
+    }
+
     pub fn iterative_trading_granularity(&self, trade_type: TradeType) -> Decimal {
         match self.holding {
             Holding::Stock(ref holding) => holding.iterative_trading_granularity(trade_type),
@@ -329,6 +623,65 @@ impl AssetAllocation {
     }
 }
 
+/// Tolerance band of asset weight
+#[derive(Clone, Copy)]
+pub struct Band {
+    absolute: Option<Decimal>,
+    relative: Option<Decimal>,
+    pub rebalance_to: BandRebalancingTarget,
+}
+
+impl Band {
+    fn new(config: &AssetAllocationConfig) -> GenericResult<Option<Band>> {
+        if config.absolute_band.is_none() && config.relative_band.is_none() {
+            if config.rebalance_to.is_some() {
+                return Err!("{:?} assets configuration: rebalance_to requires a tolerance band to be specified",
+                    config.name);
+            }
+            return Ok(None);
+        }
+
+        Ok(Some(Band {
+            absolute: config.absolute_band,
+            relative: config.relative_band,
+            rebalance_to: config.rebalance_to.unwrap_or(BandRebalancingTarget::Target),
+        }))
+    }
+
+    #[cfg(test)]
+    pub fn mock(
+        absolute: Option<Decimal>, relative: Option<Decimal>, rebalance_to: BandRebalancingTarget,
+    ) -> Band {
+        Band {absolute, relative, rebalance_to}
+    }
+
+    /// Returns maximum allowed deviation from the specified weight: the narrowest of the absolute and
+    /// relative bands if both are specified.
+    pub fn width(&self, weight: Decimal) -> Decimal {
+        let relative = self.relative.map(|band| weight * band);
+
+        match (self.absolute, relative) {
+            (Some(absolute), Some(relative)) => std::cmp::min(absolute, relative),
+            (Some(band), None) | (None, Some(band)) => band,
+            (None, None) => unreachable!(),
+        }
+    }
+}
+
+pub struct BandBreach {
+    pub name: String,
+    pub current_weight: Decimal,
+    pub expected_weight: Decimal,
+    pub lower_edge: Decimal,
+    pub upper_edge: Decimal,
+}
+
+pub enum BandPosition {
+    Inside,
+    Below(Decimal),
+    Above(Decimal),
+}
+
 fn check_weights(name: &str, assets: &[AssetAllocation]) -> EmptyResult {
     let mut weight = dec!(0);
 
@@ -342,4 +695,90 @@ fn check_weights(name: &str, assets: &[AssetAllocation]) -> EmptyResult {
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::brokers::Broker;
+    use crate::config::Config;
+
+    use super::*;
+
+    #[test]
+    fn contribution() {
+        let mut portfolio = mock_portfolio();
+        portfolio.contribute(dec!(500));
+
+        assert_eq!(portfolio.cash_balances.get("USD"), Some(Cash::new("USD", dec!(600))));
+        assert_eq!(portfolio.current_cash_assets, dec!(600));
+        assert_eq!(portfolio.current_net_value, dec!(1540));
+        assert_eq!(portfolio.target_cash_assets, dec!(600));
+        assert_eq!(portfolio.target_net_value, dec!(1540));
+    }
+
+    #[test]
+    fn selling_restriction() {
+        let mut portfolio = mock_portfolio();
+
+        portfolio.assets[0].apply_restrictions(None, Some(false));
+        portfolio.assets[1].apply_restrictions(Some(true), Some(false));
+        portfolio.restrict_selling();
+
+        for asset in &portfolio.assets {
+            assert_eq!(asset.restrict_selling, Some(true));
+        }
+
+        let bonds = match portfolio.assets[1].holding {
+            Holding::Group(ref assets) => assets,
+            Holding::Stock(_) => unreachable!(),
+        };
+
+        for asset in bonds {
+            assert_eq!(asset.restrict_buying, Some(true));
+            assert_eq!(asset.restrict_selling, Some(true));
+        }
+    }
+
+    #[rstest(absolute, relative, width,
+        case(Some(dec!(0.05)), None, dec!(0.05)),
+        case(None, Some(dec!(0.25)), dec!(0.1)),
+        case(Some(dec!(0.05)), Some(dec!(0.25)), dec!(0.05)),
+        case(Some(dec!(0.2)), Some(dec!(0.25)), dec!(0.1)),
+    )]
+    fn band_width(absolute: Option<Decimal>, relative: Option<Decimal>, width: Decimal) {
+        let band = Band::mock(absolute, relative, BandRebalancingTarget::Target);
+        assert_eq!(band.width(dec!(0.4)), width);
+    }
+
+    #[rstest(bnd_shares, breaches,
+        // Bonds: 33% with 30-50% band, BND: 47% with 37.5-62.5% band
+        case(dec!(2), vec![]),
+        // Bonds: 27% with 30-50% band, BND: 31% and VCIT: 69% with 37.5-62.5% band
+        case(dec!(1), vec!["Bonds", "Treasury (BND)", "Corporate (VCIT)"]),
+    )]
+    fn band_breaches(bnd_shares: Decimal, breaches: Vec<&str>) {
+        let mut portfolio = mock_portfolio_with(bnd_shares);
+        assert!(!portfolio.has_bands());
+        assert!(portfolio.band_breaches().is_empty());
+
+        let band = Band::mock(None, Some(dec!(0.25)), BandRebalancingTarget::Target);
+        portfolio.assets[1].apply_band(band);
+        assert!(portfolio.has_bands());
+
+        let actual: Vec<String> = portfolio.band_breaches().into_iter()
+            .map(|breach| breach.name)
+            .collect();
+        assert_eq!(actual, breaches);
+    }
+
+    fn mock_portfolio() -> Portfolio {
+        mock_portfolio_with(dec!(2))
+    }
+
+    fn mock_portfolio_with(bnd_shares: Decimal) -> Portfolio {
+        let broker = Broker::InteractiveBrokers.get_info(&Config::mock(), None).unwrap();
+        Portfolio::mock_stocks_and_bonds(broker, bnd_shares)
+    }
+}
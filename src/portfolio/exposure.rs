@@ -0,0 +1,200 @@
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use static_table_derive::StaticTable;
+
+use crate::config::{Config, InstrumentCompositionConfig, PortfolioConfig};
+use crate::core::GenericResult;
+use crate::currency::Cash;
+use crate::currency::converter::CurrencyConverter;
+use crate::db;
+use crate::formatting::table::Cell;
+use crate::quotes::{Quotes, QuoteQuery};
+use crate::telemetry::TelemetryRecordBuilder;
+use crate::types::Decimal;
+
+use super::assets::Assets;
+
+const UNKNOWN: &str = "Unknown";
+const CASH: &str = "Cash";
+
+/// Shows real exposure of the specified portfolios (all portfolios if not specified) by country,
+/// asset class, sector and currency looking through the instruments using their configured
+/// composition.
+pub fn show(
+    config: &Config, portfolio_name: Option<&str>, currency: Option<&str>,
+) -> GenericResult<TelemetryRecordBuilder> {
+    let portfolios: Vec<&PortfolioConfig> = match portfolio_name {
+        Some(name) => vec![config.get_portfolio(name)?],
+        None => {
+            if config.portfolios.is_empty() {
+                return Err!("There is no any portfolio defined in the configuration file");
+            }
+            config.portfolios.iter().collect()
+        },
+    };
+    let currency = currency.unwrap_or(config.get_tax_country().currency);
+
+    let database = db::connect(&config.db_path)?;
+    let quotes = Rc::new(Quotes::new(config, database.clone())?);
+    let converter = CurrencyConverter::new(database.clone(), Some(quotes.clone()), false);
+
+    let mut telemetry = TelemetryRecordBuilder::new();
+    let mut exposure = Exposure::default();
+
+    for portfolio in portfolios {
+        telemetry.add_broker(portfolio.broker);
+
+        let broker = portfolio.broker.get_info(config, portfolio.plan.as_ref())?;
+        let assets = Assets::load(database.clone(), &portfolio.name)?;
+
+        for symbol in assets.stocks.keys() {
+            quotes.batch(QuoteQuery::Stock(symbol.clone(), broker.exchanges()))?;
+        }
+
+        for (symbol, &shares) in &assets.stocks {
+            let price = quotes.get(QuoteQuery::Stock(symbol.clone(), broker.exchanges()))?;
+            let value = converter.real_time_convert_to(price * shares, currency)?;
+            exposure.add_instrument(config.instrument_compositions.get(symbol), price.currency, value);
+        }
+
+        for cash in assets.cash.iter() {
+            let value = converter.real_time_convert_to(cash, currency)?;
+            exposure.add_cash(cash.currency, value);
+        }
+    }
+
+    exposure.print(currency);
+
+    Ok(telemetry)
+}
+
+#[derive(Default)]
+struct Exposure {
+    total: Decimal,
+    countries: BTreeMap<String, Decimal>,
+    asset_classes: BTreeMap<String, Decimal>,
+    sectors: BTreeMap<String, Decimal>,
+    currencies: BTreeMap<String, Decimal>,
+}
+
+impl Exposure {
+    fn add_instrument(
+        &mut self, composition: Option<&InstrumentCompositionConfig>, quote_currency: &str, value: Decimal,
+    ) {
+        self.total += value;
+
+        add(&mut self.countries, composition.map(|c| &c.countries), UNKNOWN, value);
+        add(&mut self.asset_classes, composition.map(|c| &c.asset_classes), UNKNOWN, value);
+        add(&mut self.sectors, composition.map(|c| &c.sectors), UNKNOWN, value);
+
+        // Instrument is assumed to be exposed to its quote currency if its composition is unknown
+        match composition.map(|c| &c.currencies).filter(|currencies| !currencies.is_empty()) {
+            Some(currencies) => add(&mut self.currencies, Some(currencies), UNKNOWN, value),
+            None => add(&mut self.currencies, None, quote_currency, value),
+        }
+    }
+
+    fn add_cash(&mut self, currency: &str, value: Decimal) {
+        self.total += value;
+
+        add(&mut self.countries, None, CASH, value);
+        add(&mut self.asset_classes, None, CASH, value);
+        add(&mut self.sectors, None, CASH, value);
+        add(&mut self.currencies, None, currency, value);
+    }
+
+    fn print(&self, currency: &str) {
+        for (name, exposure) in [
+            ("country", &self.countries),
+            ("asset class", &self.asset_classes),
+            ("sector", &self.sectors),
+            ("currency", &self.currencies),
+        ] {
+            let mut exposure: Vec<(&String, &Decimal)> = exposure.iter().collect();
+            exposure.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+
+            let mut table = ExposureTable::new();
+
+            for (name, &value) in exposure {
+                table.add_row(ExposureRow {
+                    name: name.clone(),
+                    value: Cash::new(currency, value).round(),
+                    weight: if self.total.is_zero() {
+                        Cell::new_empty()
+                    } else {
+                        Cell::new_ratio(value / self.total)
+                    },
+                });
+            }
+
+            table.print(&format!("Exposure by {}", name));
+        }
+    }
+}
+
+fn add(
+    exposure: &mut BTreeMap<String, Decimal>, weights: Option<&BTreeMap<String, Decimal>>,
+    default: &str, value: Decimal,
+) {
+    let mut rest = value;
+
+    for (name, &weight) in weights.into_iter().flatten() {
+        let value = value * weight;
+        *exposure.entry(name.clone()).or_default() += value;
+        rest -= value;
+    }
+
+    if !rest.is_zero() {
+        *exposure.entry(default.to_owned()).or_default() += rest;
+    }
+}
+
+#[derive(StaticTable)]
+#[table(name="ExposureTable")]
+struct ExposureRow {
+    #[column(name="Name")]
+    name: String,
+    #[column(name="Value")]
+    value: Cash,
+    #[column(name="Weight")]
+    weight: Cell,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_composition() {
+        let mut exposure = Exposure::default();
+
+        let composition = InstrumentCompositionConfig {
+            countries: btreemap!{s!("US") => dec!(0.6), s!("China") => dec!(0.3)},
+            asset_classes: btreemap!{s!("Stocks") => dec!(1)},
+            sectors: BTreeMap::new(),
+            currencies: BTreeMap::new(),
+        };
+
+        exposure.add_instrument(Some(&composition), "RUB", dec!(1000));
+        exposure.add_instrument(None, "USD", dec!(500));
+        exposure.add_cash("RUB", dec!(100));
+
+        assert_eq!(exposure.total, dec!(1600));
+        assert_eq!(exposure.countries, btreemap!{
+            s!("US") => dec!(600),
+            s!("China") => dec!(300),
+            s!(UNKNOWN) => dec!(600),
+            s!(CASH) => dec!(100),
+        });
+        assert_eq!(exposure.asset_classes, btreemap!{
+            s!("Stocks") => dec!(1000),
+            s!(UNKNOWN) => dec!(500),
+            s!(CASH) => dec!(100),
+        });
+        assert_eq!(exposure.currencies, btreemap!{
+            s!("RUB") => dec!(1100),
+            s!("USD") => dec!(500),
+        });
+    }
+}
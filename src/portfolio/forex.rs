@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+
+use crate::brokers::BrokerInfo;
+use crate::core::GenericResult;
+use crate::currency::{Cash, MultiCurrencyCashAccount};
+use crate::currency::converter::CurrencyConverter;
+use crate::types::Decimal;
+
+use super::asset_allocation::StockHolding;
+
+/// Currency conversion which is required to fund the buy trades
+pub struct CurrencyConversion {
+    pub from: Cash,
+    pub to: Cash,
+    /// Commission in portfolio currency
+    pub commission: Decimal,
+}
+
+/// Calculates currency conversions which are required to execute the target trades: cash assets of
+/// each currency must cover the buys in this currency after the sells are made. The deficit is
+/// covered from portfolio currency first and then from the currencies with the largest surplus.
+pub fn calculate_conversions(
+    currency: &str, cash_balances: &MultiCurrencyCashAccount, holdings: &[&StockHolding],
+    broker: &BrokerInfo, converter: &CurrencyConverter,
+) -> GenericResult<Vec<CurrencyConversion>> {
+    let portfolio_currency = Cash::zero(currency).currency;
+    let mut balances: BTreeMap<&'static str, Decimal> = BTreeMap::new();
+
+    for cash in cash_balances.iter() {
+        *balances.entry(cash.currency).or_default() += cash.amount;
+    }
+
+    for holding in holdings {
+        let price = holding.currency_price;
+        *balances.entry(price.currency).or_default() -=
+            (holding.target_shares - holding.current_shares) * price.amount;
+    }
+
+    let deficits: Vec<(&'static str, Decimal)> = balances.iter()
+        .filter(|(_, balance)| balance.is_sign_negative())
+        .map(|(&currency, &balance)| (currency, -balance))
+        .collect();
+
+    let mut conversions = Vec::new();
+
+    for (currency, mut deficit) in deficits {
+        while deficit.is_sign_positive() && !deficit.is_zero() {
+            let source = match get_funding_source(&balances, currency, portfolio_currency, converter)? {
+                Some(source) => source,
+                None => break,
+            };
+
+            let available = Cash::new(source, balances[source]);
+            let available_volume = converter.real_time_convert_to(available, currency)?;
+
+            let (from, to) = if available_volume <= deficit {
+                (available, Cash::new(currency, available_volume))
+            } else {
+                let to = Cash::new(currency, deficit);
+                (Cash::new(source, converter.real_time_convert_to(to, source)?), to)
+            };
+
+            *balances.get_mut(source).unwrap() -= from.amount;
+            *balances.get_mut(currency).unwrap() += to.amount;
+            deficit -= to.amount;
+
+            let volume = converter.real_time_convert_to(to, portfolio_currency)?;
+            conversions.push(CurrencyConversion {
+                from: from.round(),
+                to: to.round(),
+                commission: broker.get_forex_commission(volume),
+            });
+        }
+    }
+
+    Ok(conversions)
+}
+
+fn get_funding_source(
+    balances: &BTreeMap<&'static str, Decimal>, currency: &str, portfolio_currency: &'static str,
+    converter: &CurrencyConverter,
+) -> GenericResult<Option<&'static str>> {
+    let mut best_source = None;
+
+    for (&source, &balance) in balances {
+        if source == currency || !balance.is_sign_positive() || balance.is_zero() {
+            continue;
+        } else if source == portfolio_currency {
+            return Ok(Some(source));
+        }
+
+        let value = converter.real_time_convert_to(Cash::new(source, balance), portfolio_currency)?;
+        if best_source.map(|(_, best_value)| value > best_value).unwrap_or(true) {
+            best_source = Some((source, value));
+        }
+    }
+
+    Ok(best_source.map(|(source, _)| source))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::brokers::Broker;
+    use crate::config::Config;
+    use crate::core::EmptyResult;
+    use crate::currency::converter::CurrencyConverterBackend;
+    use crate::localities::Jurisdiction;
+    use crate::portfolio::asset_allocation::{AssetAllocation, Portfolio};
+    use crate::types::Date;
+
+    use super::*;
+
+    #[test]
+    fn no_conversions() {
+        let (portfolio, converter) = mock_portfolio(dec!(0), &[], dec!(1));
+        assert!(calculate(&portfolio, &converter).is_empty());
+    }
+
+    #[test]
+    fn portfolio_currency_funding() {
+        let (portfolio, converter) = mock_portfolio(dec!(150000), &[("EUR", dec!(1000))], dec!(5));
+        check_conversions(&portfolio, &converter, &[
+            (Cash::new("RUB", dec!(68000)), Cash::new("USD", dec!(680))),
+        ]);
+    }
+
+    #[test]
+    fn largest_surplus_funding() {
+        let (portfolio, converter) = mock_portfolio(dec!(22000), &[
+            ("EUR", dec!(500)), ("GBP", dec!(1000)),
+        ], dec!(6));
+
+        check_conversions(&portfolio, &converter, &[
+            (Cash::new("RUB", dec!(22000)), Cash::new("USD", dec!(220))),
+            (Cash::new("GBP", dec!(550)), Cash::new("USD", dec!(660))),
+        ]);
+    }
+
+    #[test]
+    fn insufficient_funds() {
+        let (portfolio, converter) = mock_portfolio(dec!(50000), &[], dec!(10));
+        check_conversions(&portfolio, &converter, &[
+            (Cash::new("RUB", dec!(50000)), Cash::new("USD", dec!(500))),
+        ]);
+    }
+
+    fn check_conversions(portfolio: &Portfolio, converter: &CurrencyConverter, expected: &[(Cash, Cash)]) {
+        let conversions = calculate(portfolio, converter);
+
+        assert_eq!(conversions.iter().map(|conversion| {
+            (conversion.from, conversion.to)
+        }).collect::<Vec<_>>(), expected);
+
+        for conversion in conversions {
+            assert_eq!(conversion.commission, dec!(0));
+        }
+    }
+
+    fn calculate(portfolio: &Portfolio, converter: &CurrencyConverter) -> Vec<CurrencyConversion> {
+        calculate_conversions(
+            &portfolio.currency, &portfolio.cash_balances, &portfolio.get_stock_holdings(),
+            &portfolio.broker, converter,
+        ).unwrap()
+    }
+
+    // RUB portfolio which buys VTI ($200) and sells all of its BND ($80 x 4) stocks
+    fn mock_portfolio(
+        rub_cash: Decimal, cash_assets: &[(&str, Decimal)], vti_shares: Decimal,
+    ) -> (Portfolio, CurrencyConverter) {
+        let broker = Broker::Generic(Jurisdiction::Russia).get_info(&Config::mock(), None).unwrap();
+
+        let mut portfolio = Portfolio::mock(broker, "RUB", rub_cash, vec![
+            AssetAllocation::mock_stock("Stocks", "VTI", dec!(0.6), dec!(200), dec!(0)),
+            AssetAllocation::mock_stock("Bonds", "BND", dec!(0.4), dec!(80), dec!(4)),
+        ]);
+
+        for &(currency, amount) in cash_assets {
+            portfolio.cash_balances.deposit(Cash::new(currency, amount));
+        }
+
+        for holding in portfolio.stock_holdings() {
+            holding.target_shares = match holding.symbol.as_str() {
+                "VTI" => vti_shares,
+                "BND" => dec!(0),
+                _ => unreachable!(),
+            };
+        }
+
+        (portfolio, CurrencyConverter::new_with_backend(Box::new(CurrencyRatesMock {})))
+    }
+
+    struct CurrencyRatesMock {
+    }
+
+    impl CurrencyRatesMock {
+        fn get_rate(currency: &str) -> GenericResult<Decimal> {
+            Ok(match currency {
+                "RUB" => dec!(1),
+                "USD" => dec!(100),
+                "EUR" => dec!(110),
+                "GBP" => dec!(120),
+                _ => return Err!("Unsupported currency: {}", currency),
+            })
+        }
+    }
+
+    impl CurrencyConverterBackend for CurrencyRatesMock {
+        fn batch(&self, _from: &str, _to: &str, _date: Date) -> EmptyResult {
+            Ok(())
+        }
+
+        fn currency_rate(&self, from: &str, to: &str, _date: Date) -> GenericResult<(Option<Decimal>, Option<Decimal>)> {
+            Ok((Some(CurrencyRatesMock::get_rate(from)?), Some(CurrencyRatesMock::get_rate(to)?)))
+        }
+    }
+}
@@ -1,12 +1,15 @@
 use std::fmt::Write;
 
 use ansi_term::{Style, Color, ANSIString};
+use serde::Serialize;
+use serde_json::Value;
 
 use crate::currency::Cash;
+use crate::formatting::output;
 use crate::types::Decimal;
 use crate::util;
 
-use super::asset_allocation::{Portfolio, AssetAllocation, Holding};
+use super::asset_allocation::{Portfolio, AssetAllocation, BandBreach, Holding};
 
 pub fn print_portfolio(portfolio: Portfolio, flat: bool) {
     let mut assets = portfolio.assets;
@@ -14,6 +17,29 @@ pub fn print_portfolio(portfolio: Portfolio, flat: bool) {
         assets = flatify(assets, dec!(1));
     }
 
+    if output::is_structured() {
+        let expected_total_value = portfolio.target_net_value - portfolio.min_cash_assets;
+
+        output::add_value(&portfolio.name, &PortfolioOutput {
+            currency: portfolio.currency.clone(),
+            assets: get_assets_output(assets, expected_total_value),
+            total_value: output::decimal(portfolio.target_net_value),
+            current_cash_assets: output::decimal(portfolio.current_cash_assets),
+            target_cash_assets: output::decimal(portfolio.target_cash_assets),
+            currency_conversions: portfolio.currency_conversions.iter().map(|conversion| {
+                CurrencyConversionOutput {
+                    from: output::cash(conversion.from),
+                    to: output::cash(conversion.to),
+                    commission: output::decimal(conversion.commission),
+                }
+            }).collect(),
+            commissions: output::decimal(portfolio.commissions),
+            taxes: output::decimal(portfolio.taxes),
+        });
+
+        return;
+    }
+
     print_assets(assets, portfolio.target_net_value - portfolio.min_cash_assets, &portfolio.currency, 0);
 
     println!("\n{} {}", colorify_title("Total value:"),
@@ -26,10 +52,136 @@ pub fn print_portfolio(portfolio: Portfolio, flat: bool) {
     }
     println!();
 
+    if !portfolio.currency_conversions.is_empty() {
+        println!("{}", colorify_title("Currency conversions:"));
+        for conversion in &portfolio.currency_conversions {
+            print!("• {} -> {}", conversion.from.format_rounded(), conversion.to.format_rounded());
+            if !conversion.commission.is_zero() {
+                print!(" ({})", colorify_commission(&format_cash(&portfolio.currency, conversion.commission)));
+            }
+            println!();
+        }
+    }
+
     if !portfolio.commissions.is_zero() {
         println!("{} {}", colorify_title("Commissions:"),
                  colorify_commission(&format_cash(&portfolio.currency, portfolio.commissions)));
     }
+
+    if !portfolio.taxes.is_zero() {
+        println!("{} {}", colorify_title("Estimated taxes:"),
+                 colorify_commission(&format_cash(&portfolio.currency, portfolio.taxes)));
+    }
+}
+
+pub fn print_band_breaches(breaches: &[BandBreach]) {
+    if output::is_structured() {
+        let breaches: Vec<BandBreachOutput> = breaches.iter().map(|breach| BandBreachOutput {
+            name: breach.name.clone(),
+            current_weight: output::decimal(breach.current_weight),
+            expected_weight: output::decimal(breach.expected_weight),
+            lower_edge: output::decimal(breach.lower_edge),
+            upper_edge: output::decimal(breach.upper_edge),
+        }).collect();
+
+        output::add_value("Band breaches", &breaches);
+        return;
+    }
+
+    println!("The following assets are out of their tolerance bands:");
+
+    for breach in breaches {
+        println!("• {name}: {current_weight} (target: {expected_weight}, band: {lower_edge} - {upper_edge})",
+                 name=breach.name, current_weight=format_weight(breach.current_weight),
+                 expected_weight=format_weight(breach.expected_weight),
+                 lower_edge=format_weight(breach.lower_edge), upper_edge=format_weight(breach.upper_edge));
+    }
+
+    println!();
+}
+
+#[derive(Serialize)]
+struct PortfolioOutput {
+    currency: String,
+    assets: Vec<AssetOutput>,
+    total_value: Value,
+    current_cash_assets: Value,
+    target_cash_assets: Value,
+    currency_conversions: Vec<CurrencyConversionOutput>,
+    commissions: Value,
+    taxes: Value,
+}
+
+#[derive(Serialize)]
+struct AssetOutput {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    symbol: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    price: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    current_shares: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_shares: Option<Value>,
+    current_value: Value,
+    target_value: Value,
+    expected_weight: Value,
+    buy_blocked: bool,
+    sell_blocked: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    assets: Vec<AssetOutput>,
+}
+
+#[derive(Serialize)]
+struct CurrencyConversionOutput {
+    from: Value,
+    to: Value,
+    commission: Value,
+}
+
+#[derive(Serialize)]
+struct BandBreachOutput {
+    name: String,
+    current_weight: Value,
+    expected_weight: Value,
+    lower_edge: Value,
+    upper_edge: Value,
+}
+
+fn get_assets_output(mut assets: Vec<AssetAllocation>, expected_total_value: Decimal) -> Vec<AssetOutput> {
+    assets.sort_by_key(|asset: &AssetAllocation| -asset.target_value);
+
+    assets.into_iter().map(|asset| {
+        let expected_value = expected_total_value * asset.expected_weight;
+
+        let mut asset_output = AssetOutput {
+            name: asset.full_name(),
+            symbol: None,
+            price: None,
+            current_shares: None,
+            target_shares: None,
+            current_value: output::decimal(asset.current_value),
+            target_value: output::decimal(asset.target_value),
+            expected_weight: output::decimal(asset.expected_weight),
+            buy_blocked: asset.buy_blocked,
+            sell_blocked: asset.sell_blocked,
+            assets: Vec::new(),
+        };
+
+        match asset.holding {
+            Holding::Stock(holding) => {
+                asset_output.price = Some(output::cash(holding.currency_price));
+                asset_output.current_shares = Some(output::decimal(holding.current_shares));
+                asset_output.target_shares = Some(output::decimal(holding.target_shares));
+                asset_output.symbol = Some(holding.symbol);
+            },
+            Holding::Group(holdings) => {
+                asset_output.assets = get_assets_output(holdings, expected_value);
+            },
+        }
+
+        asset_output
+    }).collect()
 }
 
 fn flatify(assets: Vec<AssetAllocation>, expected_weight: Decimal) -> Vec<AssetAllocation> {
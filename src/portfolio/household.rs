@@ -0,0 +1,688 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use log::debug;
+use static_table_derive::StaticTable;
+
+use crate::config::{
+    self, Config, AssetAllocationConfig, HouseholdConfig, HouseholdPortfolioConfig, WeightConfig};
+use crate::core::{EmptyResult, GenericResult};
+use crate::currency::Cash;
+use crate::currency::converter::{CurrencyConverter, CurrencyConverterRc};
+use crate::db;
+use crate::formatting::table::Cell;
+use crate::quotes::Quotes;
+use crate::telemetry::TelemetryRecordBuilder;
+use crate::time;
+use crate::types::Decimal;
+use crate::util;
+
+use super::asset_allocation::{Portfolio, AssetAllocation, Holding, StockHolding};
+use super::assets::Assets;
+use super::rebalancing;
+
+/// Rebalances a household - several portfolios with a combined asset allocation. The combined
+/// target is calculated for the whole household and then split across the portfolios: overweight
+/// instruments are sold from the portfolios where they can't be bought first and free cash of each
+/// portfolio is spent on the most underweight instruments which are allowed to be bought in it.
+/// The resulting split is used as the asset allocation of each portfolio which is then rebalanced
+/// as a regular portfolio with all its restrictions and commissions.
+pub fn rebalance(config: &Config, name: &str) -> GenericResult<TelemetryRecordBuilder> {
+    let household = config.get_household(name)?;
+    let currency = household.currency.as_str();
+
+    let mut instruments = Vec::new();
+    flatten(household, &household.name, &household.assets, dec!(1), None, None, &mut instruments)?;
+
+    let database = db::connect(&config.db_path)?;
+    let quotes = Rc::new(Quotes::new(config, database.clone())?);
+    let converter = CurrencyConverter::new(database.clone(), Some(quotes.clone()), false);
+
+    let mut telemetry = TelemetryRecordBuilder::new();
+    let mut accounts = Vec::new();
+
+    for portfolio in &household.portfolios {
+        telemetry.add_broker(config.get_portfolio(&portfolio.name)?.broker);
+
+        let account = Account::load(config, portfolio, &instruments, currency, &database, &converter, &quotes)
+            .map_err(|e| format!("{:?} portfolio: {}", portfolio.name, e))?;
+
+        accounts.push(account);
+    }
+
+    let expected_net_value = rebalance_accounts(&mut instruments, &mut accounts, converter)?;
+    print_results(household, &instruments, &accounts, expected_net_value);
+
+    Ok(telemetry)
+}
+
+/// Instrument of the household asset allocation (all values are in household currency)
+struct Instrument {
+    name: String,
+    symbol: String,
+    weight: Decimal,
+    restrict_buying: Option<bool>,
+    restrict_selling: Option<bool>,
+    lot_size: Option<Decimal>,
+
+    expected_value: Decimal,
+    current_value: Decimal,
+    target_value: Decimal,
+}
+
+fn flatten(
+    household: &HouseholdConfig, name: &str, assets: &[AssetAllocationConfig], weight: Decimal,
+    restrict_buying: Option<bool>, restrict_selling: Option<bool>, instruments: &mut Vec<Instrument>,
+) -> EmptyResult {
+    let weights = config::get_asset_weights(assets, time::today(), household.birth_year)?;
+
+    let total_weight: Decimal = weights.iter().sum();
+    if total_weight != dec!(1) {
+        return Err!("{:?} assets have unbalanced weights: {}% total",
+            name, (total_weight * dec!(100)).normalize());
+    }
+
+    for (asset, asset_weight) in assets.iter().zip(weights) {
+        let weight = weight * asset_weight;
+        let restrict_buying = asset.restrict_buying.or(restrict_buying);
+        let restrict_selling = asset.restrict_selling.or(restrict_selling);
+
+        match (&asset.symbol, &asset.assets) {
+            (Some(symbol), None) => {
+                if instruments.iter().any(|instrument| &instrument.symbol == symbol) {
+                    return Err!("Invalid asset allocation configuration: Duplicated symbol: {}", symbol);
+                }
+
+                instruments.push(Instrument {
+                    name: asset.name.clone(),
+                    symbol: symbol.clone(),
+                    weight,
+                    restrict_buying,
+                    restrict_selling,
+                    lot_size: asset.lot_size,
+
+                    expected_value: dec!(0),
+                    current_value: dec!(0),
+                    target_value: dec!(0),
+                });
+            },
+            (None, Some(assets)) => {
+                if asset.lot_size.is_some() {
+                    return Err!("Invalid {:?} assets configuration: lot size can be specified only for stocks",
+                        asset.name);
+                }
+
+                flatten(
+                    household, &asset.name, assets, weight, restrict_buying, restrict_selling,
+                    instruments)?;
+            },
+            _ => return Err!(
+                "Invalid {:?} assets configuration: either symbol or assets must be specified",
+                asset.name),
+        }
+    }
+
+    Ok(())
+}
+
+/// Portfolio of the household
+struct Account {
+    // Has flat asset allocation of the household instruments which are held or can be bought in
+    // the portfolio
+    portfolio: Portfolio,
+    // Rate to convert the portfolio currency to the household currency
+    rate: Decimal,
+    // Free cash assets which aren't distributed yet (in household currency)
+    free_cash_assets: Decimal,
+}
+
+impl Account {
+    fn new(portfolio: Portfolio, rate: Decimal) -> Account {
+        let free_cash_assets = (portfolio.current_cash_assets - portfolio.min_cash_assets) * rate;
+        Account {portfolio, rate, free_cash_assets}
+    }
+
+    fn load(
+        config: &Config, household_portfolio: &HouseholdPortfolioConfig, instruments: &[Instrument],
+        household_currency: &str, database: &db::Connection, converter: &CurrencyConverter,
+        quotes: &Quotes,
+    ) -> GenericResult<Account> {
+        let portfolio = config.get_portfolio(&household_portfolio.name)?;
+        let broker = portfolio.broker.get_info(config, portfolio.plan.as_ref())?;
+        let currency = portfolio.currency()?;
+
+        let is_household_instrument = |symbol: &str| {
+            instruments.iter().any(|instrument| instrument.symbol == symbol)
+        };
+
+        let tradable: HashSet<String> = match household_portfolio.symbols {
+            Some(ref symbols) => {
+                for symbol in symbols {
+                    if !is_household_instrument(symbol.as_str()) {
+                        return Err!(
+                            "{} is missing in the household asset allocation configuration", symbol);
+                    }
+                }
+                symbols.iter().cloned().collect()
+            },
+            None => portfolio.get_stock_symbols().into_iter()
+                .filter(|symbol| is_household_instrument(symbol.as_str()))
+                .collect(),
+        };
+
+        let assets = Assets::load(database.clone(), &portfolio.name)?;
+
+        // The actual weights are calculated later from the household asset allocation
+        let allocation: Vec<AssetAllocationConfig> = instruments.iter()
+            .filter(|instrument| {
+                tradable.contains(&instrument.symbol) || assets.stocks.contains_key(&instrument.symbol)
+            })
+            .enumerate()
+            .map(|(index, instrument)| AssetAllocationConfig {
+                name: instrument.name.clone(),
+                symbol: Some(instrument.symbol.clone()),
+
+                weight: WeightConfig::Fixed(if index == 0 { dec!(1) } else { dec!(0) }),
+                restrict_buying: if tradable.contains(&instrument.symbol) {
+                    instrument.restrict_buying
+                } else {
+                    Some(true)
+                },
+                restrict_selling: instrument.restrict_selling,
+
+                absolute_band: None,
+                relative_band: None,
+                rebalance_to: None,
+
+                lot_size: instrument.lot_size,
+                assets: None,
+            })
+            .collect();
+
+        let portfolio = Portfolio::load_allocation(
+            portfolio, &allocation, broker, assets, None, converter, quotes)?;
+        let rate = converter.real_time_convert_to(Cash::new(currency, dec!(1)), household_currency)?;
+
+        Ok(Account::new(portfolio, rate))
+    }
+
+    fn asset(&self, symbol: &str) -> Option<&AssetAllocation> {
+        self.portfolio.assets.iter().find(|asset| get_holding(asset).symbol == symbol)
+    }
+
+    fn asset_mut(&mut self, symbol: &str) -> Option<&mut AssetAllocation> {
+        self.portfolio.assets.iter_mut().find(|asset| get_holding(asset).symbol == symbol)
+    }
+
+    fn can_buy(&self, symbol: &str) -> bool {
+        self.asset(symbol).map_or(false, |asset| !asset.restrict_buying.unwrap_or(false))
+    }
+}
+
+fn get_holding(asset: &AssetAllocation) -> &StockHolding {
+    match asset.holding {
+        Holding::Stock(ref holding) => holding,
+        Holding::Group(_) => unreachable!(),
+    }
+}
+
+// Splits the household asset allocation across the accounts and rebalances them. Returns net value
+// of the household which is expected to be invested according to the asset allocation.
+fn rebalance_accounts(
+    instruments: &mut [Instrument], accounts: &mut [Account], converter: CurrencyConverterRc,
+) -> GenericResult<Decimal> {
+    let expected_net_value = calculate_current_values(instruments, accounts);
+    for instrument in instruments.iter_mut() {
+        instrument.expected_value = expected_net_value * instrument.weight;
+    }
+
+    sell_overweight(instruments, accounts);
+    buy_underweight(instruments, accounts);
+
+    for account in accounts.iter_mut() {
+        apply_split(account);
+
+        rebalancing::rebalance_portfolio(&mut account.portfolio, converter.clone())
+            .map_err(|e| format!("{:?} portfolio: {}", account.portfolio.name, e))?;
+    }
+
+    calculate_target_values(instruments, accounts);
+
+    Ok(expected_net_value)
+}
+
+// Calculates current value of each instrument and returns net value of the household which is
+// expected to be invested according to the asset allocation
+fn calculate_current_values(instruments: &mut [Instrument], accounts: &[Account]) -> Decimal {
+    let mut net_value = dec!(0);
+
+    for account in accounts {
+        net_value += account.free_cash_assets;
+
+        for asset in &account.portfolio.assets {
+            let value = asset.current_value * account.rate;
+            let symbol = &get_holding(asset).symbol;
+            let instrument = instruments.iter_mut().find(|instrument| &instrument.symbol == symbol).unwrap();
+
+            instrument.current_value += value;
+            net_value += value;
+        }
+    }
+
+    for instrument in instruments {
+        instrument.target_value = instrument.current_value;
+    }
+
+    net_value
+}
+
+fn sell_overweight(instruments: &mut [Instrument], accounts: &mut [Account]) {
+    debug!("Selling overweight instruments:");
+
+    for instrument in instruments {
+        let symbol = instrument.symbol.as_str();
+
+        // Sell from the portfolios where the instrument can't be bought first, then from the
+        // largest positions
+        let mut sources: Vec<(usize, bool, Decimal)> = accounts.iter().enumerate()
+            .filter_map(|(index, account)| {
+                account.asset(symbol)
+                    .filter(|asset| !asset.restrict_selling.unwrap_or(false) && !asset.current_value.is_zero())
+                    .map(|asset| (index, account.can_buy(symbol), asset.current_value * account.rate))
+            })
+            .collect();
+        sources.sort_by_key(|&(_, can_buy, value)| (can_buy, Reverse(value)));
+
+        for (index, _, value) in sources {
+            let excess = instrument.target_value - instrument.expected_value;
+            if !excess.is_sign_positive() {
+                break;
+            }
+
+            let account = &mut accounts[index];
+            let volume = std::cmp::min(value, excess);
+            debug!("* {}: {} -{}", account.portfolio.name, symbol, volume.normalize());
+
+            account.asset_mut(symbol).unwrap().target_value -= volume / account.rate;
+            account.free_cash_assets += volume;
+            instrument.target_value -= volume;
+        }
+    }
+}
+
+fn buy_underweight(instruments: &mut [Instrument], accounts: &mut [Account]) {
+    debug!("Buying underweight instruments:");
+
+    // The portfolios which are able to buy less instruments have less options, so their cash is
+    // distributed first
+    let mut order: Vec<usize> = (0..accounts.len()).collect();
+    order.sort_by_key(|&index| {
+        let account = &accounts[index];
+        instruments.iter().filter(|instrument| account.can_buy(&instrument.symbol)).count()
+    });
+
+    for index in order {
+        let account = &mut accounts[index];
+        if !account.free_cash_assets.is_sign_positive() || account.free_cash_assets.is_zero() {
+            continue;
+        }
+
+        let candidates: Vec<usize> = instruments.iter().enumerate()
+            .filter(|(_, instrument)| {
+                instrument.target_value < instrument.expected_value && account.can_buy(&instrument.symbol)
+            })
+            .map(|(index, _)| index)
+            .collect();
+
+        let volumes = distribute_cash(
+            &candidates.iter().map(|&index| {
+                let instrument = &instruments[index];
+                (instrument.target_value, instrument.expected_value)
+            }).collect::<Vec<_>>(),
+            account.free_cash_assets);
+
+        for (instrument_index, volume) in candidates.into_iter().zip(volumes) {
+            let instrument = &mut instruments[instrument_index];
+            debug!("* {}: {} +{}", account.portfolio.name, instrument.symbol, volume.normalize());
+
+            account.asset_mut(&instrument.symbol).unwrap().target_value += volume / account.rate;
+            account.free_cash_assets -= volume;
+            instrument.target_value += volume;
+        }
+    }
+}
+
+// Distributes the cash among the underweight instruments (target and expected values are
+// specified) bringing the most underweight of them to the same target/expected ratio. Returns the
+// volumes to buy.
+fn distribute_cash(instruments: &[(Decimal, Decimal)], cash: Decimal) -> Vec<Decimal> {
+    let ratio = |index: usize| {
+        let (target_value, expected_value) = instruments[index];
+        target_value / expected_value
+    };
+
+    let mut order: Vec<usize> = (0..instruments.len()).collect();
+    order.sort_by_key(|&index| ratio(index));
+
+    let mut result_ratio = dec!(1);
+    let mut target_value = dec!(0);
+    let mut expected_value = dec!(0);
+
+    for (position, &index) in order.iter().enumerate() {
+        target_value += instruments[index].0;
+        expected_value += instruments[index].1;
+
+        // The cash required to bring the instruments to the ratio of the next one
+        let next_ratio = order.get(position + 1).map_or(dec!(1), |&next| ratio(next));
+        if next_ratio * expected_value - target_value >= cash {
+            result_ratio = (target_value + cash) / expected_value;
+            break;
+        }
+    }
+
+    instruments.iter().map(|&(target_value, expected_value)| {
+        std::cmp::max(dec!(0), result_ratio * expected_value - target_value)
+    }).collect()
+}
+
+// Precision of the weights calculated from the household split
+const WEIGHT_PRECISION: u32 = 8;
+
+// Sets asset weights of the account according to its share of the household asset allocation
+fn apply_split(account: &mut Account) {
+    let portfolio = &mut account.portfolio;
+
+    // The cash which can't be spent on the household instruments stays in the portfolio
+    let free_cash_assets = util::round(account.free_cash_assets / account.rate, 2);
+    if free_cash_assets.is_sign_positive() {
+        portfolio.min_cash_assets += free_cash_assets;
+    }
+
+    let total_value: Decimal = portfolio.assets.iter().map(|asset| asset.target_value).sum();
+
+    for asset in &mut portfolio.assets {
+        asset.expected_weight = if total_value.is_zero() {
+            dec!(0)
+        } else {
+            util::round(asset.target_value / total_value, WEIGHT_PRECISION)
+        };
+        asset.target_value = asset.current_value;
+    }
+
+    // Attribute the rounding error to the largest weight
+    let error = dec!(1) - portfolio.assets.iter().map(|asset| asset.expected_weight).sum::<Decimal>();
+    if let Some(asset) = portfolio.assets.iter_mut().max_by_key(|asset| asset.expected_weight) {
+        asset.expected_weight += error;
+    }
+}
+
+fn calculate_target_values(instruments: &mut [Instrument], accounts: &[Account]) {
+    for instrument in instruments.iter_mut() {
+        instrument.target_value = dec!(0);
+    }
+
+    for account in accounts {
+        for asset in &account.portfolio.assets {
+            let holding = get_holding(asset);
+            let instrument = instruments.iter_mut().find(|instrument| instrument.symbol == holding.symbol).unwrap();
+            instrument.target_value += holding.target_shares * holding.price * account.rate;
+        }
+    }
+}
+
+#[derive(StaticTable)]
+#[table(name="AllocationTable")]
+struct AllocationRow {
+    #[column(name="Instrument")]
+    instrument: String,
+    #[column(name="Current value")]
+    current_value: Cash,
+    #[column(name="Current weight")]
+    current_weight: Cell,
+    #[column(name="Target value")]
+    target_value: Cash,
+    #[column(name="Target weight")]
+    target_weight: Cell,
+    #[column(name="Expected weight")]
+    expected_weight: Cell,
+}
+
+#[derive(StaticTable)]
+#[table(name="OrdersTable")]
+struct OrderRow {
+    #[column(name="Instrument")]
+    instrument: String,
+    #[column(name="Current")]
+    current: Cell,
+    #[column(name="Trade", align="right")]
+    trade: String,
+    #[column(name="Target")]
+    target: Cell,
+    #[column(name="Volume")]
+    volume: Option<Cash>,
+}
+
+fn print_results(
+    household: &HouseholdConfig, instruments: &[Instrument], accounts: &[Account],
+    expected_net_value: Decimal,
+) {
+    let currency = household.currency.as_str();
+    let weight = |value: Decimal| if expected_net_value.is_zero() {
+        Cell::new_empty()
+    } else {
+        Cell::new_ratio(value / expected_net_value)
+    };
+
+    let mut table = AllocationTable::new();
+
+    for instrument in instruments {
+        table.add_row(AllocationRow {
+            instrument: format!("{} ({})", instrument.name, instrument.symbol),
+            current_value: Cash::new(currency, instrument.current_value).round(),
+            current_weight: weight(instrument.current_value),
+            target_value: Cash::new(currency, instrument.target_value).round(),
+            target_weight: weight(instrument.target_value),
+            expected_weight: Cell::new_ratio(instrument.weight),
+        });
+    }
+
+    table.print(&format!("{} household asset allocation", household.name));
+
+    for account in accounts {
+        let portfolio = &account.portfolio;
+        let mut table = OrdersTable::new();
+
+        for asset in &portfolio.assets {
+            let holding = get_holding(asset);
+
+            let shares = holding.target_shares - holding.current_shares;
+            if shares.is_zero() {
+                continue;
+            }
+
+            table.add_row(OrderRow {
+                instrument: holding.symbol.clone(),
+                current: holding.current_shares.normalize().into(),
+                trade: format!("{:+}", shares.normalize()),
+                target: holding.target_shares.normalize().into(),
+                volume: Some(Cash::new(&portfolio.currency, (shares * holding.price).abs()).round()),
+            });
+        }
+
+        let cash = |amount| Cash::new(&portfolio.currency, amount).round();
+
+        if !portfolio.commissions.is_zero() {
+            table.add_row(OrderRow {
+                instrument: s!("Commissions"),
+                current: Cell::new_empty(),
+                trade: cash(-portfolio.commissions).to_string(),
+                target: Cell::new_empty(),
+                volume: None,
+            });
+        }
+
+        table.add_row(OrderRow {
+            instrument: s!("Cash"),
+            current: cash(portfolio.current_cash_assets).into(),
+            trade: cash(portfolio.target_cash_assets - portfolio.current_cash_assets).to_string(),
+            target: cash(portfolio.target_cash_assets).into(),
+            volume: None,
+        });
+
+        table.print(&format!("{} orders", portfolio.name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::brokers::Broker;
+    use crate::localities::Jurisdiction;
+
+    use super::*;
+
+    #[test]
+    fn household_rebalancing() {
+        let mut instruments = mock_instruments(dec!(0.6), dec!(0.4));
+        let mut accounts = vec![
+            mock_account(dec!(300), vec![
+                mock_stock("VTI", dec!(2), false),
+                mock_stock("BND", dec!(0), false),
+            ]),
+            mock_account(dec!(400), vec![
+                mock_stock("VTI", dec!(0), false),
+                mock_stock("BND", dec!(2), true),
+            ]),
+        ];
+
+        let net_value = rebalance_accounts(&mut instruments, &mut accounts, CurrencyConverter::mock()).unwrap();
+        assert_eq!(net_value, dec!(1000));
+
+        // The second portfolio can buy only VTI, so it buys it for all its cash and BND is bought
+        // in the first one
+        assert_eq!(get_target_shares(&accounts), vec![
+            vec![dec!(2), dec!(6)],
+            vec![dec!(4), dec!(2)],
+        ]);
+        assert_eq!(get_target_values(&instruments), vec![dec!(600), dec!(400)]);
+    }
+
+    #[rstest(restrict_selling => [false, true])]
+    fn overweight_selling(restrict_selling: bool) {
+        let mut instruments = mock_instruments(dec!(0.5), dec!(0.5));
+        let mut accounts = vec![
+            mock_account(dec!(0), vec![
+                mock_stock("VTI", dec!(0), false),
+                mock_stock("BND", dec!(4), true),
+            ]),
+            mock_account(dec!(0), vec![
+                mock_stock("VTI", dec!(0), false),
+                mock_stock("BND", dec!(4), false),
+            ]),
+        ];
+
+        if restrict_selling {
+            accounts[0].portfolio.restrict_selling();
+        }
+
+        rebalance_accounts(&mut instruments, &mut accounts, CurrencyConverter::mock()).unwrap();
+
+        // BND is sold from the portfolio where it can't be bought unless selling is restricted there
+        assert_eq!(get_target_shares(&accounts), if restrict_selling {
+            vec![
+                vec![dec!(0), dec!(4)],
+                vec![dec!(2), dec!(0)],
+            ]
+        } else {
+            vec![
+                vec![dec!(2), dec!(0)],
+                vec![dec!(0), dec!(4)],
+            ]
+        });
+        assert_eq!(get_target_values(&instruments), vec![dec!(200), dec!(200)]);
+    }
+
+    #[test]
+    fn undistributable_cash() {
+        let mut instruments = mock_instruments(dec!(0.5), dec!(0.5));
+        let mut accounts = vec![
+            mock_account(dec!(100), vec![
+                mock_stock("VTI", dec!(0), false),
+            ]),
+            mock_account(dec!(0), vec![
+                mock_stock("VTI", dec!(3), true),
+                mock_stock("BND", dec!(0), false),
+            ]),
+        ];
+
+        rebalance_accounts(&mut instruments, &mut accounts, CurrencyConverter::mock()).unwrap();
+
+        // VTI is overweight, so cash of the first portfolio stays unspent
+        assert_eq!(get_target_shares(&accounts), vec![
+            vec![dec!(0)],
+            vec![dec!(2), dec!(2)],
+        ]);
+        assert_eq!(accounts[0].portfolio.target_cash_assets, dec!(100));
+        assert_eq!(get_target_values(&instruments), vec![dec!(200), dec!(100)]);
+    }
+
+    #[rstest(cash, expected,
+        case(dec!(10), vec![dec!(10), dec!(0), dec!(0)]),
+        case(dec!(55), vec![dec!(50), dec!(5), dec!(0)]),
+        case(dec!(100), vec![dec!(72), dec!(16), dec!(12)]),
+        case(dec!(1000), vec![dec!(80), dec!(20), dec!(20)]),
+    )]
+    fn cash_distribution(cash: Decimal, expected: Vec<Decimal>) {
+        // Target/expected ratios: 20%, 60% and 80%
+        let instruments = [(dec!(20), dec!(100)), (dec!(30), dec!(50)), (dec!(80), dec!(100))];
+        assert_eq!(distribute_cash(&instruments, cash), expected);
+    }
+
+    fn mock_instruments(vti_weight: Decimal, bnd_weight: Decimal) -> Vec<Instrument> {
+        [("Stocks", "VTI", vti_weight), ("Bonds", "BND", bnd_weight)].iter().map(|&(name, symbol, weight)| {
+            Instrument {
+                name: name.to_owned(),
+                symbol: symbol.to_owned(),
+                weight: weight,
+                restrict_buying: None,
+                restrict_selling: None,
+                lot_size: None,
+
+                expected_value: dec!(0),
+                current_value: dec!(0),
+                target_value: dec!(0),
+            }
+        }).collect()
+    }
+
+    fn mock_stock(symbol: &str, shares: Decimal, restrict_buying: bool) -> AssetAllocation {
+        let price = match symbol {
+            "VTI" => dec!(100),
+            "BND" => dec!(50),
+            _ => unreachable!(),
+        };
+
+        let mut asset = AssetAllocation::mock_stock(symbol, symbol, dec!(0), price, shares);
+        if restrict_buying {
+            asset.restrict_buying = Some(true);
+        }
+        asset
+    }
+
+    fn mock_account(cash: Decimal, assets: Vec<AssetAllocation>) -> Account {
+        let broker = Broker::Generic(Jurisdiction::Usa).get_info(&Config::mock(), None).unwrap();
+        Account::new(Portfolio::mock(broker, "USD", cash, assets), dec!(1))
+    }
+
+    fn get_target_shares(accounts: &[Account]) -> Vec<Vec<Decimal>> {
+        accounts.iter().map(|account| {
+            account.portfolio.assets.iter().map(|asset| get_holding(asset).target_shares).collect()
+        }).collect()
+    }
+
+    fn get_target_values(instruments: &[Instrument]) -> Vec<Decimal> {
+        instruments.iter().map(|instrument| instrument.target_value).collect()
+    }
+}
@@ -13,24 +13,38 @@ use crate::types::Decimal;
 
 use self::asset_allocation::Portfolio;
 use self::assets::Assets;
-use self::formatting::print_portfolio;
+use self::formatting::{print_band_breaches, print_portfolio};
+
+pub use self::exposure::show as show_exposure;
+pub use self::household::rebalance as rebalance_household;
 
 mod asset_allocation;
 mod assets;
+mod exposure;
+mod forex;
 mod formatting;
+mod household;
+mod orders;
 mod rebalancing;
+mod taxes;
 
 pub fn sync(config: &Config, portfolio_name: &str) -> GenericResult<TelemetryRecordBuilder> {
     let portfolio = config.get_portfolio(portfolio_name)?;
-    let broker = portfolio.broker.get_info(config, portfolio.plan.as_ref())?;
     let database = db::connect(&config.db_path)?;
 
-    let statement = BrokerStatement::read(
-        broker, portfolio.statements_path()?, &portfolio.symbol_remapping, &portfolio.instrument_internal_ids,
-        &portfolio.instrument_names, portfolio.get_tax_remapping()?, &portfolio.corporate_actions,
-        ReadingStrictness::empty())?;
+    let statement = BrokerStatement::read_portfolio(config, portfolio, ReadingStrictness::empty())?;
     statement.check_date();
 
+    for (symbol, quantity) in &statement.open_positions {
+        if quantity.is_sign_negative() {
+            return Err!("The portfolio has short {} position which isn't supported by rebalancing", symbol);
+        }
+
+        if statement.instrument_info.get_type(symbol).is_derivative() {
+            return Err!("The portfolio has {} derivative position which isn't supported by rebalancing", symbol);
+        }
+    }
+
     let assets = Assets::new(statement.assets.cash, statement.open_positions);
     assets.validate(portfolio)?;
     assets.save(database, &portfolio.name)?;
@@ -38,8 +52,16 @@ pub fn sync(config: &Config, portfolio_name: &str) -> GenericResult<TelemetryRec
     Ok(TelemetryRecordBuilder::new_with_broker(portfolio.broker))
 }
 
+/// Cash assets specified by user
+pub enum CashAssetsSpec {
+    /// Total amount in portfolio currency
+    Total(Decimal),
+    /// Amount of each currency
+    PerCurrency(Vec<(String, Decimal)>),
+}
+
 pub fn buy(
-    config: &Config, portfolio_name: &str, positions: &[(String, Decimal)], cash_assets: Decimal,
+    config: &Config, portfolio_name: &str, positions: &[(String, Decimal)], cash_assets: &CashAssetsSpec,
 ) -> GenericResult<TelemetryRecordBuilder> {
     modify_assets(config, portfolio_name, |portfolio, assets| {
         let asset_allocation_symbols = portfolio.get_stock_symbols();
@@ -62,7 +84,7 @@ pub fn buy(
 
 pub fn sell(
     config: &Config, portfolio_name: &str, positions: &[(String, Option<Decimal>)],
-    cash_assets: Decimal,
+    cash_assets: &CashAssetsSpec,
 ) -> GenericResult<TelemetryRecordBuilder> {
     modify_assets(config, portfolio_name, |portfolio, assets| {
         for (symbol, quantity) in positions {
@@ -94,7 +116,9 @@ pub fn sell(
     })
 }
 
-pub fn set_cash_assets(config: &Config, portfolio_name: &str, cash_assets: Decimal) -> GenericResult<TelemetryRecordBuilder> {
+pub fn set_cash_assets(
+    config: &Config, portfolio_name: &str, cash_assets: &CashAssetsSpec,
+) -> GenericResult<TelemetryRecordBuilder> {
     modify_assets(config, portfolio_name, |portfolio, assets| {
         set_cash_assets_impl(portfolio, assets, cash_assets)
     })
@@ -113,21 +137,51 @@ fn modify_assets<F>(config: &Config, portfolio_name: &str, modify: F) -> Generic
     Ok(TelemetryRecordBuilder::new_with_broker(portfolio.broker))
 }
 
-fn set_cash_assets_impl(portfolio: &PortfolioConfig, assets: &mut Assets, cash_assets: Decimal) -> EmptyResult {
+fn set_cash_assets_impl(portfolio: &PortfolioConfig, assets: &mut Assets, cash_assets: &CashAssetsSpec) -> EmptyResult {
     assets.cash.clear();
-    assets.cash.deposit(Cash::new(portfolio.currency()?, cash_assets));
+
+    match cash_assets {
+        CashAssetsSpec::Total(amount) => {
+            assets.cash.deposit(Cash::new(portfolio.currency()?, *amount));
+        },
+        CashAssetsSpec::PerCurrency(amounts) => {
+            for (currency, amount) in amounts {
+                assets.cash.deposit(Cash::new(currency, *amount));
+            }
+        },
+    }
+
     Ok(())
 }
 
 pub fn show(config: &Config, portfolio_name: &str, flat: bool) -> GenericResult<TelemetryRecordBuilder> {
-    process(config, portfolio_name, false, flat)
+    process(config, portfolio_name, None, flat)
 }
 
-pub fn rebalance(config: &Config, portfolio_name: &str, flat: bool) -> GenericResult<TelemetryRecordBuilder> {
-    process(config, portfolio_name, true, flat)
+pub struct RebalancingOptions {
+    /// Sell only the lots with low tax to pay
+    pub tax_aware: bool,
+    /// Don't sell anything: only buy underweight assets
+    pub buy_only: bool,
+    /// Cash amount which is going to be deposited to the account
+    pub contribution: Option<Decimal>,
+    /// Rebalance only if some asset is out of its tolerance band
+    pub cron_mode: bool,
+    /// Path to export the resulting orders to
+    pub orders_path: Option<String>,
+    /// Export the orders as Interactive Brokers basket
+    pub ib_basket: bool,
 }
 
-fn process(config: &Config, portfolio_name: &str, rebalance: bool, flat: bool) -> GenericResult<TelemetryRecordBuilder> {
+pub fn rebalance(
+    config: &Config, portfolio_name: &str, options: RebalancingOptions, flat: bool,
+) -> GenericResult<TelemetryRecordBuilder> {
+    process(config, portfolio_name, Some(options), flat)
+}
+
+fn process(
+    config: &Config, portfolio_name: &str, rebalance: Option<RebalancingOptions>, flat: bool,
+) -> GenericResult<TelemetryRecordBuilder> {
     let portfolio_config = config.get_portfolio(portfolio_name)?;
     let broker = portfolio_config.broker.get_info(config, portfolio_config.plan.as_ref())?;
     let database = db::connect(&config.db_path)?;
@@ -138,19 +192,63 @@ fn process(config: &Config, portfolio_name: &str, rebalance: bool, flat: bool) -
     let assets = Assets::load(database, &portfolio_config.name)?;
     assets.validate(portfolio_config)?;
 
-    let statement = portfolio_config.statements.as_ref().map(|path| {
-        BrokerStatement::read(
-            broker.clone(), path, &portfolio_config.symbol_remapping,
-            &portfolio_config.instrument_internal_ids, &portfolio_config.instrument_names,
-            portfolio_config.get_tax_remapping()?, &portfolio_config.corporate_actions,
-            ReadingStrictness::empty())
+    let tax_aware = rebalance.as_ref().map(|options| options.tax_aware).unwrap_or_default();
+    if tax_aware && portfolio_config.statements.is_none() {
+        return Err!("Tax-aware rebalancing requires broker statements to be specified for the portfolio");
+    }
+
+    // Tax-aware rebalancing emulates selling of the open positions
+    let reading_strictness = if tax_aware {
+        ReadingStrictness::TRADE_SETTLE_DATE
+    } else {
+        ReadingStrictness::empty()
+    };
+
+    let statement = portfolio_config.statements.as_ref().map(|_| {
+        BrokerStatement::read_portfolio(config, portfolio_config, reading_strictness)
     }).transpose()?;
 
+    let positions = assets.stocks.clone();
     let mut portfolio = Portfolio::load(
         portfolio_config, broker, assets, statement.as_ref(), &converter, &quotes)?;
 
-    if rebalance {
+    if tax_aware {
+        let mut tax_lots = taxes::calculate(
+            &config.get_tax_country(), portfolio_config, statement.unwrap(), &positions,
+            converter.clone(), &quotes)?;
+
+        for holding in portfolio.stock_holdings() {
+            holding.tax_lots = tax_lots.remove(&holding.symbol);
+        }
+    }
+
+    if let Some(options) = rebalance {
+        if let Some(contribution) = options.contribution {
+            portfolio.contribute(contribution);
+        }
+
+        if options.cron_mode {
+            if !portfolio.has_bands() {
+                return Err!(
+                    "Cron mode requires tolerance bands to be configured for the portfolio assets");
+            }
+
+            let breaches = portfolio.band_breaches();
+            if breaches.is_empty() {
+                return Ok(TelemetryRecordBuilder::new_with_broker(portfolio_config.broker));
+            }
+            print_band_breaches(&breaches);
+        }
+
+        if options.buy_only {
+            portfolio.restrict_selling();
+        }
+
         rebalancing::rebalance_portfolio(&mut portfolio, converter)?;
+
+        if let Some(ref path) = options.orders_path {
+            orders::export(&portfolio, path, options.ib_basket)?;
+        }
     }
 
     print_portfolio(portfolio, flat);
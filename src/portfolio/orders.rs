@@ -0,0 +1,271 @@
+use std::fs::File;
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::brokers::Broker;
+use crate::core::{EmptyResult, GenericResult};
+use crate::formatting::output::{self, OutputFormat};
+use crate::types::{Decimal, TradeType};
+use crate::util;
+
+use super::asset_allocation::{Portfolio, StockHolding};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum OrdersFormat {
+    /// Interactive Brokers basket file which can be imported into Trader Workstation
+    IbBasket,
+    Csv,
+    Json,
+}
+
+struct Order<'a> {
+    holding: &'a StockHolding,
+    trade_type: TradeType,
+    quantity: Decimal,
+}
+
+/// Exports the orders which are required to get the portfolio to its target state. The orders are
+/// written in JSON if it's the chosen output format and in CSV otherwise.
+pub fn export(portfolio: &Portfolio, path: &str, ib_basket: bool) -> EmptyResult {
+    let format = if ib_basket {
+        if portfolio.broker.type_ != Broker::InteractiveBrokers {
+            return Err!("Interactive Brokers basket can't be exported for {} portfolio",
+                portfolio.broker.brief_name);
+        }
+        OrdersFormat::IbBasket
+    } else if output::format() == OutputFormat::Json {
+        OrdersFormat::Json
+    } else {
+        OrdersFormat::Csv
+    };
+
+    let orders = get_orders(portfolio)?;
+
+    let file = File::create(path).map_err(|e| format!("Unable to create {:?}: {}", path, e))?;
+    write(file, portfolio, &orders, format).map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+
+    Ok(())
+}
+
+// Returns sell orders first to free up cash for the buy ones
+fn get_orders(portfolio: &Portfolio) -> GenericResult<Vec<Order>> {
+    let mut orders = Vec::new();
+
+    for holding in portfolio.get_stock_holdings() {
+        let (trade_type, quantity) = if holding.target_shares > holding.current_shares {
+            (TradeType::Buy, holding.target_shares - holding.current_shares)
+        } else if holding.target_shares < holding.current_shares {
+            (TradeType::Sell, holding.current_shares - holding.target_shares)
+        } else {
+            continue;
+        };
+
+        if let Some(lot) = holding.trade_lot() {
+            if !(quantity / lot).fract().is_zero() {
+                return Err!("Unable to export {} order: {} shares doesn't match its trading lot ({})",
+                    holding.symbol, quantity.normalize(), lot.normalize());
+            }
+        }
+
+        orders.push(Order {holding, trade_type, quantity: quantity.normalize()});
+    }
+
+    orders.sort_by(|a, b| {
+        let priority = |order: &Order| match order.trade_type {
+            TradeType::Sell => 0,
+            TradeType::Buy => 1,
+        };
+        (priority(a), &a.holding.symbol).cmp(&(priority(b), &b.holding.symbol))
+    });
+
+    Ok(orders)
+}
+
+fn write<W: Write>(writer: W, portfolio: &Portfolio, orders: &[Order], format: OrdersFormat) -> EmptyResult {
+    #[derive(Serialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct IbBasketRecord<'a> {
+        action: &'static str,
+        quantity: String,
+        symbol: &'a str,
+        sec_type: &'static str,
+        exchange: &'static str,
+        currency: &'static str,
+        order_type: &'static str,
+    }
+
+    #[derive(Serialize)]
+    struct Record<'a> {
+        portfolio: &'a str,
+        symbol: &'a str,
+        action: &'static str,
+        quantity: String,
+        price: String,
+        currency: &'static str,
+        volume: String,
+    }
+
+    match format {
+        OrdersFormat::IbBasket => {
+            let mut writer = csv::Writer::from_writer(writer);
+
+            for order in orders {
+                writer.serialize(IbBasketRecord {
+                    action: match order.trade_type {
+                        TradeType::Buy => "BUY",
+                        TradeType::Sell => "SELL",
+                    },
+                    quantity: format_quantity(order),
+                    symbol: &order.holding.symbol,
+                    sec_type: "STK",
+                    exchange: "SMART",
+                    currency: order.holding.currency_price.currency,
+                    order_type: "MKT",
+                })?;
+            }
+
+            writer.flush()?;
+        },
+
+        OrdersFormat::Csv | OrdersFormat::Json => {
+            let records = orders.iter().map(|order| {
+                let price = order.holding.currency_price.amount;
+
+                Record {
+                    portfolio: &portfolio.name,
+                    symbol: &order.holding.symbol,
+                    action: match order.trade_type {
+                        TradeType::Buy => "buy",
+                        TradeType::Sell => "sell",
+                    },
+                    quantity: format_quantity(order),
+                    // Price step isn't known, so use quote precision but not less than cents
+                    price: format_decimal(price, util::decimal_precision(price).max(2)),
+                    currency: order.holding.currency_price.currency,
+                    volume: format_decimal(order.quantity * price, 2),
+                }
+            });
+
+            if format == OrdersFormat::Csv {
+                let mut writer = csv::Writer::from_writer(writer);
+                for record in records {
+                    writer.serialize(record)?;
+                }
+                writer.flush()?;
+            } else {
+                let records: Vec<Record> = records.collect();
+                let mut writer = writer;
+                serde_json::to_writer_pretty(&mut writer, &records)?;
+                writeln!(writer)?;
+            }
+        },
+    }
+
+    Ok(())
+}
+
+// Formats quantity to the precision of the trading lot
+fn format_quantity(order: &Order) -> String {
+    let precision = match order.holding.trade_lot() {
+        Some(lot) => util::decimal_precision(lot),
+        None => util::decimal_precision(order.quantity),
+    };
+    format_decimal(order.quantity, precision)
+}
+
+fn format_decimal(value: Decimal, precision: u32) -> String {
+    format!("{:.*}", precision as usize, util::round(value, precision))
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+    use rstest::rstest;
+    use tempfile::NamedTempFile;
+
+    use crate::config::Config;
+    use crate::localities::Jurisdiction;
+
+    use super::*;
+
+    #[rstest(format, expected,
+        case(OrdersFormat::IbBasket, indoc!(r#"
+            Action,Quantity,Symbol,SecType,Exchange,Currency,OrderType
+            SELL,3,BND,STK,SMART,USD,MKT
+            BUY,2,VTI,STK,SMART,USD,MKT
+        "#)),
+        case(OrdersFormat::Csv, indoc!(r#"
+            portfolio,symbol,action,quantity,price,currency,volume
+            mock,BND,sell,3,80.00,USD,240.00
+            mock,VTI,buy,2,200.00,USD,400.00
+        "#)),
+        case(OrdersFormat::Json, indoc!(r#"
+            [
+              {
+                "portfolio": "mock",
+                "symbol": "BND",
+                "action": "sell",
+                "quantity": "3",
+                "price": "80.00",
+                "currency": "USD",
+                "volume": "240.00"
+              },
+              {
+                "portfolio": "mock",
+                "symbol": "VTI",
+                "action": "buy",
+                "quantity": "2",
+                "price": "200.00",
+                "currency": "USD",
+                "volume": "400.00"
+              }
+            ]
+        "#)),
+    )]
+    fn orders(format: OrdersFormat, expected: &str) {
+        let portfolio = mock_portfolio(Broker::InteractiveBrokers, dec!(1));
+        let orders = get_orders(&portfolio).unwrap();
+
+        let mut data = Vec::new();
+        write(&mut data, &portfolio, &orders, format).unwrap();
+        assert_eq!(String::from_utf8(data).unwrap(), expected);
+    }
+
+    #[test]
+    fn ib_basket_for_other_brokers() {
+        let portfolio = mock_portfolio(Broker::Generic(Jurisdiction::Usa), dec!(1));
+        let path = NamedTempFile::new().unwrap().into_temp_path();
+
+        assert_eq!(
+            export(&portfolio, path.to_str().unwrap(), true).unwrap_err().to_string(),
+            "Interactive Brokers basket can't be exported for Брокер portfolio");
+    }
+
+    #[rstest(broker, lot_size, valid,
+        case(Broker::Generic(Jurisdiction::Usa), dec!(1), true),
+        case(Broker::Generic(Jurisdiction::Usa), dec!(10), false),
+        case(Broker::InteractiveBrokers, dec!(10), true),
+    )]
+    fn trading_lot(broker: Broker, lot_size: Decimal, valid: bool) {
+        let portfolio = mock_portfolio(broker, lot_size);
+        assert_eq!(get_orders(&portfolio).is_ok(), valid);
+    }
+
+    fn mock_portfolio(broker: Broker, lot_size: Decimal) -> Portfolio {
+        let broker = broker.get_info(&Config::mock(), None).unwrap();
+
+        let mut portfolio = Portfolio::mock_stocks_and_bonds(broker, dec!(4));
+
+        for holding in portfolio.stock_holdings() {
+            holding.lot_size = lot_size;
+            holding.target_shares = match holding.symbol.as_str() {
+                "VTI" => dec!(5),
+                "BND" => dec!(1),
+                _ => holding.current_shares,
+            };
+        }
+
+        portfolio
+    }
+}
@@ -5,6 +5,7 @@ use num_traits::ToPrimitive;
 
 use crate::brokers::BrokerInfo;
 use crate::commissions::CommissionCalc;
+use crate::config::BandRebalancingTarget;
 use crate::core::{GenericResult, EmptyResult};
 use crate::currency::Cash;
 use crate::currency::converter::CurrencyConverterRc;
@@ -12,13 +13,16 @@ use crate::time;
 use crate::types::{Decimal, TradeType};
 use crate::util;
 
-use super::asset_allocation::{Portfolio, AssetAllocation, Holding, StockHolding};
+use super::asset_allocation::{Portfolio, AssetAllocation, BandPosition, Holding, StockHolding};
+use super::forex::{self, CurrencyConversion};
 
 pub fn rebalance_portfolio(portfolio: &mut Portfolio, converter: CurrencyConverterRc) -> EmptyResult {
     let portfolio_info = PortfolioInfo::new(portfolio);
 
     // The first step is bottom-up and calculates strict limits on asset min/max value
-    calculate_restrictions(&mut portfolio.assets);
+    calculate_restrictions(
+        &mut portfolio.assets, portfolio.target_net_value - portfolio.min_cash_assets,
+        portfolio.max_sell_tax);
 
     // The second step is top-down and tries to apply the specified weights and limits calculated in
     // the first step to the current assets
@@ -40,6 +44,10 @@ pub fn rebalance_portfolio(portfolio: &mut Portfolio, converter: CurrencyConvert
     assert!(portfolio.commissions.is_zero());
     portfolio.change_commission(interim_total_commissions);
 
+    // Buys in other currencies require currency conversions which have their own commissions
+    let interim_forex_commissions = calculate_forex_commissions(&calculate_conversions(portfolio, &converter)?);
+    portfolio.change_commission(interim_forex_commissions);
+
     // The rebalancing logic is relatively inaccurate because it distributes funds only inside of
     // one group which leads to accumulation of free cash / debt from each asset group. Also it
     // can't take into account accumulated commissions. The following step operates on all levels of
@@ -47,31 +55,55 @@ pub fn rebalance_portfolio(portfolio: &mut Portfolio, converter: CurrencyConvert
     // allocation configuration.
     distribute_cash_assets(portfolio, &portfolio_info, converter.clone())?;
 
-    let (trade_commissions, additional_commissions) = calculate_total_commissions(portfolio, converter)?;
+    let (trade_commissions, additional_commissions) = calculate_total_commissions(portfolio, converter.clone())?;
     assert_eq!(
-        portfolio.commissions - interim_total_commissions,
+        portfolio.commissions - interim_total_commissions - interim_forex_commissions,
         trade_commissions - interim_trade_commissions,
     );
     portfolio.change_commission(additional_commissions - interim_additional_commissions);
 
+    let currency_conversions = calculate_conversions(portfolio, &converter)?;
+    portfolio.change_commission(calculate_forex_commissions(&currency_conversions) - interim_forex_commissions);
+    portfolio.currency_conversions = currency_conversions;
+
+    let taxes = portfolio.stock_holdings().iter()
+        .filter_map(|holding| holding.tax_lots.as_ref().map(|tax_lots| (holding, tax_lots)))
+        .filter(|(holding, _)| holding.target_shares < holding.current_shares)
+        .map(|(holding, tax_lots)| tax_lots.tax(holding.current_shares - holding.target_shares))
+        .sum();
+    portfolio.taxes = taxes;
+
     Ok(())
 }
 
-fn calculate_restrictions(assets: &mut Vec<AssetAllocation>) -> (Decimal, Option<Decimal>) {
+fn calculate_restrictions(
+    assets: &mut Vec<AssetAllocation>, total_value: Decimal, max_sell_tax: Decimal,
+) -> (Decimal, Option<Decimal>) {
     let mut total_min_value = dec!(0);
     let mut total_max_value = dec!(0);
     let mut all_with_max_value = true;
 
     for asset in assets {
         let (min_value, max_value) = match &mut asset.holding {
-            Holding::Group(holdings) => calculate_restrictions(holdings),
-            Holding::Stock(_) => {
-                let min_value = if asset.restrict_selling.unwrap_or(false) {
+            Holding::Group(holdings) => calculate_restrictions(holdings, asset.current_value, max_sell_tax),
+            Holding::Stock(holding) => {
+                let mut min_value = if asset.restrict_selling.unwrap_or(false) {
                     asset.current_value
                 } else {
                     dec!(0)
                 };
 
+                // In tax-aware mode we are allowed to sell only the lots with low tax
+                if let Some(ref tax_lots) = holding.tax_lots {
+                    let mut sellable_shares = tax_lots.sellable_shares(holding.price, max_sell_tax);
+                    if let Some(lot) = holding.trade_lot() {
+                        sellable_shares = (sellable_shares / lot).floor() * lot;
+                    }
+
+                    min_value = std::cmp::max(
+                        min_value, (holding.current_shares - sellable_shares) * holding.price);
+                }
+
                 let max_value = if asset.restrict_buying.unwrap_or(false) {
                     Some(asset.current_value)
                 } else {
@@ -84,6 +116,7 @@ fn calculate_restrictions(assets: &mut Vec<AssetAllocation>) -> (Decimal, Option
 
         asset.min_value = min_value;
         asset.max_value = max_value;
+        apply_band(asset, total_value);
 
         // Treat zero weight as a special case of restrictions (deprecated asset)
         if asset.expected_weight.is_zero() {
@@ -109,6 +142,43 @@ fn calculate_restrictions(assets: &mut Vec<AssetAllocation>) -> (Decimal, Option
     (total_min_value, total_max_value)
 }
 
+// Assets within their tolerance band keep their current value and are traded only to absorb cash
+// flows. The ones outside of it are traded either to the target weight or only to the band edge.
+fn apply_band(asset: &mut AssetAllocation, total_value: Decimal) {
+    let (band, position) = match (asset.band, asset.band_position(total_value)) {
+        (Some(band), Some(position)) => (band, position),
+        _ => return,
+    };
+
+    let granularity = match asset.holding {
+        Holding::Stock(ref holding) => Some(holding.trade_granularity()),
+        Holding::Group(_) => None,
+    };
+
+    // Rounds the trade volume to whole trading units not crossing the band edge
+    let round = |volume: Decimal| match granularity {
+        Some(granularity) => (volume / granularity).floor() * granularity,
+        None => volume,
+    };
+
+    match position {
+        BandPosition::Inside => asset.inside_band = true,
+        BandPosition::Below(_) if band.rebalance_to == BandRebalancingTarget::Target => {},
+        BandPosition::Below(edge) => {
+            let max_value = asset.current_value + round(total_value * edge - asset.current_value);
+            asset.max_value = Some(match asset.max_value {
+                Some(value) => std::cmp::min(value, max_value),
+                None => max_value,
+            });
+        },
+        BandPosition::Above(_) if band.rebalance_to == BandRebalancingTarget::Target => {},
+        BandPosition::Above(edge) => {
+            let min_value = asset.current_value - round(asset.current_value - total_value * edge);
+            asset.min_value = std::cmp::max(asset.min_value, min_value);
+        },
+    }
+}
+
 fn propagate_zero_weight(asset: &mut AssetAllocation) {
     if asset.min_value.is_zero() {
         if let Holding::Group(ref mut holdings) = asset.holding {
@@ -118,7 +188,14 @@ fn propagate_zero_weight(asset: &mut AssetAllocation) {
             }
         }
     } else if let Some(max_value) = asset.max_value {
-        assert_eq!(max_value, asset.min_value);
+        // Non-zero minimum value used to come only from selling restriction, so with buying
+        // restriction both limits were equal to the current value. Now it also may come from lots
+        // with high tax or tolerance band, which leave the minimum value below the current one.
+        if asset.restrict_selling.unwrap_or(false) && asset.restrict_buying.unwrap_or(false) {
+            assert_eq!(max_value, asset.min_value);
+        } else {
+            assert!(max_value >= asset.min_value);
+        }
     }
 
     asset.max_value = Some(asset.min_value);
@@ -162,7 +239,12 @@ impl<'a> AssetGroupRebalancer<'a> {
         let state = self.get_current_state();
 
         for asset in self.assets.iter_mut() {
-            let mut difference = asset.target_value - asset.current_value;
+            // Assets within their tolerance band keep their current value (see apply_band())
+            let mut difference = if asset.inside_band {
+                dec!(0)
+            } else {
+                asset.target_value - asset.current_value
+            };
 
             if let Holding::Stock(ref holding) = asset.holding {
                 let trade_granularity = holding.trade_granularity();
@@ -455,7 +537,12 @@ fn find_assets_for_cash_distribution(
                         expected_value, asset.target_value, trade.volume);
                 }
 
-                trade
+                // Banded group may have its own restrictions (see apply_band())
+                trade.filter(|trade| {
+                    let target_value = asset.target_value + trade.volume;
+                    asset.band.is_none() || target_value >= asset.min_value &&
+                        asset.max_value.map_or(true, |max_value| target_value <= max_value)
+                })
             },
         };
 
@@ -647,6 +734,18 @@ fn calculate_total_commissions(portfolio: &Portfolio, converter: CurrencyConvert
     Ok((trade_commissions, additional_commissions))
 }
 
+fn calculate_conversions(
+    portfolio: &Portfolio, converter: &CurrencyConverterRc,
+) -> GenericResult<Vec<CurrencyConversion>> {
+    forex::calculate_conversions(
+        &portfolio.currency, &portfolio.cash_balances, &portfolio.get_stock_holdings(),
+        &portfolio.broker, converter)
+}
+
+fn calculate_forex_commissions(conversions: &[CurrencyConversion]) -> Decimal {
+    conversions.iter().map(|conversion| conversion.commission).sum()
+}
+
 fn calculate_trade_commissions(
     assets: &[AssetAllocation], calc: &mut CommissionCalc,
     currency: &str, converter: CurrencyConverterRc,
@@ -745,4 +844,75 @@ fn calculate_min_buy_volume(asset: &AssetAllocation, min_trade_volume: Decimal)
 
 fn round_min_trade_volume(volume: Decimal, granularity: Decimal) -> Decimal {
     (volume / granularity).ceil() * granularity
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use crate::brokers::Broker;
+    use crate::config::Config;
+    use crate::currency::converter::CurrencyConverter;
+    use crate::localities::Jurisdiction;
+
+    use crate::portfolio::asset_allocation::Band;
+
+    use super::*;
+
+    #[rstest(rebalance_to, vti_shares, bnd_shares,
+        case(BandRebalancingTarget::Target, dec!(10), dec!(10)),
+        case(BandRebalancingTarget::BandEdge, dec!(11), dec!(9)),
+    )]
+    fn outside_of_band(rebalance_to: BandRebalancingTarget, vti_shares: Decimal, bnd_shares: Decimal) {
+        // VTI: 70% and BND: 30% with 45-55% bands
+        let mut portfolio = mock_portfolio(dec!(0), dec!(50), dec!(14), dec!(6), Some(rebalance_to));
+        rebalance_portfolio(&mut portfolio, CurrencyConverter::mock()).unwrap();
+
+        assert_eq!(get_target_shares(&mut portfolio), vec![vti_shares, bnd_shares]);
+        assert_eq!(portfolio.target_cash_assets, dec!(0));
+    }
+
+    #[rstest(banded, vti_shares, bnd_shares,
+        case(false, dec!(50), dec!(50)),
+        case(true, dec!(52), dec!(48)),
+    )]
+    fn inside_of_band(banded: bool, vti_shares: Decimal, bnd_shares: Decimal) {
+        // VTI: 52% and BND: 45% with 45-55% bands and 3% of cash
+        let band = if banded {
+            Some(BandRebalancingTarget::Target)
+        } else {
+            None
+        };
+        let mut portfolio = mock_portfolio(dec!(30), dec!(10), dec!(52), dec!(45), band);
+
+        // The assets within their bands aren't traded, but receive the free cash
+        rebalance_portfolio(&mut portfolio, CurrencyConverter::mock()).unwrap();
+
+        assert_eq!(get_target_shares(&mut portfolio), vec![vti_shares, bnd_shares]);
+        assert_eq!(portfolio.target_cash_assets, dec!(0));
+    }
+
+    fn mock_portfolio(
+        cash: Decimal, price: Decimal, vti_shares: Decimal, bnd_shares: Decimal,
+        band: Option<BandRebalancingTarget>,
+    ) -> Portfolio {
+        let broker = Broker::Generic(Jurisdiction::Usa).get_info(&Config::mock(), None).unwrap();
+
+        let mut assets = vec![
+            AssetAllocation::mock_stock("Stocks", "VTI", dec!(0.5), price, vti_shares),
+            AssetAllocation::mock_stock("Bonds", "BND", dec!(0.5), price, bnd_shares),
+        ];
+
+        if let Some(rebalance_to) = band {
+            for asset in &mut assets {
+                asset.band = Some(Band::mock(Some(dec!(0.05)), None, rebalance_to));
+            }
+        }
+
+        Portfolio::mock(broker, "USD", cash, assets)
+    }
+
+    fn get_target_shares(portfolio: &mut Portfolio) -> Vec<Decimal> {
+        portfolio.stock_holdings().iter().map(|holding| holding.target_shares).collect()
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+use chrono::Duration;
+use log::debug;
+
+use crate::broker_statement::BrokerStatement;
+use crate::commissions::CommissionCalc;
+use crate::config::PortfolioConfig;
+use crate::core::GenericResult;
+use crate::currency::converter::CurrencyConverterRc;
+use crate::formatting;
+use crate::localities::Country;
+use crate::quotes::Quotes;
+use crate::taxes::{NetTaxCalculator, TaxExemption, long_term_ownership};
+use crate::types::{Date, Decimal};
+
+/// Lots which are going to get long-term ownership tax exemption during this period aren't sold by
+/// tax-aware rebalancing: it's cheaper to wait a little than to pay the tax.
+const LONG_TERM_OWNERSHIP_WAITING_DAYS: i64 = 180;
+
+/// Open position lot with estimated tax to pay (in portfolio currency) on its selling by the
+/// current price.
+struct TaxLot {
+    quantity: Decimal,
+    tax: Decimal,
+    long_term_ownership_soon: bool,
+}
+
+/// Open position lots in FIFO order (in which they are going to be sold)
+pub struct TaxLots(Vec<TaxLot>);
+
+impl TaxLots {
+    /// Adjusts the lots calculated from broker statement to the actual number of shares (which may
+    /// be changed by trades that aren't in the broker statements yet): missing shares are assumed
+    /// to be sold by FIFO and the extra ones - bought by the current price (without any tax).
+    fn adjust(&mut self, shares: Decimal) {
+        let mut excess = self.0.iter().map(|lot| lot.quantity).sum::<Decimal>() - shares;
+
+        if excess.is_sign_negative() {
+            self.0.push(TaxLot {
+                quantity: -excess,
+                tax: dec!(0),
+                long_term_ownership_soon: false,
+            });
+            return;
+        }
+
+        while !excess.is_zero() {
+            let lot = self.0.first_mut().unwrap();
+
+            if lot.quantity <= excess {
+                excess -= lot.quantity;
+                self.0.remove(0);
+            } else {
+                lot.tax = lot.tax * (lot.quantity - excess) / lot.quantity;
+                lot.quantity -= excess;
+                excess = dec!(0);
+            }
+        }
+    }
+
+    /// Returns the number of shares that can be sold without paying tax above the specified rate
+    /// (in percents of selling volume): lots are sold in FIFO order, so we stop on the first lot
+    /// which is too expensive to sell.
+    pub fn sellable_shares(&self, price: Decimal, max_tax_rate: Decimal) -> Decimal {
+        let mut shares = dec!(0);
+
+        for lot in &self.0 {
+            if lot.long_term_ownership_soon || lot.tax > lot.quantity * price * max_tax_rate / dec!(100) {
+                break;
+            }
+            shares += lot.quantity;
+        }
+
+        shares
+    }
+
+    /// Estimates tax to pay on selling of the specified number of shares
+    pub fn tax(&self, mut shares: Decimal) -> Decimal {
+        let mut tax = dec!(0);
+
+        for lot in &self.0 {
+            if shares.is_zero() {
+                break;
+            }
+
+            let quantity = std::cmp::min(shares, lot.quantity);
+            tax += lot.tax * quantity / lot.quantity;
+            shares -= quantity;
+        }
+
+        tax
+    }
+}
+
+/// Taxable profit of an open position lot on its emulated selling
+struct LotProfit<'a> {
+    symbol: &'a str,
+    date: Date,
+    tax_year: i32,
+    quantity: Decimal,
+    taxable_profit: Decimal,
+    long_term_ownership_soon: bool,
+}
+
+/// Calculates tax lots for each open position of the portfolio by emulating its selling by the
+/// current price (the same way as sell simulation does).
+pub fn calculate(
+    country: &Country, portfolio: &PortfolioConfig, mut statement: BrokerStatement,
+    positions: &HashMap<String, Decimal>, converter: CurrencyConverterRc, quotes: &Quotes,
+) -> GenericResult<HashMap<String, TaxLots>> {
+    let currency = portfolio.currency()?;
+    let long_term_ownership = portfolio.tax_exemptions.iter().any(|exemption| {
+        matches!(exemption, TaxExemption::LongTermOwnership)
+    });
+
+    let net_value = statement.net_value(&converter, quotes, currency, true)?;
+    let mut commission_calc = CommissionCalc::new(
+        converter.clone(), statement.broker.commission_spec.clone(), net_value)?;
+
+    let mut open_positions: Vec<(String, Decimal)> = statement.open_positions.iter()
+        .filter(|(_, quantity)| quantity.is_sign_positive())
+        .map(|(symbol, &quantity)| (symbol.clone(), quantity))
+        .collect();
+    open_positions.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+    for (symbol, quantity) in &open_positions {
+        let price = statement.get_price(quotes, symbol)?;
+        statement.emulate_sell(symbol, *quantity, price, &mut commission_calc)?;
+    }
+    statement.process_trades(None)?;
+
+    // Losses of some lots offset profit of the others, so all lots are sold within one tax
+    // calculation and the resulting tax is allocated to the profitable lots proportionally to their
+    // taxable profit.
+    let mut lots = Vec::new();
+    let mut net_tax_calculator = NetTaxCalculator::new(country.clone(), portfolio.tax_payment_day());
+
+    for trade in statement.stock_sells.iter().filter(|trade| trade.emulation) {
+        let execution_date = trade.execution_date;
+        let (tax_year, _) = portfolio.tax_payment_day().get(execution_date, true);
+        let details = trade.calculate(country, tax_year, &portfolio.tax_exemptions, &converter)?;
+
+        for source in &details.fifo {
+            let quantity = source.quantity * source.multiplier;
+            let ratio = quantity / trade.quantity;
+
+            let local_profit = details.local_revenue * ratio
+                - details.local_commission * ratio
+                - source.total_cost(country.currency, &converter)?;
+
+            let exempted = source.tax_exemption_applied || source.long_term_ownership_deductible.is_some();
+            let taxable_local_profit = if exempted {
+                country.cash(dec!(0))
+            } else {
+                local_profit
+            };
+            net_tax_calculator.add_profit(execution_date, local_profit, taxable_local_profit, &[], true);
+
+            let long_term_ownership_soon =
+                long_term_ownership && source.long_term_ownership_deductible.is_none() &&
+                long_term_ownership::is_deductible(
+                    source.execution_date, execution_date + Duration::days(LONG_TERM_OWNERSHIP_WAITING_DAYS),
+                ).is_some();
+
+            lots.push(LotProfit {
+                symbol: &trade.symbol,
+                date: source.execution_date,
+                tax_year, quantity,
+                taxable_profit: taxable_local_profit.amount,
+                long_term_ownership_soon,
+            });
+        }
+    }
+
+    let net_taxes = net_tax_calculator.calculate();
+    let mut taxable_profit: HashMap<i32, Decimal> = HashMap::new();
+
+    for lot in &lots {
+        if lot.taxable_profit > dec!(0) {
+            *taxable_profit.entry(lot.tax_year).or_default() += lot.taxable_profit;
+        }
+    }
+
+    let mut tax_lots = HashMap::new();
+
+    for lot in lots {
+        let local_tax = match (net_taxes.get(&lot.tax_year), taxable_profit.get(&lot.tax_year)) {
+            (Some(net_tax), Some(&total_profit)) if lot.taxable_profit > dec!(0) => {
+                net_tax.tax_to_pay.amount * lot.taxable_profit / total_profit
+            },
+            _ => dec!(0),
+        };
+        let tax = converter.real_time_convert_to(country.cash(local_tax), currency)?;
+
+        debug!("{} tax lot: {}: {} shares, {} tax{}",
+               lot.symbol, formatting::format_date(lot.date), lot.quantity.normalize(), tax.normalize(),
+               if lot.long_term_ownership_soon { " (long-term ownership soon)" } else { "" });
+
+        tax_lots.entry(lot.symbol.to_owned()).or_insert_with(|| TaxLots(Vec::new())).0.push(TaxLot {
+            quantity: lot.quantity,
+            tax,
+            long_term_ownership_soon: lot.long_term_ownership_soon,
+        });
+    }
+
+    for (symbol, &shares) in positions {
+        tax_lots.entry(symbol.clone()).or_insert_with(|| TaxLots(Vec::new())).adjust(shares);
+    }
+    tax_lots.retain(|symbol, _| positions.contains_key(symbol));
+
+    Ok(tax_lots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tax_lots() {
+        let lot = |quantity, tax, long_term_ownership_soon| TaxLot {quantity, tax, long_term_ownership_soon};
+
+        let mut lots = TaxLots(vec![
+            lot(dec!(10), dec!(0), false),
+            lot(dec!(10), dec!(5), false),
+            lot(dec!(10), dec!(1), true),
+            lot(dec!(10), dec!(0), false),
+        ]);
+        assert_eq!(lots.sellable_shares(dec!(10), dec!(0)), dec!(10));
+        assert_eq!(lots.sellable_shares(dec!(10), dec!(5)), dec!(20));
+        assert_eq!(lots.tax(dec!(15)), dec!(2.5));
+
+        lots.adjust(dec!(35));
+        assert_eq!(lots.sellable_shares(dec!(10), dec!(0)), dec!(5));
+        assert_eq!(lots.tax(dec!(10)), dec!(2.5));
+
+        lots.adjust(dec!(40));
+        assert_eq!(lots.tax(dec!(40)), dec!(6));
+    }
+}
@@ -2,6 +2,7 @@ use std::collections::HashMap;
 use std::sync::Mutex;
 use std::time::Duration;
 
+use chrono::NaiveDateTime;
 #[cfg(test)] use indoc::indoc;
 use log::debug;
 #[cfg(test)] use mockito::{self, Mock, mock};
@@ -15,10 +16,11 @@ use crate::core::{GenericResult, EmptyResult};
 use crate::currency::Cash;
 use crate::exchanges::Exchange;
 use crate::rate_limiter::RateLimiter;
+use crate::time::Period;
 use crate::util::{self, DecimalRestrictions};
 use crate::types::Decimal;
 
-use super::{QuotesMap, QuotesProvider};
+use super::{QuotesMap, QuotesProvider, HistoricalQuotesMap};
 use super::common::{send_request, is_outdated_unix_time};
 
 #[derive(Deserialize)]
@@ -70,34 +72,100 @@ impl Finnhub {
         let price = util::validate_decimal(price, DecimalRestrictions::StrictlyPositive)
             .map_err(|_| format!("Got an invalid {} price: {:?}", symbol, price))?;
 
-        // Profile API has too expensive rate limit weight, so try to avoid using it
-        let currency = if symbol.contains('.') {
-            #[derive(Deserialize)]
-            struct Profile {
-                currency: String,
-            }
+        let currency = match self.get_currency(symbol)? {
+            Some(currency) => currency,
+            None => return Ok(None),
+        };
+
+        Ok(Some(Cash::new(&currency, price)))
+    }
+
+    fn get_candles(&self, symbol: &str, period: Period) -> GenericResult<Option<HistoricalQuotesMap>> {
+        #[derive(Deserialize)]
+        struct Candles {
+            #[serde(rename = "s")]
+            status: String,
 
-            let profile = match self.query::<Profile>("stock/profile2", symbol)? {
-                Some(profile) => profile,
-                None => return Ok(None),
-            };
+            #[serde(rename = "t", default)]
+            times: Vec<i64>,
+
+            #[serde(rename = "c", default)]
+            close_prices: Vec<Decimal>,
+        }
 
-            profile.currency
-        } else {
-            s!("USD")
+        // Daily candles are timestamped by day start in UTC
+        let start_time = period.first_date().and_hms(0, 0, 0).timestamp().to_string();
+        let end_time = period.last_date().and_hms(23, 59, 59).timestamp().to_string();
+
+        let candles = match self.query_with::<Candles>("stock/candle", symbol, &[
+            ("resolution", "D"),
+            ("from", start_time.as_str()),
+            ("to", end_time.as_str()),
+        ])? {
+            Some(candles) if candles.status == "ok" => candles,
+            Some(candles) if candles.status == "no_data" => return Ok(None),
+            Some(candles) => return Err!("Got an unexpected {} candles status: {:?}", symbol, candles.status),
+            None => return Ok(None),
         };
 
-        Ok(Some(Cash::new(&currency, price)))
+        if candles.times.len() != candles.close_prices.len() {
+            return Err!("Got an unexpected {} candles: time and price arrays have different length", symbol);
+        }
+
+        let currency = match self.get_currency(symbol)? {
+            Some(currency) => currency,
+            None => return Ok(None),
+        };
+
+        let mut quotes = HistoricalQuotesMap::new();
+
+        for (&time, &price) in candles.times.iter().zip(candles.close_prices.iter()) {
+            let date = NaiveDateTime::from_timestamp_opt(time, 0).ok_or_else(|| format!(
+                "Got an invalid UNIX time: {}", time))?.date();
+
+            if !period.contains(date) {
+                continue;
+            }
+
+            let price = util::validate_decimal(price, DecimalRestrictions::StrictlyPositive)
+                .map_err(|_| format!("Got an invalid {} price: {:?}", symbol, price))?;
+
+            quotes.insert(date, Cash::new(&currency, price));
+        }
+
+        Ok(Some(quotes))
+    }
+
+    fn get_currency(&self, symbol: &str) -> GenericResult<Option<String>> {
+        // Profile API has too expensive rate limit weight, so try to avoid using it
+        if !symbol.contains('.') {
+            return Ok(Some(s!("USD")));
+        }
+
+        #[derive(Deserialize)]
+        struct Profile {
+            currency: String,
+        }
+
+        Ok(self.query::<Profile>("stock/profile2", symbol)?.map(|profile| profile.currency))
     }
 
     fn query<T: DeserializeOwned>(&self, method: &str, symbol: &str) -> GenericResult<Option<T>> {
+        self.query_with(method, symbol, &[])
+    }
+
+    fn query_with<T: DeserializeOwned>(
+        &self, method: &str, symbol: &str, params: &[(&str, &str)],
+    ) -> GenericResult<Option<T>> {
         #[cfg(not(test))] let base_url = "https://finnhub.io";
         #[cfg(test)] let base_url = mockito::server_url();
 
-        let url = Url::parse_with_params(&format!("{}/api/v1/{}", base_url, method), &[
+        let mut url = Url::parse_with_params(&format!("{}/api/v1/{}", base_url, method), &[
             ("symbol", symbol),
-            ("token", self.token.as_ref()),
         ])?;
+        url.query_pairs_mut()
+            .extend_pairs(params)
+            .append_pair("token", &self.token);
 
         let get = |url| -> GenericResult<Option<T>> {
             self.rate_limiter.wait(&format!("request to {}", url));
@@ -145,6 +213,10 @@ impl QuotesProvider for Finnhub {
 
         Ok(quotes.into_inner().unwrap())
     }
+
+    fn get_historical_quotes(&self, symbol: &str, period: Period) -> GenericResult<Option<HistoricalQuotesMap>> {
+        self.get_candles(symbol, period)
+    }
 }
 
 #[cfg(test)]
@@ -268,6 +340,37 @@ mod tests {
         ]).unwrap(), quotes);
     }
 
+    #[rstest]
+    fn historical_quotes(client: Finnhub) {
+        let period = Period::new(date!(2021, 3, 1), date!(2021, 3, 3)).unwrap();
+
+        let _bnd_mock = mock_response(
+            "/api/v1/stock/candle?symbol=BND&resolution=D&from=1614556800&to=1614815999&token=mock",
+            indoc!(r#"
+                {
+                    "c": [85.27, 85.45, 85.14],
+                    "h": [85.32, 85.45, 85.39],
+                    "l": [85.17, 85.28, 85.12],
+                    "o": [85.27, 85.33, 85.38],
+                    "s": "ok",
+                    "t": [1614556800, 1614643200, 1614729600],
+                    "v": [3956430, 3539810, 4196734]
+                }
+            "#),
+        );
+        assert_eq!(client.get_historical_quotes("BND", period).unwrap(), Some(btreemap!{
+            date!(2021, 3, 1) => Cash::new("USD", dec!(85.27)),
+            date!(2021, 3, 2) => Cash::new("USD", dec!(85.45)),
+            date!(2021, 3, 3) => Cash::new("USD", dec!(85.14)),
+        }));
+
+        let _unknown_mock = mock_response(
+            "/api/v1/stock/candle?symbol=UNKNOWN&resolution=D&from=1614556800&to=1614815999&token=mock",
+            r#"{"s": "no_data"}"#,
+        );
+        assert_eq!(client.get_historical_quotes("UNKNOWN", period).unwrap(), None);
+    }
+
     fn mock_response(path: &str, data: &str) -> Mock {
         // All responses are always 200 OK, some of them are returned with application/json content
         // type, some - with text/plain even for JSON payload.
@@ -0,0 +1,205 @@
+use std::collections::BTreeMap;
+
+use chrono::Duration;
+use diesel::{self, prelude::*};
+#[cfg(test)] use tempfile::NamedTempFile;
+
+use crate::core::{GenericResult, GenericError, EmptyResult};
+use crate::currency::Cash;
+use crate::db::{self, schema::historical_quotes, models};
+use crate::formatting;
+use crate::time::{self, Date, Period};
+use crate::util::{self, DecimalRestrictions};
+
+pub type HistoricalQuotesMap = BTreeMap<Date, Cash>;
+
+/// Stores daily close prices. Each day of the backfilled period up to the last received quote is
+/// stored in the database: days without trading are stored with empty price, so we can distinguish
+/// them from days for which quotes haven't been requested yet. Days after the last received quote
+/// aren't stored, because the provider may just have no data for them yet.
+///
+/// Only past days are stored: today's price isn't final until trading session close.
+pub struct HistoricalQuotes {
+    db: db::Connection,
+    pub(super) today: Date,
+}
+
+impl HistoricalQuotes {
+    pub fn new(connection: db::Connection) -> HistoricalQuotes {
+        HistoricalQuotes {
+            db: connection,
+            today: time::today(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn new_temporary() -> (NamedTempFile, HistoricalQuotes) {
+        let (database, connection) = db::new_temporary();
+        (database, HistoricalQuotes::new(connection))
+    }
+
+    pub fn today(&self) -> Date {
+        self.today
+    }
+
+    pub fn get(&self, symbol: &str, date: Date) -> GenericResult<HistoricalQuoteResult> {
+        if date >= self.today {
+            return Err!("An attempt to get historical quote for today or future");
+        }
+
+        self.db.transaction::<_, GenericError, _>(|| {
+            let result = historical_quotes::table
+                .select((historical_quotes::currency, historical_quotes::price))
+                .filter(historical_quotes::symbol.eq(symbol))
+                .filter(historical_quotes::date.eq(date))
+                .get_result::<(Option<String>, Option<String>)>(&*self.db).optional()?;
+
+            if let Some(result) = result {
+                let price = match result {
+                    (Some(currency), Some(price)) => {
+                        let price = util::parse_decimal(&price, DecimalRestrictions::StrictlyPositive).map_err(|_| format!(
+                            "Got an invalid price from the database: {:?}", price))?;
+                        Some(Cash::new(&currency, price))
+                    },
+                    (None, None) => None,
+                    _ => return Err!("Got an invalid {} historical quote from the database", symbol),
+                };
+
+                return Ok(HistoricalQuoteResult::Exists(price));
+            }
+
+            let start_date = historical_quotes::table
+                .select(historical_quotes::date)
+                .filter(historical_quotes::symbol.eq(symbol))
+                .filter(historical_quotes::date.lt(date))
+                .order(historical_quotes::date.desc())
+                .limit(1)
+                .get_result::<Date>(&*self.db).optional()?
+                .map(|last_date| last_date.succ())
+                .unwrap_or_else(|| date - Duration::days(365));
+
+            let end_date = historical_quotes::table
+                .select(historical_quotes::date)
+                .filter(historical_quotes::symbol.eq(symbol))
+                .filter(historical_quotes::date.gt(date))
+                .order(historical_quotes::date.asc())
+                .limit(1)
+                .get_result::<Date>(&*self.db).optional()?
+                .map(|first_date| first_date.pred())
+                .unwrap_or_else(|| self.today.pred());
+
+            Ok(HistoricalQuoteResult::Missing(Period::new(start_date, end_date)?))
+        })
+    }
+
+    pub fn save(&self, symbol: &str, period: Period, quotes: &HistoricalQuotesMap) -> EmptyResult {
+        if period.last_date() >= self.today {
+            return Err!("An attempt to save historical quotes for today or future");
+        }
+
+        if let Some((&date, _)) = quotes.iter().find(|(&date, _)| !period.contains(date)) {
+            return Err!("Got {} historical quote for {} which is out of the requested period: {}",
+                symbol, formatting::format_date(date), period.format());
+        }
+
+        let last_date = match quotes.keys().next_back() {
+            Some(&last_date) => last_date,
+            None => return Ok(()),
+        };
+
+        let mut rows = Vec::with_capacity(period.days() as usize);
+        let mut date = period.first_date();
+
+        while date <= last_date {
+            let quote = quotes.get(&date);
+
+            rows.push(models::NewHistoricalQuote {
+                symbol: symbol,
+                date: date,
+                currency: quote.map(|price| price.currency),
+                price: quote.map(|price| price.amount.to_string()),
+            });
+
+            date = date.succ();
+        }
+
+        diesel::replace_into(historical_quotes::table)
+            .values(rows)
+            .execute(&*self.db)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum HistoricalQuoteResult {
+    Exists(Option<Cash>),
+    Missing(Period),
+}
+
+#[cfg(test)]
+mod tests {
+    use matches::assert_matches;
+    use super::*;
+
+    #[test]
+    fn historical_quotes() {
+        let symbol = "VTI";
+        let (_database, mut history) = HistoricalQuotes::new_temporary();
+        history.today = date!(2021, 3, 10);
+
+        let period = |first, last| Period::new(first, last).unwrap();
+        let price = Cash::new("USD", dec!(199.68));
+
+        assert_matches!(
+            history.get(symbol, date!(2021, 3, 10)),
+            Err(ref e) if e.to_string() == "An attempt to get historical quote for today or future"
+        );
+
+        assert_matches!(
+            history.get(symbol, date!(2021, 3, 5)).unwrap(),
+            HistoricalQuoteResult::Missing(missing) if missing == period(date!(2020, 3, 5), date!(2021, 3, 9))
+        );
+
+        history.save(symbol, period(date!(2021, 3, 1), date!(2021, 3, 7)), &btreemap!{
+            date!(2021, 3, 5) => price,
+        }).unwrap();
+
+        assert_matches!(
+            history.get(symbol, date!(2021, 3, 5)).unwrap(),
+            HistoricalQuoteResult::Exists(Some(quote)) if quote == price
+        );
+        assert_matches!(
+            history.get(symbol, date!(2021, 3, 4)).unwrap(),
+            HistoricalQuoteResult::Exists(None)
+        );
+        assert_matches!(
+            history.get(symbol, date!(2021, 3, 6)).unwrap(),
+            HistoricalQuoteResult::Missing(missing) if missing == period(date!(2021, 3, 6), date!(2021, 3, 9))
+        );
+        assert_matches!(
+            history.get(symbol, date!(2021, 2, 1)).unwrap(),
+            HistoricalQuoteResult::Missing(missing) if missing == period(date!(2020, 2, 1), date!(2021, 2, 28))
+        );
+        assert_matches!(
+            history.get(symbol, date!(2021, 3, 9)).unwrap(),
+            HistoricalQuoteResult::Missing(missing) if missing == period(date!(2021, 3, 6), date!(2021, 3, 9))
+        );
+        assert_matches!(
+            history.get("BND", date!(2021, 3, 5)).unwrap(),
+            HistoricalQuoteResult::Missing(_)
+        );
+
+        // Empty response doesn't mark the period as days without trading
+        history.save(symbol, period(date!(2021, 3, 6), date!(2021, 3, 9)), &btreemap!{}).unwrap();
+        assert_matches!(
+            history.get(symbol, date!(2021, 3, 8)).unwrap(),
+            HistoricalQuoteResult::Missing(missing) if missing == period(date!(2021, 3, 6), date!(2021, 3, 9))
+        );
+
+        assert!(history.save(symbol, period(date!(2021, 3, 8), date!(2021, 3, 10)), &btreemap!{}).is_err());
+        assert!(history.save(symbol, period(date!(2021, 3, 8), date!(2021, 3, 9)), &btreemap!{
+            date!(2021, 3, 7) => price,
+        }).is_err());
+    }
+}
@@ -3,6 +3,7 @@ mod cache;
 mod common;
 pub mod fcsapi;
 pub mod finnhub;
+mod history;
 mod moex;
 pub mod twelvedata;
 
@@ -11,6 +12,9 @@ use std::collections::{hash_map::Entry, HashMap};
 use std::sync::Arc;
 #[cfg(test)] use std::sync::Mutex;
 
+#[cfg(test)] use tempfile::NamedTempFile;
+
+use chrono::Duration;
 use itertools::Itertools;
 use log::debug;
 use rayon::prelude::*;
@@ -20,11 +24,15 @@ use crate::core::{EmptyResult, GenericResult};
 use crate::currency::Cash;
 use crate::db;
 use crate::exchanges::{Exchange, Exchanges};
+use crate::formatting;
+use crate::time::{Date, Period};
 
 use self::cache::Cache;
 use self::common::parse_currency_pair;
 use self::fcsapi::FcsApi;
 use self::finnhub::Finnhub;
+use self::history::{HistoricalQuotes, HistoricalQuoteResult};
+pub use self::history::HistoricalQuotesMap;
 use self::moex::Moex;
 
 #[derive(Clone)]
@@ -49,6 +57,7 @@ impl QuoteQuery {
 
 pub struct Quotes {
     cache: Cache,
+    history: HistoricalQuotes,
     providers: Vec<Arc<dyn QuotesProvider>>,
     batched_requests: RefCell<HashMap<String, QuoteRequest>>,
 }
@@ -61,17 +70,35 @@ impl Quotes {
         let finnhub = config.finnhub.as_ref().ok_or(
             "Finnhub token is not set in the configuration file")?;
 
-        Ok(Quotes::new_with(Cache::new(database, config.cache_expire_time, true), vec![
+        let cache = Cache::new(database.clone(), config.cache_expire_time, true);
+        let history = HistoricalQuotes::new(database);
+
+        Ok(Quotes::new_with(cache, history, vec![
             Arc::new(Finnhub::new(finnhub)),
             Arc::new(FcsApi::new(fcsapi)),
             Arc::new(Moex::new("TQTF")),
             Arc::new(Moex::new("TQBR")),
+            Arc::new(Moex::new_bonds("TQOB")),
+            Arc::new(Moex::new_bonds("TQCB")),
         ]))
     }
 
-    fn new_with(cache: Cache, providers: Vec<Arc<dyn QuotesProvider>>) -> Quotes {
+    /// Returns quotes which get both real time and historical prices of US stocks from the specified
+    /// daily close prices.
+    #[cfg(test)]
+    pub fn mock(today: Date, quotes: HashMap<String, HistoricalQuotesMap>) -> (Vec<NamedTempFile>, Quotes) {
+        let (cache_database, cache) = Cache::new_temporary();
+        let (history_database, mut history) = HistoricalQuotes::new_temporary();
+        history.today = today;
+
+        let provider = Arc::new(QuotesProviderMock {quotes});
+        (vec![cache_database, history_database], Quotes::new_with(cache, history, vec![provider]))
+    }
+
+    fn new_with(cache: Cache, history: HistoricalQuotes, providers: Vec<Arc<dyn QuotesProvider>>) -> Quotes {
         Quotes {
             cache: cache,
+            history: history,
             providers: providers,
             batched_requests: RefCell::new(HashMap::new()),
         }
@@ -95,6 +122,75 @@ impl Quotes {
         Ok(self.cache.get(query.symbol())?.unwrap())
     }
 
+    /// Returns close price of the last trading day preceding or equal to the specified date.
+    /// Missing history is backfilled from the providers on demand.
+    pub fn get_historical(&self, query: QuoteQuery, date: Date) -> GenericResult<Cash> {
+        if date >= self.history.today() {
+            return self.get(query);
+        }
+
+        let (symbol, exchanges) = match query {
+            QuoteQuery::Stock(symbol, exchanges) => (symbol, get_exchanges(exchanges)),
+            QuoteQuery::Forex(_) => return Err!("Historical quotes are supported only for stocks"),
+        };
+
+        // Exchanges may be closed for a long time during the New Year holidays
+        let max_lookback = 14;
+        let min_date = date - Duration::days(max_lookback - 1);
+        let mut cur_date = date;
+
+        while cur_date >= min_date {
+            match self.history.get(&symbol, cur_date)? {
+                HistoricalQuoteResult::Exists(Some(price)) => return Ok(price),
+                HistoricalQuoteResult::Exists(None) => {
+                    cur_date = cur_date.pred();
+                },
+                HistoricalQuoteResult::Missing(period) => {
+                    let quotes = self.get_historical_quotes(&symbol, &exchanges, period)?.unwrap_or_default();
+                    self.history.save(&symbol, period, &quotes)?;
+
+                    // Only the days up to the last returned quote are saved, so if the providers
+                    // have no data for the current day, look for the price in the returned quotes
+                    // and then in the history preceding the requested period.
+                    if quotes.keys().next_back().map_or(true, |&last_date| last_date < cur_date) {
+                        if let Some((_, &price)) = quotes.range(min_date..=cur_date).next_back() {
+                            return Ok(price);
+                        }
+                        cur_date = period.first_date().pred();
+                    }
+                },
+            }
+        }
+
+        Err!("Unable to find {} quotes for {} with {} days precision",
+             symbol, formatting::format_date(date), max_lookback)
+    }
+
+    fn get_historical_quotes(
+        &self, symbol: &str, exchanges: &[Exchange], period: Period,
+    ) -> GenericResult<Option<HistoricalQuotesMap>> {
+        for &exchange in exchanges {
+            for provider in &self.providers {
+                if provider.supports_stocks() != Some(exchange) {
+                    continue;
+                }
+
+                debug!("Getting {} historical quotes for {} from {}...",
+                       symbol, period.format(), provider.name());
+
+                let quotes = provider.get_historical_quotes(symbol, period).map_err(|e| format!(
+                    "Failed to get historical quotes from {}: {}", provider.name(), e))?;
+
+                if quotes.is_some() {
+                    return Ok(quotes);
+                }
+            }
+        }
+
+        // There may be no trading days in short periods, so it's not an error
+        Ok(None)
+    }
+
     fn batch_forex(&self, mut symbol: String) -> GenericResult<Option<Cash>> {
         let (base, quote) = parse_currency_pair(&symbol)?;
 
@@ -135,21 +231,7 @@ impl Quotes {
             return Ok(Some(price));
         }
 
-        let exchanges = {
-            let mut new_exchanges = Exchanges::new_empty();
-
-            for exchange in exchanges.into_iter().rev() {
-                if exchange == Exchange::Spb {
-                    // We don't have SPB provider yet, so emulate it using existing providers
-                    new_exchanges.add_prioritized(Exchange::Moex);
-                    new_exchanges.add_prioritized(Exchange::Us);
-                } else {
-                    new_exchanges.add_prioritized(exchange);
-                }
-            }
-
-            new_exchanges.get_prioritized()
-        };
+        let exchanges = get_exchanges(exchanges);
 
         match self.batched_requests.borrow_mut().entry(symbol) {
             Entry::Vacant(entry) => {
@@ -277,6 +359,22 @@ impl Quotes {
     }
 }
 
+fn get_exchanges(exchanges: Vec<Exchange>) -> Vec<Exchange> {
+    let mut new_exchanges = Exchanges::new_empty();
+
+    for exchange in exchanges.into_iter().rev() {
+        if exchange == Exchange::Spb {
+            // We don't have SPB provider yet, so emulate it using existing providers
+            new_exchanges.add_prioritized(Exchange::Moex);
+            new_exchanges.add_prioritized(Exchange::Us);
+        } else {
+            new_exchanges.add_prioritized(exchange);
+        }
+    }
+
+    new_exchanges.get_prioritized()
+}
+
 type QuotesMap = HashMap<String, Cash>;
 
 trait QuotesProvider: Send + Sync {
@@ -285,6 +383,41 @@ trait QuotesProvider: Send + Sync {
     fn supports_forex(&self) -> bool {false}
     fn high_precision(&self) -> bool {false}
     fn get_quotes(&self, symbols: &[&str]) -> GenericResult<QuotesMap>;
+
+    /// Returns daily close prices for the specified period or None if the provider doesn't know
+    /// anything about the symbol or the period.
+    fn get_historical_quotes(&self, _symbol: &str, _period: Period) -> GenericResult<Option<HistoricalQuotesMap>> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+struct QuotesProviderMock {
+    quotes: HashMap<String, HistoricalQuotesMap>,
+}
+
+#[cfg(test)]
+impl QuotesProvider for QuotesProviderMock {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn supports_stocks(&self) -> Option<Exchange> {
+        Some(Exchange::Us)
+    }
+
+    fn get_quotes(&self, symbols: &[&str]) -> GenericResult<QuotesMap> {
+        Ok(symbols.iter().filter_map(|&symbol| {
+            let (_, &price) = self.quotes.get(symbol)?.iter().next_back()?;
+            Some((symbol.to_owned(), price))
+        }).collect())
+    }
+
+    fn get_historical_quotes(&self, symbol: &str, period: Period) -> GenericResult<Option<HistoricalQuotesMap>> {
+        Ok(self.quotes.get(symbol).map(|quotes| {
+            quotes.range(period.first_date()..=period.last_date()).map(|(&date, &price)| (date, price)).collect()
+        }))
+    }
 }
 
 pub fn get_currency_pair(base: &str, quote: &str) -> String {
@@ -378,7 +511,8 @@ mod tests {
         }
 
         let (_database, cache) = Cache::new_temporary();
-        let quotes = Quotes::new_with(cache, vec![
+        let (_history_database, history) = HistoricalQuotes::new_temporary();
+        let quotes = Quotes::new_with(cache, history, vec![
             Arc::new(FirstProvider {request_id: Mutex::new(0)}),
             Arc::new(OtherProvider {}),
             Arc::new(SecondProvider {request_id: Mutex::new(0)}),
@@ -396,4 +530,68 @@ mod tests {
         assert_eq!(quotes.get(query("VTI")).unwrap(), Cash::new("USD", dec!(56.78)));
         assert_eq!(quotes.get(query("BNDX")).unwrap(), Cash::new("USD", dec!(90.12)));
     }
-}
\ No newline at end of file
+
+    #[test]
+    #[allow(clippy::mutex_atomic)]
+    fn historical_quotes() {
+        struct HistoryProvider {
+            requests: Mutex<Vec<(String, Period)>>,
+        }
+
+        impl QuotesProvider for HistoryProvider {
+            fn name(&self) -> &'static str {
+                "history-provider"
+            }
+
+            fn supports_stocks(&self) -> Option<Exchange> {
+                Some(Exchange::Us)
+            }
+
+            fn get_quotes(&self, _symbols: &[&str]) -> GenericResult<QuotesMap> {
+                unreachable!()
+            }
+
+            fn get_historical_quotes(&self, symbol: &str, period: Period) -> GenericResult<Option<HistoricalQuotesMap>> {
+                self.requests.lock().unwrap().push((symbol.to_owned(), period));
+                if symbol == "BND" {
+                    return Ok(None);
+                }
+                assert_eq!(symbol, "VTI");
+
+                Ok(Some(btreemap!{
+                    date!(2021, 3, 5) => Cash::new("USD", dec!(199.68)),
+                }.into_iter().filter(|(date, _)| period.contains(*date)).collect()))
+            }
+        }
+
+        let provider = Arc::new(HistoryProvider {requests: Mutex::new(Vec::new())});
+        let (_database, cache) = Cache::new_temporary();
+        let (_history_database, mut history) = HistoricalQuotes::new_temporary();
+        history.today = date!(2021, 3, 10);
+
+        let quotes = Quotes::new_with(cache, history, vec![provider.clone() as Arc<dyn QuotesProvider>]);
+        let query = |symbol: &str| QuoteQuery::Stock(symbol.to_owned(), vec![Exchange::Us]);
+        let period = |first, last| Period::new(first, last).unwrap();
+
+        // Weekend is resolved to the last trading day
+        for &date in &[date!(2021, 3, 5), date!(2021, 3, 7)] {
+            assert_eq!(quotes.get_historical(query("VTI"), date).unwrap(), Cash::new("USD", dec!(199.68)));
+        }
+
+        // Days after the last returned quote aren't saved, so they are requested again
+        assert_eq!(quotes.get_historical(query("VTI"), date!(2021, 3, 8)).unwrap(), Cash::new("USD", dec!(199.68)));
+
+        // Empty response isn't saved as days without trading
+        for _ in 0..2 {
+            assert!(quotes.get_historical(query("BND"), date!(2021, 3, 5)).is_err());
+        }
+
+        assert_eq!(*provider.requests.lock().unwrap(), vec![
+            (s!("VTI"), period(date!(2020, 3, 5), date!(2021, 3, 9))),
+            (s!("VTI"), period(date!(2021, 3, 6), date!(2021, 3, 9))),
+            (s!("VTI"), period(date!(2021, 3, 6), date!(2021, 3, 9))),
+            (s!("BND"), period(date!(2020, 3, 5), date!(2021, 3, 9))),
+            (s!("BND"), period(date!(2020, 3, 5), date!(2021, 3, 9))),
+        ]);
+    }
+}
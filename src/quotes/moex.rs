@@ -11,18 +11,23 @@ use crate::core::GenericResult;
 use crate::currency::Cash;
 use crate::exchanges::Exchange;
 #[cfg(not(test))] use crate::localities;
-use crate::time;
+use crate::time::{self, Period};
 use crate::types::{Decimal, Date};
 
-use super::{QuotesMap, QuotesProvider};
+use super::{QuotesMap, QuotesProvider, HistoricalQuotesMap};
 
 pub struct Moex {
+    market: &'static str,
     board: String,
 }
 
 impl Moex {
     pub fn new(board: &str) -> Moex {
-        Moex {board: board.to_owned()}
+        Moex {market: "shares", board: board.to_owned()}
+    }
+
+    pub fn new_bonds(board: &str) -> Moex {
+        Moex {market: "bonds", board: board.to_owned()}
     }
 }
 
@@ -40,7 +45,8 @@ impl QuotesProvider for Moex {
         #[cfg(test)] let base_url = mockito::server_url();
 
         let url = Url::parse_with_params(
-            &format!("{}/iss/engines/stock/markets/shares/boards/{}/securities.xml", base_url, self.board),
+            &format!("{}/iss/engines/stock/markets/{}/boards/{}/securities.xml",
+                     base_url, self.market, self.board),
             &[("securities", symbols.join(",").as_str())],
         )?;
 
@@ -53,16 +59,74 @@ impl QuotesProvider for Moex {
                 return Err!("The server returned an error: {}", response.status());
             }
 
-            Ok(parse_quotes(&response.text()?).map_err(|e| format!(
+            let bonds = self.market == "bonds";
+            Ok(parse_quotes(&response.text()?, bonds).map_err(|e| format!(
                 "Quotes info parsing error: {}", e))?)
         };
 
         Ok(get(url.as_str()).map_err(|e| format!(
             "Failed to get quotes from {}: {}", url, e))?)
     }
+
+    fn get_historical_quotes(&self, symbol: &str, period: Period) -> GenericResult<Option<HistoricalQuotesMap>> {
+        #[cfg(not(test))] let base_url = "https://iss.moex.com";
+        #[cfg(test)] let base_url = mockito::server_url();
+
+        let date_format = "%Y-%m-%d";
+        let start_date = period.first_date().format(date_format).to_string();
+        let end_date = period.last_date().format(date_format).to_string();
+
+        let bonds = self.market == "bonds";
+        let mut quotes = HistoricalQuotesMap::new();
+        let mut start = 0;
+
+        // The history is returned page by page
+        loop {
+            let start_string = start.to_string();
+            let url = Url::parse_with_params(
+                &format!("{}/iss/history/engines/stock/markets/{}/boards/{}/securities/{}.xml",
+                         base_url, self.market, self.board, symbol),
+                &[
+                    ("from", start_date.as_str()),
+                    ("till", end_date.as_str()),
+                    ("start", start_string.as_str()),
+                ],
+            )?;
+
+            let get = |url| -> GenericResult<(HistoricalQuotesMap, Option<usize>)> {
+                trace!("Sending request to {}...", url);
+                let response = Client::new().get(url).send()?;
+                trace!("Got response from {}.", url);
+
+                if !response.status().is_success() {
+                    return Err!("The server returned an error: {}", response.status());
+                }
+
+                Ok(parse_historical_quotes(&response.text()?, bonds).map_err(|e| format!(
+                    "Quotes info parsing error: {}", e))?)
+            };
+
+            let (page, next_start) = get(url.as_str()).map_err(|e| format!(
+                "Failed to get historical quotes from {}: {}", url, e))?;
+            quotes.extend(page);
+
+            match next_start {
+                Some(next_start) => start = next_start,
+                None => break,
+            }
+        }
+
+        Ok(if quotes.is_empty() {
+            None
+        } else {
+            Some(quotes)
+        })
+    }
 }
 
-fn parse_quotes(data: &str) -> GenericResult<HashMap<String, Cash>> {
+// Bond prices are specified in percents of face value, so for bonds we calculate the full price
+// (including accrued coupon interest) to match the way they are represented in broker statements.
+fn parse_quotes(data: &str, bonds: bool) -> GenericResult<HashMap<String, Cash>> {
     #[derive(Deserialize)]
     struct Document {
         data: Vec<Data>,
@@ -102,6 +166,14 @@ fn parse_quotes(data: &str) -> GenericResult<HashMap<String, Cash>> {
         #[serde(rename = "PREVLEGALCLOSEPRICE")]
         prev_price: Option<Decimal>,
 
+        /// Bond face value
+        #[serde(default, rename = "FACEVALUE", deserialize_with = "deserialize_optional_decimal")]
+        face_value: Option<Decimal>,
+
+        /// Bond accrued coupon interest
+        #[serde(default, rename = "ACCRUEDINT", deserialize_with = "deserialize_optional_decimal")]
+        accrued_interest: Option<Decimal>,
+
         // Market data fields
 
         #[serde(rename = "NUMTRADES")]
@@ -149,6 +221,21 @@ fn parse_quotes(data: &str) -> GenericResult<HashMap<String, Cash>> {
         let prev_date = get_value(row.prev_date)?;
         let prev_price = get_value(row.prev_price)?;
 
+        let bond_info = if bonds {
+            let face_value = get_value(row.face_value)?;
+            let accrued_interest = get_value(row.accrued_interest)?;
+
+            if face_value.is_zero() || face_value.is_sign_negative() {
+                return Err!("Invalid {} face value: {}", symbol, face_value);
+            } else if accrued_interest.is_sign_negative() {
+                return Err!("Invalid {} accrued interest: {}", symbol, accrued_interest);
+            }
+
+            Some((face_value, accrued_interest))
+        } else {
+            None
+        };
+
         let currency = match currency.as_str() {
             "SUR" => "RUB",
             _ => return Err!("{} is nominated in an unsupported currency: {}", symbol, currency),
@@ -159,7 +246,7 @@ fn parse_quotes(data: &str) -> GenericResult<HashMap<String, Cash>> {
             return Err!("Invalid price: {}", prev_price);
         }
 
-        if symbols.insert(symbol.clone(), (currency, prev_date, prev_price)).is_some() {
+        if symbols.insert(symbol.clone(), (currency, prev_date, prev_price, bond_info)).is_some() {
             return Err!("Duplicated symbol: {}", symbol);
         }
     }
@@ -177,7 +264,7 @@ fn parse_quotes(data: &str) -> GenericResult<HashMap<String, Cash>> {
         }
 
         let trades = get_value(row.trades)?;
-        let &(currency, prev_date, prev_price) = symbols.get(&symbol).ok_or_else(|| format!(
+        let &(currency, prev_date, prev_price, bond_info) = symbols.get(&symbol).ok_or_else(|| format!(
             "There is market data for {} but security info is missing", symbol))?;
 
         let price = match row.price {
@@ -202,6 +289,11 @@ fn parse_quotes(data: &str) -> GenericResult<HashMap<String, Cash>> {
             },
         };
 
+        let price = match bond_info {
+            Some((face_value, accrued_interest)) => price * face_value / dec!(100) + accrued_interest,
+            None => price,
+        };
+
         if quotes.insert(symbol.clone(), Cash::new(currency, price)).is_some() {
             return Err!("Duplicated symbol: {}", symbol);
         }
@@ -214,6 +306,136 @@ fn parse_quotes(data: &str) -> GenericResult<HashMap<String, Cash>> {
     Ok(quotes)
 }
 
+// Returns the quotes and the next page start index
+fn parse_historical_quotes(data: &str, bonds: bool) -> GenericResult<(HistoricalQuotesMap, Option<usize>)> {
+    #[derive(Deserialize)]
+    struct Document {
+        data: Vec<Data>,
+    }
+
+    #[derive(Deserialize)]
+    struct Data {
+        id: String,
+
+        #[serde(rename = "rows")]
+        table: Table,
+    }
+
+    #[derive(Deserialize)]
+    struct Table {
+        #[serde(rename = "row", default)]
+        rows: Vec<Row>,
+    }
+
+    #[derive(Deserialize)]
+    struct Row {
+        // History fields
+
+        #[serde(rename = "TRADEDATE")]
+        date: Option<String>,
+
+        #[serde(rename = "CURRENCYID")]
+        currency: Option<String>,
+
+        /// Close price (empty if there were no trades)
+        #[serde(default, rename = "LEGALCLOSEPRICE", deserialize_with = "deserialize_optional_decimal")]
+        price: Option<Decimal>,
+
+        /// Bond face value
+        #[serde(default, rename = "FACEVALUE", deserialize_with = "deserialize_optional_decimal")]
+        face_value: Option<Decimal>,
+
+        /// Bond accrued coupon interest
+        #[serde(default, rename = "ACCINT", deserialize_with = "deserialize_optional_decimal")]
+        accrued_interest: Option<Decimal>,
+
+        // Cursor fields
+
+        #[serde(rename = "INDEX")]
+        index: Option<usize>,
+
+        #[serde(rename = "TOTAL")]
+        total: Option<usize>,
+
+        #[serde(rename = "PAGESIZE")]
+        page_size: Option<usize>,
+    }
+
+    let result: Document = serde_xml_rs::from_str(data).map_err(|e| e.to_string())?;
+    let (mut history, mut cursor) = (None, None);
+
+    for data in result.data {
+        let data_ref = match data.id.as_str() {
+            "history" => &mut history,
+            "history.cursor" => &mut cursor,
+            _ => continue,
+        };
+
+        if data_ref.replace(data.table.rows).is_some() {
+            return Err!("Duplicated {:?} data", data.id);
+        }
+    }
+
+    let (history, cursor) = match (history, cursor) {
+        (Some(history), Some(cursor)) => (history, cursor),
+        _ => return Err!("Unable to find history info in server response"),
+    };
+
+    let mut quotes = HistoricalQuotesMap::new();
+
+    for row in history {
+        let date = time::parse_date(&get_value(row.date)?, "%Y-%m-%d")?;
+
+        let price = match row.price {
+            Some(price) => price,
+            None => continue,
+        };
+
+        if price.is_zero() || price.is_sign_negative() {
+            return Err!("Invalid price: {}", price);
+        }
+
+        let price = if bonds {
+            let face_value = get_value(row.face_value)?;
+            let accrued_interest = get_value(row.accrued_interest)?;
+
+            if face_value.is_zero() || face_value.is_sign_negative() {
+                return Err!("Invalid face value: {}", face_value);
+            } else if accrued_interest.is_sign_negative() {
+                return Err!("Invalid accrued interest: {}", accrued_interest);
+            }
+
+            price * face_value / dec!(100) + accrued_interest
+        } else {
+            price
+        };
+
+        let currency = get_value(row.currency)?;
+        let currency = match currency.as_str() {
+            "SUR" => "RUB",
+            _ => return Err!("Got an unsupported currency: {}", currency),
+        };
+
+        if quotes.insert(date, Cash::new(currency, price)).is_some() {
+            return Err!("Duplicated date: {}", date);
+        }
+    }
+
+    let next_start = match cursor.into_iter().next() {
+        Some(row) => {
+            let next_start = get_value(row.index)? + get_value(row.page_size)?;
+            if next_start < get_value(row.total)? {
+                Some(next_start)
+            } else {
+                None
+            }
+        },
+        None => None,
+    };
+
+    Ok((quotes, next_start))
+}
+
 fn get_value<T>(value: Option<T>) -> GenericResult<T> {
     Ok(value.ok_or("Got an unexpected response from server")?)
 }
@@ -271,6 +493,19 @@ mod tests {
         assert_eq!(Moex::new(board).get_quotes(&["FXUS", "FXIT", "INVALID"]).unwrap(), quotes);
     }
 
+    #[test]
+    fn bond_quotes() {
+        let board = "TQOB";
+        let securities = ["SU26207RMFS9", "SU26209RMFS5"];
+        let _mock = mock_response_for("bonds", board, &securities, "moex-bonds.xml");
+
+        let mut quotes = HashMap::new();
+        quotes.insert(s!("SU26207RMFS9"), Cash::new("RUB", dec!(1067.95)));
+        quotes.insert(s!("SU26209RMFS5"), Cash::new("RUB", dec!(1045.41)));
+
+        assert_eq!(Moex::new_bonds(board).get_quotes(&securities).unwrap(), quotes);
+    }
+
     #[test]
     fn exchange_closed() {
         test_exchange_status("closed")
@@ -298,20 +533,43 @@ mod tests {
         );
     }
 
+    #[test]
+    fn historical_quotes() {
+        let path = "/iss/history/engines/stock/markets/shares/boards/TQTF/securities/FXUS.xml";
+        let query = "from=2021-01-01&till=2021-01-12";
+
+        let _first_page_mock = mock_path(&format!("{}?{}&start=0", path, query), "moex-history-1.xml");
+        let _second_page_mock = mock_path(&format!("{}?{}&start=2", path, query), "moex-history-2.xml");
+
+        let period = Period::new(date!(2021, 1, 1), date!(2021, 1, 12)).unwrap();
+        assert_eq!(Moex::new("TQTF").get_historical_quotes("FXUS", period).unwrap(), Some(btreemap!{
+            date!(2021, 1, 4) => Cash::new("RUB", dec!(5470)),
+            date!(2021, 1, 11) => Cash::new("RUB", dec!(5612)),
+        }));
+    }
+
     fn mock_response(board: &str, securities: &[&str], body_path: &str) -> Mock {
+        mock_response_for("shares", board, securities, body_path)
+    }
+
+    fn mock_response_for(market: &str, board: &str, securities: &[&str], body_path: &str) -> Mock {
         let securities =
             url::form_urlencoded::byte_serialize(securities.join(",").as_bytes())
             .collect::<String>();
 
         let path = format!(
-            "/iss/engines/stock/markets/shares/boards/{}/securities.xml?securities={}",
-            board, securities);
+            "/iss/engines/stock/markets/{}/boards/{}/securities.xml?securities={}",
+            market, board, securities);
+
+        mock_path(&path, body_path)
+    }
 
+    fn mock_path(path: &str, body_path: &str) -> Mock {
         let mut body = String::new();
         let body_path = Path::new(file!()).parent().unwrap().join("testdata").join(body_path);
         File::open(body_path).unwrap().read_to_string(&mut body).unwrap();
 
-        mock("GET", path.as_str())
+        mock("GET", path)
             .with_status(200)
             .with_header("Content-Type", "application/xml; charset=utf-8")
             .with_body(body)
@@ -14,17 +14,18 @@ use crate::currency::{Cash, MultiCurrencyCashAccount};
 use crate::currency::converter::CurrencyConverter;
 use crate::instruments::IssuerTaxationType;
 use crate::localities::{Country, Jurisdiction};
+use crate::taxes::TaxBase;
 use crate::types::{Date, Decimal};
 
 use super::statement::{TaxStatement, CountryCode};
 
 pub fn process_income(
     country: &Country, broker_statement: &BrokerStatement, year: Option<i32>,
-    tax_statement: Option<&mut TaxStatement>, converter: &CurrencyConverter,
+    tax_statement: Option<&mut TaxStatement>, tax_base: &mut TaxBase, converter: &CurrencyConverter,
 ) -> GenericResult<Cash> {
     let mut processor = Processor {
         broker_statement, tax_statement, tax_year: year,
-        country, converter,
+        country, converter, tax_base,
 
         table: Table::new(),
         warning: false,
@@ -88,6 +89,7 @@ struct Processor<'a> {
 
     country: &'a Country,
     converter: &'a CurrencyConverter,
+    tax_base: &'a mut TaxBase,
 
     table: Table,
     warning: bool,
@@ -143,7 +145,7 @@ impl<'a> Processor<'a> {
             dividend.date, foreign_amount, self.country.currency)?;
         self.total_amount += amount;
 
-        let tax = dividend.tax(self.country, self.converter)?;
+        let tax = dividend.tax(self.country, self.converter, self.tax_base)?;
 
         let foreign_paid_tax = dividend.paid_tax.round();
         self.total_foreign_paid_tax.deposit(foreign_paid_tax);
@@ -153,8 +155,9 @@ impl<'a> Processor<'a> {
             dividend.date, foreign_paid_tax, self.country.currency)?;
         self.total_paid_tax += paid_tax;
 
-        let tax_to_pay = dividend.tax_to_pay(self.country, self.converter)?;
+        let tax_to_pay = dividend.tax_to_pay(self.country, self.converter, self.tax_base)?;
         self.total_tax_to_pay += tax_to_pay;
+        self.tax_base.add(dividend.date.year(), amount.amount);
 
         let tax_deduction = self.country.round_tax(paid_tax);
         if dividend.taxation_type == IssuerTaxationType::TaxAgent && tax_deduction != paid_tax {
@@ -277,7 +280,7 @@ impl<'a> Processor<'a> {
             table.hide_paid_tax();
         }
 
-        let mut totals = table.add_empty_row();
+        let mut totals = table.add_totals_row();
 
         totals.set_foreign_amount(self.total_foreign_amount);
         totals.set_amount(self.total_amount);
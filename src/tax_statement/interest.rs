@@ -10,6 +10,7 @@ use crate::currency::converter::CurrencyConverter;
 use crate::formatting;
 use crate::localities::{Country, Jurisdiction};
 use crate::tax_statement::statement::CountryCode;
+use crate::taxes::TaxBase;
 use crate::types::{Date, Decimal};
 
 use super::statement::TaxStatement;
@@ -34,7 +35,8 @@ struct Row {
 
 pub fn process_income(
     country: &Country, broker_statement: &BrokerStatement, year: Option<i32>,
-    mut tax_statement: Option<&mut TaxStatement>, converter: &CurrencyConverter,
+    mut tax_statement: Option<&mut TaxStatement>, tax_base: &mut TaxBase,
+    converter: &CurrencyConverter,
 ) -> GenericResult<Cash> {
     let broker_jurisdiction = broker_statement.broker.type_.jurisdiction();
 
@@ -65,8 +67,9 @@ pub fn process_income(
         let amount = converter.convert_to_cash_rounding(interest.date, foreign_amount, country.currency)?;
         total_amount += amount;
 
-        let tax_to_pay = interest.tax_to_pay(country, converter)?;
+        let tax_to_pay = interest.tax_to_pay(country, converter, tax_base)?;
         total_tax_to_pay += tax_to_pay;
+        tax_base.add(interest.date.year(), amount.amount);
 
         let income = amount - tax_to_pay;
         total_income += income;
@@ -113,7 +116,7 @@ pub fn process_income(
     }
 
     if !table.is_empty() {
-        let mut totals = table.add_empty_row();
+        let mut totals = table.add_totals_row();
         totals.set_foreign_amount(total_foreign_amount);
         totals.set_amount(total_amount);
         totals.set_tax_to_pay(total_tax_to_pay);
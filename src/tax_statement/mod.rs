@@ -4,10 +4,12 @@ use crate::core::GenericResult;
 use crate::currency::converter::CurrencyConverter;
 use crate::db;
 use crate::localities::Jurisdiction;
+use crate::taxes::TaxpayerIncome;
 use crate::telemetry::TelemetryRecordBuilder;
 
 pub use self::statement::TaxStatement;
 
+mod coupons;
 mod dividends;
 mod interest;
 mod statement;
@@ -19,11 +21,8 @@ pub fn generate_tax_statement(
 ) -> GenericResult<TelemetryRecordBuilder> {
     let country = config.get_tax_country();
     let portfolio = config.get_portfolio(portfolio_name)?;
-    let broker = portfolio.broker.get_info(config, portfolio.plan.as_ref())?;
-
-    let broker_statement = BrokerStatement::read(
-        broker, portfolio.statements_path()?, &portfolio.symbol_remapping, &portfolio.instrument_internal_ids,
-        &portfolio.instrument_names, portfolio.get_tax_remapping()?, &portfolio.corporate_actions,
+    let broker_statement = BrokerStatement::read_portfolio(
+        config, portfolio,
         ReadingStrictness::TRADE_SETTLE_DATE | ReadingStrictness::REPO_TRADES | ReadingStrictness::GRANTS)?;
 
     if let Some(year) = year {
@@ -48,20 +47,36 @@ pub fn generate_tax_statement(
     let database = db::connect(&config.db_path)?;
     let converter = CurrencyConverter::new(database, None, true);
 
+    // Progressive tax rates and loss carryforward are applied to the total income of the taxpayer.
+    // Income from the other portfolios is considered as received first, then dividends, coupons and
+    // interest (in this order) and trading profit - at the end of the year.
+    let other_income = TaxpayerIncome::calculate(
+        config, &country, &converter, &[(portfolio, &broker_statement)],
+    )?.other(&portfolio.name);
+
+    let mut trading_tax_base = other_income.tax_base();
+    trading_tax_base.extend(&broker_statement.non_trading_tax_base(&country, &converter)?);
+    let mut tax_base = other_income.tax_base();
+
     let trades_tax = trades::process_income(
-        &country, portfolio, &broker_statement, year, tax_statement.as_mut(), &converter,
+        &country, portfolio, &broker_statement, year, tax_statement.as_mut(),
+        &trading_tax_base, other_income.loss_carryforward(), &converter,
     ).map_err(|e| format!("Failed to process income from stock trading: {}", e))?;
 
     let dividends_tax = dividends::process_income(
-        &country, &broker_statement, year, tax_statement.as_mut(), &converter,
+        &country, &broker_statement, year, tax_statement.as_mut(), &mut tax_base, &converter,
     ).map_err(|e| format!("Failed to process dividend income: {}", e))?;
 
+    let coupons_tax = coupons::process_income(
+        &country, &broker_statement, year, tax_statement.as_mut(), &mut tax_base, &converter,
+    ).map_err(|e| format!("Failed to process coupon income: {}", e))?;
+
     let interest_tax = interest::process_income(
-        &country, &broker_statement, year, tax_statement.as_mut(), &converter,
+        &country, &broker_statement, year, tax_statement.as_mut(), &mut tax_base, &converter,
     ).map_err(|e| format!("Failed to process income from idle cash interest: {}", e))?;
 
     if broker_statement.broker.type_.jurisdiction() == Jurisdiction::Russia {
-        let total_tax = trades_tax + dividends_tax + interest_tax;
+        let total_tax = trades_tax + dividends_tax + coupons_tax + interest_tax;
         tax_agent::process_tax_agent_withholdings(&broker_statement, year, total_tax);
     }
 
@@ -4,17 +4,21 @@ mod countries;
 mod encoding;
 mod foreign_income;
 mod parser;
+mod securities_deduction;
 mod types;
 
+use std::collections::BTreeMap;
 use std::fs;
 
 use crate::core::{EmptyResult, GenericResult};
+use crate::currency;
 use crate::types::{Date, Decimal};
 
 use self::foreign_income::{ForeignIncome, CurrencyIncome, CurrencyInfo, DeductionInfo, IncomeType,
                            ControlledForeignCompanyInfo};
 use self::record::Record;
 use self::parser::{TaxStatementReader, TaxStatementWriter};
+use self::securities_deduction::SecuritiesDeduction;
 
 pub use self::countries::CountryCode;
 
@@ -137,6 +141,16 @@ impl TaxStatement {
         Ok(())
     }
 
+    /// Declares trading losses of the previous years (by loss year) carried forward to the current
+    /// tax year
+    pub fn add_loss_carryforward(&mut self, losses: &BTreeMap<i32, Decimal>) -> EmptyResult {
+        let losses = losses.iter().map(|(&year, &loss)| (year, currency::round(loss))).collect();
+
+        self.get_mut_record::<SecuritiesDeduction>(SecuritiesDeduction::RECORD_NAME)?
+            .ok_or("The tax statement has no securities deduction record")?
+            .add_loss_carryforward(&losses)
+    }
+
     fn get_foreign_incomes(&mut self) -> GenericResult<&mut Vec<CurrencyIncome>> {
         Ok(self.get_mut_record(ForeignIncome::RECORD_NAME)?
             .map(|record: &mut ForeignIncome| &mut record.incomes)
@@ -22,6 +22,7 @@ use super::TaxStatement;
 use super::record::{Record, UnknownRecord, is_record_name};
 use super::encoding::{TaxStatementType, TaxStatementPrimitiveType};
 use super::foreign_income::ForeignIncome;
+use super::securities_deduction::SecuritiesDeduction;
 
 const SUPPORTED_YEAR: i32 = 2021;
 
@@ -87,6 +88,11 @@ impl TaxStatementReader {
 
             let record: Box<dyn Record> = match record_name.as_str() {
                 ForeignIncome::RECORD_NAME => Box::new(ForeignIncome::read(&mut reader)?),
+                SecuritiesDeduction::RECORD_NAME => {
+                    let (record, read_next_record_name) = SecuritiesDeduction::read(&mut reader)?;
+                    next_record_name = read_next_record_name;
+                    Box::new(record)
+                },
                 _ => {
                     let (record, read_next_record_name) = UnknownRecord::read(&mut reader, record_name)?;
                     next_record_name = read_next_record_name;
@@ -0,0 +1,173 @@
+use std::any::Any;
+use std::collections::BTreeMap;
+
+use crate::core::{EmptyResult, GenericResult};
+use crate::types::Decimal;
+
+use super::encoding::TaxStatementPrimitiveType;
+use super::parser::{TaxStatementReader, TaxStatementWriter};
+use super::record::{Record, is_record_name};
+
+/// Deductions for operations with securities. Only loss carryforward is supported: the losses are
+/// added to the already declared ones.
+#[derive(Debug)]
+pub struct SecuritiesDeduction {
+    fields: Vec<String>,
+}
+
+impl SecuritiesDeduction {
+    pub const RECORD_NAME: &'static str = "@CBDeduct";
+
+    pub fn read(reader: &mut TaxStatementReader) -> GenericResult<(SecuritiesDeduction, Option<String>)> {
+        let mut fields = Vec::new();
+        let mut next_record_name = None;
+
+        while !reader.at_eof()? {
+            let data: String = reader.read_value()?;
+
+            if is_record_name(&data) {
+                next_record_name.replace(data);
+                break;
+            }
+
+            fields.push(data);
+        }
+
+        Ok((SecuritiesDeduction {fields}, next_record_name))
+    }
+
+    /// Adds losses to the declared loss carryforward: several portfolios of the taxpayer may be
+    /// declared in the same tax statement.
+    pub fn add_loss_carryforward(&mut self, losses: &BTreeMap<i32, Decimal>) -> EmptyResult {
+        let mut total = self.get_loss_carryforward().map_err(|e| format!(concat!(
+            "The tax statement has deductions for operations with securities which can't be ",
+            "processed ({}). Please declare the losses manually"), e))?;
+
+        for (&year, &loss) in losses {
+            *total.entry(year).or_default() += loss;
+        }
+
+        let mut fields = vec![s!("1"), total.len().to_string()];
+
+        for (year, loss) in total {
+            let mut amount = String::new();
+            loss.encode(&mut amount)?;
+
+            fields.push(year.to_string());
+            fields.push(amount);
+        }
+
+        self.fields = fields;
+        Ok(())
+    }
+
+    fn get_loss_carryforward(&self) -> GenericResult<BTreeMap<i32, Decimal>> {
+        let mut fields = self.fields.iter().map(String::as_str);
+        let mut losses = BTreeMap::new();
+
+        let count = match fields.next() {
+            Some("0") => 0,
+            Some("1") => usize::decode(fields.next().ok_or("Got an unexpected end of record")?)?,
+            _ => return Err!("Got an unexpected record contents: {:?}", self.fields),
+        };
+
+        for _ in 0..count {
+            let (year, loss) = match (fields.next(), fields.next()) {
+                (Some(year), Some(loss)) => (year, loss),
+                _ => return Err!("Got an unexpected end of record"),
+            };
+
+            let year: i32 = year.parse().map_err(|_| format!("Invalid year: {:?}", year))?;
+            if losses.insert(year, Decimal::decode(loss)?).is_some() {
+                return Err!("Got a duplicated loss for {}", year);
+            }
+        }
+
+        if fields.next().is_some() {
+            return Err!("Got an unexpected record contents: {:?}", self.fields);
+        }
+
+        Ok(losses)
+    }
+}
+
+impl Record for SecuritiesDeduction {
+    fn name(&self) -> &str {
+        SecuritiesDeduction::RECORD_NAME
+    }
+
+    fn as_mut_any(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn write(&self, writer: &mut TaxStatementWriter) -> EmptyResult {
+        writer.write_data(SecuritiesDeduction::RECORD_NAME)?;
+
+        for field in &self.fields {
+            writer.write_data(field)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tempfile::NamedTempFile;
+
+    use crate::tax_statement::TaxStatement;
+
+    use super::*;
+
+    #[test]
+    fn loss_carryforward() {
+        let mut deduction = SecuritiesDeduction {fields: vec![s!("0")]};
+
+        deduction.add_loss_carryforward(&btreemap!{
+            2018 => dec!(1000.5),
+            2020 => dec!(20),
+        }).unwrap();
+        assert_eq!(deduction.fields, vec!["1", "2", "2018", "1000.5", "2020", "20"]);
+
+        deduction.add_loss_carryforward(&btreemap!{
+            2019 => dec!(5),
+            2020 => dec!(10.25),
+        }).unwrap();
+        assert_eq!(deduction.fields, vec!["1", "3", "2018", "1000.5", "2019", "5", "2020", "30.25"]);
+
+        let mut deduction = SecuritiesDeduction {fields: vec![s!("1"), s!("2"), s!("2018"), s!("100")]};
+        assert!(deduction.add_loss_carryforward(&btreemap!{2018 => dec!(1000)}).is_err());
+    }
+
+    #[test]
+    fn statement_round_trip() {
+        let path = Path::new(file!()).parent().unwrap().join("testdata/empty.dc1");
+        let temp_file = NamedTempFile::new().unwrap();
+        let temp_path = temp_file.path().to_str().unwrap();
+
+        let mut statement = TaxStatement::read(path.to_str().unwrap()).unwrap();
+        assert_eq!(get_loss_carryforward(&mut statement), btreemap!{});
+
+        // Losses of two portfolios are declared in the same statement
+        for losses in [
+            btreemap!{2018 => dec!(1000.5)},
+            btreemap!{2018 => dec!(10), 2020 => dec!(20)},
+        ] {
+            statement.add_loss_carryforward(&losses).unwrap();
+            TaxStatementWriter::write(&statement, temp_path).unwrap();
+            statement = TaxStatement::read(temp_path).unwrap();
+        }
+
+        assert_eq!(get_loss_carryforward(&mut statement), btreemap!{
+            2018 => dec!(1010.5),
+            2020 => dec!(20),
+        });
+    }
+
+    fn get_loss_carryforward(statement: &mut TaxStatement) -> BTreeMap<i32, Decimal> {
+        statement.get_mut_record::<SecuritiesDeduction>(SecuritiesDeduction::RECORD_NAME).unwrap().unwrap()
+            .get_loss_carryforward().unwrap()
+    }
+}
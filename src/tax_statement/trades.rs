@@ -7,7 +7,8 @@ use static_table_derive::StaticTable;
 
 use crate::brokers::Broker;
 use crate::broker_statement::{
-    BrokerStatement, StockSell, StockSellType, SellDetails, FifoDetails, StockSourceDetails, Fee};
+    BrokerStatement, StockSell, StockSellType, SellDetails, FifoDetails, StockSourceDetails, Fee,
+    VariationMargin, VariationMarginProfit, calculate_variation_margin_profit};
 use crate::config::PortfolioConfig;
 use crate::core::{EmptyResult, GenericResult};
 use crate::currency::{Cash, MultiCurrencyCashAccount};
@@ -15,7 +16,7 @@ use crate::currency::converter::CurrencyConverter;
 use crate::formatting::{self, table::Cell};
 use crate::localities::{Country, Jurisdiction};
 use crate::tax_statement::statement::CountryCode;
-use crate::taxes::{IncomeType, TaxPaymentDaySpec};
+use crate::taxes::{IncomeType, LossCarryforwardCalculator, TaxBase, TaxPaymentDaySpec};
 use crate::taxes::long_term_ownership::LtoDeductionCalculator;
 use crate::time::{self, Date};
 use crate::trades::{self, RealProfit};
@@ -25,33 +26,43 @@ use super::statement::TaxStatement;
 
 pub fn process_income(
     country: &Country, portfolio: &PortfolioConfig, broker_statement: &BrokerStatement,
-    year: Option<i32>, tax_statement: Option<&mut TaxStatement>, converter: &CurrencyConverter,
+    year: Option<i32>, mut tax_statement: Option<&mut TaxStatement>, tax_base: &TaxBase,
+    mut loss_carryforward: LossCarryforwardCalculator, converter: &CurrencyConverter,
 ) -> GenericResult<Cash> {
-    let mut processor = TradesProcessor {
-        portfolio,
-        broker_statement,
-        tax_year: year,
 
-        country,
-        converter,
+    if let Some(year) = year {
+        // Losses of the previous years are carried forward to the requested tax year, so we have to
+        // calculate results of all previous tax years first.
+        let mut processor = TradesProcessor::new(country, portfolio, broker_statement, None, converter);
+        processor.process_trades(None)?;
+        processor.process_lto_deductions();
 
-        trades_table: TradesTable::new(),
-        fifo_table: FifoTable::new(),
-        lto_table: LtoTable::new(),
+        for (&tax_year, stat) in processor.tax_year_stat.range(..year) {
+            loss_carryforward.process(tax_year, stat.taxable_local_profit.amount);
+        }
+    }
 
-        same_dates: true,
-        same_currency: true,
-        non_trade_sources: false,
-        stock_splits: false,
-        tax_exemptions: false,
-        long_term_ownership: false,
+    let mut processor = TradesProcessor::new(country, portfolio, broker_statement, year, converter);
+    processor.process_trades(tax_statement.as_deref_mut())?;
 
-        tax_year_stat: BTreeMap::new(),
-    };
+    let totals = processor.process_totals(loss_carryforward, tax_base)?;
+
+    if let Some(statement) = tax_statement {
+        if !totals.loss_carryforward.is_empty() {
+            match broker_statement.broker.type_.jurisdiction() {
+                Jurisdiction::Usa => {
+                    statement.add_loss_carryforward(&totals.loss_carryforward).map_err(|e| format!(
+                        "Unable to declare loss carryforward in the tax statement: {}", e))?;
+                },
 
-    processor.process_trades(tax_statement)?;
+                Jurisdiction::Russia => warn!(concat!(
+                    "Trading losses of the previous years aren't declared in the tax statement for ",
+                    "income from Russian brokers. Please specify them manually."
+                )),
+            }
+        }
+    }
 
-    let totals = processor.process_totals()?;
     if !processor.trades_table.is_empty() {
         processor.print(&totals);
     }
@@ -70,6 +81,8 @@ struct TradesProcessor<'a> {
     trades_table: TradesTable,
     fifo_table: FifoTable,
     lto_table: LtoTable,
+    loss_carryforward_table: LossCarryforwardTable,
+    derivatives_table: DerivativesTable,
 
     same_dates: bool,
     same_currency: bool,
@@ -77,11 +90,44 @@ struct TradesProcessor<'a> {
     stock_splits: bool,
     tax_exemptions: bool,
     long_term_ownership: bool,
+    loss_carryforward: bool,
+    derivatives: bool,
 
     tax_year_stat: BTreeMap<i32, TaxYearStat>,
 }
 
 impl<'a> TradesProcessor<'a> {
+    fn new(
+        country: &'a Country, portfolio: &'a PortfolioConfig, broker_statement: &'a BrokerStatement,
+        tax_year: Option<i32>, converter: &'a CurrencyConverter,
+    ) -> TradesProcessor<'a> {
+        TradesProcessor {
+            portfolio,
+            broker_statement,
+            tax_year,
+
+            country,
+            converter,
+
+            trades_table: TradesTable::new(),
+            fifo_table: FifoTable::new(),
+            lto_table: LtoTable::new(),
+            loss_carryforward_table: LossCarryforwardTable::new(),
+            derivatives_table: DerivativesTable::new(),
+
+            same_dates: true,
+            same_currency: true,
+            non_trade_sources: false,
+            stock_splits: false,
+            tax_exemptions: false,
+            long_term_ownership: false,
+            loss_carryforward: false,
+            derivatives: false,
+
+            tax_year_stat: BTreeMap::new(),
+        }
+    }
+
     fn pre_process_fees(&mut self) -> GenericResult<VecDeque<PreprocessedFee>> {
         let broker = self.broker_statement.broker.type_;
         let mut fees = VecDeque::new();
@@ -112,6 +158,19 @@ impl<'a> TradesProcessor<'a> {
             }
         }
 
+        let margins = &self.broker_statement.variation_margin;
+        let profits = calculate_variation_margin_profit(margins, self.country.currency, self.converter)?;
+
+        for (margin, profit) in margins.iter().zip(profits) {
+            let tax_year = self.get_tax_year(margin.date);
+            if self.needs_processing(tax_year) {
+                fees.push_back(self.pre_process_variation_margin(margin, profit));
+            }
+        }
+
+        // Fees and variation margin are already sorted, so stable sorting just merges them
+        fees.make_contiguous().sort_by_key(|fee| fee.date);
+
         Ok(fees)
     }
 
@@ -135,6 +194,32 @@ impl<'a> TradesProcessor<'a> {
         })
     }
 
+    // Variation margin is processed as a fee with the opposite sign: it's a profit or loss from
+    // futures, which is netted with results of trading with securities (see
+    // calculate_variation_margin_profit()).
+    fn pre_process_variation_margin(
+        &mut self, margin: &VariationMargin, profit: VariationMarginProfit,
+    ) -> PreprocessedFee {
+        self.same_currency &= margin.amount.currency == self.country.currency;
+        self.derivatives = true;
+
+        let amount = margin.amount.round();
+        let local_amount = profit.local_profit;
+
+        let tax_year = self.tax_year_stat(margin.date);
+        tax_year.profit.deposit(amount);
+        tax_year.local_profit += local_amount;
+        tax_year.taxable_local_profit += local_amount;
+        tax_year.derivatives_local_profit += local_amount;
+
+        PreprocessedFee {
+            date: margin.date,
+            amount: -amount,
+            local_amount: -local_amount,
+            description: s!("Вариационная маржа"),
+        }
+    }
+
     fn post_process_fee(&mut self, fee: PreprocessedFee) {
         let mut row = self.trades_table.add_empty_row();
         row.set_conclusion_date(fee.date);
@@ -152,7 +237,7 @@ impl<'a> TradesProcessor<'a> {
         row.set_taxable_local_profit(-fee.local_amount);
     }
 
-    fn process_trades(&mut self, mut tax_statement: Option<&'a mut TaxStatement>) -> EmptyResult {
+    fn process_trades(&mut self, mut tax_statement: Option<&mut TaxStatement>) -> EmptyResult {
         let mut fees = self.pre_process_fees()?;
         let broker_jurisdiction = self.broker_statement.broker.type_.jurisdiction();
 
@@ -161,10 +246,15 @@ impl<'a> TradesProcessor<'a> {
         for trade in &self.broker_statement.stock_sells {
             match trade.type_ {
                 StockSellType::Trade {..} => (),
-                StockSellType::CorporateAction => continue,
+                StockSellType::CorporateAction | StockSellType::Transfer => continue,
             };
 
-            let tax_year = self.get_tax_year(trade.execution_date);
+            // Open short positions have no realized profit yet
+            if !trade.is_processed() {
+                continue;
+            }
+
+            let tax_year = self.get_tax_year(trade.realization_date());
             if !self.needs_processing(tax_year) {
                 continue;
             }
@@ -203,6 +293,10 @@ impl<'a> TradesProcessor<'a> {
             self.post_process_fee(fee);
         }
 
+        if self.derivatives && tax_statement.is_some() && broker_jurisdiction == Jurisdiction::Usa {
+            return Err!("Declaration of income from derivatives isn't supported yet");
+        }
+
         Ok(())
     }
 
@@ -218,6 +312,9 @@ impl<'a> TradesProcessor<'a> {
             commission.currency == self.country.currency;
         self.tax_exemptions |= details.tax_exemption_applied();
 
+        let derivative = self.broker_statement.instrument_info.get_type(&trade.symbol).is_derivative();
+        self.derivatives |= derivative;
+
         let conclusion_currency_rate = if commission.currency != self.country.currency {
             Some(self.converter.precise_currency_rate(
                 trade.conclusion_time.date, commission.currency, self.country.currency)?)
@@ -235,7 +332,7 @@ impl<'a> TradesProcessor<'a> {
         let real = details.real_profit(self.converter)?;
 
         {
-            let tax_year = self.tax_year_stat(trade.execution_date);
+            let tax_year = self.tax_year_stat(trade.realization_date());
 
             tax_year.purchase_cost.deposit(details.purchase_cost);
             tax_year.purchase_local_cost += details.purchase_local_cost;
@@ -243,6 +340,10 @@ impl<'a> TradesProcessor<'a> {
             tax_year.profit.deposit(details.profit);
             tax_year.local_profit += details.local_profit;
             tax_year.taxable_local_profit += details.taxable_local_profit;
+
+            if derivative {
+                tax_year.derivatives_local_profit += details.local_profit;
+            }
         }
 
         self.trades_table.add_row(TradeRow {
@@ -277,7 +378,7 @@ impl<'a> TradesProcessor<'a> {
         });
 
         for (index, buy_trade) in details.fifo.iter().enumerate() {
-            self.process_fifo(trade_id, buy_trade, trade.execution_date, index == 0)?;
+            self.process_fifo(trade_id, buy_trade, trade.realization_date(), index == 0)?;
         }
 
         Ok(())
@@ -388,7 +489,9 @@ impl<'a> TradesProcessor<'a> {
         assert!(details.fifo.iter().all(|trade| trade.long_term_ownership_deductible.is_none()));
 
         let name = self.broker_statement.instrument_info.get_name(&trade.original_symbol);
-        let description = format!("{}: Продажа {}", self.broker_statement.broker.name, name);
+        let description = format!(
+            "{}: {} {}", self.broker_statement.broker.name,
+            if trade.is_short() { "Короткая продажа" } else { "Продажа" }, name);
         let country_code = CountryCode::new(self.broker_statement.broker.type_.jurisdiction().code())?;
 
         let cost = details.total_local_cost.amount + additional_fees;
@@ -396,7 +499,7 @@ impl<'a> TradesProcessor<'a> {
             trade.execution_date, details.revenue.currency, self.country.currency)?;
 
         tax_statement.add_stock_income(
-            &description, trade.execution_date, country_code,
+            &description, trade.realization_date(), country_code,
             details.revenue.currency, precise_currency_rate,
             details.revenue.amount, details.local_revenue.amount, cost,
         ).map_err(|e| format!(
@@ -407,9 +510,8 @@ impl<'a> TradesProcessor<'a> {
         Ok(())
     }
 
-    fn process_totals(&mut self) -> GenericResult<Totals> {
+    fn process_lto_deductions(&mut self) {
         let local_currency = self.country.currency;
-        let tax_payment_day = self.portfolio.tax_payment_day();
 
         for (&year, stat) in &mut self.tax_year_stat {
             let lto = stat.lto_calculator.take().unwrap().calculate();
@@ -422,6 +524,15 @@ impl<'a> TradesProcessor<'a> {
                 });
             }
         }
+    }
+
+    fn process_totals(
+        &mut self, mut loss_carryforward: LossCarryforwardCalculator, tax_base: &TaxBase,
+    ) -> GenericResult<Totals> {
+        let local_currency = self.country.currency;
+        let tax_payment_day = self.portfolio.tax_payment_day();
+
+        self.process_lto_deductions();
 
         let mut total_local_profit = Cash::zero(local_currency);
         let mut total_taxable_local_profit = Cash::zero(local_currency);
@@ -430,8 +541,35 @@ impl<'a> TradesProcessor<'a> {
         let mut total_tax_to_pay = Cash::zero(local_currency);
 
         let mut real = None;
+        let mut carried_losses = BTreeMap::new();
+
+        for (&year, stat) in &mut self.tax_year_stat {
+            if self.derivatives {
+                self.derivatives_table.add_row(DerivativesRow {
+                    year,
+                    securities_local_profit: stat.local_profit - stat.derivatives_local_profit,
+                    derivatives_local_profit: stat.derivatives_local_profit,
+                    local_profit: stat.local_profit,
+                });
+            }
+
+            let carryforward = loss_carryforward.process(year, stat.taxable_local_profit.amount);
+            if !carryforward.deduction.is_zero() {
+                stat.taxable_local_profit.amount -= carryforward.deduction;
+                self.loss_carryforward = true;
+
+                for (loss_year, deduction) in carryforward.sources {
+                    if self.tax_year.is_some() {
+                        *carried_losses.entry(loss_year).or_default() += deduction;
+                    }
+
+                    self.loss_carryforward_table.add_row(LossCarryforwardRow {
+                        year, loss_year,
+                        deduction: Cash::new(local_currency, deduction),
+                    });
+                }
+            }
 
-        for (&year, stat) in &self.tax_year_stat {
             let single_tax_year = match tax_payment_day.spec {
                 TaxPaymentDaySpec::Day {..} => if let Some(tax_year) = self.tax_year {
                     assert_eq!(year, tax_year);
@@ -448,11 +586,11 @@ impl<'a> TradesProcessor<'a> {
             total_local_profit += stat.local_profit;
             total_taxable_local_profit += stat.taxable_local_profit;
 
-            total_tax_without_deduction += self.country.tax_to_pay(
-                IncomeType::Trading, year, stat.local_profit, None);
+            total_tax_without_deduction += self.country.tax_to_pay_with_base(
+                IncomeType::Trading, year, stat.local_profit, tax_base.get(year), None);
 
-            let tax_to_pay = self.country.tax_to_pay(
-                IncomeType::Trading, year, stat.taxable_local_profit, None);
+            let tax_to_pay = self.country.tax_to_pay_with_base(
+                IncomeType::Trading, year, stat.taxable_local_profit, tax_base.get(year), None);
             total_tax_to_pay += tax_to_pay;
 
             if single_tax_year {
@@ -478,6 +616,7 @@ impl<'a> TradesProcessor<'a> {
             tax_deduction: total_tax_deduction,
 
             real,
+            loss_carryforward: carried_losses,
         })
     }
 
@@ -509,7 +648,7 @@ impl<'a> TradesProcessor<'a> {
         if !self.stock_splits {
             self.fifo_table.hide_multiplier();
         }
-        if !self.tax_exemptions && !self.long_term_ownership {
+        if !self.tax_exemptions && !self.long_term_ownership && !self.loss_carryforward {
             self.trades_table.hide_taxable_local_profit();
             self.trades_table.hide_tax_deduction();
         }
@@ -524,9 +663,11 @@ impl<'a> TradesProcessor<'a> {
         }
         if self.tax_year.is_some() {
             self.lto_table.hide_year();
+            self.loss_carryforward_table.hide_year();
+            self.derivatives_table.hide_year();
         }
 
-        let mut totals_row = self.trades_table.add_empty_row();
+        let mut totals_row = self.trades_table.add_totals_row();
 
         totals_row.set_local_profit(totals.local_profit);
         totals_row.set_taxable_local_profit(totals.taxable_local_profit);
@@ -550,6 +691,14 @@ impl<'a> TradesProcessor<'a> {
         if !self.lto_table.is_empty() {
             self.lto_table.print("Льгота на долгосрочное владение ценными бумагами");
         }
+
+        if !self.derivatives_table.is_empty() {
+            self.derivatives_table.print("Сальдирование прибыли по операциям с ценными бумагами и ПФИ");
+        }
+
+        if !self.loss_carryforward_table.is_empty() {
+            self.loss_carryforward_table.print("Перенос убытков прошлых лет");
+        }
     }
 
     fn tax_year_stat(&mut self, date: Date) -> &mut TaxYearStat {
@@ -567,6 +716,7 @@ impl<'a> TradesProcessor<'a> {
                 profit: MultiCurrencyCashAccount::new(),
                 local_profit: zero,
                 taxable_local_profit: zero,
+                derivatives_local_profit: zero,
 
                 deductible_fees: None,
                 lto_calculator: Some(LtoDeductionCalculator::new()),
@@ -600,6 +750,7 @@ struct TaxYearStat {
     profit: MultiCurrencyCashAccount,
     local_profit: Cash,
     taxable_local_profit: Cash,
+    derivatives_local_profit: Cash,
 
     deductible_fees: Option<Decimal>,
     lto_calculator: Option<LtoDeductionCalculator>,
@@ -613,6 +764,9 @@ struct Totals {
     tax_deduction: Cash,
 
     real: Option<RealProfit>,
+
+    // Losses of the previous years carried forward to the requested tax year (by loss year)
+    loss_carryforward: BTreeMap<i32, Decimal>,
 }
 
 #[derive(StaticTable)]
@@ -710,4 +864,28 @@ struct LtoRow {
     deduction: Cash,
     #[column(name="Лимит")]
     limit: Cash,
-}
\ No newline at end of file
+}
+
+#[derive(StaticTable)]
+#[table(name="LossCarryforwardTable")]
+struct LossCarryforwardRow {
+    #[column(name="Год")]
+    year: i32,
+    #[column(name="Год убытка")]
+    loss_year: i32,
+    #[column(name="Перенесенный убыток")]
+    deduction: Cash,
+}
+
+#[derive(StaticTable)]
+#[table(name="DerivativesTable")]
+struct DerivativesRow {
+    #[column(name="Год")]
+    year: i32,
+    #[column(name="Ценные бумаги")]
+    securities_local_profit: Cash,
+    #[column(name="ПФИ")]
+    derivatives_local_profit: Cash,
+    #[column(name="Итого")]
+    local_profit: Cash,
+}
@@ -0,0 +1,194 @@
+// Trading loss carryforward logic (article 220.1 of the Tax Code of the Russian Federation)
+
+use std::collections::BTreeMap;
+
+use num_traits::Zero;
+
+use crate::types::Decimal;
+
+// Losses can be carried forward to the next 10 tax years
+const CARRYFORWARD_YEARS: i32 = 10;
+
+#[derive(Clone)]
+pub struct LossCarryforwardCalculator {
+    losses: BTreeMap<i32, Decimal>,
+    last_year: Option<i32>,
+    other_profit: BTreeMap<i32, Decimal>,
+}
+
+pub struct LossCarryforward {
+    // Deduction from the processed taxable profit. It may be negative if the processed loss reduces
+    // deduction of the profit received through the other portfolios.
+    pub deduction: Decimal,
+    // Taxpayer's losses by loss year which are carried forward to the tax year
+    pub sources: BTreeMap<i32, Decimal>,
+}
+
+impl LossCarryforwardCalculator {
+    pub fn new(losses: &BTreeMap<i32, Decimal>) -> LossCarryforwardCalculator {
+        for loss in losses.values() {
+            assert!(loss.is_sign_positive());
+        }
+
+        LossCarryforwardCalculator {
+            losses: losses.clone(),
+            last_year: None,
+            other_profit: BTreeMap::new(),
+        }
+    }
+
+    /// Sets taxable trading profit (by tax years) which the taxpayer receives through the other
+    /// portfolios. Losses are carried forward at the taxpayer level, so they are netted with this
+    /// profit, which is considered as received before the processed one.
+    pub fn with_other_profit(mut self, profit: &BTreeMap<i32, Decimal>) -> LossCarryforwardCalculator {
+        self.other_profit = profit.clone();
+        self
+    }
+
+    /// Registers taxable profit of the specified tax year: negative profit is remembered as a loss
+    /// and positive profit is reduced by losses of the previous years (starting from the oldest
+    /// ones). Tax years must be processed in ascending order.
+    pub fn process(&mut self, year: i32, taxable_profit: Decimal) -> LossCarryforward {
+        while let Some((&other_year, &other_profit)) = self.other_profit.iter().next() {
+            if other_year >= year {
+                break;
+            }
+
+            self.other_profit.remove(&other_year);
+            self.process_profit(other_year, other_profit);
+        }
+
+        let other_profit = match self.other_profit.remove(&year) {
+            Some(profit) => profit,
+            None => return self.process_profit(year, taxable_profit),
+        };
+
+        let other_deduction = self.clone().process_profit(year, other_profit).deduction;
+        let mut carryforward = self.process_profit(year, other_profit + taxable_profit);
+        carryforward.deduction -= other_deduction;
+
+        carryforward
+    }
+
+    fn process_profit(&mut self, year: i32, taxable_profit: Decimal) -> LossCarryforward {
+        if let Some(last_year) = self.last_year {
+            assert!(year > last_year);
+        }
+        self.last_year.replace(year);
+
+        let mut deduction = dec!(0);
+        let mut sources = BTreeMap::new();
+
+        if taxable_profit < dec!(0) {
+            *self.losses.entry(year).or_default() += -taxable_profit;
+        } else {
+            for (&loss_year, loss) in self.losses.range_mut(year - CARRYFORWARD_YEARS..year) {
+                let remaining_profit = taxable_profit - deduction;
+                if remaining_profit.is_zero() {
+                    break;
+                }
+
+                let amount = std::cmp::min(*loss, remaining_profit);
+                if amount.is_zero() {
+                    continue;
+                }
+
+                *loss -= amount;
+                deduction += amount;
+                sources.insert(loss_year, amount);
+            }
+
+            self.losses.retain(|_, loss| !loss.is_zero());
+        }
+
+        LossCarryforward {deduction, sources}
+    }
+
+    /// Returns losses which can be carried forward to the specified tax year
+    pub fn available(&self, year: i32) -> Decimal {
+        self.losses.range(year - CARRYFORWARD_YEARS..year).map(|(_, &loss)| loss).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loss_carryforward() {
+        let mut calculator = LossCarryforwardCalculator::new(&btreemap!{
+            2008 => dec!(1_000),
+            2012 => dec!(2_000),
+        });
+        assert_eq!(calculator.available(2019), dec!(2_000));
+
+        let result = calculator.process(2019, dec!(-3_000));
+        assert_eq!(result.deduction, dec!(0));
+        assert!(result.sources.is_empty());
+        assert_eq!(calculator.available(2020), dec!(5_000));
+
+        let result = calculator.process(2020, dec!(2_500));
+        assert_eq!(result.deduction, dec!(2_500));
+        assert_eq!(result.sources, btreemap!{
+            2012 => dec!(2_000),
+            2019 => dec!(500),
+        });
+        assert_eq!(calculator.available(2021), dec!(2_500));
+
+        let result = calculator.process(2021, dec!(0));
+        assert_eq!(result.deduction, dec!(0));
+
+        // The loss is expired
+        assert_eq!(calculator.available(2030), dec!(0));
+        let result = calculator.process(2030, dec!(1_000));
+        assert_eq!(result.deduction, dec!(0));
+    }
+
+    #[test]
+    fn partial_loss_carryforward() {
+        let mut calculator = LossCarryforwardCalculator::new(&btreemap!{
+            2020 => dec!(10_000),
+        });
+
+        let result = calculator.process(2021, dec!(4_000));
+        assert_eq!(result.deduction, dec!(4_000));
+        assert_eq!(calculator.available(2022), dec!(6_000));
+
+        let result = calculator.process(2022, dec!(7_000));
+        assert_eq!(result.deduction, dec!(6_000));
+        assert_eq!(result.sources, btreemap!{2020 => dec!(6_000)});
+        assert_eq!(calculator.available(2023), dec!(0));
+    }
+
+    #[test]
+    fn taxpayer_loss_carryforward() {
+        let mut calculator = LossCarryforwardCalculator::new(&btreemap!{
+            2019 => dec!(10_000),
+        }).with_other_profit(&btreemap!{
+            2020 => dec!(-5_000),
+            2021 => dec!(12_000),
+            2022 => dec!(3_000),
+        });
+
+        // Loss of the other portfolios is carried forward too
+        let result = calculator.process(2021, dec!(4_000));
+        assert_eq!(result.deduction, dec!(3_000));
+        assert_eq!(result.sources, btreemap!{
+            2019 => dec!(10_000),
+            2020 => dec!(5_000),
+        });
+        assert_eq!(calculator.available(2022), dec!(0));
+
+        // The loss reduces taxable profit of the other portfolios instead
+        let mut calculator = LossCarryforwardCalculator::new(&btreemap!{
+            2021 => dec!(5_000),
+        }).with_other_profit(&btreemap!{
+            2022 => dec!(3_000),
+        });
+
+        let result = calculator.process(2022, dec!(-1_000));
+        assert_eq!(result.deduction, dec!(-1_000));
+        assert_eq!(result.sources, btreemap!{2021 => dec!(2_000)});
+        assert_eq!(calculator.available(2023), dec!(3_000));
+    }
+}
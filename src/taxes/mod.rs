@@ -1,7 +1,10 @@
 pub mod long_term_ownership;
+mod loss_carryforward;
 mod net_calculator;
 mod payment_day;
 mod remapping;
+mod tax_base;
+mod taxpayer;
 
 use serde::Deserialize;
 use serde::de::{Deserializer, Error};
@@ -13,9 +16,12 @@ use crate::localities::Jurisdiction;
 pub use self::long_term_ownership::{
     LtoDeductibleProfit, LtoDeductionCalculator, LtoDeduction,
     NetLtoDeduction, NetLtoDeductionCalculator};
+pub use self::loss_carryforward::{LossCarryforward, LossCarryforwardCalculator};
 pub use self::net_calculator::{NetTax, NetTaxCalculator};
 pub use self::payment_day::{TaxPaymentDay, TaxPaymentDaySpec};
 pub use self::remapping::TaxRemapping;
+pub use self::tax_base::TaxBase;
+pub use self::taxpayer::{OtherIncome, TaxpayerIncome};
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub enum IncomeType {
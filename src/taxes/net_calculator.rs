@@ -1,16 +1,18 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::BTreeMap;
 
 use crate::currency::Cash;
 use crate::localities::Country;
 use crate::taxes::long_term_ownership::{LtoDeductibleProfit, LtoDeductionCalculator};
 use crate::time::Date;
 
-use super::{IncomeType, TaxPaymentDay};
+use super::{IncomeType, LossCarryforwardCalculator, TaxBase, TaxPaymentDay};
 
 pub struct NetTaxCalculator {
     country: Country,
     tax_payment_day: TaxPaymentDay,
-    profit: HashMap<(i32, Date), NetProfit>,
+    profit: BTreeMap<(i32, Date), NetProfit>,
+    loss_carryforward: Option<LossCarryforwardCalculator>,
+    tax_base: TaxBase,
 }
 
 pub struct NetTax {
@@ -33,10 +35,26 @@ impl NetTaxCalculator {
         NetTaxCalculator {
             country,
             tax_payment_day,
-            profit: HashMap::new(),
+            profit: BTreeMap::new(),
+            loss_carryforward: None,
+            tax_base: TaxBase::new(),
         }
     }
 
+    /// Enables carryforward of trading losses: losses of the previous years (including the ones
+    /// which aren't covered by the added profit) reduce taxable profit of the following years.
+    pub fn with_loss_carryforward(mut self, calculator: LossCarryforwardCalculator) -> NetTaxCalculator {
+        self.loss_carryforward.replace(calculator);
+        self
+    }
+
+    /// Sets other taxable income of the tax years which is received before trading profit, so
+    /// progressive tax rates are applied to their sum.
+    pub fn with_tax_base(mut self, tax_base: TaxBase) -> NetTaxCalculator {
+        self.tax_base = tax_base;
+        self
+    }
+
     pub fn add_profit(
         &mut self, date: Date, total: Cash, taxable: Cash,
         lto_deductible: &[LtoDeductibleProfit], emulated_trade: bool,
@@ -58,20 +76,28 @@ impl NetTaxCalculator {
         }
     }
 
-    pub fn calculate(self) -> BTreeMap<i32, NetTax> {
+    pub fn calculate(mut self) -> BTreeMap<i32, NetTax> {
         let mut taxes = BTreeMap::new();
 
+        // Iterating in tax year order is essential for loss carryforward
         for ((tax_year, tax_payment_date), profit) in self.profit.into_iter() {
             let lto = profit.lto.calculate();
 
             let lto_deduction = self.country.cash(lto.deduction);
             let lto_loss = self.country.cash(lto.loss);
 
-            let tax_to_pay = self.country.tax_to_pay(
-                IncomeType::Trading, tax_year, profit.taxable - lto_deduction, None);
+            let mut taxable_profit = profit.taxable - lto_deduction;
+            if let Some(loss_carryforward) = self.loss_carryforward.as_mut() {
+                taxable_profit.amount -= loss_carryforward.process(tax_year, taxable_profit.amount).deduction;
+            }
+
+            let tax_base = self.tax_base.get(tax_year);
+
+            let tax_to_pay = self.country.tax_to_pay_with_base(
+                IncomeType::Trading, tax_year, taxable_profit, tax_base, None);
 
-            let tax_without_deduction = self.country.tax_to_pay(
-                IncomeType::Trading, tax_year, profit.total, None);
+            let tax_without_deduction = self.country.tax_to_pay_with_base(
+                IncomeType::Trading, tax_year, profit.total, tax_base, None);
 
             let tax_deduction = tax_without_deduction - tax_to_pay;
             assert!(!tax_deduction.is_negative());
@@ -0,0 +1,32 @@
+use std::collections::HashMap;
+
+use crate::types::Decimal;
+
+/// Taxable income received during tax years. Progressive tax rates are applied to the total income
+/// of the tax year, so tax for each income depends on the income received before it.
+#[derive(Clone, Default)]
+pub struct TaxBase {
+    income: HashMap<i32, Decimal>,
+}
+
+impl TaxBase {
+    pub fn new() -> TaxBase {
+        TaxBase::default()
+    }
+
+    pub fn get(&self, year: i32) -> Decimal {
+        self.income.get(&year).cloned().unwrap_or_default()
+    }
+
+    pub fn add(&mut self, year: i32, income: Decimal) {
+        if income > dec!(0) {
+            *self.income.entry(year).or_default() += income;
+        }
+    }
+
+    pub fn extend(&mut self, other: &TaxBase) {
+        for (&year, &income) in &other.income {
+            self.add(year, income);
+        }
+    }
+}
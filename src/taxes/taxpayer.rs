@@ -0,0 +1,226 @@
+use std::collections::{BTreeMap, HashMap};
+
+use log::{debug, warn};
+
+use crate::broker_statement::{
+    BrokerStatement, ReadingStrictness, StockSellType, calculate_variation_margin_profit};
+use crate::config::{Config, PortfolioConfig};
+use crate::core::{EmptyResult, GenericResult};
+use crate::currency::converter::CurrencyConverter;
+use crate::localities::Country;
+use crate::types::Decimal;
+
+use super::{LossCarryforwardCalculator, LtoDeductionCalculator, TaxBase};
+
+/// Taxable income which the taxpayers receive through their portfolios.
+///
+/// Progressive tax rates and loss carryforward are applied to the combined income of the taxpayer,
+/// so taxes of each portfolio are calculated as if its income is received on top of the income of
+/// the other portfolios of the same taxpayer. Portfolios without configured taxpayer are considered
+/// as belonging to separate taxpayers.
+pub struct TaxpayerIncome {
+    portfolios: HashMap<String, PortfolioIncome>,
+}
+
+#[derive(Default)]
+struct PortfolioIncome {
+    taxpayer: Option<String>,
+    losses: BTreeMap<i32, Decimal>,
+    trading: BTreeMap<i32, Decimal>,
+    non_trading: TaxBase,
+}
+
+/// Income received through the other portfolios of the taxpayer
+#[derive(Clone, Default)]
+pub struct OtherIncome {
+    losses: BTreeMap<i32, Decimal>,
+    trading: BTreeMap<i32, Decimal>,
+    tax_base: TaxBase,
+}
+
+impl TaxpayerIncome {
+    /// Calculates income of the specified portfolios and the other portfolios of their taxpayers.
+    /// The specified statements are used as is (their trades must be processed) and statements of
+    /// the other portfolios are read.
+    pub fn calculate(
+        config: &Config, country: &Country, converter: &CurrencyConverter,
+        statements: &[(&PortfolioConfig, &BrokerStatement)],
+    ) -> GenericResult<TaxpayerIncome> {
+        let mut income = TaxpayerIncome {portfolios: HashMap::new()};
+
+        for &(portfolio, statement) in statements {
+            income.add(country, portfolio, statement, converter)?;
+        }
+
+        for portfolio in &config.portfolios {
+            if income.portfolios.contains_key(&portfolio.name) || portfolio.statements.is_none() {
+                continue;
+            }
+
+            let taxpayer = match portfolio.taxpayer {
+                Some(ref taxpayer) => taxpayer,
+                None => continue,
+            };
+
+            if !statements.iter().any(|(other, _)| other.taxpayer.as_ref() == Some(taxpayer)) {
+                continue;
+            }
+
+            debug!("Reading {:?} portfolio to calculate taxpayer's income...", portfolio.name);
+
+            let result = BrokerStatement::read_portfolio(config, portfolio, ReadingStrictness::empty())
+                .and_then(|statement| income.add(country, portfolio, &statement, converter));
+
+            if let Err(e) = result {
+                warn!(concat!(
+                    "Unable to calculate taxpayer's income from {:?} portfolio: {}. ",
+                    "Its income won't be taken into account in tax calculations."), portfolio.name, e);
+            }
+        }
+
+        Ok(income)
+    }
+
+    /// Returns income which is received through all portfolios of the taxpayer except the specified
+    /// one
+    pub fn other(&self, portfolio: &str) -> OtherIncome {
+        let mut other = OtherIncome::default();
+
+        let taxpayer = match self.portfolios.get(portfolio) {
+            Some(income) => &income.taxpayer,
+            None => return other,
+        };
+
+        for (name, income) in &self.portfolios {
+            if name != portfolio && (taxpayer.is_none() || income.taxpayer != *taxpayer) {
+                continue;
+            }
+
+            for (&year, &loss) in &income.losses {
+                *other.losses.entry(year).or_default() += loss;
+            }
+
+            if name == portfolio {
+                continue;
+            }
+
+            for (&year, &profit) in &income.trading {
+                *other.trading.entry(year).or_default() += profit;
+            }
+            other.tax_base.extend(&income.non_trading);
+        }
+
+        // Other portfolios' trading profit is taxed after applying loss carryforward
+        let mut loss_carryforward = LossCarryforwardCalculator::new(&other.losses);
+        for (&year, &profit) in &other.trading {
+            let deduction = loss_carryforward.process(year, profit).deduction;
+            other.tax_base.add(year, profit - deduction);
+        }
+
+        other
+    }
+
+    fn add(
+        &mut self, country: &Country, portfolio: &PortfolioConfig, statement: &BrokerStatement,
+        converter: &CurrencyConverter,
+    ) -> EmptyResult {
+        let tax_payment_day = portfolio.tax_payment_day();
+        let mut trading: BTreeMap<i32, (Decimal, LtoDeductionCalculator)> = BTreeMap::new();
+
+        for trade in &statement.stock_sells {
+            let is_trade = matches!(trade.type_, StockSellType::Trade {..});
+            if trade.emulation || !trade.is_processed() || !is_trade {
+                continue;
+            }
+
+            let (tax_year, _) = tax_payment_day.get(trade.realization_date(), true);
+            let details = trade.calculate(country, tax_year, &portfolio.tax_exemptions, converter)?;
+
+            let (profit, lto) = trading.entry(tax_year).or_insert_with(|| {
+                (dec!(0), LtoDeductionCalculator::new())
+            });
+            *profit += details.taxable_local_profit.amount;
+
+            for source in &details.fifo {
+                if let Some(deductible) = source.long_term_ownership_deductible {
+                    lto.add(deductible.profit, deductible.years, false);
+                }
+            }
+        }
+
+        for margin in calculate_variation_margin_profit(
+            &statement.variation_margin, country.currency, converter)?
+        {
+            let (tax_year, _) = tax_payment_day.get(margin.date, true);
+            let (total, _) = trading.entry(tax_year).or_insert_with(|| {
+                (dec!(0), LtoDeductionCalculator::new())
+            });
+            *total += margin.local_profit.amount;
+        }
+
+        let income = PortfolioIncome {
+            taxpayer: portfolio.taxpayer.clone(),
+            losses: portfolio.loss_carryforward.clone(),
+            trading: trading.into_iter().map(|(year, (profit, lto))| {
+                (year, profit - lto.calculate().deduction)
+            }).collect(),
+            non_trading: statement.non_trading_tax_base(country, converter)?,
+        };
+
+        self.portfolios.insert(portfolio.name.clone(), income);
+        Ok(())
+    }
+}
+
+impl OtherIncome {
+    /// Returns loss carryforward calculator for the taxpayer's losses
+    pub fn loss_carryforward(&self) -> LossCarryforwardCalculator {
+        LossCarryforwardCalculator::new(&self.losses).with_other_profit(&self.trading)
+    }
+
+    /// Returns taxable income of the other portfolios which is considered as received before the
+    /// portfolio's income when progressive tax rates are applied
+    pub fn tax_base(&self) -> TaxBase {
+        self.tax_base.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn other_income() {
+        let portfolio = |taxpayer: Option<&str>, loss, profit, non_trading| {
+            let mut income = PortfolioIncome {
+                taxpayer: taxpayer.map(ToOwned::to_owned),
+                losses: btreemap!{2020 => loss},
+                trading: btreemap!{2021 => profit},
+                non_trading: TaxBase::new(),
+            };
+            income.non_trading.add(2021, non_trading);
+            income
+        };
+
+        let income = TaxpayerIncome {portfolios: hashmap!{
+            s!("first") => portfolio(Some("me"), dec!(100), dec!(1000), dec!(10)),
+            s!("second") => portfolio(Some("me"), dec!(200), dec!(2000), dec!(20)),
+            s!("third") => portfolio(Some("spouse"), dec!(400), dec!(4000), dec!(40)),
+            s!("fourth") => portfolio(None, dec!(800), dec!(8000), dec!(80)),
+        }};
+
+        let other = income.other("first");
+        assert_eq!(other.losses, btreemap!{2020 => dec!(300)});
+        assert_eq!(other.trading, btreemap!{2021 => dec!(2000)});
+        assert_eq!(other.tax_base().get(2021), dec!(20) + dec!(2000) - dec!(300));
+
+        let other = income.other("fourth");
+        assert_eq!(other.losses, btreemap!{2020 => dec!(800)});
+        assert!(other.trading.is_empty());
+        assert_eq!(other.tax_base().get(2021), dec!(0));
+
+        let other = income.other("unknown");
+        assert!(other.losses.is_empty());
+        assert!(other.trading.is_empty());
+    }
+}
@@ -14,8 +14,8 @@ fn generate_regression_tests() {
     t.add("Help", "--help");
 
     for command in [
-        "sync", "show", "rebalance", "cash", "buy", "sell",
-        "analyse", "simulate-sell", "tax-statement", "cash-flow",
+        "sync", "show", "exposure", "rebalance", "rebalance-household", "cash", "buy", "sell",
+        "analyse", "nav", "simulate-sell", "tax-statement", "cash-flow",
         "deposits", "metrics", "completion",
     ] {
         t.add(&format!("Help {}", command), &format!("{} --help", command));
@@ -29,11 +29,19 @@ fn generate_regression_tests() {
     t.add("Show", "show ib");
     t.add("Show flat", "show ib --flat");
 
+    // exposure
+    t.add("Exposure", "exposure");
+    t.add("Exposure in USD", "exposure ib --currency USD");
+
     // analyse
     t.add("Analyse", "analyse --all");
     t.add("Analyse complex", "analyse ib-complex --all").config("other");
     t.add("Analyse IIA type A", "analyse open-first-iia-a --all").config("other");
 
+    // nav
+    t.add("NAV", "nav ib --monthly --currency USD");
+    t.add("NAV CSV", "--format csv nav ib --monthly --currency USD");
+
     // simulate-sell
     t.add("Simulate sell partial", "simulate-sell ib all VTI 50 BND 50 BND");
     t.add("Simulate sell IIA type A", "simulate-sell open-first-iia-a").config("other");